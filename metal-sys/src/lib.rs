@@ -35,10 +35,11 @@ pub use constants::{MTLCommandBufferStatus, MTLCompareFunction, MTLFeatureSet, M
 pub use functions::{MTLClearColorMake, MTLCopyAllDevices, MTLCreateSystemDefaultDevice,
                     MTLOriginMake, MTLRegionMake1D, MTLRegionMake2D, MTLRegionMake3D, MTLSizeMake};
 
-pub use protocols::{MTLCPUCacheMode, MTLCommandBuffer, MTLCommandEncoder, MTLCommandQueue,
-                    MTLComputePipelineState, MTLCullMode, MTLDepthClipMode, MTLDepthStencilState,
-                    MTLDevice, MTLDrawable, MTLFunction, MTLFunctionType, MTLIndexType,
-                    MTLLanguageVersion, MTLLibrary, MTLLibraryError, MTLLibraryErrorDomain,
+pub use protocols::{MTLBuffer, MTLCPUCacheMode, MTLCommandBuffer, MTLCommandEncoder,
+                    MTLCommandQueue, MTLComputePipelineState, MTLCullMode, MTLDepthClipMode,
+                    MTLDepthStencilState, MTLDevice, MTLDrawable, MTLFunction, MTLFunctionType,
+                    MTLIndexType, MTLLanguageVersion, MTLLibrary, MTLLibraryError,
+                    MTLLibraryErrorDomain,
                     MTLNewComputePipelineStateCompletionHandler,
                     MTLNewComputePipelineStateWithReflectionCompletionHandler,
                     MTLNewLibraryCompletionHandler,
@@ -46,12 +47,16 @@ pub use protocols::{MTLCPUCacheMode, MTLCommandBuffer, MTLCommandEncoder, MTLCom
                     MTLNewRenderPipleineStateCompletionHandler, MTLPrimitiveType,
                     MTLPurgeableState, MTLRenderCommandEncoder, MTLRenderPipelineError,
                     MTLRenderPipelineErrorDomain, MTLResource,
-                    MTLResourceCPUCacheModeDefaultCache, MTLResourceCPUCacheModeShift,
-                    MTLResourceCPUCacheModeWriteCombined, MTLResourceOptionCPUCacheModeDefault,
+                    MTLResourceCPUCacheModeDefaultCache, MTLResourceCPUCacheModeMask,
+                    MTLResourceCPUCacheModeShift, MTLResourceCPUCacheModeWriteCombined,
+                    MTLResourceOptionCPUCacheModeDefault,
                     MTLResourceOptionCPUCacheModeWriteCombined, MTLResourceOptions,
+                    MTLResourceStorageModeManaged, MTLResourceStorageModeMask,
                     MTLResourceStorageModePrivate, MTLResourceStorageModeShared,
                     MTLResourceStorageModeShift, MTLSamplerState, MTLStorageMode, MTLTexture,
-                    MTLTextureType, MTLTextureUsage, MTLTriangleFillMode,
+                    MTLTextureType, MTLTextureUsage, MTLTextureUsagePixelFormatView,
+                    MTLTextureUsageRenderTarget, MTLTextureUsageShaderRead,
+                    MTLTextureUsageShaderWrite, MTLTextureUsageUnknown, MTLTriangleFillMode,
                     MTLVisibilityResultMode, MTLWinding};
 
 pub use types::{MTLClearColor, MTLDispatchThreadgroupsIndirectArguments,
@@ -1,3 +1,4 @@
+mod mtl_buffer;
 mod mtl_command_buffer;
 mod mtl_command_encoder;
 mod mtl_command_queue;
@@ -12,6 +13,7 @@ mod mtl_resource;
 mod mtl_sampler_state;
 mod mtl_texture;
 
+pub use self::mtl_buffer::MTLBuffer;
 pub use self::mtl_command_buffer::MTLCommandBuffer;
 pub use self::mtl_command_encoder::MTLCommandEncoder;
 pub use self::mtl_command_queue::MTLCommandQueue;
@@ -33,11 +35,15 @@ pub use self::mtl_render_command_encoder::{MTLCullMode, MTLDepthClipMode, MTLInd
                                            MTLTriangleFillMode, MTLVisibilityResultMode,
                                            MTLWinding};
 pub use self::mtl_resource::{MTLCPUCacheMode, MTLPurgeableState, MTLResource,
-                             MTLResourceCPUCacheModeDefaultCache, MTLResourceCPUCacheModeShift,
-                             MTLResourceCPUCacheModeWriteCombined,
+                             MTLResourceCPUCacheModeDefaultCache, MTLResourceCPUCacheModeMask,
+                             MTLResourceCPUCacheModeShift, MTLResourceCPUCacheModeWriteCombined,
                              MTLResourceOptionCPUCacheModeDefault,
                              MTLResourceOptionCPUCacheModeWriteCombined, MTLResourceOptions,
+                             MTLResourceStorageModeManaged, MTLResourceStorageModeMask,
                              MTLResourceStorageModePrivate, MTLResourceStorageModeShared,
                              MTLResourceStorageModeShift, MTLStorageMode};
 pub use self::mtl_sampler_state::MTLSamplerState;
-pub use self::mtl_texture::{MTLTexture, MTLTextureType, MTLTextureUsage};
+pub use self::mtl_texture::{MTLTexture, MTLTextureType, MTLTextureUsage,
+                            MTLTextureUsagePixelFormatView, MTLTextureUsageRenderTarget,
+                            MTLTextureUsageShaderRead, MTLTextureUsageShaderWrite,
+                            MTLTextureUsageUnknown};
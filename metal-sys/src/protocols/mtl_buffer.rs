@@ -0,0 +1,88 @@
+use cocoa::base::id;
+use cocoa::foundation::NSUInteger;
+#[cfg(target_os = "macos")]
+use cocoa::foundation::NSRange;
+use libc::c_void;
+
+/// The `MTLBuffer` protocol defines the interface for objects that represent an
+/// allocation of unformatted, device-accessible memory that can contain any type
+/// of data. Buffers are typically used for vertex, shader, and compute state data.
+///
+/// Do not use standard allocation and initialization techniques to create a
+/// `MTLBuffer` object. The following `MTLDevice` methods create and return a
+/// `MTLBuffer` object:
+///
+/// * `newBufferWithLength:options:` creates a `MTLBuffer` object with a new storage
+///   allocation.
+///
+/// * `newBufferWithBytes:length:options:` creates a `MTLBuffer` object by copying
+///   data from existing storage into a new storage allocation.
+///
+/// * `newBufferWithBytesNoCopy:length:options:deallocator:` creates a `MTLBuffer`
+///   object that reuses an existing storage allocation and does not allocate any
+///   new storage for this object.
+pub trait MTLBuffer {
+    /// The logical size of the buffer, in bytes. (read-only)
+    unsafe fn length(self) -> NSUInteger;
+
+    /// Returns the system address of the buffer's storage allocation.
+    ///
+    /// # Discussion
+    ///
+    /// This method returns a null pointer if the buffer has a private storage mode.
+    unsafe fn contents(self) -> *mut c_void;
+
+    /// Informs the GPU that the CPU has modified a section of the buffer.
+    ///
+    /// # Discussion
+    ///
+    /// This method is only needed for buffers with a managed storage mode.
+    ///
+    /// # Availability
+    ///
+    /// Available in OS X v10.11 and later.
+    #[cfg(target_os = "macos")]
+    unsafe fn didModifyRange(self, range: NSRange);
+
+    /// Creates a new texture object that shares its storage with the buffer.
+    ///
+    /// # Parameters
+    ///
+    /// `descriptor` - A `MTLTextureDescriptor` object describing the texture.
+    ///
+    /// `offset` - The offset in bytes into the buffer at which the texture data begins.
+    ///
+    /// `bytesPerRow` - The stride in bytes between rows of the texture.
+    ///
+    /// # Availability
+    ///
+    /// Available in iOS 8.0 and macOS 10.13 and later.
+    unsafe fn newTextureWithDescriptor_offset_bytesPerRow(self, descriptor: id,
+                                                          offset: NSUInteger,
+                                                          bytesPerRow: NSUInteger)
+                                                          -> id;
+}
+
+impl MTLBuffer for id {
+    unsafe fn length(self) -> NSUInteger {
+        msg_send![self, length]
+    }
+
+    unsafe fn contents(self) -> *mut c_void {
+        msg_send![self, contents]
+    }
+
+    #[cfg(target_os = "macos")]
+    unsafe fn didModifyRange(self, range: NSRange) {
+        msg_send![self, didModifyRange:range]
+    }
+
+    unsafe fn newTextureWithDescriptor_offset_bytesPerRow(self, descriptor: id,
+                                                          offset: NSUInteger,
+                                                          bytesPerRow: NSUInteger)
+                                                          -> id {
+        msg_send![self, newTextureWithDescriptor:descriptor
+                                          offset:offset
+                                     bytesPerRow:bytesPerRow]
+    }
+}
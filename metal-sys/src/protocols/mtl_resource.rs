@@ -132,15 +132,19 @@ pub enum MTLStorageMode {
     /// The default storage mode for buffers.
     MTLStorageModeShared = 0,
 
+    /// The storage mode for a resource with separate copies in CPU and GPU memory, which are
+    /// synchronized explicitly. Only available in OS X.
+    MTLStorageModeManaged = 1,
+
     /// The storage mode for a resource kept entirely in GPU memory.
     MTLStorageModePrivate = 2
 }
 
-// TODO(burtonageo): Placeholder
-pub const MTLResourceStorageModeShift: usize = 16;
+pub const MTLResourceCPUCacheModeShift: usize = 0;
+pub const MTLResourceCPUCacheModeMask: usize = 0xf << MTLResourceCPUCacheModeShift;
 
-// TODO(burtonageo): Placeholder
-pub const MTLResourceCPUCacheModeShift: usize = 12;
+pub const MTLResourceStorageModeShift: usize = 4;
+pub const MTLResourceStorageModeMask: usize = 0xf << MTLResourceStorageModeShift;
 
 /// Optional arguments used to create and influence behavior of buffer and
 /// texture objects.
@@ -164,6 +168,11 @@ bitflags! {
         const MTLResourceStorageModeShared = (::MTLStorageMode::MTLStorageModeShared as usize) <<
                                              ::MTLResourceStorageModeShift,
 
+/// The CPU and GPU each have their own copy of the contents of the resource,
+/// which must be synchronized explicitly. Only available in OS X.
+        const MTLResourceStorageModeManaged = (::MTLStorageMode::MTLStorageModeManaged as usize) <<
+                                              ::MTLResourceStorageModeShift,
+
 /// The storage mode for a resource kept entirely in GPU memory. No coherency
 /// of any kind must be maintained in this mode because the resource will
 /// never be directly accessed by the CPU.
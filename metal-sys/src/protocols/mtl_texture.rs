@@ -104,11 +104,8 @@ pub trait MTLTexture {
     unsafe fn rootResource(self) -> id;
     unsafe fn usage(self) -> MTLTextureUsage;
 
-    #[cfg(target_os = "ios")]
     unsafe fn buffer(self) -> id;
-    #[cfg(target_os = "ios")]
     unsafe fn bufferOffset(self) -> NSUInteger;
-    #[cfg(target_os = "ios")]
     unsafe fn bufferBytesPerRow(self) -> NSUInteger;
 
     #[cfg(target_os = "macos")]
@@ -208,18 +205,15 @@ impl MTLTexture for id {
         msg_send![self, usage]
     }
 
-    #[cfg(target_os = "ios")]
     unsafe fn buffer(self) -> id {
         msg_send![self, buffer]
     }
 
-    #[cfg(target_os = "ios")]
     unsafe fn bufferOffset(self) -> NSUInteger {
         msg_send![self, bufferOffset]
     }
 
-    #[cfg(target_os = "ios")]
-    unsafe fn bufferBytesPerRow(self) -> id {
+    unsafe fn bufferBytesPerRow(self) -> NSUInteger {
         msg_send![self, bufferBytesPerRow]
     }
 
@@ -258,12 +252,22 @@ pub enum MTLTextureType {
     MTLTextureType3D = 7
 }
 
-#[repr(usize)]
-#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
-pub enum MTLTextureUsage {
-    MTLTextureUsageUnknown = 0x0000,
-    MTLTextureUsageShaderRead = 0x0001,
-    MTLTextureUsageShaderWrite = 0x0002,
-    MTLTextureUsageRenderTarget = 0x0004,
-    MTLTextureUsagePixelFormatView = 0x0010
+bitflags! {
+    /// Options that determine how a texture will be used.
+    pub flags MTLTextureUsage: usize {
+/// The usage of the texture is not known.
+        const MTLTextureUsageUnknown = 0x0000,
+
+/// The texture is read or sampled by shaders.
+        const MTLTextureUsageShaderRead = 0x0001,
+
+/// The texture is written to by compute shaders.
+        const MTLTextureUsageShaderWrite = 0x0002,
+
+/// The texture is used as a color, depth or stencil render target.
+        const MTLTextureUsageRenderTarget = 0x0004,
+
+/// The texture is used to create texture views with a different pixel format.
+        const MTLTextureUsagePixelFormatView = 0x0010
+    }
 }
@@ -0,0 +1,184 @@
+//! Custom derives for the `metl` crate.
+//!
+//! `#[derive(GpuPod)]` implements `metl::GpuPod` for a `#[repr(C)]` struct. The derive checks
+//! at compile time that every field is `GpuPod` and that the struct contains no padding.
+//!
+//! `#[derive(ShaderStruct)]` implements `metl::ShaderStruct` for a `#[repr(C)]` struct whose
+//! fields are all `metl::ShaderType`s, generating the matching Metal Shading Language struct
+//! declaration. The derive checks at compile time that every field is at the offset the Metal
+//! compiler would place it, and that the struct is not padded at the end in MSL.
+
+extern crate proc_macro;
+extern crate syn;
+#[macro_use]
+extern crate quote;
+
+use proc_macro::TokenStream;
+use syn::{Body, DeriveInput, Ident, MetaItem, NestedMetaItem, VariantData};
+
+#[proc_macro_derive(GpuPod)]
+pub fn derive_gpu_pod(input: TokenStream) -> TokenStream {
+    let input = syn::parse_derive_input(&input.to_string()).unwrap();
+    match impl_gpu_pod(&input) {
+        Ok(tokens) => tokens.parse().unwrap(),
+        Err(message) => panic!("cannot derive GpuPod for `{}`: {}", input.ident, message),
+    }
+}
+
+fn impl_gpu_pod(input: &DeriveInput) -> Result<quote::Tokens, String> {
+    if !has_repr_c(input) {
+        return Err("the struct must be `#[repr(C)]`".into());
+    }
+
+    if !input.generics.lifetimes.is_empty() || !input.generics.ty_params.is_empty() {
+        return Err("generic structs are not supported".into());
+    }
+
+    let fields = match input.body {
+        Body::Struct(VariantData::Struct(ref fields)) |
+        Body::Struct(VariantData::Tuple(ref fields)) => fields,
+        Body::Struct(VariantData::Unit) => return Err("the struct has no fields".into()),
+        Body::Enum(_) => return Err("only structs are supported".into()),
+    };
+    if fields.is_empty() {
+        return Err("the struct has no fields".into());
+    }
+
+    let name = &input.ident;
+    let checks = Ident::new(format!("__metl_gpu_pod_checks_{}", name));
+    let field_types = fields.iter().map(|field| &field.ty).collect::<Vec<_>>();
+    let field_sizes = field_types.clone();
+
+    Ok(quote! {
+        unsafe impl ::metl::GpuPod for #name {}
+
+        #[allow(dead_code, non_snake_case)]
+        fn #checks() {
+            fn assert_gpu_pod<T: ::metl::GpuPod>() {}
+            #(assert_gpu_pod::<#field_types>();)*
+
+            // Fails to compile if the struct contains padding.
+            let _: [(); 0 #(+ ::std::mem::size_of::<#field_sizes>())*] =
+                [(); ::std::mem::size_of::<#name>()];
+        }
+    })
+}
+
+#[proc_macro_derive(ShaderStruct)]
+pub fn derive_shader_struct(input: TokenStream) -> TokenStream {
+    let input = syn::parse_derive_input(&input.to_string()).unwrap();
+    match impl_shader_struct(&input) {
+        Ok(tokens) => tokens.parse().unwrap(),
+        Err(message) => panic!("cannot derive ShaderStruct for `{}`: {}", input.ident, message),
+    }
+}
+
+fn impl_shader_struct(input: &DeriveInput) -> Result<quote::Tokens, String> {
+    if !has_repr_c(input) {
+        return Err("the struct must be `#[repr(C)]`".into());
+    }
+
+    if !input.generics.lifetimes.is_empty() || !input.generics.ty_params.is_empty() {
+        return Err("generic structs are not supported".into());
+    }
+
+    let fields = match input.body {
+        Body::Struct(VariantData::Struct(ref fields)) => fields,
+        Body::Struct(VariantData::Tuple(_)) => {
+            return Err("the fields must be named, as they are used in the MSL struct".into())
+        }
+        Body::Struct(VariantData::Unit) => return Err("the struct has no fields".into()),
+        Body::Enum(_) => return Err("only structs are supported".into()),
+    };
+    if fields.is_empty() {
+        return Err("the struct has no fields".into());
+    }
+
+    let name = &input.ident;
+    let name_str = name.as_ref();
+    let checks = Ident::new(format!("__metl_shader_struct_checks_{}", name));
+    let field_names = fields.iter().map(|field| field.ident.as_ref().unwrap().as_ref())
+                            .collect::<Vec<_>>();
+    let field_types = fields.iter().map(|field| &field.ty).collect::<Vec<_>>();
+    let (field_names_1, field_types_1, field_types_2, field_types_3) =
+        (field_names.clone(), field_types.clone(), field_types.clone(), field_types.clone());
+
+    // The fields of a `GpuPod` struct are packed, so each field starts at the sum of the sizes of
+    // the fields before it. This must be a multiple of the MSL alignment of the field.
+    let mut offset = quote!(0);
+    let mut offset_checks = vec![];
+    for ty in &field_types {
+        offset_checks.push(quote! {
+            let _: [(); 0] = [(); (#offset) % <#ty as ::metl::ShaderType>::ALIGNMENT];
+        });
+        offset = quote!(#offset + ::std::mem::size_of::<#ty>());
+    }
+
+    Ok(quote! {
+        unsafe impl ::metl::ShaderType for #name {
+            // The alignments are powers of two, so this is the largest of them.
+            const ALIGNMENT: usize =
+                1 + (0 #(| (<#field_types as ::metl::ShaderType>::ALIGNMENT - 1))*);
+
+            fn msl_type_name() -> String {
+                #name_str.into()
+            }
+
+            fn member_type() -> ::metl::MemberType {
+                ::metl::MemberType::Struct(<#name as ::metl::ShaderStruct>::struct_layout())
+            }
+
+            fn msl_declarations(declarations: &mut Vec<String>) {
+                #(<#field_types_1 as ::metl::ShaderType>::msl_declarations(declarations);)*
+                let declaration = <#name as ::metl::ShaderStruct>::msl_declaration();
+                if !declarations.contains(&declaration) {
+                    declarations.push(declaration);
+                }
+            }
+        }
+
+        impl ::metl::ShaderStruct for #name {
+            fn struct_layout() -> ::metl::StructLayout {
+                ::metl::StructLayout::new(vec![
+                    #((#field_names, <#field_types_2 as ::metl::ShaderType>::member_type())),*
+                ]).unwrap()
+            }
+
+            fn msl_declaration() -> String {
+                let mut declaration = format!("struct {} {{\n", #name_str);
+                #(
+                    declaration.push_str(&format!("    {} {};\n",
+                        <#field_types_3 as ::metl::ShaderType>::msl_type_name(), #field_names_1));
+                )*
+                declaration.push_str("};\n");
+                declaration
+            }
+        }
+
+        #[allow(dead_code, non_snake_case)]
+        fn #checks() {
+            // Fails to compile if a field is not at the same offset as in MSL.
+            #(#offset_checks)*
+
+            // Fails to compile if the struct would be padded at the end in MSL.
+            let _: [(); 0] =
+                [(); ::std::mem::size_of::<#name>() % <#name as ::metl::ShaderType>::ALIGNMENT];
+        }
+    })
+}
+
+fn has_repr_c(input: &DeriveInput) -> bool {
+    input.attrs.iter().any(|attr| {
+        match attr.value {
+            MetaItem::List(ref ident, ref items) if ident == "repr" => {
+                items.iter().any(|item| {
+                    match *item {
+                        NestedMetaItem::MetaItem(MetaItem::Word(ref word)) => word == "C",
+                        _ => false,
+                    }
+                })
+            }
+            _ => false,
+        }
+    })
+}
@@ -0,0 +1,11 @@
+extern crate metl;
+
+use metl::CompileOptions;
+use metl::embed::EmbedMsl;
+
+fn main() {
+    EmbedMsl::new("shaders/blur.metal")
+        .with_options(CompileOptions::default().fast_math_enabled(true).with_macro("TAPS", 9))
+        .generate()
+        .unwrap();
+}
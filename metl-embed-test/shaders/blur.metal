@@ -0,0 +1,19 @@
+#include "common.h"
+
+vertex VertexOut blur_vertex(uint vid [[vertex_id]]) {
+    VertexOut out;
+    out.uv = float2((vid << 1) & 2, vid & 2);
+    out.position = float4(out.uv * 2.0 - 1.0, 0.0, 1.0);
+    return out;
+}
+
+fragment float4 blur_fragment(VertexOut in [[stage_in]],
+                              texture2d<float> source [[texture(0)]],
+                              sampler linear [[sampler(0)]]) {
+    float4 sum = float4(0.0);
+    for (int i = 0; i < TAPS; i++) {
+        float offset = (i - TAPS / 2) * RADIUS / float(TAPS);
+        sum += source.sample(linear, in.uv + DIRECTION * (offset / float(source.get_width())));
+    }
+    return sum / float(TAPS);
+}
@@ -0,0 +1,45 @@
+//! Embeds a shader with `include_msl!`, to check that the generated code compiles and matches the
+//! shader. This crate is not published.
+
+#[macro_use]
+extern crate metl;
+
+include_msl!("shaders/blur.metal", "RADIUS" => 2.5, "DIRECTION" => "float2(1, 0)");
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use metl::{Device, EmbeddedLibrary, FunctionType, PreprocessorMacroValue};
+
+    #[test]
+    fn test_entry_points() {
+        assert_eq!(Blur::BLUR_VERTEX.name, "blur_vertex");
+        assert_eq!(Blur::BLUR_VERTEX.function_type, FunctionType::Vertex);
+        assert_eq!(Blur::BLUR_FRAGMENT.name, "blur_fragment");
+        assert_eq!(Blur::BLUR_FRAGMENT.function_type, FunctionType::Fragment);
+        assert_eq!(Blur::ENTRY_POINTS, &[Blur::BLUR_VERTEX, Blur::BLUR_FRAGMENT][..]);
+    }
+
+    #[test]
+    fn test_source_has_includes_expanded() {
+        assert!(Blur::SOURCE.contains("struct VertexOut"));
+        assert!(!Blur::SOURCE.contains("#include \"common.h\""));
+    }
+
+    #[test]
+    fn test_macros_are_added_to_the_build_options() {
+        let options = Blur::compile_options();
+        assert_eq!(options.fast_math_enabled, Some(true));
+        let macros = &options.preprocessor_macros;
+        assert_eq!(macros.get("TAPS"), Some(&PreprocessorMacroValue::Integer(9)));
+        assert_eq!(macros.get("RADIUS"), Some(&PreprocessorMacroValue::Floating(2.5)));
+        assert_eq!(macros.get("DIRECTION"),
+                   Some(&PreprocessorMacroValue::String("float2(1, 0)".into())));
+    }
+
+    #[test]
+    fn test_compile_with_the_device() {
+        let mut device = Device::system_default_device().unwrap();
+        assert!(Blur.compile(&mut device).is_ok());
+    }
+}
@@ -0,0 +1,653 @@
+//! Translation of WGSL, SPIR-V and GLSL shaders into MSL for use with metl.
+//!
+//! Shaders shared with a Vulkan or WebGPU renderer bind their resources by descriptor set and
+//! binding, while Metal binds them by buffer, texture and sampler index. `ShaderTranslator` uses
+//! naga to write MSL source, along with a `BindingRemap` which records the Metal index that each
+//! resource was given. Translation does not need a device, so it works on any platform.
+//!
+//! This is a separate crate from metl so that it can be built and tested without a Metal device.
+//! metl's `translate` feature adds `Device::new_library_with_translated_source`, which translates
+//! a shader and compiles the MSL source with `Device::new_library_with_source`:
+//!
+//! ```ignore
+//! let translator = ShaderTranslator::new();
+//! let (mut library, shader) =
+//!     device.new_library_with_translated_source(&translator,
+//!                                               TranslationSource::Wgsl(source),
+//!                                               &Default::default())?;
+//! let function = library.new_function_with_name(shader.msl_name("vs_main").unwrap());
+//! ```
+
+use naga::{AddressSpace, ArraySize, Handle, Module, ShaderStage, Type, TypeInner};
+use naga::back::msl;
+use naga::front::{glsl, spv, wgsl};
+use naga::valid::{Capabilities, ValidationFlags, Validator};
+use std::collections::BTreeMap;
+use std::collections::btree_map;
+use std::error::Error;
+use std::fmt;
+
+const MAX_BUFFERS: usize = 31;
+const MAX_TEXTURES: usize = 128;
+const MAX_SAMPLERS: usize = 16;
+
+/// The type of an entry point. metl's `translate` feature converts it to and from
+/// `metl::FunctionType`.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum FunctionType {
+    Vertex,
+    Fragment,
+    Kernel
+}
+
+/// The Metal attribute which a resource is bound with.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum Binding {
+    /// `[[buffer(n)]]`
+    Buffer(usize),
+
+    /// `[[texture(n)]]`
+    Texture(usize),
+
+    /// `[[sampler(n)]]`
+    Sampler(usize)
+}
+
+impl fmt::Display for Binding {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Binding::Buffer(index) => write!(f, "buffer({})", index),
+            Binding::Texture(index) => write!(f, "texture({})", index),
+            Binding::Sampler(index) => write!(f, "sampler({})", index),
+        }
+    }
+}
+
+/// Shader source in a language which can be translated into MSL.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum TranslationSource<'a> {
+    /// WGSL source text.
+    Wgsl(&'a str),
+
+    /// A SPIR-V binary module.
+    SpirV(&'a [u8]),
+
+    /// GLSL source text for a single stage. GLSL declares one entry point, called `main`.
+    Glsl(&'a str, FunctionType)
+}
+
+/// A descriptor set and binding number, as used by Vulkan, or a group and binding, as used by
+/// WGSL.
+#[derive(Clone, Copy, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub struct DescriptorBinding {
+    pub set: u32,
+    pub binding: u32
+}
+
+impl fmt::Display for DescriptorBinding {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "set {}, binding {}", self.set, self.binding)
+    }
+}
+
+/// The Metal bindings given to the resources of a translated shader.
+///
+/// Buffers, textures and samplers are numbered separately, in order of set and then binding, and
+/// a binding array takes up as many consecutive indices as it has elements. The same indices are
+/// used by every entry point in the shader.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct BindingRemap {
+    bindings: BTreeMap<DescriptorBinding, Binding>,
+    push_constant_buffer: Option<usize>,
+    sizes_buffer: Option<usize>
+}
+
+impl BindingRemap {
+    /// The Metal binding of the resource at `set` and `binding`.
+    pub fn get(&self, set: u32, binding: u32) -> Option<&Binding> {
+        self.bindings.get(&DescriptorBinding { set, binding })
+    }
+
+    /// Iterates over the resources in order of set and binding.
+    pub fn iter(&self) -> btree_map::Iter<'_, DescriptorBinding, Binding> {
+        self.bindings.iter()
+    }
+
+    /// The buffer index which push constants are bound to, if the shader uses them.
+    pub fn push_constant_buffer(&self) -> Option<usize> {
+        self.push_constant_buffer
+    }
+
+    /// The buffer index of the lengths of the runtime sized storage buffers, if the shader has
+    /// any. The buffer holds a `u32` for each of them, in order of set and binding.
+    pub fn sizes_buffer(&self) -> Option<usize> {
+        self.sizes_buffer
+    }
+}
+
+impl fmt::Display for BindingRemap {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for (descriptor, binding) in &self.bindings {
+            writeln!(f, "{} -> {}", descriptor, binding)?;
+        }
+        if let Some(index) = self.push_constant_buffer {
+            writeln!(f, "push constants -> buffer({})", index)?;
+        }
+        if let Some(index) = self.sizes_buffer {
+            writeln!(f, "buffer sizes -> buffer({})", index)?;
+        }
+        Ok(())
+    }
+}
+
+/// An entry point of a translated shader.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct TranslatedEntryPoint {
+    /// The name of the entry point in the original shader.
+    pub name: String,
+
+    /// The name of the function in the MSL source, which differs from `name` when that is
+    /// reserved in MSL, such as `main`.
+    pub msl_name: String,
+
+    pub function_type: FunctionType
+}
+
+/// A shader translated into MSL.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct TranslatedShader {
+    source: String,
+    bindings: BindingRemap,
+    entry_points: Vec<TranslatedEntryPoint>
+}
+
+impl TranslatedShader {
+    /// The MSL source.
+    pub fn source(&self) -> &str {
+        &self.source
+    }
+
+    pub fn into_source(self) -> String {
+        self.source
+    }
+
+    pub fn bindings(&self) -> &BindingRemap {
+        &self.bindings
+    }
+
+    pub fn entry_points(&self) -> &[TranslatedEntryPoint] {
+        &self.entry_points
+    }
+
+    /// The name of the MSL function for the entry point called `name` in the original shader,
+    /// which can be given to `metl::Library::new_function_with_name`.
+    pub fn msl_name(&self, name: &str) -> Option<&str> {
+        self.entry_points.iter()
+            .find(|entry_point| entry_point.name == name)
+            .map(|entry_point| &entry_point.msl_name[..])
+    }
+}
+
+/// Translates shaders into MSL.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct ShaderTranslator {
+    msl_version: (u8, u8)
+}
+
+impl Default for ShaderTranslator {
+    fn default() -> Self {
+        ShaderTranslator { msl_version: msl::Options::default().lang_version }
+    }
+}
+
+impl ShaderTranslator {
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// Sets the version of MSL to write. Some features of the source language need a later
+    /// version than the default of 1.0.
+    pub fn with_msl_version(self, major: u8, minor: u8) -> Self {
+        ShaderTranslator { msl_version: (major, minor) }
+    }
+
+    pub fn msl_version(&self) -> (u8, u8) {
+        self.msl_version
+    }
+
+    /// Translates `source` into MSL.
+    pub fn translate(&self, source: TranslationSource)
+                     -> Result<TranslatedShader, TranslationError> {
+        let module = parse(source)?;
+        let info = match Validator::new(ValidationFlags::all(), Capabilities::all())
+                             .validate(&module) {
+            Ok(info) => info,
+            Err(error) => {
+                let message = match source {
+                    TranslationSource::Wgsl(text) | TranslationSource::Glsl(text, _) => {
+                        error.emit_to_string(text)
+                    }
+                    TranslationSource::SpirV(_) => error.as_inner().to_string(),
+                };
+                return Err(TranslationError::Validation(message));
+            }
+        };
+
+        let (bindings, resources) = assign_bindings(&module)?;
+        let mut options = msl::Options {
+            lang_version: self.msl_version,
+            fake_missing_bindings: false,
+            ..Default::default()
+        };
+        for entry_point in &module.entry_points {
+            options.per_entry_point_map.insert(entry_point.name.clone(), resources.clone());
+        }
+
+        let pipeline_options = msl::PipelineOptions::default();
+        let (msl_source, translation) =
+            match msl::write_string(&module, &info, &options, &pipeline_options) {
+                Ok(output) => output,
+                Err(error) => return Err(TranslationError::Msl(error.to_string())),
+            };
+
+        let mut entry_points = vec![];
+        for (entry_point, msl_name) in module.entry_points.iter()
+                                                          .zip(translation.entry_point_names) {
+            let msl_name = match msl_name {
+                Ok(msl_name) => msl_name,
+                Err(error) => {
+                    let message = format!("{}: {}", entry_point.name, error);
+                    return Err(TranslationError::Msl(message));
+                }
+            };
+            entry_points.push(TranslatedEntryPoint {
+                name: entry_point.name.clone(),
+                msl_name,
+                function_type: function_type(entry_point.stage)
+            });
+        }
+
+        Ok(TranslatedShader { source: msl_source, bindings, entry_points })
+    }
+}
+
+fn parse(source: TranslationSource) -> Result<Module, TranslationError> {
+    match source {
+        TranslationSource::Wgsl(text) => {
+            wgsl::parse_str(text)
+                .map_err(|error| TranslationError::Parse(error.emit_to_string(text)))
+        }
+        TranslationSource::SpirV(data) => {
+            spv::parse_u8_slice(data, &spv::Options::default())
+                .map_err(|error| TranslationError::Parse(error.to_string()))
+        }
+        TranslationSource::Glsl(text, function_type) => {
+            let options = glsl::Options::from(shader_stage(function_type));
+            glsl::Frontend::default()
+                .parse(&options, text)
+                .map_err(|errors| {
+                    let messages = errors.iter()
+                                         .map(|error| {
+                                             let location = error.meta.location(text);
+                                             format!("{}:{}: {}",
+                                                     location.line_number,
+                                                     location.line_position,
+                                                     error)
+                                         })
+                                         .collect::<Vec<_>>();
+                    TranslationError::Parse(messages.join("\n"))
+                })
+        }
+    }
+}
+
+fn shader_stage(function_type: FunctionType) -> ShaderStage {
+    match function_type {
+        FunctionType::Vertex => ShaderStage::Vertex,
+        FunctionType::Fragment => ShaderStage::Fragment,
+        FunctionType::Kernel => ShaderStage::Compute,
+    }
+}
+
+fn function_type(stage: ShaderStage) -> FunctionType {
+    match stage {
+        ShaderStage::Vertex => FunctionType::Vertex,
+        ShaderStage::Fragment => FunctionType::Fragment,
+        ShaderStage::Compute => FunctionType::Kernel,
+    }
+}
+
+/// Counts the indices used for one kind of Metal resource.
+struct Slots {
+    next: usize,
+    max: usize,
+    kind: ResourceKind
+}
+
+impl Slots {
+    fn take(&mut self, count: usize) -> Result<usize, TranslationError> {
+        if self.next + count > self.max {
+            return Err(TranslationError::TooManyBindings(self.kind));
+        }
+        self.next += count;
+        Ok(self.next - count)
+    }
+}
+
+/// Gives each resource in `module` a Metal index, and returns the remap table along with the
+/// equivalent resource map for naga.
+fn assign_bindings(module: &Module)
+                   -> Result<(BindingRemap, msl::EntryPointResources), TranslationError> {
+    let mut globals = module.global_variables
+                            .iter()
+                            .filter_map(|(_, global)| {
+                                global.binding.as_ref().map(|binding| (binding.clone(), global))
+                            })
+                            .collect::<Vec<_>>();
+    globals.sort_by_key(|(binding, _)| (binding.group, binding.binding));
+
+    let mut buffers = Slots { next: 0, max: MAX_BUFFERS, kind: ResourceKind::Buffer };
+    let mut textures = Slots { next: 0, max: MAX_TEXTURES, kind: ResourceKind::Texture };
+    let mut samplers = Slots { next: 0, max: MAX_SAMPLERS, kind: ResourceKind::Sampler };
+
+    let mut remap = BindingRemap::default();
+    let mut resources = msl::EntryPointResources::default();
+    let mut runtime_sized = false;
+    for (resource_binding, global) in globals {
+        let descriptor = DescriptorBinding {
+            set: resource_binding.group,
+            binding: resource_binding.binding
+        };
+        let (inner, array_size) = match module.types[global.ty].inner {
+            TypeInner::BindingArray { base, size: ArraySize::Constant(size) } => {
+                (&module.types[base].inner, Some(size.get()))
+            }
+            TypeInner::BindingArray { .. } => {
+                return Err(TranslationError::UnsupportedBinding(descriptor));
+            }
+            ref inner => (inner, None),
+        };
+        let count = array_size.unwrap_or(1) as usize;
+
+        let mut target = msl::BindTarget { binding_array_size: array_size, ..Default::default() };
+        let binding = match (global.space, inner) {
+            (AddressSpace::Uniform, _) | (AddressSpace::Storage { .. }, _) => {
+                runtime_sized |= is_runtime_sized(module, global.ty);
+                let index = buffers.take(count)?;
+                target.buffer = Some(index as msl::Slot);
+                Binding::Buffer(index)
+            }
+            (AddressSpace::Handle, &TypeInner::Image { .. }) => {
+                let index = textures.take(count)?;
+                target.texture = Some(index as msl::Slot);
+                Binding::Texture(index)
+            }
+            (AddressSpace::Handle, &TypeInner::Sampler { .. }) => {
+                let index = samplers.take(count)?;
+                target.sampler = Some(msl::BindSamplerTarget::Resource(index as msl::Slot));
+                Binding::Sampler(index)
+            }
+            _ => return Err(TranslationError::UnsupportedBinding(descriptor)),
+        };
+        remap.bindings.insert(descriptor, binding);
+        resources.resources.insert(resource_binding, target);
+    }
+
+    let has_push_constants = module.global_variables
+                                   .iter()
+                                   .any(|(_, global)| global.space == AddressSpace::PushConstant);
+    if has_push_constants {
+        let index = buffers.take(1)?;
+        remap.push_constant_buffer = Some(index);
+        resources.push_constant_buffer = Some(index as msl::Slot);
+    }
+    if runtime_sized {
+        let index = buffers.take(1)?;
+        remap.sizes_buffer = Some(index);
+        resources.sizes_buffer = Some(index as msl::Slot);
+    }
+    Ok((remap, resources))
+}
+
+/// Whether `ty` is a runtime sized array, or a struct which ends with one.
+fn is_runtime_sized(module: &Module, ty: Handle<Type>) -> bool {
+    match module.types[ty].inner {
+        TypeInner::Array { size: ArraySize::Dynamic, .. } => true,
+        TypeInner::Struct { ref members, .. } => {
+            members.last().is_some_and(|member| is_runtime_sized(module, member.ty))
+        }
+        _ => false,
+    }
+}
+
+/// A kind of resource that Metal numbers separately.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum ResourceKind {
+    Buffer,
+    Texture,
+    Sampler
+}
+
+/// An error produced when a shader cannot be translated.
+#[derive(Debug)]
+pub enum TranslationError {
+    /// The shader could not be parsed. The message includes the location of the error in the
+    /// source, if it is text.
+    Parse(String),
+
+    /// The shader is not valid.
+    Validation(String),
+
+    /// The resource at this binding has a type which Metal cannot bind, such as a binding array
+    /// without a fixed size.
+    UnsupportedBinding(DescriptorBinding),
+
+    /// The shader uses more of this kind of resource than a Metal function can bind.
+    TooManyBindings(ResourceKind),
+
+    /// The MSL source could not be written.
+    Msl(String)
+}
+
+impl fmt::Display for TranslationError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            TranslationError::Parse(ref message) => {
+                write!(f, "the shader could not be parsed: {}", message)
+            }
+            TranslationError::Validation(ref message) => {
+                write!(f, "the shader is not valid: {}", message)
+            }
+            TranslationError::UnsupportedBinding(descriptor) => {
+                write!(f, "the resource at {} cannot be bound in Metal", descriptor)
+            }
+            TranslationError::TooManyBindings(kind) => {
+                let kind = match kind {
+                    ResourceKind::Buffer => "buffer",
+                    ResourceKind::Texture => "texture",
+                    ResourceKind::Sampler => "sampler",
+                };
+                write!(f, "the shader uses more than the maximum number of {} bindings", kind)
+            }
+            TranslationError::Msl(ref message) => {
+                write!(f, "the MSL source could not be written: {}", message)
+            }
+        }
+    }
+}
+
+impl Error for TranslationError {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::env;
+    use std::fs;
+    use std::path::Path;
+
+    const LIGHTING_WGSL: &str = "
+struct Globals {
+    view_proj: mat4x4<f32>,
+}
+
+@group(0) @binding(0) var<uniform> globals: Globals;
+@group(1) @binding(0) var albedo: texture_2d<f32>;
+@group(1) @binding(1) var albedo_sampler: sampler;
+@group(1) @binding(2) var<storage, read> lights: array<vec4<f32>>;
+
+@vertex
+fn vs_main(@location(0) position: vec3<f32>) -> @builtin(position) vec4<f32> {
+    return globals.view_proj * vec4<f32>(position, 1.0);
+}
+
+@fragment
+fn fs_main(@builtin(position) coord: vec4<f32>) -> @location(0) vec4<f32> {
+    return textureSample(albedo, albedo_sampler, coord.xy) * lights[0];
+}
+";
+
+    const SCALE_GLSL: &str = "
+#version 450
+layout(local_size_x = 64) in;
+layout(set = 0, binding = 0) buffer Data { float values[]; };
+layout(set = 0, binding = 1) uniform Params { float scale; };
+layout(push_constant) uniform Push { uint offset; } push;
+
+void main() {
+    values[gl_GlobalInvocationID.x + push.offset] *= scale;
+}
+";
+
+    const SCALE_SPIRV_WGSL: &str = "
+@group(0) @binding(0) var<storage, read_write> values: array<f32>;
+@group(0) @binding(1) var<uniform> scale: f32;
+@group(1) @binding(0) var weights: texture_2d<f32>;
+
+@compute @workgroup_size(64)
+fn scale_values(@builtin(global_invocation_id) id: vec3<u32>) {
+    values[id.x] *= scale * textureLoad(weights, vec2<i32>(id.xy), 0).x;
+}
+";
+
+    /// Checks `source` against the MSL in `tests/golden/{name}`. Set `METL_TRANSLATE_BLESS` to
+    /// write `source` to the file instead, after checking that a change in naga's output is
+    /// expected.
+    fn assert_golden(name: &str, source: &str) {
+        let path = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests").join("golden").join(name);
+        if env::var_os("METL_TRANSLATE_BLESS").is_some() {
+            fs::write(&path, source).unwrap();
+            return;
+        }
+        let expected = fs::read_to_string(&path).unwrap();
+        assert!(source == expected,
+                "the MSL source differs from {}:\n{}",
+                path.display(),
+                source);
+    }
+
+    /// Writes a SPIR-V module for `wgsl` with naga's SPIR-V backend.
+    fn spirv_from_wgsl(wgsl: &str) -> Vec<u8> {
+        let module = wgsl::parse_str(wgsl).unwrap();
+        let info = Validator::new(ValidationFlags::all(), Capabilities::all()).validate(&module)
+                                                                              .unwrap();
+        let words = naga::back::spv::write_vec(&module, &info, &Default::default(), None)
+                        .unwrap();
+        words.iter().flat_map(|word| word.to_le_bytes()).collect()
+    }
+
+    #[test]
+    fn test_translate_wgsl() {
+        let shader = ShaderTranslator::new().with_msl_version(2, 0)
+                                            .translate(TranslationSource::Wgsl(LIGHTING_WGSL))
+                                            .unwrap();
+        assert_eq!(shader.bindings().to_string(),
+                   "set 0, binding 0 -> buffer(0)\n\
+                    set 1, binding 0 -> texture(0)\n\
+                    set 1, binding 1 -> sampler(0)\n\
+                    set 1, binding 2 -> buffer(1)\n\
+                    buffer sizes -> buffer(2)\n");
+        assert_eq!(shader.bindings().get(1, 1), Some(&Binding::Sampler(0)));
+        assert_eq!(shader.bindings().get(2, 0), None);
+
+        let entry_points = shader.entry_points()
+                                 .iter()
+                                 .map(|entry_point| {
+                                     (&entry_point.name[..], entry_point.function_type)
+                                 })
+                                 .collect::<Vec<_>>();
+        assert_eq!(entry_points,
+                   [("vs_main", FunctionType::Vertex), ("fs_main", FunctionType::Fragment)]);
+
+        let vs_main = shader.msl_name("vs_main").unwrap();
+        assert!(shader.source().contains(&format!(" {}(", vs_main)[..]));
+        for attribute in &["[[buffer(0)]]", "[[texture(0)]]", "[[sampler(0)]]", "[[buffer(1)]]"] {
+            assert!(shader.source().contains(attribute), "missing {}", attribute);
+        }
+        assert_golden("lighting.metal", shader.source());
+    }
+
+    #[test]
+    fn test_translate_glsl() {
+        let source = TranslationSource::Glsl(SCALE_GLSL, FunctionType::Kernel);
+        let shader = ShaderTranslator::new().with_msl_version(2, 0).translate(source).unwrap();
+        assert_eq!(shader.bindings().to_string(),
+                   "set 0, binding 0 -> buffer(0)\n\
+                    set 0, binding 1 -> buffer(1)\n\
+                    push constants -> buffer(2)\n\
+                    buffer sizes -> buffer(3)\n");
+        assert_eq!(shader.entry_points().len(), 1);
+        assert_eq!(shader.entry_points()[0].function_type, FunctionType::Kernel);
+
+        // `main` is reserved in MSL, so the kernel is renamed.
+        let kernel = shader.msl_name("main").unwrap();
+        assert!(kernel != "main");
+        assert!(shader.source().contains(&format!("kernel void {}(", kernel)[..]));
+        assert_golden("scale_glsl.metal", shader.source());
+    }
+
+    #[test]
+    fn test_translate_spirv() {
+        let spirv = spirv_from_wgsl(SCALE_SPIRV_WGSL);
+        let shader = ShaderTranslator::new().with_msl_version(2, 0)
+                                            .translate(TranslationSource::SpirV(&spirv))
+                                            .unwrap();
+        assert_eq!(shader.bindings().to_string(),
+                   "set 0, binding 0 -> buffer(0)\n\
+                    set 0, binding 1 -> buffer(1)\n\
+                    set 1, binding 0 -> texture(0)\n\
+                    buffer sizes -> buffer(2)\n");
+        assert_eq!(shader.entry_points().len(), 1);
+        assert_eq!(shader.entry_points()[0].name, "scale_values");
+        assert_eq!(shader.entry_points()[0].function_type, FunctionType::Kernel);
+        assert_golden("scale_spirv.metal", shader.source());
+    }
+
+    #[test]
+    fn test_translation_errors() {
+        let translator = ShaderTranslator::new();
+        match translator.translate(TranslationSource::Wgsl("fn broken( {}")) {
+            Err(TranslationError::Parse(_)) => (),
+            other => panic!("expected a parse error, found {:?}", other),
+        }
+        let glsl = TranslationSource::Glsl("#version 450\nvoid main() { undefined(); }\n",
+                                           FunctionType::Fragment);
+        match translator.translate(glsl) {
+            Err(TranslationError::Parse(ref message)) if message.starts_with("2:") => (),
+            other => panic!("expected a parse error on line 2, found {:?}", other),
+        }
+        match translator.translate(TranslationSource::SpirV(&[0, 1, 2, 3])) {
+            Err(TranslationError::Parse(_)) => (),
+            other => panic!("expected a parse error, found {:?}", other),
+        }
+
+        let mut samplers = String::new();
+        for binding in 0..MAX_SAMPLERS + 1 {
+            samplers.push_str(&format!("@group(0) @binding({0}) var s{0}: sampler;\n", binding));
+        }
+        samplers.push_str("@compute @workgroup_size(1)\nfn main() {}\n");
+        match translator.translate(TranslationSource::Wgsl(&samplers)) {
+            Err(TranslationError::TooManyBindings(ResourceKind::Sampler)) => (),
+            other => panic!("expected too many samplers, found {:?}", other),
+        }
+    }
+}
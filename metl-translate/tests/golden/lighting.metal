@@ -0,0 +1,47 @@
+// language: metal2.0
+#include <metal_stdlib>
+#include <simd/simd.h>
+
+using metal::uint;
+
+struct _mslBufferSizes {
+    uint size3;
+};
+
+struct Globals {
+    metal::float4x4 view_proj;
+};
+typedef metal::float4 type_4[1];
+
+struct vs_mainInput {
+    metal::float3 position [[attribute(0)]];
+};
+struct vs_mainOutput {
+    metal::float4 member [[position]];
+};
+vertex vs_mainOutput vs_main(
+  vs_mainInput varyings [[stage_in]]
+, constant Globals& globals [[buffer(0)]]
+) {
+    const auto position = varyings.position;
+    metal::float4x4 _e3 = globals.view_proj;
+    return vs_mainOutput { _e3 * metal::float4(position, 1.0) };
+}
+
+
+struct fs_mainInput {
+};
+struct fs_mainOutput {
+    metal::float4 member_1 [[color(0)]];
+};
+fragment fs_mainOutput fs_main(
+  metal::float4 coord [[position]]
+, metal::texture2d<float, metal::access::sample> albedo [[texture(0)]]
+, metal::sampler albedo_sampler [[sampler(0)]]
+, device type_4 const& lights [[buffer(1)]]
+, constant _mslBufferSizes& _buffer_sizes [[buffer(2)]]
+) {
+    metal::float4 _e4 = albedo.sample(albedo_sampler, coord.xy);
+    metal::float4 _e7 = lights[0];
+    return fs_mainOutput { _e4 * _e7 };
+}
@@ -0,0 +1,52 @@
+// language: metal2.0
+#include <metal_stdlib>
+#include <simd/simd.h>
+
+using metal::uint;
+
+struct _mslBufferSizes {
+    uint size0;
+};
+
+typedef float type_1[1];
+struct Data {
+    type_1 values;
+};
+struct Params {
+    float scale;
+};
+struct Push {
+    uint offset;
+};
+
+void main_1(
+    device Data& global,
+    constant Params& global_1,
+    constant Push& push,
+    thread metal::uint3& gl_GlobalInvocationID,
+    constant _mslBufferSizes& _buffer_sizes
+) {
+    metal::uint3 _e8 = gl_GlobalInvocationID;
+    Push _e10 = push;
+    metal::uint3 _e14 = gl_GlobalInvocationID;
+    Push _e16 = push;
+    float _e20 = global.values[_e14.x + _e16.offset];
+    float _e21 = global_1.scale;
+    global.values[_e8.x + _e10.offset] = _e20 * _e21;
+    return;
+}
+
+struct main_Input {
+};
+kernel void main_(
+  metal::uint3 param [[thread_position_in_grid]]
+, device Data& global [[buffer(0)]]
+, constant Params& global_1 [[buffer(1)]]
+, constant Push& push [[buffer(2)]]
+, constant _mslBufferSizes& _buffer_sizes [[buffer(3)]]
+) {
+    metal::uint3 gl_GlobalInvocationID = {};
+    gl_GlobalInvocationID = param;
+    main_1(global, global_1, push, gl_GlobalInvocationID, _buffer_sizes);
+    return;
+}
@@ -0,0 +1,46 @@
+// language: metal2.0
+#include <metal_stdlib>
+#include <simd/simd.h>
+
+using metal::uint;
+
+struct _mslBufferSizes {
+    uint size0;
+};
+
+typedef float type_1[1];
+struct type_6 {
+    type_1 member;
+};
+struct type_8 {
+    float member;
+};
+
+void scale_values_1(
+    device type_6& values,
+    constant type_8& scale,
+    metal::texture2d<float, metal::access::sample> weights,
+    thread metal::uint3& id_1,
+    constant _mslBufferSizes& _buffer_sizes
+) {
+    metal::uint3 _e6 = id_1;
+    float _e10 = scale.member;
+    metal::float4 _e13 = weights.read(metal::uint2(as_type<metal::int2>(_e6.xy)), 0);
+    float _e17 = values.member[_e6.x];
+    values.member[_e6.x] = _e17 * (_e10 * _e13.x);
+    return;
+}
+
+struct scale_valuesInput {
+};
+kernel void scale_values(
+  metal::uint3 id [[thread_position_in_grid]]
+, device type_6& values [[buffer(0)]]
+, constant type_8& scale [[buffer(1)]]
+, metal::texture2d<float, metal::access::sample> weights [[texture(0)]]
+, constant _mslBufferSizes& _buffer_sizes [[buffer(2)]]
+) {
+    metal::uint3 id_1 = {};
+    id_1 = id;
+    scale_values_1(values, scale, weights, id_1, _buffer_sizes);
+}
@@ -0,0 +1,705 @@
+//! Sub-allocation of small regions from large buffers.
+//!
+//! Creating a `Buffer` for every small upload is expensive, so these allocators carve aligned
+//! regions out of a single large buffer instead. Each allocator is split into a bookkeeping type,
+//! which only deals in byte offsets, and a wrapper which owns the `Buffer` and returns
+//! `BufferAllocation`s:
+//!
+//! * `LinearBufferAllocator` hands out regions in order and frees them all at once, which suits
+//!   data which only lives for a single frame.
+//! * `RingBufferAllocator` recycles regions once the command buffers which used them have
+//!   completed, so that it can be used across frames without stalling.
+//! * `BuddyBufferAllocator` frees regions individually, for data which lives longer.
+//!
+//! Alignments must be powers of two.
+
+use cocoa::base::id;
+use std::cell::Cell;
+use std::cmp;
+use std::collections::{BTreeSet, HashMap, VecDeque};
+use std::mem;
+#[cfg(target_os = "macos")]
+use std::ops::Range;
+use std::rc::Rc;
+use std::slice;
+use sys::{MTLCommandBuffer, MTLCommandBufferStatus};
+use {AsRaw, Buffer, CommandBuffer, StrongPtr};
+
+fn align_up(value: usize, alignment: usize) -> usize {
+    assert!(alignment.is_power_of_two(), "the alignment {} is not a power of two", alignment);
+    (value + alignment - 1) & !(alignment - 1)
+}
+
+/// Allocates regions in order from the start of a range of bytes, and frees them all at once.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct LinearAllocator {
+    capacity: usize,
+    offset: usize
+}
+
+impl LinearAllocator {
+    pub fn new(capacity: usize) -> Self {
+        LinearAllocator { capacity: capacity, offset: 0 }
+    }
+
+    /// Allocates `len` bytes aligned to `alignment`, returning the offset of the region.
+    pub fn allocate(&mut self, len: usize, alignment: usize) -> Option<usize> {
+        let start = align_up(self.offset, alignment);
+        if start <= self.capacity && len <= self.capacity - start {
+            self.offset = start + len;
+            Some(start)
+        } else {
+            None
+        }
+    }
+
+    /// Frees all allocations.
+    pub fn reset(&mut self) {
+        self.offset = 0;
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    /// The number of bytes allocated since the last reset, including alignment padding.
+    pub fn used(&self) -> usize {
+        self.offset
+    }
+}
+
+/// Identifies the allocations made by a `RingAllocator` between two calls to `end_frame`.
+#[derive(Clone, Copy, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub struct FrameId(u64);
+
+/// Allocates regions from a range of bytes which is used as a ring buffer.
+///
+/// Allocations are grouped into frames by `end_frame`. Frames are released in the order that
+/// they were ended, and the regions which they used are recycled for new allocations.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct RingAllocator {
+    capacity: usize,
+    head: usize,
+    tail: usize,
+    used: usize,
+    pending: usize,
+    frames: VecDeque<(FrameId, usize, usize)>,
+    next_frame: u64
+}
+
+impl RingAllocator {
+    pub fn new(capacity: usize) -> Self {
+        RingAllocator {
+            capacity: capacity,
+            head: 0,
+            tail: 0,
+            used: 0,
+            pending: 0,
+            frames: VecDeque::new(),
+            next_frame: 0
+        }
+    }
+
+    /// Allocates `len` bytes aligned to `alignment`, returning the offset of the region, or
+    /// `None` if there is not enough space until more frames are released.
+    pub fn allocate(&mut self, len: usize, alignment: usize) -> Option<usize> {
+        // Frames which are waiting to be released may be empty, but they do not hold any space,
+        // so the ring can start again from the beginning once no frame holds any.
+        if self.pending == 0 && self.frames.iter().all(|&(_, _, size)| size == 0) {
+            self.head = 0;
+            self.tail = 0;
+        }
+
+        let start = align_up(self.head, alignment);
+        let fits_before = |end: usize| start <= end && len <= end - start;
+        let start = if self.head > self.tail || self.used == 0 {
+            // The free space runs from the head to the end, and then from the start to the tail.
+            if fits_before(self.capacity) {
+                start
+            } else if len <= self.tail {
+                0
+            } else {
+                return None;
+            }
+        } else if self.head < self.tail && fits_before(self.tail) {
+            start
+        } else {
+            return None;
+        };
+
+        // Any space skipped at the end of the ring is counted as part of the allocation, so that
+        // it is recycled along with it.
+        let consumed = if start < self.head {
+            self.capacity - self.head + len
+        } else {
+            start + len - self.head
+        };
+        self.head = start + len;
+        self.used += consumed;
+        self.pending += consumed;
+        Some(start)
+    }
+
+    /// Groups the allocations made since the last call into a frame, which can be released once
+    /// the GPU has finished with them.
+    pub fn end_frame(&mut self) -> FrameId {
+        let frame = FrameId(self.next_frame);
+        self.next_frame += 1;
+        self.frames.push_back((frame, self.head, self.pending));
+        self.pending = 0;
+        frame
+    }
+
+    /// Releases `frame` and any frames which were ended before it.
+    pub fn release_frame(&mut self, frame: FrameId) {
+        while self.frames.front().map_or(false, |&(id, _, _)| id <= frame) {
+            let (_, end, size) = self.frames.pop_front().unwrap();
+            // An empty frame did not allocate anything, so the head it recorded may be stale.
+            if size > 0 {
+                self.tail = end;
+                self.used -= size;
+            }
+        }
+    }
+
+    /// The oldest frame which has not been released.
+    pub fn oldest_frame(&self) -> Option<FrameId> {
+        self.frames.front().map(|&(id, _, _)| id)
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+}
+
+/// Allocates regions from a range of bytes using a buddy allocator, so that regions can be freed
+/// individually.
+///
+/// Regions are rounded up to a power of two which is at least `min_block_size`, and are aligned
+/// to their size.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct BuddyAllocator {
+    capacity: usize,
+    min_block_size: usize,
+    free_blocks: Vec<BTreeSet<usize>>,
+    allocated: HashMap<usize, usize>
+}
+
+impl BuddyAllocator {
+    /// Creates an allocator for `capacity` bytes. `min_block_size` must be a power of two.
+    pub fn new(capacity: usize, min_block_size: usize) -> Self {
+        assert!(min_block_size.is_power_of_two(),
+                "the minimum block size {} is not a power of two", min_block_size);
+        let capacity = capacity / min_block_size * min_block_size;
+        let mut allocator = BuddyAllocator {
+            capacity: capacity,
+            min_block_size: min_block_size,
+            free_blocks: vec![],
+            allocated: HashMap::new()
+        };
+
+        // Split the capacity into the largest possible blocks, each aligned to its size.
+        let mut offset = 0;
+        while offset < capacity {
+            let mut size = min_block_size;
+            while offset + size * 2 <= capacity && offset % (size * 2) == 0 {
+                size *= 2;
+            }
+            allocator.insert_free_block(offset, size);
+            offset += size;
+        }
+        allocator
+    }
+
+    /// Allocates `len` bytes aligned to `alignment`, returning the offset of the region.
+    pub fn allocate(&mut self, len: usize, alignment: usize) -> Option<usize> {
+        assert!(alignment.is_power_of_two(), "the alignment {} is not a power of two", alignment);
+        let size = match cmp::max(cmp::max(len, alignment), self.min_block_size)
+                               .checked_next_power_of_two() {
+            Some(size) => size,
+            None => return None,
+        };
+        let order = self.order(size);
+
+        let found = (order..self.free_blocks.len()).find(|&order| {
+            !self.free_blocks[order].is_empty()
+        });
+        let found = match found {
+            Some(found) => found,
+            None => return None,
+        };
+
+        let offset = *self.free_blocks[found].iter().next().unwrap();
+        self.free_blocks[found].remove(&offset);
+        // Split the block, returning the upper halves to the free lists.
+        for split in (order..found).rev() {
+            let half = self.min_block_size << split;
+            self.insert_free_block(offset + half, half);
+        }
+        self.allocated.insert(offset, size);
+        Some(offset)
+    }
+
+    /// Frees the region at `offset`, returning its size, or `None` if no region was allocated
+    /// there.
+    pub fn free(&mut self, offset: usize) -> Option<usize> {
+        let allocated_size = match self.allocated.remove(&offset) {
+            Some(size) => size,
+            None => return None,
+        };
+
+        // Merge the block with its buddy for as long as the buddy is also free.
+        let (mut offset, mut size) = (offset, allocated_size);
+        loop {
+            let order = self.order(size);
+            let buddy = offset ^ size;
+            if order < self.free_blocks.len() && self.free_blocks[order].remove(&buddy) {
+                offset = cmp::min(offset, buddy);
+                size *= 2;
+            } else {
+                break;
+            }
+        }
+        self.insert_free_block(offset, size);
+        Some(allocated_size)
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    /// The number of bytes which are free, including free space which is too fragmented to be
+    /// allocated as a single region.
+    pub fn free_bytes(&self) -> usize {
+        self.free_blocks.iter().enumerate().map(|(order, blocks)| {
+            blocks.len() * (self.min_block_size << order)
+        }).sum()
+    }
+
+    fn order(&self, size: usize) -> usize {
+        (size / self.min_block_size).trailing_zeros() as usize
+    }
+
+    fn insert_free_block(&mut self, offset: usize, size: usize) {
+        let order = self.order(size);
+        while self.free_blocks.len() <= order {
+            self.free_blocks.push(BTreeSet::new());
+        }
+        self.free_blocks[order].insert(offset);
+    }
+}
+
+/// A region of a buffer allocated by `LinearBufferAllocator`, `RingBufferAllocator` or
+/// `BuddyBufferAllocator`.
+///
+/// The contents of an allocation can only be accessed within its own region, so that mutable
+/// access to different allocations never overlaps. Once the allocator recycles the region, the
+/// allocation is stale and its contents can no longer be accessed. Use
+/// `RenderCommandEncoder::set_vertex_allocation` and `set_fragment_allocation` to bind it.
+pub struct BufferAllocation {
+    buffer: Buffer,
+    offset: usize,
+    len: usize,
+    generation: u64,
+    recycled: Rc<Cell<u64>>
+}
+
+impl BufferAllocation {
+    fn new(buffer: &Buffer, offset: usize, len: usize, generation: u64,
+           recycled: &Rc<Cell<u64>>) -> Self {
+        // `Buffer` does not retain the `MTLBuffer`, so the allocation can wrap the allocator's
+        // buffer directly, without checking its type again as `FromRaw::from_raw` would.
+        let buffer: Buffer = unsafe { mem::transmute(*buffer.as_raw()) };
+        BufferAllocation {
+            buffer: buffer,
+            offset: offset,
+            len: len,
+            generation: generation,
+            recycled: recycled.clone()
+        }
+    }
+
+    /// The offset in bytes of the region from the start of the buffer.
+    pub fn offset(&self) -> usize {
+        self.offset
+    }
+
+    /// The length of the region in bytes.
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Whether the allocator has recycled the region, so that it may now belong to another
+    /// allocation.
+    pub fn is_stale(&self) -> bool {
+        self.generation < self.recycled.get()
+    }
+
+    /// Returns the contents of the region, or `None` if the allocation is stale or the buffer has
+    /// private storage.
+    pub fn contents(&self) -> Option<&[u8]> {
+        self.contents_ptr().map(|ptr| unsafe { slice::from_raw_parts(ptr, self.len) })
+    }
+
+    /// Returns the contents of the region mutably, or `None` if the allocation is stale or the
+    /// buffer has private storage.
+    ///
+    /// Buffers with managed storage must be told about writes with `did_modify_range` before
+    /// they are next used by the GPU.
+    ///
+    /// # Safety
+    ///
+    /// Other allocations cannot overlap the region, but the buffer which the allocator was
+    /// created with may still be wrapped elsewhere. The caller must ensure that the region is not
+    /// accessed through another `Buffer` while the slice is borrowed.
+    pub unsafe fn contents_mut(&mut self) -> Option<&mut [u8]> {
+        self.contents_ptr().map(|ptr| slice::from_raw_parts_mut(ptr, self.len))
+    }
+
+    fn contents_ptr(&self) -> Option<*mut u8> {
+        if self.is_stale() {
+            return None;
+        }
+        self.buffer.contents_ptr().map(|ptr| unsafe { ptr.offset(self.offset as isize) })
+    }
+
+    /// Informs the GPU that the bytes in `range`, relative to the start of the region, were
+    /// modified by the CPU.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `range` is not within the region.
+    #[cfg(target_os = "macos")]
+    pub fn did_modify_range(&mut self, range: Range<usize>) {
+        assert!(range.start <= range.end && range.end <= self.len,
+                "the range {:?} is out of bounds of an allocation of length {}", range, self.len);
+        self.buffer.did_modify_range(self.offset + range.start..self.offset + range.end)
+    }
+}
+
+impl AsRaw for BufferAllocation {
+    fn as_raw(&self) -> &id {
+        self.buffer.as_raw()
+    }
+
+    fn as_raw_mut(&mut self) -> &mut id {
+        self.buffer.as_raw_mut()
+    }
+}
+
+/// Carves per-frame allocations out of a buffer. See `LinearAllocator`.
+pub struct LinearBufferAllocator {
+    buffer: Buffer,
+    allocator: LinearAllocator,
+    generation: u64,
+    recycled: Rc<Cell<u64>>
+}
+
+impl LinearBufferAllocator {
+    pub fn new(buffer: Buffer) -> Self {
+        let allocator = LinearAllocator::new(buffer.length());
+        LinearBufferAllocator {
+            buffer: buffer,
+            allocator: allocator,
+            generation: 0,
+            recycled: Rc::new(Cell::new(0))
+        }
+    }
+
+    /// Allocates `len` bytes aligned to `alignment`.
+    pub fn allocate(&mut self, len: usize, alignment: usize) -> Option<BufferAllocation> {
+        let (buffer, generation, recycled) = (&self.buffer, self.generation, &self.recycled);
+        self.allocator.allocate(len, alignment).map(|offset| {
+            BufferAllocation::new(buffer, offset, len, generation, recycled)
+        })
+    }
+
+    /// Frees all allocations, which become stale. Any command buffers which use them must have
+    /// completed.
+    pub fn reset(&mut self) {
+        self.allocator.reset();
+        self.generation += 1;
+        self.recycled.set(self.generation);
+    }
+}
+
+/// Carves allocations out of a buffer, recycling them once the command buffers which use them
+/// have completed. See `RingAllocator`.
+pub struct RingBufferAllocator {
+    buffer: Buffer,
+    allocator: RingAllocator,
+    command_buffers: VecDeque<(FrameId, StrongPtr)>,
+    recycled: Rc<Cell<u64>>
+}
+
+impl RingBufferAllocator {
+    pub fn new(buffer: Buffer) -> Self {
+        let allocator = RingAllocator::new(buffer.length());
+        RingBufferAllocator {
+            buffer: buffer,
+            allocator: allocator,
+            command_buffers: VecDeque::new(),
+            recycled: Rc::new(Cell::new(0))
+        }
+    }
+
+    /// Allocates `len` bytes aligned to `alignment`, or returns `None` if the buffer is full of
+    /// regions which are still in use.
+    pub fn allocate(&mut self, len: usize, alignment: usize) -> Option<BufferAllocation> {
+        let offset = match self.allocator.allocate(len, alignment) {
+            Some(offset) => Some(offset),
+            None => {
+                self.reclaim();
+                self.allocator.allocate(len, alignment)
+            }
+        };
+        // The allocation belongs to the frame which is ended by the next call to `submit`.
+        let (buffer, frame, recycled) = (&self.buffer, self.allocator.next_frame, &self.recycled);
+        offset.map(|offset| BufferAllocation::new(buffer, offset, len, frame, recycled))
+    }
+
+    /// Marks the allocations made since the last call as used by `command_buffer`. They will be
+    /// recycled once it has completed, and become stale.
+    pub fn submit(&mut self, command_buffer: &CommandBuffer) {
+        let frame = self.allocator.end_frame();
+        let retained: id = unsafe { msg_send![*command_buffer.as_raw(), retain] };
+        self.command_buffers.push_back((frame, StrongPtr::from(retained)));
+    }
+
+    /// Recycles the regions used by command buffers which have completed.
+    pub fn reclaim(&mut self) {
+        loop {
+            let frame = match self.command_buffers.front() {
+                Some(&(frame, ref command_buffer)) => {
+                    match unsafe { MTLCommandBuffer::status(**command_buffer) } {
+                        MTLCommandBufferStatus::MTLCommandBufferStatusCompleted |
+                        MTLCommandBufferStatus::MTLCommandBufferStatusError => frame,
+                        _ => break,
+                    }
+                }
+                None => break,
+            };
+            self.allocator.release_frame(frame);
+            self.recycled.set(frame.0 + 1);
+            self.command_buffers.pop_front();
+        }
+    }
+}
+
+/// Carves allocations which can be freed individually out of a buffer. See `BuddyAllocator`.
+pub struct BuddyBufferAllocator {
+    buffer: Buffer,
+    allocator: BuddyAllocator,
+    recycled: Rc<Cell<u64>>
+}
+
+impl BuddyBufferAllocator {
+    pub fn new(buffer: Buffer, min_block_size: usize) -> Self {
+        let allocator = BuddyAllocator::new(buffer.length(), min_block_size);
+        BuddyBufferAllocator {
+            buffer: buffer,
+            allocator: allocator,
+            recycled: Rc::new(Cell::new(0))
+        }
+    }
+
+    /// Allocates `len` bytes aligned to `alignment`.
+    pub fn allocate(&mut self, len: usize, alignment: usize) -> Option<BufferAllocation> {
+        let (buffer, recycled) = (&self.buffer, &self.recycled);
+        self.allocator.allocate(len, alignment).map(|offset| {
+            BufferAllocation::new(buffer, offset, len, 0, recycled)
+        })
+    }
+
+    /// Frees `allocation`. Any command buffers which use it must have completed.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `allocation` was not allocated by this allocator.
+    pub fn free(&mut self, allocation: BufferAllocation) {
+        assert!(Rc::ptr_eq(&allocation.recycled, &self.recycled),
+                "the allocation was not made by this allocator");
+        self.allocator.free(allocation.offset);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_linear_allocator() {
+        let mut allocator = LinearAllocator::new(256);
+        assert_eq!(allocator.allocate(10, 1), Some(0));
+        assert_eq!(allocator.allocate(16, 16), Some(16));
+        assert_eq!(allocator.allocate(0, 64), Some(64));
+        assert_eq!(allocator.allocate(192, 4), Some(64));
+        assert_eq!(allocator.used(), 256);
+        assert_eq!(allocator.allocate(1, 1), None);
+        assert_eq!(allocator.allocate(0, 512), None);
+
+        allocator.reset();
+        assert_eq!(allocator.allocate(256, 256), Some(0));
+        assert_eq!(allocator.allocate(usize::max_value(), 1), None);
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_alignment_must_be_power_of_two() {
+        LinearAllocator::new(256).allocate(4, 3);
+    }
+
+    #[test]
+    fn test_ring_allocator_recycles_frames() {
+        let mut ring = RingAllocator::new(100);
+        assert_eq!(ring.allocate(40, 1), Some(0));
+        let frame0 = ring.end_frame();
+        assert_eq!(ring.allocate(40, 1), Some(40));
+        let frame1 = ring.end_frame();
+
+        // Frame 0 is still in use, so the space at the start cannot be reused yet.
+        assert_eq!(ring.allocate(30, 1), None);
+        assert_eq!(ring.oldest_frame(), Some(frame0));
+        ring.release_frame(frame0);
+        assert_eq!(ring.oldest_frame(), Some(frame1));
+
+        // The remaining 20 bytes at the end are skipped, and the allocation wraps around.
+        assert_eq!(ring.allocate(30, 1), Some(0));
+        assert_eq!(ring.allocate(10, 1), Some(30));
+        assert_eq!(ring.allocate(1, 1), None);
+
+        ring.end_frame();
+
+        // The free space is split between [40, 80) and [80, 100), so only 40 bytes fit.
+        ring.release_frame(frame1);
+        assert_eq!(ring.allocate(41, 1), None);
+        assert_eq!(ring.allocate(40, 4), Some(40));
+        assert_eq!(ring.allocate(1, 1), None);
+        let frame3 = ring.end_frame();
+
+        // Releasing everything resets the ring.
+        ring.release_frame(frame3);
+        assert_eq!(ring.oldest_frame(), None);
+        assert_eq!(ring.allocate(100, 1), Some(0));
+    }
+
+    #[test]
+    fn test_ring_allocator_alignment_and_full_ring() {
+        let mut ring = RingAllocator::new(64);
+        assert_eq!(ring.allocate(3, 1), Some(0));
+        assert_eq!(ring.allocate(16, 16), Some(16));
+        assert_eq!(ring.allocate(32, 32), Some(32));
+        let frame0 = ring.end_frame();
+        assert_eq!(ring.allocate(1, 1), None);
+        assert_eq!(ring.allocate(101, 1), None);
+
+        ring.release_frame(frame0);
+        assert_eq!(ring.allocate(64, 64), Some(0));
+        let frame1 = ring.end_frame();
+        assert_eq!(ring.allocate(1, 1), None);
+        ring.release_frame(frame1);
+        assert_eq!(ring.allocate(0, 1), Some(0));
+    }
+
+    #[test]
+    fn test_ring_allocator_empty_frames() {
+        let mut ring = RingAllocator::new(120);
+        assert_eq!(ring.allocate(50, 1), Some(0));
+        let frame_a = ring.end_frame();
+        ring.release_frame(frame_a);
+        let frame_b = ring.end_frame();
+        assert_eq!(ring.allocate(100, 1), Some(0));
+        ring.end_frame();
+
+        // Releasing the empty frame must not free any of the space used by the last frame.
+        ring.release_frame(frame_b);
+        assert_eq!(ring.allocate(40, 1), None);
+        assert_eq!(ring.allocate(20, 1), Some(100));
+    }
+
+    #[test]
+    fn test_ring_allocator_never_overlaps_live_regions() {
+        // Simulates frames of varying sizes with two frames in flight, checking that no live
+        // regions overlap.
+        let mut ring = RingAllocator::new(1000);
+        let mut live: VecDeque<(FrameId, Vec<(usize, usize)>)> = VecDeque::new();
+        let mut seed = 12345u32;
+        for _ in 0..500 {
+            // Some frames are empty, as they are when nothing is drawn.
+            let mut regions = vec![];
+            seed = seed.wrapping_mul(1103515245).wrapping_add(12345);
+            for _ in 0..(seed >> 16) % 5 {
+                seed = seed.wrapping_mul(1103515245).wrapping_add(12345);
+                let len = (seed >> 16) as usize % 100 + 1;
+                let alignment = 1 << ((seed >> 8) % 5);
+                let offset = match ring.allocate(len, alignment) {
+                    Some(offset) => offset,
+                    None => continue,
+                };
+                assert_eq!(offset % alignment, 0);
+                assert!(offset + len <= 1000);
+                let earlier_frames = live.iter().flat_map(|&(_, ref regions)| regions.iter());
+                for &(other, other_len) in earlier_frames.chain(regions.iter()) {
+                    assert!(offset + len <= other || other + other_len <= offset);
+                }
+                regions.push((offset, len));
+            }
+            live.push_back((ring.end_frame(), regions));
+            if live.len() > 2 {
+                let (frame, _) = live.pop_front().unwrap();
+                ring.release_frame(frame);
+            }
+        }
+    }
+
+    #[test]
+    fn test_buddy_allocator() {
+        let mut buddy = BuddyAllocator::new(1024, 64);
+        assert_eq!(buddy.free_bytes(), 1024);
+        assert_eq!(buddy.allocate(100, 1), Some(0));
+        assert_eq!(buddy.allocate(64, 1), Some(128));
+        assert_eq!(buddy.allocate(1, 1), Some(192));
+        assert_eq!(buddy.allocate(10, 256), Some(256));
+        assert_eq!(buddy.allocate(512, 1), Some(512));
+        assert_eq!(buddy.free_bytes(), 0);
+        assert_eq!(buddy.allocate(1, 1), None);
+
+        assert_eq!(buddy.free(128), Some(64));
+        assert_eq!(buddy.free(128), None);
+        assert_eq!(buddy.free(192), Some(64));
+        assert_eq!(buddy.allocate(128, 1), Some(128));
+        assert_eq!(buddy.free(128), Some(128));
+
+        // Freeing everything merges the blocks back together.
+        for &offset in &[0, 256, 512] {
+            buddy.free(offset);
+        }
+        assert_eq!(buddy.free_bytes(), 1024);
+        assert_eq!(buddy.allocate(1024, 1), Some(0));
+        assert_eq!(buddy.allocate(2048, 1), None);
+    }
+
+    #[test]
+    fn test_buddy_allocator_non_power_of_two_capacity() {
+        let mut buddy = BuddyAllocator::new(100 * 16 + 5, 16);
+        assert_eq!(buddy.capacity(), 1600);
+        assert_eq!(buddy.free_bytes(), 1600);
+
+        // 1600 bytes is split into blocks of 1024, 512 and 64 bytes.
+        assert_eq!(buddy.allocate(1024, 1), Some(0));
+        assert_eq!(buddy.allocate(513, 1), None);
+        assert_eq!(buddy.allocate(512, 1), Some(1024));
+        assert_eq!(buddy.allocate(64, 1), Some(1536));
+        assert_eq!(buddy.allocate(16, 1), None);
+
+        for &offset in &[1536, 1024, 0] {
+            buddy.free(offset);
+        }
+        assert_eq!(buddy.free_bytes(), 1600);
+        assert_eq!(buddy.allocate(1024, 1), Some(0));
+    }
+}
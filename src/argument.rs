@@ -97,7 +97,8 @@ impl Argument {
 impl_from_into_raw!(Argument, of class "MTLArgument");
 
 convertible_enum!{
-    #[derive(Clone, Copy, Eq, Hash, PartialEq)]
+    #[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+    #[cfg_attr(feature = "serialize", derive(Deserialize, Serialize))]
     pub enum ArgumentAccess : MTLArgumentAccess {
         ReadOnly => MTLArgumentAccessReadOnly,
         ReadWrite => MTLArgumentAccessReadWrite,
@@ -106,7 +107,8 @@ convertible_enum!{
 }
 
 convertible_enum! {
-    #[derive(Clone, Copy, Eq, Hash, PartialEq)]
+    #[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+    #[cfg_attr(feature = "serialize", derive(Deserialize, Serialize))]
     pub enum ArgumentType : MTLArgumentType {
         Buffer => MTLArgumentTypeBuffer,
         ThreadgroupMemory => MTLArgumentTypeThreadgroupMemory,
@@ -117,7 +119,8 @@ convertible_enum! {
 
 
 convertible_enum! {
-    #[derive(Clone, Copy, Eq, Hash, PartialEq)]
+    #[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+    #[cfg_attr(feature = "serialize", derive(Deserialize, Serialize))]
     pub enum DataType : MTLDataType {
         None => MTLDataTypeNone,
         Struct => MTLDataTypeStruct,
@@ -14,7 +14,7 @@ impl ArrayType {
     }
 
     pub fn stride(&self) -> usize {
-        unsafe { self.0.stride() as usize() }
+        unsafe { self.0.stride() as usize }
     }
 
     pub fn element_array_type(&self) -> Option<Self> {
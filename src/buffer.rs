@@ -1,5 +1,269 @@
 use cocoa::base::id;
+#[cfg(target_os = "macos")]
+use cocoa::foundation::{NSRange, NSUInteger};
+use std::marker::PhantomData;
+use std::mem;
+use std::ops::{Deref, Range};
+use std::slice;
+use sys::{MTLBuffer, MTLResource, MTLStorageMode};
+use gpu_pod::{cast_slice, cast_slice_mut};
+use {GpuPod, Resource};
+#[cfg(target_os = "macos")]
+use StorageMode;
 
+/// An allocation of unformatted memory which can be accessed by the GPU.
 pub struct Buffer(id);
 
+impl Buffer {
+    /// The length of the buffer in bytes.
+    pub fn length(&self) -> usize {
+        unsafe { MTLBuffer::length(self.0) as usize }
+    }
+
+    /// Returns the contents of the buffer, or `None` if the buffer has private storage and cannot
+    /// be accessed by the CPU.
+    ///
+    /// The GPU may write to the buffer while it is borrowed, so the contents should only be read
+    /// when no command buffers which use it are executing.
+    pub fn contents(&self) -> Option<&[u8]> {
+        self.contents_ptr().map(|ptr| unsafe { slice::from_raw_parts(ptr, self.length()) })
+    }
+
+    /// Returns the contents of the buffer mutably, or `None` if the buffer has private storage
+    /// and cannot be accessed by the CPU.
+    ///
+    /// Buffers with managed storage must be told about writes with `did_modify_range` before
+    /// they are next used by the GPU.
+    ///
+    /// # Safety
+    ///
+    /// A `Buffer` does not own the `MTLBuffer` which it wraps, so there may be other wrappers of
+    /// it, such as one returned by `FromRaw::from_raw` or `Texture::buffer`. The caller must
+    /// ensure that the contents are not accessed through any other wrapper while the slice is
+    /// borrowed.
+    pub unsafe fn contents_mut(&mut self) -> Option<&mut [u8]> {
+        self.contents_ptr().map(|ptr| slice::from_raw_parts_mut(ptr, self.length()))
+    }
+
+    /// Returns the contents of the buffer as a slice of `T`. Any trailing bytes which do not make
+    /// up a whole element are not included.
+    ///
+    /// Returns `None` if the buffer has private storage, or if its contents are not aligned for
+    /// `T`.
+    pub fn contents_as<T: GpuPod>(&self) -> Option<&[T]> {
+        self.contents().and_then(cast_slice)
+    }
+
+    /// Returns the contents of the buffer as a mutable slice of `T`. Any trailing bytes which do
+    /// not make up a whole element are not included.
+    ///
+    /// Returns `None` if the buffer has private storage, or if its contents are not aligned for
+    /// `T`.
+    ///
+    /// # Safety
+    ///
+    /// The contents must not be accessed through another wrapper of the buffer while the slice
+    /// is borrowed, as with `contents_mut`.
+    pub unsafe fn contents_as_mut<T: GpuPod>(&mut self) -> Option<&mut [T]> {
+        self.contents_mut().and_then(cast_slice_mut)
+    }
+
+    /// Returns a pointer to the contents of the buffer, or `None` if the buffer has private
+    /// storage and cannot be accessed by the CPU.
+    pub fn contents_ptr(&self) -> Option<*mut u8> {
+        unsafe {
+            if MTLResource::storageMode(self.0) == MTLStorageMode::MTLStorageModePrivate {
+                return None;
+            }
+            let ptr = MTLBuffer::contents(self.0) as *mut u8;
+            if !ptr.is_null() {
+                Some(ptr)
+            } else if self.length() == 0 {
+                // Empty buffers may not have any storage, so use a dangling aligned pointer.
+                Some(mem::align_of::<u64>() as *mut u8)
+            } else {
+                None
+            }
+        }
+    }
+
+    /// Informs the GPU that the bytes in `range` were modified by the CPU. This must be called
+    /// after writing to a buffer with managed storage.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `range` is not within the buffer.
+    #[cfg(target_os = "macos")]
+    pub fn did_modify_range(&mut self, range: Range<usize>) {
+        assert!(range.start <= range.end && range.end <= self.length(),
+                "the range {:?} is out of bounds of a buffer of length {}", range,
+                self.length());
+        let range = NSRange::new(range.start as NSUInteger,
+                                 (range.end - range.start) as NSUInteger);
+        unsafe { self.0.didModifyRange(range) }
+    }
+}
+
+impl Deref for Buffer {
+    type Target = Resource;
+    fn deref(&self) -> &Self::Target {
+        unsafe { mem::transmute(self) }
+    }
+}
+
 impl_from_into_raw!(Buffer, of protocol "MTLBuffer");
+
+/// A `Buffer` whose contents are an array of `T`.
+pub struct TypedBuffer<T: GpuPod> {
+    buffer: Buffer,
+    marker: PhantomData<T>
+}
+
+impl<T: GpuPod> TypedBuffer<T> {
+    /// Views the contents of `buffer` as an array of `T`. Any trailing bytes which do not make up
+    /// a whole element are not included.
+    pub fn from_buffer(buffer: Buffer) -> Self {
+        TypedBuffer { buffer: buffer, marker: PhantomData }
+    }
+
+    /// The number of elements in the buffer.
+    pub fn len(&self) -> usize {
+        if mem::size_of::<T>() == 0 { 0 } else { self.buffer.length() / mem::size_of::<T>() }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Returns the element at `index`, or `None` if `index` is out of bounds or the buffer has
+    /// private storage.
+    pub fn get(&self, index: usize) -> Option<T> {
+        self.as_slice().and_then(|elements| elements.get(index).cloned())
+    }
+
+    /// Writes `value` to the element at `index`. In OS X, buffers with managed storage are told
+    /// about the modification.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `index` is out of bounds, or if the buffer has private storage.
+    ///
+    /// # Safety
+    ///
+    /// The contents must not be borrowed through another wrapper of the buffer, as with
+    /// `Buffer::contents_mut`.
+    pub unsafe fn set(&mut self, index: usize, value: T) {
+        let len = self.len();
+        match self.as_mut_slice().and_then(|elements| elements.get_mut(index)) {
+            Some(element) => *element = value,
+            None if index >= len => {
+                panic!("index {} is out of bounds of a buffer of length {}", index, len)
+            }
+            None => panic!("the buffer contents are not accessible by the CPU"),
+        }
+        self.did_modify(index..index + 1);
+    }
+
+    #[cfg(target_os = "macos")]
+    fn did_modify(&mut self, range: Range<usize>) {
+        if self.buffer.storage_mode() == StorageMode::Managed {
+            let size = mem::size_of::<T>();
+            self.buffer.did_modify_range(range.start * size..range.end * size);
+        }
+    }
+
+    #[cfg(not(target_os = "macos"))]
+    fn did_modify(&mut self, _range: Range<usize>) {}
+
+    /// Returns the elements of the buffer, or `None` if the buffer has private storage.
+    pub fn as_slice(&self) -> Option<&[T]> {
+        self.buffer.contents_as()
+    }
+
+    /// Returns the elements of the buffer mutably, or `None` if the buffer has private storage.
+    ///
+    /// # Safety
+    ///
+    /// The contents must not be accessed through another wrapper of the buffer while the slice
+    /// is borrowed, as with `Buffer::contents_mut`.
+    pub unsafe fn as_mut_slice(&mut self) -> Option<&mut [T]> {
+        self.buffer.contents_as_mut()
+    }
+
+    /// Returns a view of the elements in `range`, which can be bound to shaders using its
+    /// buffer and byte offset.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `range` is out of bounds.
+    pub fn slice(&self, range: Range<usize>) -> BufferSlice<T> {
+        assert!(range.start <= range.end && range.end <= self.len(),
+                "the range {:?} is out of bounds of a buffer of length {}", range, self.len());
+        BufferSlice {
+            buffer: &self.buffer,
+            offset: range.start * mem::size_of::<T>(),
+            len: range.end - range.start,
+            marker: PhantomData
+        }
+    }
+
+    pub fn buffer(&self) -> &Buffer {
+        &self.buffer
+    }
+
+    pub fn buffer_mut(&mut self) -> &mut Buffer {
+        &mut self.buffer
+    }
+
+    pub fn into_buffer(self) -> Buffer {
+        self.buffer
+    }
+}
+
+/// A range of elements in a `TypedBuffer`.
+pub struct BufferSlice<'a, T: GpuPod> {
+    buffer: &'a Buffer,
+    offset: usize,
+    len: usize,
+    marker: PhantomData<&'a [T]>
+}
+
+impl<'a, T: GpuPod> BufferSlice<'a, T> {
+    /// The buffer which contains the elements.
+    pub fn buffer(&self) -> &'a Buffer {
+        self.buffer
+    }
+
+    /// The offset in bytes of the first element from the start of the buffer.
+    pub fn offset(&self) -> usize {
+        self.offset
+    }
+
+    /// The number of elements in the slice.
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// The length of the slice in bytes.
+    pub fn byte_len(&self) -> usize {
+        self.len * mem::size_of::<T>()
+    }
+
+    /// Returns the elements of the slice, or `None` if the buffer has private storage.
+    pub fn as_slice(&self) -> Option<&'a [T]> {
+        let start = self.offset / mem::size_of::<T>();
+        self.buffer.contents_as().map(|elements| &elements[start..start + self.len])
+    }
+}
+
+impl<'a, T: GpuPod> Clone for BufferSlice<'a, T> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<'a, T: GpuPod> Copy for BufferSlice<'a, T> {}
@@ -5,12 +5,21 @@ use core_foundation::number::CFNumber;
 use core_foundation::string::CFString;
 use objc::runtime::BOOL;
 use objc_bringup::NSDictionary;
-use std::collections::HashMap;
+use std::collections::BTreeMap;
 use std::convert::{From, Into};
 use std::default::Default;
+use std::fmt;
+use std::hash::{Hash, Hasher};
 use sys::{MTLCompileOptions, MTLLanguageVersion};
+use ShaderStruct;
 
-#[derive(Clone, Debug, Default, PartialEq)]
+/// Options for compiling shader source.
+///
+/// The options have a canonical form: the macros are kept sorted by name, and floating point
+/// macro values compare and hash by their bits. Equal options therefore always produce the same
+/// native options, hash and serialized form, so they can be used to key caches.
+#[derive(Clone, Debug, Default, Eq, Hash, PartialEq)]
+#[cfg_attr(feature = "serialize", derive(Deserialize, Serialize))]
 pub struct CompileOptions {
     /// Is fast math enabled. Set this option to `None` to use the system default setting.
     pub fast_math_enabled: Option<bool>,
@@ -21,7 +30,7 @@ pub struct CompileOptions {
 
     /// A map of preprocessor macro names to values. Each entry of (`MacroName`, `MacroValue`)
     /// corresponds to `#define MacroName MacroValue`
-    pub preprocessor_macros: HashMap<String, PreprocessorMacroValue>
+    pub preprocessor_macros: BTreeMap<String, PreprocessorMacroValue>
 }
 
 impl CompileOptions {
@@ -44,6 +53,24 @@ impl CompileOptions {
         self
     }
 
+    /// Append a macro which expands to the MSL declarations of `T` and the structs it depends on,
+    /// so that shader source can declare them by using the macro name.
+    pub fn with_struct_declarations<T, S>(self, mac_name: S) -> Self
+        where T: ShaderStruct,
+              S: Into<String> {
+        let declarations = T::msl_source().replace('\n', " ");
+        self.with_macro(mac_name, declarations)
+    }
+
+    /// Renders the macros as `#define` lines, in name order, for tools which preprocess shader
+    /// source before it reaches the Metal compiler.
+    pub fn to_defines(&self) -> String {
+        self.preprocessor_macros
+            .iter()
+            .map(|(name, value)| format!("#define {} {}\n", name, value))
+            .collect()
+    }
+
     /// Create a native `MTLCompileOptions` object.
     pub fn mtl_compile_options(&self) -> id {
         unsafe {
@@ -94,6 +121,7 @@ impl CompileOptions {
 }
 
 #[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+#[cfg_attr(feature = "serialize", derive(Deserialize, Serialize))]
 pub enum LanguageVersion {
     Latest,
     Specific(SpecificLanguageVersion)
@@ -110,6 +138,7 @@ convertible_enum! {
     // An underscore is a good separator for numbers
     #[allow(non_camel_case_types)]
     #[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+    #[cfg_attr(feature = "serialize", derive(Deserialize, Serialize))]
     pub enum SpecificLanguageVersion: MTLLanguageVersion {
         // each arm should have a doc comment above it, but it's broken due to
         // issue #24189
@@ -122,13 +151,72 @@ convertible_enum! {
     }
 }
 
-#[derive(Clone, Debug, PartialEq)]
+/// The value of a preprocessor macro.
+///
+/// Floating point values are compared and hashed by their bits, so `NAN` equals itself, but `0.0`
+/// and `-0.0` are different values.
+#[derive(Clone, Debug)]
+#[cfg_attr(feature = "serialize", derive(Deserialize, Serialize))]
 pub enum PreprocessorMacroValue {
     Floating(f64),
     Integer(i64),
     String(String)
 }
 
+impl PartialEq for PreprocessorMacroValue {
+    fn eq(&self, other: &Self) -> bool {
+        match (self, other) {
+            (&PreprocessorMacroValue::Floating(a), &PreprocessorMacroValue::Floating(b)) => {
+                a.to_bits() == b.to_bits()
+            }
+            (&PreprocessorMacroValue::Integer(a), &PreprocessorMacroValue::Integer(b)) => a == b,
+            (&PreprocessorMacroValue::String(ref a), &PreprocessorMacroValue::String(ref b)) => {
+                a == b
+            }
+            _ => false,
+        }
+    }
+}
+
+impl Eq for PreprocessorMacroValue {}
+
+impl Hash for PreprocessorMacroValue {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        match *self {
+            PreprocessorMacroValue::Floating(f) => {
+                state.write_u8(0);
+                f.to_bits().hash(state);
+            }
+            PreprocessorMacroValue::Integer(i) => {
+                state.write_u8(1);
+                i.hash(state);
+            }
+            PreprocessorMacroValue::String(ref s) => {
+                state.write_u8(2);
+                s.hash(state);
+            }
+        }
+    }
+}
+
+/// Formats the value as it would appear in source. Floating point values are written with the
+/// shortest mantissa that reads back as the same value and an exponent, such as `2.5e0`, so that
+/// they remain floating point literals. Infinities and NaNs are written as the `INFINITY` and
+/// `NAN` macros from the Metal standard library.
+impl fmt::Display for PreprocessorMacroValue {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            PreprocessorMacroValue::Floating(value) if value.is_nan() => f.write_str("NAN"),
+            PreprocessorMacroValue::Floating(value) if value.is_infinite() => {
+                f.write_str(if value > 0.0 { "INFINITY" } else { "-INFINITY" })
+            }
+            PreprocessorMacroValue::Floating(value) => write!(f, "{:e}", value),
+            PreprocessorMacroValue::Integer(value) => write!(f, "{}", value),
+            PreprocessorMacroValue::String(ref value) => f.write_str(value),
+        }
+    }
+}
+
 impl From<i64> for PreprocessorMacroValue {
     fn from(val: i64) -> Self {
         PreprocessorMacroValue::Integer(val)
@@ -170,3 +258,73 @@ impl<'a> From<&'a str> for PreprocessorMacroValue {
         PreprocessorMacroValue::String(val.into())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::hash_map::DefaultHasher;
+    use std::hash::{Hash, Hasher};
+
+    fn hash_of<T: Hash>(value: &T) -> u64 {
+        let mut hasher = DefaultHasher::new();
+        value.hash(&mut hasher);
+        hasher.finish()
+    }
+
+    #[test]
+    fn test_canonical_form() {
+        let options = CompileOptions::default()
+            .with_macro("WIDTH", 640)
+            .with_macro("SCALE", 0.5)
+            .with_macro("NAME", "lights");
+        let reordered = CompileOptions::default()
+            .with_macro("NAME", "lights")
+            .with_macro("SCALE", 0.5)
+            .with_macro("WIDTH", 640);
+        assert_eq!(options, reordered);
+        assert_eq!(hash_of(&options), hash_of(&reordered));
+        assert_eq!(options.preprocessor_macros.keys().collect::<Vec<_>>(),
+                   ["NAME", "SCALE", "WIDTH"]);
+
+        let nan = CompileOptions::default().with_macro("X", ::std::f64::NAN);
+        assert_eq!(nan, nan.clone());
+        assert_eq!(hash_of(&nan), hash_of(&nan.clone()));
+        assert!(CompileOptions::default().with_macro("X", 0.0) !=
+                CompileOptions::default().with_macro("X", -0.0));
+        assert!(CompileOptions::default().with_macro("X", 1) !=
+                CompileOptions::default().with_macro("X", 1.0));
+    }
+
+    #[test]
+    fn test_to_defines() {
+        let options = CompileOptions::default()
+            .with_macro("WIDTH", 640)
+            .with_macro("SCALE", 2.0)
+            .with_macro("OFFSET", -640.25)
+            .with_macro("TINY", 1e-20)
+            .with_macro("FAR", ::std::f64::INFINITY)
+            .with_macro("NAME", "lights[0]");
+        assert_eq!(options.to_defines(),
+                   "#define FAR INFINITY\n\
+                    #define NAME lights[0]\n\
+                    #define OFFSET -6.4025e2\n\
+                    #define SCALE 2e0\n\
+                    #define TINY 1e-20\n\
+                    #define WIDTH 640\n");
+        assert_eq!(CompileOptions::default().to_defines(), "");
+    }
+
+    #[cfg(feature = "serialize")]
+    #[test]
+    fn test_json_round_trip() {
+        extern crate serde_json;
+
+        let options = CompileOptions::default()
+            .fast_math_enabled(false)
+            .language_version(LanguageVersion::Specific(SpecificLanguageVersion::Version_1_1))
+            .with_macro("WIDTH", 640)
+            .with_macro("SCALE", 0.1);
+        let json = serde_json::to_string(&options).unwrap();
+        assert_eq!(serde_json::from_str::<CompileOptions>(&json).unwrap(), options);
+    }
+}
@@ -2,9 +2,11 @@ use block::ConcreteBlock;
 use cocoa::base::{id, nil};
 use cocoa::foundation::{NSString, NSUInteger};
 use error::NSError;
+use host_allocation::HostDeallocator;
+use libc::c_void;
 use objc::runtime::YES;
 use objc_bringup::NSArray;
-use sys::{MTLCopyAllDevices, MTLCreateSystemDefaultDevice, MTLDevice};
+use sys::{MTLBuffer, MTLCopyAllDevices, MTLCreateSystemDefaultDevice, MTLDevice};
 use std::convert::From;
 use std::error::Error;
 use std::ffi::CStr;
@@ -15,8 +17,16 @@ use std::path::Path;
 use std::sync::mpsc;
 use sys::MTLFeatureSet;
 use {AsRaw, Buffer, CommandQueue, CommandQueueError, CompileOptions, DepthStencilDescriptor,
-     DepthStencilState, FromRaw, FromRawError, Library, LibraryError, ResourceOptions,
-     SamplerDescriptor, SamplerState, Size, Texture, TextureDescriptor};
+     DepthStencilState, Diagnostic, FromRaw, FromRawError, GpuPod, HostAllocation, Library,
+     LibraryError, Origin, PageAlignedVec, Region, ResourceOptions, SamplerDescriptor, SamplerState,
+     Size, Texture, TextureDescriptor, TextureLayoutError, TypedBuffer, as_bytes,
+     parse_diagnostics};
+
+// libdispatch is part of libSystem, which every binary links against.
+extern "C" {
+    fn dispatch_data_create(buffer: *const c_void, size: usize, queue: id, destructor: id) -> id;
+    fn dispatch_release(object: id);
+}
 
 pub struct Device(id);
 
@@ -114,6 +124,16 @@ impl Device {
 
     pub fn new_library_with_source(&mut self, source: &str, compile_options: &CompileOptions)
                                    -> Result<Library, LibraryError> {
+        self.new_library_with_source_and_diagnostics(source, compile_options)
+            .map(|(library, _)| library)
+    }
+
+    /// Compiles a library like `new_library_with_source`, and also returns the warnings which the
+    /// compiler reported if compilation succeeded.
+    pub fn new_library_with_source_and_diagnostics(&mut self, source: &str,
+                                                   compile_options: &CompileOptions)
+                                                   -> Result<(Library, Vec<Diagnostic>),
+                                                             LibraryError> {
         unsafe {
             let source = NSString::alloc(nil).init_str(source);
             let options = compile_options.mtl_compile_options();
@@ -122,7 +142,10 @@ impl Device {
             if library == nil {
                 Err(LibraryError::from(NSError::new(error)))
             } else {
-                Ok(try!(FromRaw::from_raw(library)))
+                let warnings = NSError::new(error)
+                    .map(|error| parse_diagnostics(error.localized_description()))
+                    .unwrap_or_default();
+                Ok((try!(FromRaw::from_raw(library)), warnings))
             }
         }
     }
@@ -148,10 +171,23 @@ impl Device {
         }
     }
 
-    #[allow(unused_variables)]
-    // TODO(burtonageo): `data` parameter should be of the correct type
-    pub fn new_library_with_data(&mut self, data: ()) -> Result<Library, LibraryError> {
-        unimplemented!();
+    /// Loads a library from the contents of a `.metallib` file, such as one produced by the
+    /// offline shader compiler or stored in a `LibraryCache`.
+    pub fn new_library_with_data(&mut self, data: &[u8]) -> Result<Library, LibraryError> {
+        unsafe {
+            // The default destructor makes dispatch copy the bytes, so `data` need not outlive
+            // the library.
+            let dispatch_data = dispatch_data_create(data.as_ptr() as *const c_void, data.len(),
+                                                     nil, nil);
+            let mut error = nil;
+            let library = self.0.newLibraryWithData_error(dispatch_data, &mut error);
+            dispatch_release(dispatch_data);
+            if library == nil {
+                Err(LibraryError::from(NSError::new(error)))
+            } else {
+                Ok(try!(FromRaw::from_raw(library)))
+            }
+        }
     }
 
     pub fn new_buffer_with_length(&mut self, length: usize, options: ResourceOptions) -> Buffer {
@@ -173,27 +209,91 @@ impl Device {
         }
     }
 
-    pub fn new_buffer_with_bytes_no_copy<'a>(&mut self, bytes: &'a mut [u8],
-                                             options: ResourceOptions)
-                                             -> BufferRef<'a> {
-        let len = bytes.len() as NSUInteger;
-        let bytes = bytes.as_ptr() as *mut _;
-        let dealloc = ConcreteBlock::new(|ptr, len| {
-            use std::slice;
-            unsafe { drop(slice::from_raw_parts(ptr as *const u8, len as usize)) }
-        });
+    /// Creates a buffer containing a copy of `elements`.
+    pub fn new_buffer_from_slice<T: GpuPod>(&mut self, elements: &[T], options: ResourceOptions)
+                                            -> TypedBuffer<T> {
+        TypedBuffer::from_buffer(self.new_buffer_with_bytes(as_bytes(elements), options))
+    }
+
+    /// Creates a buffer which uses the memory of `bytes` directly rather than copying it. The
+    /// buffer covers the whole allocation, including the padding at the end of the last page.
+    /// Returns an error if Metal cannot create the buffer.
+    ///
+    /// Use `new_buffer_with_host_allocation` to give ownership of the memory to the buffer
+    /// instead, which is safe.
+    ///
+    /// # Safety
+    ///
+    /// Command buffers and encoders retain the buffers which they use, so the buffer can outlive
+    /// the returned `BufferRef`. The caller must ensure that every command buffer which uses the
+    /// buffer has completed before `bytes` is dropped, moved or reallocated.
+    pub unsafe fn new_buffer_with_bytes_no_copy<'a>(&mut self, bytes: &'a mut PageAlignedVec,
+                                                    options: ResourceOptions)
+                                                    -> Result<BufferRef<'a>, FromRawError> {
+        let len = bytes.capacity() as NSUInteger;
+        let ptr = bytes.as_mut_ptr() as *mut c_void;
+        // The memory is owned by `bytes`, so there is nothing to free.
+        let dealloc = ConcreteBlock::new(|_: *mut c_void, _: NSUInteger| {}).copy();
+        let buffer = self.0.newBufferWithBytesNoCopy_length_options_deallocator(ptr, len,
+                                                                                options.into(),
+                                                                                &dealloc);
+        Ok(BufferRef { buffer: try!(FromRaw::from_raw(buffer)), marker: PhantomData })
+    }
+
+    /// Creates a buffer which uses the memory of `allocation` directly rather than copying it.
+    /// The buffer takes ownership of the allocation, which is released when the buffer is
+    /// destroyed. Returns an error if Metal cannot create the buffer, in which case the
+    /// allocation is released straight away.
+    ///
+    /// The buffer covers the whole allocation, including the padding at the end of the last
+    /// page.
+    pub fn new_buffer_with_host_allocation<H: HostAllocation>(&mut self, allocation: H,
+                                                              options: ResourceOptions)
+                                                              -> Result<BufferRef<'static>,
+                                                                        FromRawError> {
+        let deallocator = HostDeallocator::new(allocation);
+        let (ptr, len) = deallocator.allocation().unwrap();
+        let dealloc = ConcreteBlock::new(move |ptr: *mut c_void, len: NSUInteger| {
+            deallocator.deallocate(ptr, len as usize)
+        }).copy();
         let buffer = unsafe {
-            FromRaw::from_raw(self.0.newBufferWithBytesNoCopy_length_options_deallocator(bytes, len,
-                                                                                         options.into(),
-                                                                                         &dealloc)).unwrap()
+            self.0.newBufferWithBytesNoCopy_length_options_deallocator(ptr as *mut c_void,
+                                                                       len as NSUInteger,
+                                                                       options.into(), &dealloc)
         };
-        BufferRef { buffer: buffer, marker: PhantomData }
+        Ok(BufferRef { buffer: try!(FromRaw::from_raw(buffer)), marker: PhantomData })
     }
 
     pub fn new_texture(&mut self, descriptor: &TextureDescriptor) -> Result<Texture, FromRawError> {
         unsafe { FromRaw::from_raw(self.0.newTextureWithDescriptor(*descriptor.as_raw())) }
     }
 
+    /// Creates a texture which shares its storage with `buffer`, starting `offset` bytes into the
+    /// buffer with rows `bytes_per_row` bytes apart.
+    ///
+    /// Returns an error if the descriptor does not describe a valid texture layout, if
+    /// `bytes_per_row` is too small for a row of the texture, or if the texture does not fit in
+    /// the buffer. Buffer backed textures need iOS 8.0 or macOS 10.13.
+    pub fn new_texture_from_buffer(&mut self, buffer: &Buffer, descriptor: &TextureDescriptor,
+                                   offset: usize, bytes_per_row: usize)
+                                   -> Result<Texture, TextureFromBufferError> {
+        let region = Region { origin: Origin::default(), size: descriptor.size() };
+        let layout = try!(descriptor.layout());
+        let required = try!(layout.check_region(region, 0, 0, bytes_per_row, 0));
+        if offset > buffer.length() || required > buffer.length() - offset {
+            return Err(TextureFromBufferError::Layout(TextureLayoutError::BufferTooSmall {
+                len: buffer.length(),
+                required: offset.saturating_add(required)
+            }));
+        }
+        let texture = unsafe {
+            buffer.as_raw().newTextureWithDescriptor_offset_bytesPerRow(*descriptor.as_raw(),
+                                                                        offset as NSUInteger,
+                                                                        bytes_per_row as NSUInteger)
+        };
+        Ok(try!(FromRaw::from_raw(texture)))
+    }
+
     pub fn new_sampler_state(&mut self, descriptor: &SamplerDescriptor)
                              -> Result<SamplerState, FromRawError> {
         unsafe { FromRaw::from_raw(self.0.newSamplerStateWithDescriptor(*descriptor.as_raw())) }
@@ -219,6 +319,13 @@ impl<'a> Deref for BufferRef<'a> {
     }
 }
 
+impl<'a> Drop for BufferRef<'a> {
+    fn drop(&mut self) {
+        // Metal calls the deallocator once the buffer is no longer used by any command buffers.
+        unsafe { msg_send![*self.buffer.as_raw(), release] };
+    }
+}
+
 impl_from_into_raw!(Device, of protocol "MTLDevice");
 
 #[cfg(target_os = "ios")]
@@ -298,3 +405,50 @@ impl Error for DeviceError {
         }
     }
 }
+
+/// The reason that `Device::new_texture_from_buffer` could not create a texture.
+#[derive(Clone, Debug)]
+pub enum TextureFromBufferError {
+    /// The texture is invalid, or does not fit in the buffer with the given offset and row pitch.
+    Layout(TextureLayoutError),
+    RawError(FromRawError)
+}
+
+impl From<TextureLayoutError> for TextureFromBufferError {
+    fn from(error: TextureLayoutError) -> Self {
+        TextureFromBufferError::Layout(error)
+    }
+}
+
+impl From<FromRawError> for TextureFromBufferError {
+    fn from(error: FromRawError) -> Self {
+        TextureFromBufferError::RawError(error)
+    }
+}
+
+impl Display for TextureFromBufferError {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        match *self {
+            TextureFromBufferError::Layout(ref error) => {
+                write!(f, "cannot create a texture from a buffer: {}", error)
+            }
+            TextureFromBufferError::RawError(ref error) => write!(f, "{}", error),
+        }
+    }
+}
+
+impl Error for TextureFromBufferError {
+    fn description(&self) -> &str {
+        match *self {
+            TextureFromBufferError::Layout(_) => "The texture does not fit in the buffer",
+            TextureFromBufferError::RawError(_) => "The buffer could not create the texture",
+        }
+    }
+
+    fn cause(&self) -> Option<&Error> {
+        match *self {
+            TextureFromBufferError::Layout(ref error) => Some(error),
+            TextureFromBufferError::RawError(ref error) => Some(error),
+        }
+    }
+}
@@ -0,0 +1,227 @@
+//! Structured messages from the Metal shader compiler.
+//!
+//! The compiler reports errors and warnings as clang style text in the localized description of
+//! its `NSError`, such as `program_source:3:5: error: use of undeclared identifier 'x'`, followed
+//! by a copy of the source line and a caret. `parse_diagnostics` turns this text back into
+//! `Diagnostic`s, which can be inspected or rendered with snippets of the original source.
+
+use std::fmt;
+
+/// The severity of a compiler diagnostic.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum Severity {
+    Error,
+    Warning,
+    Note
+}
+
+impl Severity {
+    fn name(&self) -> &'static str {
+        match *self {
+            Severity::Error => "error",
+            Severity::Warning => "warning",
+            Severity::Note => "note",
+        }
+    }
+}
+
+impl fmt::Display for Severity {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+/// An error, warning or note produced by the shader compiler.
+///
+/// `file` is `program_source` for source passed to `Device::new_library_with_source`, unless the
+/// source sets a file name with a `#line` directive, as `ShaderSourceSet` does. Lines and columns
+/// start at 1.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Diagnostic {
+    pub file: String,
+    pub line: usize,
+    pub column: usize,
+    pub severity: Severity,
+    pub message: String,
+
+    /// The notes which follow the diagnostic, such as the location of a previous declaration.
+    pub notes: Vec<Diagnostic>
+}
+
+impl Diagnostic {
+    /// Renders the diagnostic in the style of rustc, with the source line it refers to.
+    ///
+    /// `source_for` is called with the file names of the diagnostic and its notes, and returns the
+    /// source of the file if it is known. For example, `|path| assembled.file(path)` for a
+    /// `AssembledSource`, or `|_| Some(SOURCE)` for a single source string.
+    pub fn render<'a, F>(&self, source_for: F) -> String
+        where F: Fn(&str) -> Option<&'a str> {
+        let mut output = String::new();
+        self.render_into(&mut output, &source_for);
+        output
+    }
+
+    fn render_into<'a, F>(&self, output: &mut String, source_for: &F)
+        where F: Fn(&str) -> Option<&'a str> {
+        let line_number = self.line.to_string();
+        let gutter = " ".repeat(line_number.len());
+        output.push_str(&format!("{}: {}\n", self.severity, self.message));
+        output.push_str(&format!("{}--> {}:{}:{}\n", gutter, self.file, self.line, self.column));
+
+        let source_line = source_for(&self.file)
+            .and_then(|source| source.lines().nth(self.line.saturating_sub(1)));
+        if let Some(source_line) = source_line {
+            // Keep the tabs before the caret, so that it lines up with the source line.
+            let indent = source_line.chars()
+                .take(self.column.saturating_sub(1))
+                .map(|c| if c == '\t' { '\t' } else { ' ' })
+                .collect::<String>();
+            output.push_str(&format!("{} |\n", gutter));
+            output.push_str(&format!("{} | {}\n", line_number, source_line));
+            output.push_str(&format!("{} | {}^\n", gutter, indent));
+        }
+
+        for note in &self.notes {
+            note.render_into(output, source_for);
+        }
+    }
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        try!(write!(f, "{}:{}:{}: {}: {}", self.file, self.line, self.column, self.severity,
+                    self.message));
+        for note in &self.notes {
+            try!(write!(f, "\n{}", note));
+        }
+        Ok(())
+    }
+}
+
+/// Parses the text output of the Metal shader compiler.
+///
+/// Lines which do not start a diagnostic, such as source snippets, carets and summaries like
+/// `1 error generated.`, are skipped. Notes are attached to the error or warning before them.
+pub fn parse_diagnostics(output: &str) -> Vec<Diagnostic> {
+    let mut diagnostics: Vec<Diagnostic> = vec![];
+    for diagnostic in output.lines().filter_map(parse_diagnostic_line) {
+        if diagnostic.severity == Severity::Note {
+            if let Some(last) = diagnostics.last_mut() {
+                last.notes.push(diagnostic);
+                continue;
+            }
+        }
+        diagnostics.push(diagnostic);
+    }
+    diagnostics
+}
+
+/// Parses a line of the form `file:line:column: severity: message`.
+fn parse_diagnostic_line(line: &str) -> Option<Diagnostic> {
+    const SEVERITIES: &'static [(&'static str, Severity)] = &[(": fatal error: ", Severity::Error),
+                                                              (": error: ", Severity::Error),
+                                                              (": warning: ", Severity::Warning),
+                                                              (": note: ", Severity::Note)];
+
+    let found = SEVERITIES.iter()
+        .filter_map(|&(tag, severity)| {
+            line.find(tag).map(|start| (&line[..start], severity, &line[start + tag.len()..]))
+        })
+        .min_by_key(|&(location, _, _)| location.len());
+    let (location, severity, message) = match found {
+        Some(found) => found,
+        None => return None,
+    };
+
+    // File names may contain colons, so the line and column are taken from the end.
+    let mut parts = location.rsplitn(3, ':');
+    let column = parts.next().and_then(|column| column.trim().parse().ok());
+    let line = parts.next().and_then(|line| line.trim().parse().ok());
+    let (file, line, column) = match (parts.next(), line, column) {
+        (Some(file), Some(line), Some(column)) if !file.trim().is_empty() => (file, line, column),
+        _ => return None,
+    };
+
+    Some(Diagnostic {
+        file: file.trim().into(),
+        line: line,
+        column: column,
+        severity: severity,
+        message: message.trim().into(),
+        notes: vec![]
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const OUTPUT: &'static str = "Compilation failed: \n\
+        \n\
+        program_source:4:9: warning: unused variable 'scale' [-Wunused-variable]\n    \
+            float scale = 2.0;\n        \
+                  ^\n\
+        shaders/common.h:3:5: error: redefinition of 'Vertex'\n\
+        struct Vertex {\n       \
+               ^\n\
+        shaders/main.metal:2:8: note: previous definition is here\n\
+        struct Vertex {\n       \
+               ^\n\
+        C:\\shaders\\lights.metal:12:20: fatal error: 'missing.h' file not found\n\
+        1 warning and 2 errors generated.\n";
+
+    #[test]
+    fn test_parse_compiler_output() {
+        let diagnostics = parse_diagnostics(OUTPUT);
+        assert_eq!(diagnostics.len(), 3);
+
+        assert_eq!(diagnostics[0],
+                   Diagnostic {
+                       file: "program_source".into(),
+                       line: 4,
+                       column: 9,
+                       severity: Severity::Warning,
+                       message: "unused variable 'scale' [-Wunused-variable]".into(),
+                       notes: vec![]
+                   });
+
+        assert_eq!((&diagnostics[1].file[..], diagnostics[1].severity),
+                   ("shaders/common.h", Severity::Error));
+        assert_eq!(diagnostics[1].notes.len(), 1);
+        assert_eq!(diagnostics[1].notes[0].to_string(),
+                   "shaders/main.metal:2:8: note: previous definition is here");
+
+        assert_eq!((&diagnostics[2].file[..], diagnostics[2].line, diagnostics[2].column),
+                   ("C:\\shaders\\lights.metal", 12, 20));
+        assert_eq!(diagnostics[2].severity, Severity::Error);
+        assert_eq!(diagnostics[2].message, "'missing.h' file not found");
+
+        assert!(parse_diagnostics("Compilation failed: unknown error").is_empty());
+    }
+
+    #[test]
+    fn test_render_with_source_snippets() {
+        let diagnostics = parse_diagnostics(OUTPUT);
+        let source_for = |path: &str| match path {
+            "shaders/common.h" => Some("#pragma once\n\n\tstruct Vertex {\n"),
+            "shaders/main.metal" => Some("#include \"common.h\"\nstruct Vertex {\n"),
+            _ => None,
+        };
+
+        assert_eq!(diagnostics[1].render(source_for),
+                   "error: redefinition of 'Vertex'\n \
+                    --> shaders/common.h:3:5\n  \
+                     |\n\
+                    3 | \tstruct Vertex {\n  \
+                     | \t   ^\n\
+                    note: previous definition is here\n \
+                    --> shaders/main.metal:2:8\n  \
+                     |\n\
+                    2 | struct Vertex {\n  \
+                     |        ^\n");
+
+        assert_eq!(diagnostics[2].render(source_for),
+                   "error: 'missing.h' file not found\n  \
+                    --> C:\\shaders\\lights.metal:12:20\n");
+    }
+}
@@ -0,0 +1,432 @@
+//! Embedding shader source in the program at build time.
+//!
+//! Loose shader files are easy to lose when a program is installed, and `new_default_library`
+//! only works from an application bundle. Instead, a build script can use `EmbedMsl` to assemble a
+//! shader with its includes expanded, and generate a Rust file which contains the source along with
+//! a constant for each entry point. The shader is checked when the program is built: a shader
+//! which cannot be scanned fails the build, and so does a reference to an entry point which does
+//! not exist.
+//!
+//! In `build.rs`, with `metl` as a build dependency:
+//!
+//! ```ignore
+//! extern crate metl;
+//!
+//! fn main() {
+//!     metl::embed::EmbedMsl::new("shaders/lighting.metal")
+//!         .with_options(metl::CompileOptions::default().with_macro("SHADOWS", 1))
+//!         .generate()
+//!         .unwrap();
+//! }
+//! ```
+//!
+//! In the program:
+//!
+//! ```ignore
+//! #[macro_use]
+//! extern crate metl;
+//!
+//! include_msl!("shaders/lighting.metal", "MAX_LIGHTS" => 16);
+//!
+//! let mut library = try!(Lighting.compile(&mut device));
+//! let function = library.new_function_with_name(Lighting::VERTEX_MAIN.name);
+//! ```
+
+use std::env;
+use std::error::Error;
+use std::fmt;
+use std::fs::{self, File};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use {CompileOptions, Device, DirectoryProvider, FunctionType, LanguageVersion, Library,
+     LibraryError, MslScanError, PreprocessorMacroValue, ShaderSourceError, ShaderSourceSet,
+     scan_entry_points};
+
+/// Includes the Rust file generated by `EmbedMsl::generate` for the shader at `path`, which
+/// declares a type implementing `EmbeddedLibrary`.
+///
+/// The path may be followed by preprocessor macros, written as `"NAME" => value` with any value
+/// which converts to a `PreprocessorMacroValue`. They are added to the options given to
+/// `EmbedMsl::with_options` when the shader is compiled. The entry points are found when the
+/// build script runs, so these macros must not add or remove any.
+#[macro_export]
+macro_rules! include_msl {
+    ($path:expr $(, $name:expr => $value:expr)* $(,)*) => (
+        // Used by the generated `compile_options`.
+        macro_rules! __metl_include_msl_macros {
+            ($options:expr) => ($options$(.with_macro($name, $value))*)
+        }
+        include!(concat!(env!("OUT_DIR"), "/", $path, ".rs"));
+    )
+}
+
+/// The name and type of an entry point in an embedded shader.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub struct EntryPointName {
+    pub name: &'static str,
+    pub function_type: FunctionType
+}
+
+impl fmt::Display for EntryPointName {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(self.name)
+    }
+}
+
+/// A shader embedded in the program by `include_msl!`.
+pub trait EmbeddedLibrary {
+    /// The shader source, with its includes expanded.
+    const SOURCE: &'static str;
+
+    /// The entry points declared in the source.
+    const ENTRY_POINTS: &'static [EntryPointName];
+
+    /// The options given to `EmbedMsl::with_options` in the build script, with the macros given
+    /// to `include_msl!`.
+    fn compile_options() -> CompileOptions;
+
+    /// Compiles the shader.
+    fn compile(&self, device: &mut Device) -> Result<Library, LibraryError> {
+        device.new_library_with_source(Self::SOURCE, &Self::compile_options())
+    }
+}
+
+/// Generates the Rust file for a shader from a build script.
+#[derive(Clone, Debug, PartialEq)]
+pub struct EmbedMsl {
+    path: String,
+    type_name: Option<String>,
+    options: CompileOptions
+}
+
+impl EmbedMsl {
+    /// Embeds the shader at `path`, which is relative to the directory of the package being built
+    /// and is resolved with `/` separators. Includes are looked up in the same directory.
+    pub fn new<S: Into<String>>(path: S) -> Self {
+        EmbedMsl { path: path.into(), type_name: None, options: CompileOptions::default() }
+    }
+
+    /// Sets the name of the generated type. By default, it is the file name of the shader without
+    /// its extension, in camel case, so `shaders/deferred_lighting.metal` is `DeferredLighting`.
+    pub fn with_type_name<S: Into<String>>(self, type_name: S) -> Self {
+        EmbedMsl { type_name: Some(type_name.into()), ..self }
+    }
+
+    /// Sets the options which the shader is compiled with at runtime.
+    pub fn with_options(self, options: CompileOptions) -> Self {
+        EmbedMsl { options: options, ..self }
+    }
+
+    /// Generates the Rust file in the `OUT_DIR` of the build, where `include_msl!` finds it, and
+    /// tells cargo to rebuild when any of the shader's files change. The file must be included
+    /// with `include_msl!` rather than `include!`.
+    pub fn generate(&self) -> Result<(), EmbedMslError> {
+        let package_dir = PathBuf::from(env::var("CARGO_MANIFEST_DIR").unwrap_or_default());
+        let out_dir = match env::var("OUT_DIR") {
+            Ok(out_dir) => PathBuf::from(out_dir),
+            Err(_) => return Err(EmbedMslError::NotInBuildScript),
+        };
+
+        let sources = ShaderSourceSet::new().with_provider(DirectoryProvider::new(&package_dir));
+        let (rust, paths) = try!(self.generate_with(&sources));
+        for path in paths {
+            println!("cargo:rerun-if-changed={}", package_dir.join(path).display());
+        }
+
+        let out_path = out_dir.join(format!("{}.rs", self.path));
+        if let Some(parent) = out_path.parent() {
+            try!(fs::create_dir_all(parent));
+        }
+        try!(File::create(out_path).and_then(|mut file| file.write_all(rust.as_bytes())));
+        Ok(())
+    }
+
+    /// Generates the Rust source for the shader, loading its files from `sources`. Also returns
+    /// the paths of the files which make up the shader.
+    pub fn generate_with(&self, sources: &ShaderSourceSet)
+                         -> Result<(String, Vec<String>), EmbedMslError> {
+        let assembled = try!(sources.assemble(&self.path));
+        let entry_points = try!(scan_entry_points(&assembled));
+        let type_name = match self.type_name {
+            Some(ref type_name) => type_name.clone(),
+            None => camel_case(file_stem(&self.path)),
+        };
+        if !is_identifier(&type_name) {
+            return Err(EmbedMslError::InvalidTypeName(type_name));
+        }
+
+        let mut constants: Vec<(String, &str, FunctionType)> = vec![];
+        for entry_point in &entry_points {
+            let constant = shouty_snake_case(&entry_point.name);
+            if let Some(&(_, other, _)) = constants.iter().find(|&&(ref name, _, _)| {
+                *name == constant
+            }) {
+                return Err(EmbedMslError::DuplicateConstant {
+                    constant: constant,
+                    entry_points: (other.into(), entry_point.name.clone())
+                });
+            }
+            constants.push((constant, &entry_point.name, entry_point.function_type));
+        }
+
+        let mut rust = String::new();
+        rust.push_str(&format!("// Generated from `{}` by `metl::embed::EmbedMsl`.\n\n",
+                               self.path));
+        rust.push_str("#[derive(Clone, Copy, Debug, Default, Eq, Hash, PartialEq)]\n");
+        rust.push_str(&format!("pub struct {};\n\n", type_name));
+
+        rust.push_str("#[allow(dead_code)]\n");
+        rust.push_str(&format!("impl {} {{\n", type_name));
+        for &(ref constant, name, function_type) in &constants {
+            rust.push_str(&format!("    pub const {}: ::metl::EntryPointName = \
+                                    ::metl::EntryPointName {{ name: {:?}, function_type: \
+                                    ::metl::FunctionType::{:?} }};\n",
+                                   constant, name, function_type));
+        }
+        rust.push_str("}\n\n");
+
+        rust.push_str(&format!("impl ::metl::EmbeddedLibrary for {} {{\n", type_name));
+        rust.push_str(&format!("    const SOURCE: &'static str = {:?};\n\n", assembled.source()));
+        rust.push_str("    const ENTRY_POINTS: &'static [::metl::EntryPointName] = &[");
+        let entry_point_paths = constants.iter()
+            .map(|&(ref constant, _, _)| format!("{}::{}", type_name, constant))
+            .collect::<Vec<_>>();
+        rust.push_str(&entry_point_paths.join(", "));
+        rust.push_str("];\n\n");
+        rust.push_str("    fn compile_options() -> ::metl::CompileOptions {\n");
+        rust.push_str(&format!("        __metl_include_msl_macros!({})\n",
+                               options_expression(&self.options)));
+        rust.push_str("    }\n}\n");
+
+        let paths = assembled.paths().into_iter().map(String::from).collect();
+        Ok((rust, paths))
+    }
+}
+
+/// Renders a Rust expression which builds `options`.
+fn options_expression(options: &CompileOptions) -> String {
+    let mut expression = String::from("::metl::CompileOptions::default()");
+    if let Some(fast_math) = options.fast_math_enabled {
+        expression.push_str(&format!(".fast_math_enabled({})", fast_math));
+    }
+    if let LanguageVersion::Specific(version) = options.language_version {
+        expression.push_str(&format!(".language_version(::metl::LanguageVersion::Specific(\
+                                      ::metl::SpecificLanguageVersion::{:?}))",
+                                     version));
+    }
+    for (name, value) in &options.preprocessor_macros {
+        let value = match *value {
+            PreprocessorMacroValue::Floating(f) if f.is_nan() => "::std::f64::NAN".into(),
+            PreprocessorMacroValue::Floating(f) if f.is_infinite() => {
+                if f > 0.0 { "::std::f64::INFINITY" } else { "::std::f64::NEG_INFINITY" }.into()
+            }
+            PreprocessorMacroValue::Floating(f) => format!("{:?}f64", f),
+            PreprocessorMacroValue::Integer(i) => format!("{}i64", i),
+            PreprocessorMacroValue::String(ref s) => format!("{:?}", s),
+        };
+        expression.push_str(&format!(".with_macro({:?}, {})", name, value));
+    }
+    expression
+}
+
+fn file_stem(path: &str) -> &str {
+    let name = Path::new(path).file_name().and_then(|name| name.to_str()).unwrap_or(path);
+    name.split('.').next().unwrap_or(name)
+}
+
+/// Converts `deferred_lighting` or `deferred-lighting` to `DeferredLighting`.
+fn camel_case(name: &str) -> String {
+    name.split(|c: char| !c.is_alphanumeric())
+        .filter(|word| !word.is_empty())
+        .map(|word| {
+            let mut chars = word.chars();
+            match chars.next() {
+                Some(first) => first.to_uppercase().chain(chars).collect::<String>(),
+                None => String::new(),
+            }
+        })
+        .collect()
+}
+
+/// Converts `vertex_main` or `vertexMain` to `VERTEX_MAIN`.
+fn shouty_snake_case(name: &str) -> String {
+    let mut result = String::new();
+    let mut previous_lowercase = false;
+    for c in name.chars() {
+        if c.is_uppercase() && previous_lowercase {
+            result.push('_');
+        }
+        previous_lowercase = c.is_lowercase() || c.is_numeric();
+        result.extend(c.to_uppercase());
+    }
+    result
+}
+
+fn is_identifier(name: &str) -> bool {
+    let mut chars = name.chars();
+    match chars.next() {
+        Some(first) if first.is_alphabetic() || first == '_' => {
+            chars.all(|c| c.is_alphanumeric() || c == '_')
+        }
+        _ => false,
+    }
+}
+
+/// An error produced while embedding a shader.
+#[derive(Debug)]
+pub enum EmbedMslError {
+    /// `generate` was called outside of a build script, so there is no `OUT_DIR`.
+    NotInBuildScript,
+
+    /// The shader source could not be assembled.
+    Source(ShaderSourceError),
+
+    /// The entry points could not be scanned.
+    Scan(MslScanError),
+
+    /// The type name is not a Rust identifier.
+    InvalidTypeName(String),
+
+    /// Two entry points have the same constant name, such as `vertexMain` and `vertex_main`.
+    DuplicateConstant { constant: String, entry_points: (String, String) },
+
+    /// The generated file could not be written.
+    Io(io::Error)
+}
+
+impl fmt::Display for EmbedMslError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            EmbedMslError::Source(ref error) => write!(f, "{}", error),
+            EmbedMslError::Scan(ref error) => write!(f, "{}", error),
+            EmbedMslError::InvalidTypeName(ref name) => {
+                write!(f, "`{}` is not a valid type name", name)
+            }
+            EmbedMslError::DuplicateConstant { ref constant, entry_points: (ref a, ref b) } => {
+                write!(f, "the entry points `{}` and `{}` would both be `{}`", a, b, constant)
+            }
+            EmbedMslError::Io(ref error) => write!(f, "{}", error),
+            EmbedMslError::NotInBuildScript => f.write_str(self.description()),
+        }
+    }
+}
+
+impl Error for EmbedMslError {
+    fn description(&self) -> &str {
+        match *self {
+            EmbedMslError::NotInBuildScript => "Shaders can only be embedded from a build script",
+            EmbedMslError::Source(_) => "The shader source could not be assembled",
+            EmbedMslError::Scan(_) => "The shader entry points could not be scanned",
+            EmbedMslError::InvalidTypeName(_) => "The type name is not a Rust identifier",
+            EmbedMslError::DuplicateConstant { .. } => "Two entry points have the same constant",
+            EmbedMslError::Io(_) => "The generated file could not be written",
+        }
+    }
+
+    fn cause(&self) -> Option<&Error> {
+        match *self {
+            EmbedMslError::Source(ref error) => Some(error),
+            EmbedMslError::Scan(ref error) => Some(error),
+            EmbedMslError::Io(ref error) => Some(error),
+            _ => None,
+        }
+    }
+}
+
+impl From<ShaderSourceError> for EmbedMslError {
+    fn from(error: ShaderSourceError) -> Self {
+        EmbedMslError::Source(error)
+    }
+}
+
+impl From<MslScanError> for EmbedMslError {
+    fn from(error: MslScanError) -> Self {
+        EmbedMslError::Scan(error)
+    }
+}
+
+impl From<io::Error> for EmbedMslError {
+    fn from(error: io::Error) -> Self {
+        EmbedMslError::Io(error)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use {CompileOptions, MemoryProvider, ShaderSourceSet};
+
+    fn sources() -> ShaderSourceSet {
+        let files = MemoryProvider::new()
+            .with_file("shaders/deferred_lighting.metal",
+                       "#include \"common.h\"\n\
+                        vertex float4 vertexMain(uint vid [[vertex_id]]) { return float4(0); }\n\
+                        fragment float4 fragment_main() { return float4(1); }\n")
+            .with_file("shaders/common.h", "kernel void clear_tiles() {}\n")
+            .with_file("duplicate.metal",
+                       "kernel void clear_tiles() {}\nkernel void clearTiles() {}\n")
+            .with_file("broken.metal", "kernel void broken(device float *x [[buffer(a)]]) {}\n");
+        ShaderSourceSet::new().with_provider(files)
+    }
+
+    #[test]
+    fn test_generate_rust() {
+        let options = CompileOptions::default().fast_math_enabled(false)
+                                               .with_macro("SCALE", 0.5)
+                                               .with_macro("NAME", "\"tiles\"");
+        let embed = EmbedMsl::new("shaders/deferred_lighting.metal").with_options(options);
+        let (rust, paths) = embed.generate_with(&sources()).unwrap();
+        assert_eq!(paths, ["shaders/deferred_lighting.metal", "shaders/common.h"]);
+
+        assert!(rust.contains("pub struct DeferredLighting;"));
+        assert!(rust.contains("pub const CLEAR_TILES: ::metl::EntryPointName = \
+                               ::metl::EntryPointName { name: \"clear_tiles\", function_type: \
+                               ::metl::FunctionType::Kernel };"));
+        assert!(rust.contains("pub const VERTEX_MAIN: ::metl::EntryPointName = \
+                               ::metl::EntryPointName { name: \"vertexMain\", function_type: \
+                               ::metl::FunctionType::Vertex };"));
+        assert!(rust.contains("const ENTRY_POINTS: &'static [::metl::EntryPointName] = \
+                               &[DeferredLighting::CLEAR_TILES, DeferredLighting::VERTEX_MAIN, \
+                               DeferredLighting::FRAGMENT_MAIN];"));
+        assert!(rust.contains("const SOURCE: &'static str = \"#line 1 \\\"shaders/\
+                               deferred_lighting.metal\\\"\\n#line 1 \\\"shaders/common.h\\\"\\n"));
+        assert!(rust.contains("__metl_include_msl_macros!(::metl::CompileOptions::default()\
+                               .fast_math_enabled(false)\
+                               .with_macro(\"NAME\", \"\\\"tiles\\\"\")\
+                               .with_macro(\"SCALE\", 0.5f64))"));
+
+        let renamed = EmbedMsl::new("duplicate.metal").with_type_name("Tiles");
+        match renamed.generate_with(&sources()) {
+            Err(EmbedMslError::DuplicateConstant { ref constant, .. }) => {
+                assert_eq!(constant, "CLEAR_TILES")
+            }
+            other => panic!("expected a duplicate constant, found {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_invalid_shaders_fail() {
+        match EmbedMsl::new("broken.metal").generate_with(&sources()) {
+            Err(EmbedMslError::Scan(_)) => (),
+            other => panic!("expected a scan error, found {:?}", other),
+        }
+        match EmbedMsl::new("missing.metal").generate_with(&sources()) {
+            Err(EmbedMslError::Source(_)) => (),
+            other => panic!("expected a source error, found {:?}", other),
+        }
+        match EmbedMsl::new("shaders/common.h").with_type_name("2d").generate_with(&sources()) {
+            Err(EmbedMslError::InvalidTypeName(_)) => (),
+            other => panic!("expected an invalid type name, found {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_names() {
+        assert_eq!(camel_case("deferred_lighting"), "DeferredLighting");
+        assert_eq!(camel_case("post-process"), "PostProcess");
+        assert_eq!(file_stem("shaders/blur.metal"), "blur");
+        assert_eq!(shouty_snake_case("vertexMain"), "VERTEX_MAIN");
+        assert_eq!(shouty_snake_case("blur_9x9"), "BLUR_9X9");
+        assert_eq!(shouty_snake_case("HDRTonemap"), "HDRTONEMAP");
+    }
+}
@@ -0,0 +1,552 @@
+use std::cmp;
+use super::{bc1_palette, bc4_palette};
+use formats::FormatError;
+use {PixelFormat, TextureLayoutError};
+
+/// How much effort the BC encoder spends choosing the endpoints of each block.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum Quality {
+    /// Uses the bounding box of the texels in each block.
+    Fast,
+
+    /// Fits the endpoints to the principal axis of the texels in each block.
+    Normal,
+
+    /// Refines the `Normal` endpoints with a least squares fit and a local search.
+    High
+}
+
+/// Compresses a tightly packed image to the block-compressed `format`.
+///
+/// `Bc1Rgba` and `Bc3Rgba` (and their sRGB variants) take RGBA8 texels, `Bc4RUnorm` takes R8
+/// texels and `Bc5RgUnorm` takes RG8 texels. Images whose size is not a multiple of 4 are padded
+/// by repeating their last column and row. Texels with an alpha below 128 are stored as
+/// transparent in `Bc1Rgba`, which has no other alpha values.
+///
+/// The result has `format.info().unwrap().bytes_per_row(width)` bytes per row, and can be passed
+/// straight to `Texture::replace_region`.
+pub fn encode(format: PixelFormat, pixels: &[u8], width: usize, height: usize, quality: Quality)
+              -> Result<Vec<u8>, FormatError> {
+    let channels = match format {
+        PixelFormat::Bc1Rgba | PixelFormat::Bc1RgbaSrgb | PixelFormat::Bc3Rgba |
+        PixelFormat::Bc3RgbaSrgb => 4,
+        PixelFormat::Bc4RUnorm => 1,
+        PixelFormat::Bc5RgUnorm => 2,
+        _ => return Err(FormatError::UnsupportedFormat(format)),
+    };
+    let required = width * height * channels;
+    if pixels.len() < required {
+        return Err(FormatError::Layout(TextureLayoutError::BufferTooSmall {
+            len: pixels.len(),
+            required: required
+        }));
+    }
+
+    let info = format.info().unwrap();
+    let mut blocks = Vec::with_capacity(info.bytes_per_image(width, height));
+    if width == 0 || height == 0 {
+        return Ok(blocks);
+    }
+
+    for block_y in 0..info.blocks_high(height) {
+        for block_x in 0..info.blocks_wide(width) {
+            let mut texels = [[0, 0, 0, 255]; 16];
+            for (i, texel) in texels.iter_mut().enumerate() {
+                let x = cmp::min(block_x * 4 + i % 4, width - 1);
+                let y = cmp::min(block_y * 4 + i / 4, height - 1);
+                let offset = (y * width + x) * channels;
+                texel[..channels].copy_from_slice(&pixels[offset..offset + channels]);
+            }
+
+            match format {
+                PixelFormat::Bc1Rgba | PixelFormat::Bc1RgbaSrgb => {
+                    encode_bc1(&texels, true, quality, &mut blocks)
+                }
+                PixelFormat::Bc3Rgba | PixelFormat::Bc3RgbaSrgb => {
+                    encode_bc4(&texels, 3, quality, &mut blocks);
+                    encode_bc1(&texels, false, quality, &mut blocks);
+                }
+                PixelFormat::Bc4RUnorm => encode_bc4(&texels, 0, quality, &mut blocks),
+                _ => {
+                    encode_bc4(&texels, 0, quality, &mut blocks);
+                    encode_bc4(&texels, 1, quality, &mut blocks);
+                }
+            }
+        }
+    }
+    Ok(blocks)
+}
+
+type Color = [f32; 3];
+
+fn pack_565(color: Color) -> u16 {
+    let quantize = |value: f32, max: f32| (value.max(0.0).min(255.0) * max / 255.0).round() as u16;
+    quantize(color[0], 31.0) << 11 | quantize(color[1], 63.0) << 5 | quantize(color[2], 31.0)
+}
+
+fn distance(a: Color, b: Color) -> f32 {
+    (0..3).map(|c| (a[c] - b[c]) * (a[c] - b[c])).sum()
+}
+
+/// Builds a BC1 color block from a pair of endpoints, returning the block and its squared error.
+fn bc1_block(colors: &[Option<Color>; 16], e0: Color, e1: Color, allow_transparency: bool)
+             -> (f32, [u8; 8]) {
+    let (mut color0, mut color1) = (pack_565(e0), pack_565(e1));
+    let has_transparency = colors.iter().any(|color| color.is_none());
+    // Transparent texels need the three color palette, which is selected by color0 <= color1.
+    if has_transparency == (color0 > color1) {
+        let swap = color0;
+        color0 = color1;
+        color1 = swap;
+    }
+
+    let palette = bc1_palette(color0, color1, allow_transparency);
+    let opaque_entries = if palette[3][3] == 0 { 3 } else { 4 };
+    let mut error = 0.0;
+    let mut indices = 0u32;
+    for (i, color) in colors.iter().enumerate() {
+        let index = match *color {
+            Some(color) => {
+                let mut best = (::std::f32::MAX, 0);
+                for (index, entry) in palette[..opaque_entries].iter().enumerate() {
+                    let entry = [entry[0] as f32, entry[1] as f32, entry[2] as f32];
+                    let distance = distance(color, entry);
+                    if distance < best.0 {
+                        best = (distance, index);
+                    }
+                }
+                error += best.0;
+                best.1 as u32
+            }
+            None => 3,
+        };
+        indices |= index << (2 * i);
+    }
+
+    (error,
+     [color0 as u8,
+      (color0 >> 8) as u8,
+      color1 as u8,
+      (color1 >> 8) as u8,
+      indices as u8,
+      (indices >> 8) as u8,
+      (indices >> 16) as u8,
+      (indices >> 24) as u8])
+}
+
+fn bounding_box(colors: &[Color]) -> (Color, Color) {
+    let (mut min, mut max) = ([255.0; 3], [0.0; 3]);
+    for color in colors {
+        for c in 0..3 {
+            min[c] = color[c].min(min[c]);
+            max[c] = color[c].max(max[c]);
+        }
+    }
+    // Insetting the box slightly reduces the error of the interpolated colors.
+    for c in 0..3 {
+        let inset = (max[c] - min[c]) / 16.0;
+        min[c] += inset;
+        max[c] -= inset;
+    }
+
+    // Use the diagonal of the box which follows the colors, by flipping green and blue when they
+    // decrease as red increases.
+    let center = [(min[0] + max[0]) / 2.0, (min[1] + max[1]) / 2.0, (min[2] + max[2]) / 2.0];
+    for c in 1..3 {
+        let covariance = colors.iter()
+            .map(|color| (color[0] - center[0]) * (color[c] - center[c]))
+            .sum::<f32>();
+        if covariance < 0.0 {
+            let swap = min[c];
+            min[c] = max[c];
+            max[c] = swap;
+        }
+    }
+    (max, min)
+}
+
+fn principal_axis(colors: &[Color]) -> (Color, Color) {
+    let count = colors.len() as f32;
+    let mut mean = [0.0; 3];
+    for color in colors {
+        for c in 0..3 {
+            mean[c] += color[c] / count;
+        }
+    }
+
+    let mut covariance = [[0.0; 3]; 3];
+    for color in colors {
+        for i in 0..3 {
+            for j in 0..3 {
+                covariance[i][j] += (color[i] - mean[i]) * (color[j] - mean[j]);
+            }
+        }
+    }
+
+    // Power iteration, starting from the diagonal of the bounding box.
+    let (max, min) = bounding_box(colors);
+    let mut axis = [max[0] - min[0], max[1] - min[1], max[2] - min[2]];
+    for _ in 0..8 {
+        let mut next = [0.0; 3];
+        for i in 0..3 {
+            next[i] = (0..3).map(|j| covariance[i][j] * axis[j]).sum();
+        }
+        let length = next.iter().map(|v| v * v).sum::<f32>().sqrt();
+        if length < 1e-6 {
+            return (max, min);
+        }
+        axis = [next[0] / length, next[1] / length, next[2] / length];
+    }
+
+    let (mut low, mut high) = (::std::f32::MAX, ::std::f32::MIN);
+    for color in colors {
+        let t = (0..3).map(|c| (color[c] - mean[c]) * axis[c]).sum::<f32>();
+        low = low.min(t);
+        high = high.max(t);
+    }
+    let endpoint = |t: f32| {
+        [mean[0] + axis[0] * t, mean[1] + axis[1] * t, mean[2] + axis[2] * t]
+    };
+    (endpoint(high), endpoint(low))
+}
+
+/// Solves for the endpoints which minimize the error of the indices chosen in `block`.
+fn least_squares(colors: &[Option<Color>; 16], block: &[u8; 8]) -> Option<(Color, Color)> {
+    let color0 = block[0] as u16 | (block[1] as u16) << 8;
+    let color1 = block[2] as u16 | (block[3] as u16) << 8;
+    let weights = if color0 > color1 {
+        [0.0, 1.0, 1.0 / 3.0, 2.0 / 3.0]
+    } else {
+        [0.0, 1.0, 0.5, 0.0]
+    };
+
+    let (mut aa, mut ab, mut bb) = (0.0, 0.0, 0.0);
+    let (mut ax, mut bx) = ([0.0; 3], [0.0; 3]);
+    for (i, color) in colors.iter().enumerate() {
+        if let Some(color) = *color {
+            let index = (block[4 + i / 4] >> (2 * (i % 4))) & 0x3;
+            let w = weights[index as usize];
+            aa += (1.0 - w) * (1.0 - w);
+            ab += (1.0 - w) * w;
+            bb += w * w;
+            for c in 0..3 {
+                ax[c] += (1.0 - w) * color[c];
+                bx[c] += w * color[c];
+            }
+        }
+    }
+
+    let determinant = aa * bb - ab * ab;
+    if determinant.abs() < 1e-6 {
+        return None;
+    }
+    let (mut e0, mut e1) = ([0.0; 3], [0.0; 3]);
+    for c in 0..3 {
+        e0[c] = (bb * ax[c] - ab * bx[c]) / determinant;
+        e1[c] = (aa * bx[c] - ab * ax[c]) / determinant;
+    }
+    Some((e0, e1))
+}
+
+fn encode_bc1(texels: &[[u8; 4]; 16], allow_transparency: bool, quality: Quality,
+              out: &mut Vec<u8>) {
+    let mut colors = [None; 16];
+    for (color, texel) in colors.iter_mut().zip(texels.iter()) {
+        if !allow_transparency || texel[3] >= 128 {
+            *color = Some([texel[0] as f32, texel[1] as f32, texel[2] as f32]);
+        }
+    }
+    let opaque = colors.iter().filter_map(|&color| color).collect::<Vec<_>>();
+    if opaque.is_empty() {
+        out.extend_from_slice(&[0, 0, 0, 0, 0xff, 0xff, 0xff, 0xff]);
+        return;
+    }
+
+    let (e0, e1) = match quality {
+        Quality::Fast => bounding_box(&opaque),
+        Quality::Normal | Quality::High => principal_axis(&opaque),
+    };
+    let mut best = bc1_block(&colors, e0, e1, allow_transparency);
+    if quality == Quality::High {
+        for _ in 0..4 {
+            let candidate = match least_squares(&colors, &best.1) {
+                Some((e0, e1)) => bc1_block(&colors, e0, e1, allow_transparency),
+                None => break,
+            };
+            if candidate.0 >= best.0 {
+                break;
+            }
+            best = candidate;
+        }
+    }
+    out.extend_from_slice(&best.1);
+}
+
+/// Builds a BC4 block from a pair of endpoints, returning the block and its squared error.
+fn bc4_block(values: &[u8; 16], e0: u8, e1: u8) -> (f32, [u8; 8]) {
+    let palette = bc4_palette(e0, e1, false);
+    let mut error = 0.0;
+    let mut indices = 0u64;
+    for (i, &value) in values.iter().enumerate() {
+        let mut best = (::std::f32::MAX, 0);
+        for (index, &entry) in palette.iter().enumerate() {
+            let distance = (entry * 255.0 - value as f32).abs();
+            if distance < best.0 {
+                best = (distance, index);
+            }
+        }
+        error += best.0 * best.0;
+        indices |= (best.1 as u64) << (3 * i);
+    }
+
+    let mut block = [e0, e1, 0, 0, 0, 0, 0, 0];
+    for (i, byte) in block[2..].iter_mut().enumerate() {
+        *byte = (indices >> (8 * i)) as u8;
+    }
+    (error, block)
+}
+
+fn encode_bc4(texels: &[[u8; 4]; 16], channel: usize, quality: Quality, out: &mut Vec<u8>) {
+    let mut values = [0; 16];
+    for (value, texel) in values.iter_mut().zip(texels.iter()) {
+        *value = texel[channel];
+    }
+    let min = *values.iter().min().unwrap();
+    let max = *values.iter().max().unwrap();
+
+    // The eight value mode interpolates across the whole range. The six value mode only needs to
+    // cover the values in between, as it can also represent 0 and 255 exactly.
+    let mut candidates = vec![(max, min)];
+    if quality != Quality::Fast {
+        let inner = values.iter().cloned().filter(|&value| value != 0 && value != 255);
+        let (inner_min, inner_max) = inner.fold((255, 0), |(low, high), value| {
+            (cmp::min(low, value), cmp::max(high, value))
+        });
+        if inner_min <= inner_max {
+            candidates.push((inner_min, inner_max));
+        }
+    }
+
+    let mut best = (::std::f32::MAX, [0; 8]);
+    for &(e0, e1) in &candidates {
+        let candidate = bc4_block(&values, e0, e1);
+        if candidate.0 < best.0 {
+            best = candidate;
+        }
+    }
+
+    if quality == Quality::High {
+        for &(e0, e1) in &candidates {
+            for d0 in -2..3 {
+                for d1 in -2..3 {
+                    let e0 = cmp::max(0, cmp::min(255, e0 as i32 + d0)) as u8;
+                    let e1 = cmp::max(0, cmp::min(255, e1 as i32 + d1)) as u8;
+                    let candidate = bc4_block(&values, e0, e1);
+                    if candidate.0 < best.0 {
+                        best = candidate;
+                    }
+                }
+            }
+        }
+    }
+    out.extend_from_slice(&best.1);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use formats::FormatError;
+    use formats::bc::decode_rgba8;
+    use {PixelFormat, Region, TextureLayoutError};
+
+    const QUALITIES: [Quality; 3] = [Quality::Fast, Quality::Normal, Quality::High];
+
+    /// Decodes `encoded` back to RGBA8, using the row pitch `Texture::replace_region` would use.
+    fn round_trip(format: PixelFormat, pixels: &[u8], width: usize, height: usize,
+                  quality: Quality)
+                  -> Vec<u8> {
+        let encoded = encode(format, pixels, width, height, quality).unwrap();
+        let info = format.info().unwrap();
+        assert_eq!(encoded.len(), info.bytes_per_image(width, height));
+        decode_rgba8(format,
+                     &encoded,
+                     info.bytes_per_row(width),
+                     Region::new_2d(0, 0, width, height))
+            .unwrap()
+    }
+
+    /// A deterministic pseudo-random image, so the tests don't depend on a `rand` crate.
+    fn noise(len: usize, seed: u32) -> Vec<u8> {
+        let mut state = seed;
+        (0..len)
+            .map(|_| {
+                state = state.wrapping_mul(1664525).wrapping_add(1013904223);
+                (state >> 24) as u8
+            })
+            .collect()
+    }
+
+    fn squared_error(a: &[u8], b: &[u8]) -> u64 {
+        a.iter().zip(b.iter()).map(|(&a, &b)| (a as i64 - b as i64).pow(2) as u64).sum()
+    }
+
+    fn max_error(a: &[u8], b: &[u8]) -> u8 {
+        a.iter().zip(b.iter()).map(|(&a, &b)| (a as i16 - b as i16).abs() as u8).max().unwrap()
+    }
+
+    #[test]
+    fn test_encode_errors() {
+        assert_eq!(encode(PixelFormat::Bc7RgbaUnorm, &[0; 64], 4, 4, Quality::Fast),
+                   Err(FormatError::UnsupportedFormat(PixelFormat::Bc7RgbaUnorm)));
+        assert_eq!(encode(PixelFormat::Bc5RgUnorm, &[0; 31], 4, 4, Quality::Fast),
+                   Err(FormatError::Layout(TextureLayoutError::BufferTooSmall {
+                       len: 31,
+                       required: 32
+                   })));
+        assert_eq!(encode(PixelFormat::Bc1Rgba, &[], 0, 4, Quality::High), Ok(vec![]));
+    }
+
+    #[test]
+    fn test_bc1_solid_colors_are_within_565_precision() {
+        let colors = [[0, 0, 0], [255, 255, 255], [255, 0, 0], [12, 200, 99], [127, 128, 129]];
+        for color in colors.iter() {
+            let pixels = (0..16)
+                .flat_map(|_| vec![color[0], color[1], color[2], 255])
+                .collect::<Vec<_>>();
+            for &quality in QUALITIES.iter() {
+                let decoded = round_trip(PixelFormat::Bc1Rgba, &pixels, 4, 4, quality);
+                for texel in decoded.chunks(4) {
+                    assert!((texel[0] as i16 - color[0] as i16).abs() <= 4, "{:?}", texel);
+                    assert!((texel[1] as i16 - color[1] as i16).abs() <= 2, "{:?}", texel);
+                    assert!((texel[2] as i16 - color[2] as i16).abs() <= 4, "{:?}", texel);
+                    assert_eq!(texel[3], 255);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_bc1_alpha_cutout() {
+        let mut pixels = vec![];
+        for i in 0..16 {
+            let alpha = if i % 3 == 0 { 0 } else { 255 };
+            pixels.extend_from_slice(&[200, (i * 4) as u8, 40, alpha]);
+        }
+        for &quality in QUALITIES.iter() {
+            let decoded = round_trip(PixelFormat::Bc1RgbaSrgb, &pixels, 4, 4, quality);
+            for (i, texel) in decoded.chunks(4).enumerate() {
+                assert_eq!(texel[3], pixels[4 * i + 3]);
+                if texel[3] == 255 {
+                    // Three colors cover a range of 60 in green.
+                    assert!(max_error(&texel[..3], &pixels[4 * i..4 * i + 3]) <= 16,
+                            "{:?}",
+                            texel);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_bc1_gradient_error_bound() {
+        // A ramp along a line in color space, which BC1 can represent closely.
+        let (width, height) = (16, 8);
+        let mut pixels = vec![];
+        for _ in 0..height {
+            for x in 0..width {
+                let x = x as u8;
+                pixels.extend_from_slice(&[x * 16, x * 8, 255 - x * 16, 255]);
+            }
+        }
+        for &quality in QUALITIES.iter() {
+            let decoded = round_trip(PixelFormat::Bc1Rgba, &pixels, width, height, quality);
+            assert!(max_error(&decoded, &pixels) <= 8, "{:?}", quality);
+        }
+    }
+
+    #[test]
+    fn test_bc3_alpha_gradient() {
+        let mut pixels = vec![];
+        for i in 0..64 {
+            pixels.extend_from_slice(&[255, 128, 0, (i * 4) as u8]);
+        }
+        for &quality in QUALITIES.iter() {
+            let decoded = round_trip(PixelFormat::Bc3Rgba, &pixels, 8, 8, quality);
+            let alphas = decoded.chunks(4).map(|texel| texel[3]).collect::<Vec<_>>();
+            let expected = pixels.chunks(4).map(|texel| texel[3]).collect::<Vec<_>>();
+            // Each block covers a range of 108, split into 7 steps.
+            assert!(max_error(&alphas, &expected) <= 8, "{:?}", quality);
+        }
+    }
+
+    #[test]
+    fn test_bc4_and_bc5_padded_image() {
+        let (width, height) = (7, 5);
+        let red = (0..width * height).map(|i| (i * 7) as u8).collect::<Vec<_>>();
+        for &quality in QUALITIES.iter() {
+            let decoded = round_trip(PixelFormat::Bc4RUnorm, &red, width, height, quality);
+            assert_eq!(decoded.len(), width * height * 4);
+            let reds = decoded.chunks(4).map(|texel| texel[0]).collect::<Vec<_>>();
+            // Each block covers a range of 168, split into 7 steps.
+            assert!(max_error(&reds, &red) <= 12, "{:?}", quality);
+        }
+
+        let rg = (0..width * height)
+            .flat_map(|i| vec![(i * 7) as u8, 255 - i as u8])
+            .collect::<Vec<_>>();
+        let decoded = round_trip(PixelFormat::Bc5RgUnorm, &rg, width, height, Quality::Normal);
+        for (texel, expected) in decoded.chunks(4).zip(rg.chunks(2)) {
+            assert!(max_error(&texel[..2], expected) <= 12, "{:?}", texel);
+            assert_eq!(&texel[2..], &[0, 255]);
+        }
+    }
+
+    #[test]
+    fn test_bc4_six_value_mode_keeps_extremes() {
+        let mut red = [100; 16];
+        red[0] = 0;
+        red[15] = 255;
+        red[7] = 110;
+        let decoded = round_trip(PixelFormat::Bc4RUnorm, &red, 4, 4, Quality::Normal);
+        assert_eq!(decoded[0], 0);
+        assert_eq!(decoded[60], 255);
+        assert!(max_error(&decoded[28..29], &[110]) <= 1);
+    }
+
+    #[test]
+    fn test_higher_quality_never_increases_error() {
+        let (width, height) = (32, 32);
+        let pixels = noise(width * height * 4, 7)
+            .chunks(4)
+            .enumerate()
+            .flat_map(|(i, texel)| {
+                // Smooth the noise so that blocks have some structure to fit.
+                let base = ((i % width) * 6) as u8;
+                vec![base.wrapping_add(texel[0] / 8), base / 2 + texel[1] / 16, texel[2], 255]
+            })
+            .collect::<Vec<_>>();
+
+        for &format in &[PixelFormat::Bc1Rgba, PixelFormat::Bc3Rgba] {
+            let errors = QUALITIES.iter()
+                .map(|&quality| {
+                    let decoded = round_trip(format, &pixels, width, height, quality);
+                    squared_error(&decoded, &pixels)
+                })
+                .collect::<Vec<_>>();
+            assert!(errors[2] <= errors[1], "{:?} {:?}", format, errors);
+
+            // Root mean square error per channel.
+            let rms = (errors[1] as f64 / pixels.len() as f64).sqrt();
+            assert!(rms < 16.0, "{:?} {}", format, rms);
+        }
+
+        let red = noise(width * height, 11);
+        let errors = QUALITIES.iter()
+            .map(|&quality| {
+                let decoded = round_trip(PixelFormat::Bc4RUnorm, &red, width, height, quality);
+                let reds = decoded.chunks(4).map(|texel| texel[0]).collect::<Vec<_>>();
+                squared_error(&reds, &red)
+            })
+            .collect::<Vec<_>>();
+        assert!(errors[2] <= errors[1] && errors[1] <= errors[0], "{:?}", errors);
+    }
+}
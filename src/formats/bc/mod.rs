@@ -0,0 +1,1016 @@
+//! Decoders for the BC1-BC7 block-compressed pixel formats, and encoders for BC1, BC3, BC4 and
+//! BC5.
+//!
+//! Every format stores 4x4 blocks of texels. Data is laid out in rows of blocks, `bytes_per_row`
+//! apart, which is the layout used by `Texture::get_bytes` and `Texture::replace_region` for a
+//! compressed texture.
+
+use super::{FormatError, half_to_f32, srgb_to_linear};
+use {PixelFormat, Region, TextureLayoutError};
+
+mod encode;
+
+pub use self::encode::{Quality, encode};
+
+/// A decoded 4x4 block, stored as RGBA texels in row-major order.
+pub type Block = [[f32; 4]; 16];
+
+/// Decodes a single block of `format`.
+///
+/// Unorm formats are decoded to the range `[0.0, 1.0]`, snorm formats to `[-1.0, 1.0]` and BC6H
+/// blocks to their half float values. sRGB formats are *not* converted to linear. Channels which
+/// are not stored in the format are filled as `(0, 0, 0, 1)`.
+pub fn decode_block(format: PixelFormat, block: &[u8]) -> Result<Block, FormatError> {
+    let block_len = try!(block_len(format));
+    if block.len() < block_len {
+        return Err(FormatError::Layout(TextureLayoutError::BufferTooSmall {
+            len: block.len(),
+            required: block_len
+        }));
+    }
+
+    let mut texels = [[0.0, 0.0, 0.0, 1.0]; 16];
+    match format {
+        PixelFormat::Bc1Rgba | PixelFormat::Bc1RgbaSrgb => decode_bc1(block, true, &mut texels),
+        PixelFormat::Bc2Rgba | PixelFormat::Bc2RgbaSrgb => {
+            decode_bc1(&block[8..], false, &mut texels);
+            for (i, texel) in texels.iter_mut().enumerate() {
+                let alpha = (block[i / 2] >> (4 * (i % 2))) & 0xf;
+                texel[3] = alpha as f32 / 15.0;
+            }
+        }
+        PixelFormat::Bc3Rgba | PixelFormat::Bc3RgbaSrgb => {
+            decode_bc1(&block[8..], false, &mut texels);
+            decode_bc4(block, false, 3, &mut texels);
+        }
+        PixelFormat::Bc4RUnorm => decode_bc4(block, false, 0, &mut texels),
+        PixelFormat::Bc4RSnorm => decode_bc4(block, true, 0, &mut texels),
+        PixelFormat::Bc5RgUnorm | PixelFormat::Bc5RgSnorm => {
+            let signed = format == PixelFormat::Bc5RgSnorm;
+            decode_bc4(block, signed, 0, &mut texels);
+            decode_bc4(&block[8..], signed, 1, &mut texels);
+        }
+        PixelFormat::Bc6HRgbFloat => decode_bc6h(block, true, &mut texels),
+        PixelFormat::Bc6HRgbUfloat => decode_bc6h(block, false, &mut texels),
+        PixelFormat::Bc7RgbaUnorm | PixelFormat::Bc7RgbaUnormSrgb => decode_bc7(block, &mut texels),
+        _ => unreachable!(),
+    }
+    Ok(texels)
+}
+
+/// Decodes `region` of the block-compressed `data` to tightly packed RGBA8 texels.
+///
+/// The region's origin is relative to the first block in `data`, and does not need to be aligned
+/// to a block. sRGB formats are returned sRGB encoded, signed formats are remapped from
+/// `[-1.0, 1.0]` to `[0, 255]` and BC6H values are clamped to `[0.0, 1.0]`.
+pub fn decode_rgba8(format: PixelFormat, data: &[u8], bytes_per_row: usize, region: Region)
+                    -> Result<Vec<u8>, FormatError> {
+    let signed = match format {
+        PixelFormat::Bc4RSnorm | PixelFormat::Bc5RgSnorm => true,
+        _ => false,
+    };
+    let mut rgba = vec![];
+    try!(decode_region(format, data, bytes_per_row, region, |texel| {
+        for &component in texel {
+            let unorm = if signed { (component + 1.0) * 0.5 } else { component };
+            rgba.push((unorm.max(0.0).min(1.0) * 255.0).round() as u8);
+        }
+    }));
+    Ok(rgba)
+}
+
+/// Decodes `region` of the block-compressed `data` to tightly packed RGBA32F texels.
+///
+/// The region's origin is relative to the first block in `data`, and does not need to be aligned
+/// to a block. sRGB formats are converted to linear, as they would be when sampled.
+pub fn decode_rgba32f(format: PixelFormat, data: &[u8], bytes_per_row: usize, region: Region)
+                      -> Result<Vec<f32>, FormatError> {
+    let is_srgb = format.is_srgb();
+    let mut rgba = vec![];
+    try!(decode_region(format, data, bytes_per_row, region, |texel| {
+        if is_srgb {
+            rgba.extend(texel[..3].iter().map(|&c| srgb_to_linear(c)));
+            rgba.push(texel[3]);
+        } else {
+            rgba.extend_from_slice(texel);
+        }
+    }));
+    Ok(rgba)
+}
+
+fn block_len(format: PixelFormat) -> Result<usize, FormatError> {
+    match format.info() {
+        Some(ref info) if info.is_compressed => Ok(info.bytes_per_block),
+        _ => Err(FormatError::UnsupportedFormat(format)),
+    }
+}
+
+fn decode_region<F>(format: PixelFormat, data: &[u8], bytes_per_row: usize, region: Region,
+                    mut emit: F)
+                    -> Result<(), FormatError>
+    where F: FnMut(&[f32; 4]) {
+    let block_len = try!(block_len(format));
+    let Region { origin, size } = region;
+    if size.depth > 1 {
+        return Err(FormatError::UnsupportedDepth(size.depth));
+    }
+    if size.width == 0 || size.height == 0 || size.depth == 0 {
+        return Ok(());
+    }
+
+    let (blocks_wide, blocks_high) = ((origin.x + size.width + 3) / 4,
+                                      (origin.y + size.height + 3) / 4);
+    let row_len = blocks_wide * block_len;
+    if bytes_per_row < row_len {
+        return Err(FormatError::Layout(TextureLayoutError::BytesPerRowTooSmall {
+            bytes_per_row: bytes_per_row,
+            required: row_len
+        }));
+    }
+    let required = (blocks_high - 1) * bytes_per_row + row_len;
+    if data.len() < required {
+        return Err(FormatError::Layout(TextureLayoutError::BufferTooSmall {
+            len: data.len(),
+            required: required
+        }));
+    }
+
+    let first_block = origin.x / 4;
+    let mut row_blocks = vec![[[0.0; 4]; 16]; blocks_wide - first_block];
+    for block_y in origin.y / 4..blocks_high {
+        for (i, block) in row_blocks.iter_mut().enumerate() {
+            let offset = block_y * bytes_per_row + (first_block + i) * block_len;
+            *block = try!(decode_block(format, &data[offset..offset + block_len]));
+        }
+
+        let rows = (block_y * 4..block_y * 4 + 4).filter(|&y| {
+            y >= origin.y && y < origin.y + size.height
+        });
+        for y in rows {
+            for x in origin.x..origin.x + size.width {
+                let block = &row_blocks[x / 4 - first_block];
+                emit(&block[(y % 4) * 4 + x % 4]);
+            }
+        }
+    }
+    Ok(())
+}
+
+fn unpack_565(color: u16) -> [u32; 3] {
+    let (r, g, b) = ((color >> 11) as u32, ((color >> 5) & 0x3f) as u32, (color & 0x1f) as u32);
+    [(r << 3) | (r >> 2), (g << 2) | (g >> 4), (b << 3) | (b >> 2)]
+}
+
+/// Returns the four colors of a BC1 color block, as RGBA8.
+fn bc1_palette(color0: u16, color1: u16, allow_transparency: bool) -> [[u32; 4]; 4] {
+    let (c0, c1) = (unpack_565(color0), unpack_565(color1));
+    let four_color = color0 > color1 || !allow_transparency;
+
+    let mut palette = [[0, 0, 0, 255]; 4];
+    for c in 0..3 {
+        palette[0][c] = c0[c];
+        palette[1][c] = c1[c];
+        if four_color {
+            palette[2][c] = (2 * c0[c] + c1[c] + 1) / 3;
+            palette[3][c] = (c0[c] + 2 * c1[c] + 1) / 3;
+        } else {
+            palette[2][c] = (c0[c] + c1[c] + 1) / 2;
+        }
+    }
+    if !four_color {
+        palette[3][3] = 0;
+    }
+    palette
+}
+
+fn decode_bc1(block: &[u8], allow_transparency: bool, texels: &mut Block) {
+    let color0 = block[0] as u16 | (block[1] as u16) << 8;
+    let color1 = block[2] as u16 | (block[3] as u16) << 8;
+    let palette = bc1_palette(color0, color1, allow_transparency);
+
+    for (i, texel) in texels.iter_mut().enumerate() {
+        let index = (block[4 + i / 4] >> (2 * (i % 4))) & 0x3;
+        let color = palette[index as usize];
+        texel[0] = color[0] as f32 / 255.0;
+        texel[1] = color[1] as f32 / 255.0;
+        texel[2] = color[2] as f32 / 255.0;
+        if allow_transparency {
+            texel[3] = color[3] as f32 / 255.0;
+        }
+    }
+}
+
+/// Returns the eight values of a BC4 block, normalized to `[0.0, 1.0]` or `[-1.0, 1.0]`.
+fn bc4_palette(e0: u8, e1: u8, signed: bool) -> [f32; 8] {
+    let (six_values, e0, e1) = if signed {
+        // -128 is clamped to -127 so that both ends of the range are symmetric.
+        let unpack = |byte: u8| (byte as i8).max(-127) as f32 / 127.0;
+        ((e0 as i8) <= (e1 as i8), unpack(e0), unpack(e1))
+    } else {
+        (e0 <= e1, e0 as f32 / 255.0, e1 as f32 / 255.0)
+    };
+    let (min, max) = if signed { (-1.0, 1.0) } else { (0.0, 1.0) };
+
+    let mut palette = [e0, e1, 0.0, 0.0, 0.0, 0.0, min, max];
+    if six_values {
+        for i in 1..5 {
+            palette[i + 1] = ((5 - i) as f32 * e0 + i as f32 * e1) / 5.0;
+        }
+    } else {
+        for i in 1..7 {
+            palette[i + 1] = ((7 - i) as f32 * e0 + i as f32 * e1) / 7.0;
+        }
+    }
+    palette
+}
+
+fn decode_bc4(block: &[u8], signed: bool, channel: usize, texels: &mut Block) {
+    let palette = bc4_palette(block[0], block[1], signed);
+
+    let mut indices = 0u64;
+    for (i, &byte) in block[2..8].iter().enumerate() {
+        indices |= (byte as u64) << (8 * i);
+    }
+    for (i, texel) in texels.iter_mut().enumerate() {
+        texel[channel] = palette[((indices >> (3 * i)) & 0x7) as usize];
+    }
+}
+
+/// Reads little-endian bit fields from a block, starting at the least significant bit.
+struct BitReader<'a> {
+    data: &'a [u8],
+    position: usize
+}
+
+impl<'a> BitReader<'a> {
+    fn new(data: &'a [u8]) -> Self {
+        BitReader { data: data, position: 0 }
+    }
+
+    fn read(&mut self, count: usize) -> u32 {
+        let mut value = 0;
+        for i in 0..count {
+            let bit = self.position + i;
+            value |= (((self.data[bit / 8] >> (bit % 8)) & 1) as u32) << i;
+        }
+        self.position += count;
+        value
+    }
+}
+
+const WEIGHTS_2: [u32; 4] = [0, 21, 43, 64];
+const WEIGHTS_3: [u32; 8] = [0, 9, 18, 27, 37, 46, 55, 64];
+const WEIGHTS_4: [u32; 16] = [0, 4, 9, 13, 17, 21, 26, 30, 34, 38, 43, 47, 51, 55, 60, 64];
+
+fn weights(index_bits: usize) -> &'static [u32] {
+    match index_bits {
+        2 => &WEIGHTS_2,
+        3 => &WEIGHTS_3,
+        _ => &WEIGHTS_4,
+    }
+}
+
+fn interpolate(e0: u32, e1: u32, weight: u32) -> u32 {
+    ((64 - weight) * e0 + weight * e1 + 32) >> 6
+}
+
+/// The subset of each texel for the 2-subset partitions, one bit per texel.
+const PARTITIONS_2: [u16; 64] = [
+    0xcccc, 0x8888, 0xeeee, 0xecc8, 0xc880, 0xfeec, 0xfec8, 0xec80,
+    0xc800, 0xffec, 0xfe80, 0xe800, 0xffe8, 0xff00, 0xfff0, 0xf000,
+    0xf710, 0x008e, 0x7100, 0x08ce, 0x008c, 0x7310, 0x3100, 0x8cce,
+    0x088c, 0x3110, 0x6666, 0x366c, 0x17e8, 0x0ff0, 0x718e, 0x399c,
+    0xaaaa, 0xf0f0, 0x5a5a, 0x33cc, 0x3c3c, 0x55aa, 0x9696, 0xa55a,
+    0x73ce, 0x13c8, 0x324c, 0x3bdc, 0x6996, 0xc33c, 0x9966, 0x0660,
+    0x0272, 0x04e4, 0x4e40, 0x2720, 0xc936, 0x936c, 0x39c6, 0x639c,
+    0x9336, 0x9cc6, 0x817e, 0xe718, 0xccf0, 0x0fcc, 0x7744, 0xee22
+];
+
+/// The subset of each texel for the 3-subset partitions, two bits per texel.
+const PARTITIONS_3: [u32; 64] = [
+    0xaa685050, 0x6a5a5040, 0x5a5a4200, 0x5450a0a8, 0xa5a50000, 0xa0a05050, 0x5555a0a0, 0x5a5a5050,
+    0xaa550000, 0xaa555500, 0xaaaa5500, 0x90909090, 0x94949494, 0xa4a4a4a4, 0xa9a59450, 0x2a0a4250,
+    0xa5945040, 0x0a425054, 0xa5a5a500, 0x55a0a0a0, 0xa8a85454, 0x6a6a4040, 0xa4a45000, 0x1a1a0500,
+    0x0050a4a4, 0xaaa59090, 0x14696914, 0x69691400, 0xa08585a0, 0xaa821414, 0x50a4a450, 0x6a5a0200,
+    0xa9a58000, 0x5090a0a8, 0xa8a09050, 0x24242424, 0x00aa5500, 0x24924924, 0x24499224, 0x50a50a50,
+    0x500aa550, 0xaaaa4444, 0x66660000, 0xa5a0a5a0, 0x50a050a0, 0x69286928, 0x44aaaa44, 0x66666600,
+    0xaa444444, 0x54a854a8, 0x95809580, 0x96969600, 0xa85454a8, 0x80959580, 0xaa141414, 0x96960000,
+    0xaaaa1414, 0xa05050a0, 0xa0a5a5a0, 0x96000000, 0x40804080, 0xa9a8a9a8, 0xaaaaaa44, 0x2a4a5254
+];
+
+/// The anchor texel of the second subset of each 2-subset partition.
+const ANCHORS_2: [usize; 64] = [
+    15, 15, 15, 15, 15, 15, 15, 15, 15, 15, 15, 15, 15, 15, 15, 15,
+    15, 2, 8, 2, 2, 8, 8, 15, 2, 8, 2, 2, 8, 8, 2, 2,
+    15, 15, 6, 8, 2, 8, 15, 15, 2, 8, 2, 2, 2, 15, 15, 6,
+    6, 2, 6, 8, 15, 15, 2, 2, 15, 15, 15, 15, 15, 2, 2, 15
+];
+
+/// The anchor texels of the second and third subsets of each 3-subset partition.
+const ANCHORS_3: [[usize; 64]; 2] = [
+    [3, 3, 15, 15, 8, 3, 15, 15, 8, 8, 6, 6, 6, 5, 3, 3,
+     3, 3, 8, 15, 3, 3, 6, 10, 5, 8, 8, 6, 8, 5, 15, 15,
+     8, 15, 3, 5, 6, 10, 8, 15, 15, 3, 15, 5, 15, 15, 15, 15,
+     3, 15, 5, 5, 5, 8, 5, 10, 5, 10, 8, 13, 15, 12, 3, 3],
+    [15, 8, 8, 3, 15, 15, 3, 8, 15, 15, 15, 15, 15, 15, 15, 8,
+     15, 8, 15, 3, 15, 8, 15, 8, 3, 15, 6, 10, 15, 15, 10, 8,
+     15, 3, 15, 10, 10, 8, 9, 10, 6, 15, 8, 15, 3, 6, 6, 8,
+     15, 3, 15, 15, 15, 15, 15, 15, 15, 15, 15, 15, 3, 15, 15, 8]
+];
+
+fn subset(subsets: usize, partition: usize, texel: usize) -> usize {
+    match subsets {
+        2 => ((PARTITIONS_2[partition] >> texel) & 1) as usize,
+        3 => ((PARTITIONS_3[partition] >> (2 * texel)) & 3) as usize,
+        _ => 0,
+    }
+}
+
+fn is_anchor(subsets: usize, partition: usize, texel: usize) -> bool {
+    texel == 0 ||
+    match subsets {
+        2 => texel == ANCHORS_2[partition],
+        3 => texel == ANCHORS_3[0][partition] || texel == ANCHORS_3[1][partition],
+        _ => false,
+    }
+}
+
+struct Bc7Mode {
+    subsets: usize,
+    partition_bits: usize,
+    rotation_bits: usize,
+    index_selection_bits: usize,
+    color_bits: usize,
+    alpha_bits: usize,
+    endpoint_pbits: bool,
+    shared_pbits: bool,
+    index_bits: usize,
+    secondary_index_bits: usize
+}
+
+#[cfg_attr(rustfmt, rustfmt_skip)]
+const BC7_MODES: [Bc7Mode; 8] = [
+    Bc7Mode { subsets: 3, partition_bits: 4, rotation_bits: 0, index_selection_bits: 0,
+              color_bits: 4, alpha_bits: 0, endpoint_pbits: true, shared_pbits: false,
+              index_bits: 3, secondary_index_bits: 0 },
+    Bc7Mode { subsets: 2, partition_bits: 6, rotation_bits: 0, index_selection_bits: 0,
+              color_bits: 6, alpha_bits: 0, endpoint_pbits: false, shared_pbits: true,
+              index_bits: 3, secondary_index_bits: 0 },
+    Bc7Mode { subsets: 3, partition_bits: 6, rotation_bits: 0, index_selection_bits: 0,
+              color_bits: 5, alpha_bits: 0, endpoint_pbits: false, shared_pbits: false,
+              index_bits: 2, secondary_index_bits: 0 },
+    Bc7Mode { subsets: 2, partition_bits: 6, rotation_bits: 0, index_selection_bits: 0,
+              color_bits: 7, alpha_bits: 0, endpoint_pbits: true, shared_pbits: false,
+              index_bits: 2, secondary_index_bits: 0 },
+    Bc7Mode { subsets: 1, partition_bits: 0, rotation_bits: 2, index_selection_bits: 1,
+              color_bits: 5, alpha_bits: 6, endpoint_pbits: false, shared_pbits: false,
+              index_bits: 2, secondary_index_bits: 3 },
+    Bc7Mode { subsets: 1, partition_bits: 0, rotation_bits: 2, index_selection_bits: 0,
+              color_bits: 7, alpha_bits: 8, endpoint_pbits: false, shared_pbits: false,
+              index_bits: 2, secondary_index_bits: 2 },
+    Bc7Mode { subsets: 1, partition_bits: 0, rotation_bits: 0, index_selection_bits: 0,
+              color_bits: 7, alpha_bits: 7, endpoint_pbits: true, shared_pbits: false,
+              index_bits: 4, secondary_index_bits: 0 },
+    Bc7Mode { subsets: 2, partition_bits: 6, rotation_bits: 0, index_selection_bits: 0,
+              color_bits: 5, alpha_bits: 5, endpoint_pbits: true, shared_pbits: false,
+              index_bits: 2, secondary_index_bits: 0 }
+];
+
+fn decode_bc7(block: &[u8], texels: &mut Block) {
+    let mode_index = match (0..8).find(|&bit| block[0] & (1 << bit) != 0) {
+        Some(mode_index) => mode_index,
+        None => {
+            // Reserved modes decode to transparent black.
+            *texels = [[0.0; 4]; 16];
+            return;
+        }
+    };
+    let mode = &BC7_MODES[mode_index];
+    let mut bits = BitReader::new(block);
+    bits.read(mode_index + 1);
+
+    let partition = bits.read(mode.partition_bits) as usize;
+    let rotation = bits.read(mode.rotation_bits);
+    let index_selection = bits.read(mode.index_selection_bits);
+
+    let endpoint_count = 2 * mode.subsets;
+    let mut endpoints = [[0u32; 4]; 6];
+    for channel in 0..3 {
+        for endpoint in endpoints[..endpoint_count].iter_mut() {
+            endpoint[channel] = bits.read(mode.color_bits);
+        }
+    }
+    for endpoint in endpoints[..endpoint_count].iter_mut() {
+        endpoint[3] = bits.read(mode.alpha_bits);
+    }
+
+    let mut color_bits = mode.color_bits;
+    let mut alpha_bits = mode.alpha_bits;
+    if mode.endpoint_pbits || mode.shared_pbits {
+        let mut pbits = [0; 6];
+        if mode.endpoint_pbits {
+            for pbit in pbits[..endpoint_count].iter_mut() {
+                *pbit = bits.read(1);
+            }
+        } else {
+            for subset in 0..mode.subsets {
+                let pbit = bits.read(1);
+                pbits[2 * subset] = pbit;
+                pbits[2 * subset + 1] = pbit;
+            }
+        }
+        for (endpoint, &pbit) in endpoints[..endpoint_count].iter_mut().zip(pbits.iter()) {
+            for component in endpoint.iter_mut() {
+                *component = (*component << 1) | pbit;
+            }
+        }
+        color_bits += 1;
+        if alpha_bits > 0 {
+            alpha_bits += 1;
+        }
+    }
+
+    let expand = |value: u32, bits: usize| {
+        let value = value << (8 - bits);
+        value | (value >> bits)
+    };
+    for endpoint in endpoints[..endpoint_count].iter_mut() {
+        for channel in 0..3 {
+            endpoint[channel] = expand(endpoint[channel], color_bits);
+        }
+        endpoint[3] = if alpha_bits > 0 { expand(endpoint[3], alpha_bits) } else { 255 };
+    }
+
+    let mut indices = [0; 16];
+    for (texel, index) in indices.iter_mut().enumerate() {
+        let anchor = is_anchor(mode.subsets, partition, texel);
+        *index = bits.read(mode.index_bits - anchor as usize) as usize;
+    }
+    let mut secondary_indices = [0; 16];
+    if mode.secondary_index_bits > 0 {
+        for (texel, index) in secondary_indices.iter_mut().enumerate() {
+            let anchor = texel == 0;
+            *index = bits.read(mode.secondary_index_bits - anchor as usize) as usize;
+        }
+    }
+
+    for (i, texel) in texels.iter_mut().enumerate() {
+        let subset = subset(mode.subsets, partition, i);
+        let (e0, e1) = (endpoints[2 * subset], endpoints[2 * subset + 1]);
+
+        let (color_weight, alpha_weight) = if mode.secondary_index_bits == 0 {
+            let weight = weights(mode.index_bits)[indices[i]];
+            (weight, weight)
+        } else {
+            let primary = weights(mode.index_bits)[indices[i]];
+            let secondary = weights(mode.secondary_index_bits)[secondary_indices[i]];
+            if index_selection == 0 { (primary, secondary) } else { (secondary, primary) }
+        };
+
+        let mut color = [0; 4];
+        for channel in 0..3 {
+            color[channel] = interpolate(e0[channel], e1[channel], color_weight);
+        }
+        color[3] = interpolate(e0[3], e1[3], alpha_weight);
+
+        if rotation > 0 {
+            color.swap(3, rotation as usize - 1);
+        }
+        for (component, value) in texel.iter_mut().zip(color.iter()) {
+            *component = *value as f32 / 255.0;
+        }
+    }
+}
+
+// BC6H endpoint fields, in the order they are stored in `Bc6hMode::fields`.
+const RW: usize = 0;
+const GW: usize = 1;
+const BW: usize = 2;
+const RX: usize = 3;
+const GX: usize = 4;
+const BX: usize = 5;
+const RY: usize = 6;
+const GY: usize = 7;
+const BY: usize = 8;
+const RZ: usize = 9;
+const GZ: usize = 10;
+const BZ: usize = 11;
+const PARTITION: usize = 12;
+
+struct Bc6hMode {
+    /// The value of the mode bits.
+    mode: u32,
+    transformed: bool,
+    endpoint_bits: usize,
+    delta_bits: [usize; 3],
+    subsets: usize,
+    /// Runs of `(field, first bit, bit count)` in the order they are stored in the block.
+    fields: &'static [(usize, usize, usize)]
+}
+
+#[cfg_attr(rustfmt, rustfmt_skip)]
+const BC6H_MODES: [Bc6hMode; 14] = [
+    Bc6hMode { mode: 0x00, transformed: true, endpoint_bits: 10, delta_bits: [5, 5, 5],
+               subsets: 2, fields: &[
+        (GY, 4, 1), (BY, 4, 1), (BZ, 4, 1), (RW, 0, 10), (GW, 0, 10), (BW, 0, 10), (RX, 0, 5),
+        (GZ, 4, 1), (GY, 0, 4), (GX, 0, 5), (BZ, 0, 1), (GZ, 0, 4), (BX, 0, 5), (BZ, 1, 1),
+        (BY, 0, 4), (RY, 0, 5), (BZ, 2, 1), (RZ, 0, 5), (BZ, 3, 1), (PARTITION, 0, 5)] },
+    Bc6hMode { mode: 0x01, transformed: true, endpoint_bits: 7, delta_bits: [6, 6, 6],
+               subsets: 2, fields: &[
+        (GY, 5, 1), (GZ, 4, 1), (GZ, 5, 1), (RW, 0, 7), (BZ, 0, 1), (BZ, 1, 1), (BY, 4, 1),
+        (GW, 0, 7), (BY, 5, 1), (BZ, 2, 1), (GY, 4, 1), (BW, 0, 7), (BZ, 3, 1), (BZ, 5, 1),
+        (BZ, 4, 1), (RX, 0, 6), (GY, 0, 4), (GX, 0, 6), (GZ, 0, 4), (BX, 0, 6), (BY, 0, 4),
+        (RY, 0, 6), (RZ, 0, 6), (PARTITION, 0, 5)] },
+    Bc6hMode { mode: 0x02, transformed: true, endpoint_bits: 11, delta_bits: [5, 4, 4],
+               subsets: 2, fields: &[
+        (RW, 0, 10), (GW, 0, 10), (BW, 0, 10), (RX, 0, 5), (RW, 10, 1), (GY, 0, 4), (GX, 0, 4),
+        (GW, 10, 1), (BZ, 0, 1), (GZ, 0, 4), (BX, 0, 4), (BW, 10, 1), (BZ, 1, 1), (BY, 0, 4),
+        (RY, 0, 5), (BZ, 2, 1), (RZ, 0, 5), (BZ, 3, 1), (PARTITION, 0, 5)] },
+    Bc6hMode { mode: 0x06, transformed: true, endpoint_bits: 11, delta_bits: [4, 5, 4],
+               subsets: 2, fields: &[
+        (RW, 0, 10), (GW, 0, 10), (BW, 0, 10), (RX, 0, 4), (RW, 10, 1), (GZ, 4, 1), (GY, 0, 4),
+        (GX, 0, 5), (GW, 10, 1), (GZ, 0, 4), (BX, 0, 4), (BW, 10, 1), (BZ, 1, 1), (BY, 0, 4),
+        (RY, 0, 4), (BZ, 0, 1), (BZ, 2, 1), (RZ, 0, 4), (GY, 4, 1), (BZ, 3, 1),
+        (PARTITION, 0, 5)] },
+    Bc6hMode { mode: 0x0a, transformed: true, endpoint_bits: 11, delta_bits: [4, 4, 5],
+               subsets: 2, fields: &[
+        (RW, 0, 10), (GW, 0, 10), (BW, 0, 10), (RX, 0, 4), (RW, 10, 1), (BY, 4, 1), (GY, 0, 4),
+        (GX, 0, 4), (GW, 10, 1), (BZ, 0, 1), (GZ, 0, 4), (BX, 0, 5), (BW, 10, 1), (BY, 0, 4),
+        (RY, 0, 4), (BZ, 1, 1), (BZ, 2, 1), (RZ, 0, 4), (BZ, 4, 1), (BZ, 3, 1),
+        (PARTITION, 0, 5)] },
+    Bc6hMode { mode: 0x0e, transformed: true, endpoint_bits: 9, delta_bits: [5, 5, 5],
+               subsets: 2, fields: &[
+        (RW, 0, 9), (BY, 4, 1), (GW, 0, 9), (GY, 4, 1), (BW, 0, 9), (BZ, 4, 1), (RX, 0, 5),
+        (GZ, 4, 1), (GY, 0, 4), (GX, 0, 5), (BZ, 0, 1), (GZ, 0, 4), (BX, 0, 5), (BZ, 1, 1),
+        (BY, 0, 4), (RY, 0, 5), (BZ, 2, 1), (RZ, 0, 5), (BZ, 3, 1), (PARTITION, 0, 5)] },
+    Bc6hMode { mode: 0x12, transformed: true, endpoint_bits: 8, delta_bits: [6, 5, 5],
+               subsets: 2, fields: &[
+        (RW, 0, 8), (GZ, 4, 1), (BY, 4, 1), (GW, 0, 8), (BZ, 2, 1), (GY, 4, 1), (BW, 0, 8),
+        (BZ, 3, 1), (BZ, 4, 1), (RX, 0, 6), (GY, 0, 4), (GX, 0, 5), (BZ, 0, 1), (GZ, 0, 4),
+        (BX, 0, 5), (BZ, 1, 1), (BY, 0, 4), (RY, 0, 6), (RZ, 0, 6), (PARTITION, 0, 5)] },
+    Bc6hMode { mode: 0x16, transformed: true, endpoint_bits: 8, delta_bits: [5, 6, 5],
+               subsets: 2, fields: &[
+        (RW, 0, 8), (BZ, 0, 1), (BY, 4, 1), (GW, 0, 8), (GY, 5, 1), (GY, 4, 1), (BW, 0, 8),
+        (GZ, 5, 1), (BZ, 4, 1), (RX, 0, 5), (GZ, 4, 1), (GY, 0, 4), (GX, 0, 6), (GZ, 0, 4),
+        (BX, 0, 5), (BZ, 1, 1), (BY, 0, 4), (RY, 0, 5), (BZ, 2, 1), (RZ, 0, 5), (BZ, 3, 1),
+        (PARTITION, 0, 5)] },
+    Bc6hMode { mode: 0x1a, transformed: true, endpoint_bits: 8, delta_bits: [5, 5, 6],
+               subsets: 2, fields: &[
+        (RW, 0, 8), (BZ, 1, 1), (BY, 4, 1), (GW, 0, 8), (BY, 5, 1), (GY, 4, 1), (BW, 0, 8),
+        (BZ, 5, 1), (BZ, 4, 1), (RX, 0, 5), (GZ, 4, 1), (GY, 0, 4), (GX, 0, 5), (BZ, 0, 1),
+        (GZ, 0, 4), (BX, 0, 6), (BY, 0, 4), (RY, 0, 5), (BZ, 2, 1), (RZ, 0, 5), (BZ, 3, 1),
+        (PARTITION, 0, 5)] },
+    Bc6hMode { mode: 0x1e, transformed: false, endpoint_bits: 6, delta_bits: [6, 6, 6],
+               subsets: 2, fields: &[
+        (RW, 0, 6), (GZ, 4, 1), (BZ, 0, 1), (BZ, 1, 1), (BY, 4, 1), (GW, 0, 6), (GY, 5, 1),
+        (BY, 5, 1), (BZ, 2, 1), (GY, 4, 1), (BW, 0, 6), (GZ, 5, 1), (BZ, 3, 1), (BZ, 5, 1),
+        (BZ, 4, 1), (RX, 0, 6), (GY, 0, 4), (GX, 0, 6), (GZ, 0, 4), (BX, 0, 6), (BY, 0, 4),
+        (RY, 0, 6), (RZ, 0, 6), (PARTITION, 0, 5)] },
+    Bc6hMode { mode: 0x03, transformed: false, endpoint_bits: 10, delta_bits: [10, 10, 10],
+               subsets: 1, fields: &[
+        (RW, 0, 10), (GW, 0, 10), (BW, 0, 10), (RX, 0, 10), (GX, 0, 10), (BX, 0, 10)] },
+    Bc6hMode { mode: 0x07, transformed: true, endpoint_bits: 11, delta_bits: [9, 9, 9],
+               subsets: 1, fields: &[
+        (RW, 0, 10), (GW, 0, 10), (BW, 0, 10), (RX, 0, 9), (RW, 10, 1), (GX, 0, 9), (GW, 10, 1),
+        (BX, 0, 9), (BW, 10, 1)] },
+    // The high bits of the base endpoint are stored in reverse order in the last two modes.
+    Bc6hMode { mode: 0x0b, transformed: true, endpoint_bits: 12, delta_bits: [8, 8, 8],
+               subsets: 1, fields: &[
+        (RW, 0, 10), (GW, 0, 10), (BW, 0, 10), (RX, 0, 8), (RW, 11, 1), (RW, 10, 1), (GX, 0, 8),
+        (GW, 11, 1), (GW, 10, 1), (BX, 0, 8), (BW, 11, 1), (BW, 10, 1)] },
+    Bc6hMode { mode: 0x0f, transformed: true, endpoint_bits: 16, delta_bits: [4, 4, 4],
+               subsets: 1, fields: &[
+        (RW, 0, 10), (GW, 0, 10), (BW, 0, 10), (RX, 0, 4), (RW, 15, 1), (RW, 14, 1), (RW, 13, 1),
+        (RW, 12, 1), (RW, 11, 1), (RW, 10, 1), (GX, 0, 4), (GW, 15, 1), (GW, 14, 1), (GW, 13, 1),
+        (GW, 12, 1), (GW, 11, 1), (GW, 10, 1), (BX, 0, 4), (BW, 15, 1), (BW, 14, 1), (BW, 13, 1),
+        (BW, 12, 1), (BW, 11, 1), (BW, 10, 1)] }
+];
+
+fn sign_extend(value: i32, bits: usize) -> i32 {
+    let shift = 32 - bits;
+    (value << shift) >> shift
+}
+
+fn unquantize_bc6h(value: i32, bits: usize, signed: bool) -> i32 {
+    if signed {
+        if bits >= 16 {
+            return value;
+        }
+        let magnitude = value.abs();
+        let unquantized = if magnitude == 0 {
+            0
+        } else if magnitude >= (1 << (bits - 1)) - 1 {
+            0x7fff
+        } else {
+            ((magnitude << 15) + 0x4000) >> (bits - 1)
+        };
+        if value < 0 { -unquantized } else { unquantized }
+    } else {
+        if bits >= 15 {
+            value
+        } else if value == 0 {
+            0
+        } else if value == (1 << bits) - 1 {
+            0xffff
+        } else {
+            ((value << 15) + 0x4000) >> (bits - 1)
+        }
+    }
+}
+
+fn finish_unquantize_bc6h(value: i32, signed: bool) -> u16 {
+    if signed {
+        if value < 0 { 0x8000 | ((-value * 31) >> 5) as u16 } else { ((value * 31) >> 5) as u16 }
+    } else {
+        ((value * 31) >> 6) as u16
+    }
+}
+
+fn decode_bc6h(block: &[u8], signed: bool, texels: &mut Block) {
+    let mut bits = BitReader::new(block);
+    let mut mode_bits = bits.read(2);
+    if mode_bits > 1 {
+        mode_bits |= bits.read(3) << 2;
+    }
+    let mode = match BC6H_MODES.iter().find(|mode| mode.mode == mode_bits) {
+        Some(mode) => mode,
+        None => {
+            // Reserved modes decode to black.
+            *texels = [[0.0, 0.0, 0.0, 1.0]; 16];
+            return;
+        }
+    };
+
+    let mut fields = [0i32; 13];
+    for &(field, first_bit, count) in mode.fields {
+        fields[field] |= (bits.read(count) as i32) << first_bit;
+    }
+
+    // endpoints[endpoint][channel], ordered as (w, x, y, z).
+    let endpoint_count = 2 * mode.subsets;
+    let mut endpoints = [[0i32; 3]; 4];
+    for channel in 0..3 {
+        for endpoint in 0..endpoint_count {
+            endpoints[endpoint][channel] = fields[RW + 3 * endpoint + channel];
+        }
+    }
+
+    let mask = (1 << mode.endpoint_bits) - 1;
+    for channel in 0..3 {
+        if signed {
+            endpoints[0][channel] = sign_extend(endpoints[0][channel], mode.endpoint_bits);
+        }
+        let base = endpoints[0][channel];
+        for endpoint in endpoints[1..endpoint_count].iter_mut() {
+            if mode.transformed || signed {
+                endpoint[channel] = sign_extend(endpoint[channel], mode.delta_bits[channel]);
+            }
+            if mode.transformed {
+                endpoint[channel] = (base + endpoint[channel]) & mask;
+                if signed {
+                    endpoint[channel] = sign_extend(endpoint[channel], mode.endpoint_bits);
+                }
+            }
+        }
+    }
+    for endpoint in endpoints[..endpoint_count].iter_mut() {
+        for component in endpoint.iter_mut() {
+            *component = unquantize_bc6h(*component, mode.endpoint_bits, signed);
+        }
+    }
+
+    let partition = fields[PARTITION] as usize;
+    let index_bits = if mode.subsets == 2 { 3 } else { 4 };
+    let weights = weights(index_bits);
+    for (i, texel) in texels.iter_mut().enumerate() {
+        let anchor = is_anchor(mode.subsets, partition, i);
+        let weight = weights[bits.read(index_bits - anchor as usize) as usize] as i32;
+        let subset = subset(mode.subsets, partition, i);
+        let (e0, e1) = (endpoints[2 * subset], endpoints[2 * subset + 1]);
+        for channel in 0..3 {
+            let value = ((64 - weight) * e0[channel] + weight * e1[channel] + 32) >> 6;
+            texel[channel] = half_to_f32(finish_unquantize_bc6h(value, signed));
+        }
+        texel[3] = 1.0;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::{ANCHORS_2, ANCHORS_3, subset};
+    use formats::FormatError;
+    use {PixelFormat, Region, TextureLayoutError};
+
+    /// Packs bit fields least significant bit first, the way BC6H and BC7 blocks are stored.
+    struct BitWriter {
+        block: [u8; 16],
+        position: usize
+    }
+
+    impl BitWriter {
+        fn new() -> Self {
+            BitWriter { block: [0; 16], position: 0 }
+        }
+
+        fn write(mut self, value: u32, count: usize) -> Self {
+            for i in 0..count {
+                let bit = self.position + i;
+                let value = if i < 32 { (value >> i) & 1 } else { 0 };
+                self.block[bit / 8] |= (value as u8) << (bit % 8);
+            }
+            self.position += count;
+            self
+        }
+
+        fn finish(self) -> [u8; 16] {
+            assert_eq!(self.position, 128);
+            self.block
+        }
+    }
+
+    fn to_rgba8(texel: &[f32; 4]) -> [u8; 4] {
+        let mut rgba = [0; 4];
+        for (out, &component) in rgba.iter_mut().zip(texel.iter()) {
+            *out = (component * 255.0).round() as u8;
+        }
+        rgba
+    }
+
+    #[test]
+    fn test_partition_anchors_lie_in_their_subsets() {
+        for partition in 0..64 {
+            assert_eq!(subset(2, partition, 0), 0);
+            assert_eq!(subset(2, partition, ANCHORS_2[partition]), 1, "{}", partition);
+            assert_eq!(subset(3, partition, 0), 0);
+            assert_eq!(subset(3, partition, ANCHORS_3[0][partition]), 1, "{}", partition);
+            assert_eq!(subset(3, partition, ANCHORS_3[1][partition]), 2, "{}", partition);
+        }
+    }
+
+    #[test]
+    fn test_bc1_four_color_block() {
+        // color0 = pure red, color1 = pure blue, each row uses indices 0, 1, 2, 3
+        let block = [0x00, 0xf8, 0x1f, 0x00, 0xe4, 0xe4, 0xe4, 0xe4];
+        let texels = decode_block(PixelFormat::Bc1Rgba, &block).unwrap();
+        assert_eq!(to_rgba8(&texels[0]), [255, 0, 0, 255]);
+        assert_eq!(to_rgba8(&texels[1]), [0, 0, 255, 255]);
+        assert_eq!(to_rgba8(&texels[2]), [170, 0, 85, 255]);
+        assert_eq!(to_rgba8(&texels[3]), [85, 0, 170, 255]);
+        assert_eq!(to_rgba8(&texels[15]), [85, 0, 170, 255]);
+    }
+
+    #[test]
+    fn test_bc1_three_color_block_has_transparent_black() {
+        // color0 < color1 selects the three color palette
+        let block = [0x1f, 0x00, 0x00, 0xf8, 0xe4, 0xe4, 0xe4, 0xe4];
+        let texels = decode_block(PixelFormat::Bc1RgbaSrgb, &block).unwrap();
+        assert_eq!(to_rgba8(&texels[0]), [0, 0, 255, 255]);
+        assert_eq!(to_rgba8(&texels[1]), [255, 0, 0, 255]);
+        assert_eq!(to_rgba8(&texels[2]), [128, 0, 128, 255]);
+        assert_eq!(to_rgba8(&texels[3]), [0, 0, 0, 0]);
+    }
+
+    #[test]
+    fn test_bc2_explicit_alpha() {
+        let mut block = [0u8; 16];
+        for i in 0..8 {
+            block[i] = (2 * i as u8) | ((2 * i as u8 + 1) << 4);
+        }
+        // a white color block
+        block[8..12].copy_from_slice(&[0xff, 0xff, 0xff, 0xff]);
+        let texels = decode_block(PixelFormat::Bc2Rgba, &block).unwrap();
+        for (i, texel) in texels.iter().enumerate() {
+            assert_eq!(to_rgba8(texel), [255, 255, 255, 17 * i as u8]);
+        }
+    }
+
+    #[test]
+    fn test_bc3_interpolated_alpha() {
+        // alpha0 = 255, alpha1 = 0, the first eight texels use indices 0..8
+        let block = [0xff, 0x00, 0x88, 0xc6, 0xfa, 0x00, 0x00, 0x00, 0xe0, 0x07, 0xe0, 0x07,
+                     0x00, 0x00, 0x00, 0x00];
+        let texels = decode_block(PixelFormat::Bc3Rgba, &block).unwrap();
+        let alphas = texels.iter().map(|texel| to_rgba8(texel)[3]).collect::<Vec<_>>();
+        assert_eq!(&alphas[..8], &[255, 0, 219, 182, 146, 109, 73, 36]);
+        assert_eq!(to_rgba8(&texels[0])[..3], [0, 255, 0]);
+    }
+
+    #[test]
+    fn test_bc4_six_value_and_four_value_modes() {
+        let block = [0x00, 0xff, 0x88, 0xc6, 0xfa, 0x00, 0x00, 0x00];
+        let texels = decode_block(PixelFormat::Bc4RUnorm, &block).unwrap();
+        let reds = texels.iter().map(|texel| to_rgba8(texel)[0]).collect::<Vec<_>>();
+        assert_eq!(&reds[..8], &[0, 255, 51, 102, 153, 204, 0, 255]);
+        assert_eq!(to_rgba8(&texels[0]), [0, 0, 0, 255]);
+    }
+
+    #[test]
+    fn test_bc4_and_bc5_signed() {
+        // red0 = 127, red1 = -128 (clamped to -127)
+        let block = [0x7f, 0x80, 0x88, 0xc6, 0xfa, 0x00, 0x00, 0x00];
+        let texels = decode_block(PixelFormat::Bc4RSnorm, &block).unwrap();
+        assert_eq!(texels[0][0], 1.0);
+        assert_eq!(texels[1][0], -1.0);
+        assert_eq!(texels[2][0], 5.0 / 7.0);
+
+        let mut bc5 = [0u8; 16];
+        bc5[..8].copy_from_slice(&block);
+        bc5[8] = 0x80;
+        bc5[9] = 0x7f;
+        let texels = decode_block(PixelFormat::Bc5RgSnorm, &bc5).unwrap();
+        assert_eq!(texels[0], [1.0, -1.0, 0.0, 1.0]);
+        assert_eq!(texels[1], [-1.0, -1.0, 0.0, 1.0]);
+    }
+
+    #[test]
+    fn test_bc4_signed_endpoints_choose_the_mode() {
+        // red0 = -127 and red1 = -128 select the eight value mode, as the endpoints are compared
+        // before -128 is clamped to -127.
+        let block = [0x81, 0x80, 0x88, 0xc6, 0xfa, 0x00, 0x00, 0x00];
+        let texels = decode_block(PixelFormat::Bc4RSnorm, &block).unwrap();
+        let reds = texels.iter().map(|texel| texel[0]).collect::<Vec<_>>();
+        assert_eq!(&reds[..8], &[-1.0; 8]);
+
+        // red0 = -128 and red1 = 127 select the six value mode, although the unsigned bytes
+        // compare the other way.
+        let block = [0x80, 0x7f, 0x88, 0xc6, 0xfa, 0x00, 0x00, 0x00];
+        let texels = decode_block(PixelFormat::Bc4RSnorm, &block).unwrap();
+        assert_eq!(texels[2][0], -3.0 / 5.0);
+        assert_eq!(texels[6][0], -1.0);
+        assert_eq!(texels[7][0], 1.0);
+    }
+
+    #[test]
+    fn test_bc7_mode6_opaque_gradient() {
+        // endpoint 0 = (0, 0, 0, 254) and endpoint 1 = (255, 255, 255, 255) after their pbits are
+        // applied, and texel i uses index i.
+        let mut writer = BitWriter::new().write(1 << 6, 7);
+        for &(e0, e1) in &[(0, 127), (0, 127), (0, 127), (127, 127)] {
+            writer = writer.write(e0, 7).write(e1, 7);
+        }
+        writer = writer.write(0, 1).write(1, 1).write(0, 3);
+        for i in 1..16 {
+            writer = writer.write(i, 4);
+        }
+        let texels = decode_block(PixelFormat::Bc7RgbaUnorm, &writer.finish()).unwrap();
+
+        let weights = [0, 4, 9, 13, 17, 21, 26, 30, 34, 38, 43, 47, 51, 55, 60, 64];
+        for (texel, weight) in texels.iter().zip(weights.iter()) {
+            let value = ((weight * 255 + 32) >> 6) as u8;
+            let alpha = (((64 - weight) * 254 + weight * 255 + 32) >> 6) as u8;
+            assert_eq!(to_rgba8(texel), [value, value, value, alpha]);
+        }
+    }
+
+    #[test]
+    fn test_bc7_mode1_two_subsets_with_shared_pbits() {
+        // partition 13 puts the top two rows in subset 0 and the bottom two rows in subset 1.
+        // Each row of `endpoints` is one channel of the four endpoints.
+        let mut writer = BitWriter::new().write(0b10, 2).write(13, 6);
+        let endpoints = [[10, 10, 63, 63], [20, 20, 0, 0], [30, 30, 63, 63]];
+        for channel in endpoints.iter() {
+            for &value in channel.iter() {
+                writer = writer.write(value, 6);
+            }
+        }
+        writer = writer.write(1, 1).write(0, 1).write(0, 46);
+        let texels = decode_block(PixelFormat::Bc7RgbaUnorm, &writer.finish()).unwrap();
+
+        let expand = |value: u32| ((value << 1) | (value >> 6)) as u8;
+        assert_eq!(to_rgba8(&texels[0]),
+                   [expand(10 << 1 | 1), expand(20 << 1 | 1), expand(30 << 1 | 1), 255]);
+        assert_eq!(to_rgba8(&texels[15]), [expand(63 << 1), expand(0), expand(63 << 1), 255]);
+    }
+
+    #[test]
+    fn test_bc7_mode5_rotation() {
+        // color endpoints are solid red, alpha endpoints are 0x40, and rotation 1 swaps red and
+        // alpha.
+        let mut writer = BitWriter::new().write(1 << 5, 6).write(1, 2);
+        for &value in &[127, 127, 0, 0, 0, 0] {
+            writer = writer.write(value, 7);
+        }
+        writer = writer.write(0x40, 8).write(0x40, 8).write(0, 31).write(0, 31);
+        let texels = decode_block(PixelFormat::Bc7RgbaUnormSrgb, &writer.finish()).unwrap();
+        assert_eq!(to_rgba8(&texels[7]), [0x40, 0, 0, 0xff]);
+    }
+
+    #[test]
+    fn test_bc7_reserved_mode_is_transparent_black() {
+        let texels = decode_block(PixelFormat::Bc7RgbaUnorm, &[0; 16]).unwrap();
+        assert_eq!(texels, [[0.0; 4]; 16]);
+    }
+
+    #[test]
+    fn test_bc6h_mode11_unsigned_endpoints() {
+        // 10 bit endpoints: 0 and the maximum value, which unquantize to 0.0 and 65504.0 (the
+        // largest finite half) after scaling by 31/64.
+        let mut writer = BitWriter::new().write(0x03, 5);
+        for &value in &[0, 0, 0, 0x3ff, 0x3ff, 0x3ff] {
+            writer = writer.write(value, 10);
+        }
+        writer = writer.write(0, 3);
+        for _ in 1..16 {
+            writer = writer.write(15, 4);
+        }
+        let texels = decode_block(PixelFormat::Bc6HRgbUfloat, &writer.finish()).unwrap();
+        assert_eq!(texels[0], [0.0, 0.0, 0.0, 1.0]);
+        assert_eq!(texels[1], [65504.0, 65504.0, 65504.0, 1.0]);
+    }
+
+    #[test]
+    fn test_bc6h_mode14_transformed_half_endpoints() {
+        // A 16 bit base endpoint holding the half 1.0 (0x3c00 * 64 / 31, rounded up) and a delta
+        // of zero gives a solid block of 1.0. The high bits are stored in reverse order.
+        let base = (0x3c00 * 64 + 30) / 31;
+        let mut writer = BitWriter::new().write(0x0f, 5);
+        for _ in 0..3 {
+            writer = writer.write(base & 0x3ff, 10);
+        }
+        for _ in 0..3 {
+            writer = writer.write(0, 4);
+            for bit in (10..16).rev() {
+                writer = writer.write((base >> bit) & 1, 1);
+            }
+        }
+        writer = writer.write(0, 63);
+        let texels = decode_block(PixelFormat::Bc6HRgbUfloat, &writer.finish()).unwrap();
+        for texel in texels.iter() {
+            assert_eq!(*texel, [1.0, 1.0, 1.0, 1.0]);
+        }
+    }
+
+    #[test]
+    fn test_bc6h_signed_mode11_negative_endpoint() {
+        let mut writer = BitWriter::new().write(0x03, 5);
+        // -1 in 10 bits for every channel of endpoint 0, 0 for endpoint 1
+        for &value in &[0x3ff, 0x3ff, 0x3ff, 0, 0, 0] {
+            writer = writer.write(value, 10);
+        }
+        writer = writer.write(0, 63);
+        let texels = decode_block(PixelFormat::Bc6HRgbFloat, &writer.finish()).unwrap();
+        let expected = -half_to_f32(((((1 << 15) + 0x4000) >> 9) * 31 >> 5) as u16);
+        assert_eq!(texels[0], [expected, expected, expected, 1.0]);
+    }
+
+    #[test]
+    fn test_bc6h_reserved_mode_is_black() {
+        let block = BitWriter::new().write(0x13, 5).write(0, 123).finish();
+        let texels = decode_block(PixelFormat::Bc6HRgbFloat, &block).unwrap();
+        assert_eq!(texels, [[0.0, 0.0, 0.0, 1.0]; 16]);
+    }
+
+    #[test]
+    fn test_decode_region_crosses_blocks() {
+        // Two red blocks followed by two blue blocks, with a padded row pitch.
+        let red = [0x00, 0xf8, 0x00, 0xf8, 0x00, 0x00, 0x00, 0x00];
+        let blue = [0x1f, 0x00, 0x1f, 0x00, 0x00, 0x00, 0x00, 0x00];
+        let mut data = vec![];
+        for block in &[red, blue, red, blue] {
+            data.extend_from_slice(block);
+            if data.len() % 20 == 16 {
+                data.extend_from_slice(&[0; 4]);
+            }
+        }
+
+        let rgba = decode_rgba8(PixelFormat::Bc1Rgba, &data, 20, Region::new_2d(3, 2, 2, 3))
+                       .unwrap();
+        assert_eq!(rgba.len(), 2 * 3 * 4);
+        assert_eq!(&rgba[0..8], &[255, 0, 0, 255, 0, 0, 255, 255]);
+        assert_eq!(&rgba[16..24], &[255, 0, 0, 255, 0, 0, 255, 255]);
+    }
+
+    #[test]
+    fn test_decode_rgba32f_linearizes_srgb() {
+        let gray = [0x10, 0x84, 0x10, 0x84, 0x00, 0x00, 0x00, 0x00];
+        let linear = decode_rgba32f(PixelFormat::Bc1Rgba, &gray, 8, Region::new_2d(0, 0, 1, 1))
+                         .unwrap();
+        let srgb = decode_rgba32f(PixelFormat::Bc1RgbaSrgb, &gray, 8,
+                                  Region::new_2d(0, 0, 1, 1))
+                       .unwrap();
+        assert_eq!(linear[0], 132.0 / 255.0);
+        assert!((srgb[0] - 0.2307401).abs() < 1e-6);
+        assert_eq!(srgb[3], 1.0);
+    }
+
+    #[test]
+    fn test_decode_errors() {
+        assert_eq!(decode_block(PixelFormat::Rgba8Unorm, &[0; 16]),
+                   Err(FormatError::UnsupportedFormat(PixelFormat::Rgba8Unorm)));
+        assert_eq!(decode_rgba8(PixelFormat::Bc7RgbaUnorm, &[0; 16], 8,
+                                Region::new_2d(0, 0, 4, 4)),
+                   Err(FormatError::Layout(TextureLayoutError::BytesPerRowTooSmall {
+                       bytes_per_row: 8,
+                       required: 16
+                   })));
+        assert_eq!(decode_rgba8(PixelFormat::Bc4RUnorm, &[0; 8], 8, Region::new_2d(0, 0, 4, 5)),
+                   Err(FormatError::Layout(TextureLayoutError::BufferTooSmall {
+                       len: 8,
+                       required: 16
+                   })));
+        assert_eq!(decode_rgba8(PixelFormat::Bc4RUnorm, &[0; 16], 8,
+                                Region::new_3d(0, 0, 0, 4, 4, 2)),
+                   Err(FormatError::UnsupportedDepth(2)));
+    }
+}
@@ -0,0 +1,470 @@
+//! Conversion of uncompressed texture data between pixel formats.
+//!
+//! Every conversion goes through an intermediate of RGBA32F texels. Normalized formats are
+//! unpacked to `[0.0, 1.0]` or `[-1.0, 1.0]`, integer formats to their integer values (which are
+//! only exact up to 2^24 for the 32 bit formats), and sRGB formats are decoded to linear.
+//! Channels which a format does not store are unpacked as `(0, 0, 0, 1)`, and are dropped when
+//! packing.
+//!
+//! All functions expect tightly packed texels, in the byte order used by Metal on the host.
+
+use std::cmp;
+use super::{FormatError, f32_to_half, f32_to_unsigned_float, half_to_f32, linear_to_srgb,
+            srgb_to_linear, unsigned_float_to_f32};
+use {ComponentType, PixelFormat, TextureLayoutError};
+
+/// The largest value which can be stored by `PixelFormat::Rgb9E5Float`.
+const RGB9E5_MAX: f32 = 65408.0;
+
+/// How the texels of an uncompressed format are stored.
+#[derive(Clone, Copy)]
+enum Layout {
+    /// Each channel is stored in its own `size` bytes, in the order given by `channels`.
+    Components {
+        size: usize,
+        component_type: ComponentType,
+        channels: &'static [usize]
+    },
+    Rgb10A2(ComponentType),
+    Rg11B10Float,
+    Rgb9E5Float
+}
+
+fn layout(format: PixelFormat) -> Result<Layout, FormatError> {
+    const A: &'static [usize] = &[3];
+    const R: &'static [usize] = &[0];
+    const RG: &'static [usize] = &[0, 1];
+    const RGBA: &'static [usize] = &[0, 1, 2, 3];
+    const BGRA: &'static [usize] = &[2, 1, 0, 3];
+
+    let info = match format.info() {
+        Some(info) => info,
+        None => return Err(FormatError::UnsupportedFormat(format)),
+    };
+    if info.is_compressed || info.is_depth || info.is_stencil || info.block_width > 1 {
+        return Err(FormatError::UnsupportedFormat(format));
+    }
+
+    let channels = match format {
+        PixelFormat::Rgb10A2Unorm | PixelFormat::Rgb10A2Uint => {
+            return Ok(Layout::Rgb10A2(info.component_type));
+        }
+        PixelFormat::Rg11B10Float => return Ok(Layout::Rg11B10Float),
+        PixelFormat::Rgb9E5Float => return Ok(Layout::Rgb9E5Float),
+        PixelFormat::A8Unorm => A,
+        PixelFormat::Bgra8Unorm | PixelFormat::Bgra8UnormSrgb => BGRA,
+        _ => {
+            match info.component_count {
+                1 => R,
+                2 => RG,
+                _ => RGBA,
+            }
+        }
+    };
+    Ok(Layout::Components {
+        size: info.bytes_per_block / info.component_count,
+        component_type: info.component_type,
+        channels: channels
+    })
+}
+
+fn unpack_component(bits: u32, size: usize, component_type: ComponentType) -> f32 {
+    let bit_count = 8 * size as u32;
+    let sign_extend = |bits: u32| ((bits << (32 - bit_count)) as i32) >> (32 - bit_count);
+    match component_type {
+        ComponentType::Unorm => (bits as f64 / ((1u64 << bit_count) - 1) as f64) as f32,
+        ComponentType::Snorm => {
+            let max = ((1u64 << (bit_count - 1)) - 1) as f64;
+            (sign_extend(bits) as f64 / max).max(-1.0) as f32
+        }
+        ComponentType::Uint => bits as f32,
+        ComponentType::Sint => sign_extend(bits) as f32,
+        ComponentType::Float if size == 2 => half_to_f32(bits as u16),
+        ComponentType::Float => f32::from_bits(bits),
+    }
+}
+
+fn pack_component(value: f32, bit_count: u32, component_type: ComponentType) -> u32 {
+    if value.is_nan() && component_type != ComponentType::Float {
+        return 0;
+    }
+    let value = value as f64;
+    let mask = ((1u64 << bit_count) - 1) as u32;
+    match component_type {
+        ComponentType::Unorm => {
+            (value.max(0.0).min(1.0) * ((1u64 << bit_count) - 1) as f64).round() as u32
+        }
+        ComponentType::Snorm => {
+            let max = ((1u64 << (bit_count - 1)) - 1) as f64;
+            ((value.max(-1.0).min(1.0) * max).round() as i32) as u32 & mask
+        }
+        ComponentType::Uint => value.max(0.0).min(mask as f64).round() as u32,
+        ComponentType::Sint => {
+            let max = ((1u64 << (bit_count - 1)) - 1) as f64;
+            (value.max(-max - 1.0).min(max).round() as i32) as u32 & mask
+        }
+        ComponentType::Float if bit_count == 16 => f32_to_half(value as f32) as u32,
+        ComponentType::Float => (value as f32).to_bits(),
+    }
+}
+
+fn read_bits(bytes: &[u8]) -> u32 {
+    bytes.iter().rev().fold(0, |bits, &byte| (bits << 8) | byte as u32)
+}
+
+fn write_bits(bits: u32, bytes: &mut [u8]) {
+    for (i, byte) in bytes.iter_mut().enumerate() {
+        *byte = (bits >> (8 * i)) as u8;
+    }
+}
+
+/// Unpacks a `PixelFormat::Rg11B10Float` texel.
+pub fn unpack_rg11b10f(bits: u32) -> [f32; 3] {
+    [unsigned_float_to_f32(bits & 0x7ff, 6),
+     unsigned_float_to_f32((bits >> 11) & 0x7ff, 6),
+     unsigned_float_to_f32(bits >> 22, 5)]
+}
+
+/// Packs a `PixelFormat::Rg11B10Float` texel.
+///
+/// The packed floats have no sign bit, so negative values are stored as `0.0`. Finite values which
+/// are too large are clamped to the largest finite value.
+pub fn pack_rg11b10f(rgb: [f32; 3]) -> u32 {
+    f32_to_unsigned_float(rgb[0], 6, true) | f32_to_unsigned_float(rgb[1], 6, true) << 11 |
+    f32_to_unsigned_float(rgb[2], 5, true) << 22
+}
+
+/// Unpacks a `PixelFormat::Rgb9E5Float` texel.
+pub fn unpack_rgb9e5(bits: u32) -> [f32; 3] {
+    let scale = 2.0f32.powi((bits >> 27) as i32 - 15 - 9);
+    [(bits & 0x1ff) as f32 * scale,
+     ((bits >> 9) & 0x1ff) as f32 * scale,
+     ((bits >> 18) & 0x1ff) as f32 * scale]
+}
+
+/// Packs a `PixelFormat::Rgb9E5Float` texel.
+///
+/// Each channel is clamped to `[0.0, 65408.0]`, and `NaN` is stored as `0.0`. The exponent is
+/// chosen to fit the largest channel, so smaller channels lose precision.
+pub fn pack_rgb9e5(rgb: [f32; 3]) -> u32 {
+    let clamp = |value: f32| if value.is_nan() { 0.0 } else { value.max(0.0).min(RGB9E5_MAX) };
+    let rgb = [clamp(rgb[0]), clamp(rgb[1]), clamp(rgb[2])];
+    let max = rgb[0].max(rgb[1]).max(rgb[2]);
+
+    // floor(log2(max)), read from the exponent of the float to avoid rounding errors.
+    let max_exponent = ((max.to_bits() >> 23) & 0xff) as i32 - 127;
+    let mut exponent = cmp::max(-16, max_exponent) + 1 + 15;
+    let mut scale = 2.0f64.powi(exponent - 15 - 9);
+    if (max as f64 / scale + 0.5).floor() == 512.0 {
+        scale *= 2.0;
+        exponent += 1;
+    }
+
+    let quantize = |value: f32| (value as f64 / scale + 0.5).floor() as u32;
+    quantize(rgb[0]) | quantize(rgb[1]) << 9 | quantize(rgb[2]) << 18 | (exponent as u32) << 27
+}
+
+/// Unpacks tightly packed texels of `format` to RGBA32F.
+pub fn unpack_rgba32f(format: PixelFormat, data: &[u8]) -> Result<Vec<f32>, FormatError> {
+    let layout = try!(layout(format));
+    let texel_size = format.info().unwrap().bytes_per_block;
+    try!(check_len(data.len(), texel_size));
+    let is_srgb = format.is_srgb();
+
+    let mut rgba = Vec::with_capacity(data.len() / texel_size * 4);
+    for bytes in data.chunks(texel_size) {
+        let mut texel = [0.0, 0.0, 0.0, 1.0];
+        match layout {
+            Layout::Components { size, component_type, channels } => {
+                for (component, &channel) in bytes.chunks(size).zip(channels.iter()) {
+                    texel[channel] = unpack_component(read_bits(component), size, component_type);
+                }
+            }
+            Layout::Rgb10A2(component_type) => {
+                let bits = read_bits(bytes);
+                for channel in 0..3 {
+                    let component = (bits >> (10 * channel)) & 0x3ff;
+                    texel[channel] = unpack_10_bit(component, component_type);
+                }
+                texel[3] = match component_type {
+                    ComponentType::Unorm => (bits >> 30) as f32 / 3.0,
+                    _ => (bits >> 30) as f32,
+                };
+            }
+            Layout::Rg11B10Float => texel[..3].copy_from_slice(&unpack_rg11b10f(read_bits(bytes))),
+            Layout::Rgb9E5Float => texel[..3].copy_from_slice(&unpack_rgb9e5(read_bits(bytes))),
+        }
+        if is_srgb {
+            for component in texel[..3].iter_mut() {
+                *component = srgb_to_linear(*component);
+            }
+        }
+        rgba.extend_from_slice(&texel);
+    }
+    Ok(rgba)
+}
+
+/// Packs RGBA32F texels to tightly packed texels of `format`.
+///
+/// Values are rounded to the nearest representable value, and clamped to the range of the format.
+/// `NaN` is stored as `0` in the normalized and integer formats.
+pub fn pack_rgba32f(format: PixelFormat, rgba: &[f32]) -> Result<Vec<u8>, FormatError> {
+    let layout = try!(layout(format));
+    let texel_size = format.info().unwrap().bytes_per_block;
+    try!(check_len(rgba.len(), 4));
+    let is_srgb = format.is_srgb();
+
+    let mut data = vec![0; rgba.len() / 4 * texel_size];
+    for (texel, bytes) in rgba.chunks(4).zip(data.chunks_mut(texel_size)) {
+        let mut texel = [texel[0], texel[1], texel[2], texel[3]];
+        if is_srgb {
+            for component in texel[..3].iter_mut() {
+                *component = linear_to_srgb(component.max(0.0).min(1.0));
+            }
+        }
+
+        match layout {
+            Layout::Components { size, component_type, channels } => {
+                for (component, &channel) in bytes.chunks_mut(size).zip(channels.iter()) {
+                    let bits = pack_component(texel[channel], 8 * size as u32, component_type);
+                    write_bits(bits, component);
+                }
+            }
+            Layout::Rgb10A2(component_type) => {
+                let bits = pack_component(texel[0], 10, component_type) |
+                           pack_component(texel[1], 10, component_type) << 10 |
+                           pack_component(texel[2], 10, component_type) << 20 |
+                           pack_component(texel[3], 2, component_type) << 30;
+                write_bits(bits, bytes);
+            }
+            Layout::Rg11B10Float => {
+                write_bits(pack_rg11b10f([texel[0], texel[1], texel[2]]), bytes)
+            }
+            Layout::Rgb9E5Float => write_bits(pack_rgb9e5([texel[0], texel[1], texel[2]]), bytes),
+        }
+    }
+    Ok(data)
+}
+
+/// Converts tightly packed texels of the uncompressed format `from` to the format `to`.
+pub fn convert(from: PixelFormat, data: &[u8], to: PixelFormat) -> Result<Vec<u8>, FormatError> {
+    if from == to {
+        try!(layout(from));
+        try!(check_len(data.len(), from.info().unwrap().bytes_per_block));
+        return Ok(data.to_vec());
+    }
+    // Check the destination before doing any work.
+    try!(layout(to));
+    pack_rgba32f(to, &try!(unpack_rgba32f(from, data)))
+}
+
+fn unpack_10_bit(bits: u32, component_type: ComponentType) -> f32 {
+    match component_type {
+        ComponentType::Unorm => bits as f32 / 1023.0,
+        _ => bits as f32,
+    }
+}
+
+fn check_len(len: usize, texel_size: usize) -> Result<(), FormatError> {
+    if len % texel_size == 0 {
+        Ok(())
+    } else {
+        Err(FormatError::Layout(TextureLayoutError::BufferTooSmall {
+            len: len,
+            required: (len / texel_size + 1) * texel_size
+        }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use formats::{FormatError, srgb_to_linear};
+    use {ComponentType, PixelFormat, TextureLayoutError};
+
+    const UNCOMPRESSED_FORMATS: [PixelFormat; 44] = [
+        PixelFormat::A8Unorm, PixelFormat::R8Unorm, PixelFormat::R8Snorm, PixelFormat::R8Uint,
+        PixelFormat::R8Sint, PixelFormat::R16Unorm, PixelFormat::R16Snorm, PixelFormat::R16Uint,
+        PixelFormat::R16Sint, PixelFormat::R16Float, PixelFormat::Rg8Unorm, PixelFormat::Rg8Snorm,
+        PixelFormat::Rg8Uint, PixelFormat::Rg8Sint, PixelFormat::R32Uint, PixelFormat::R32Sint,
+        PixelFormat::R32Float, PixelFormat::Rg16Unorm, PixelFormat::Rg16Snorm,
+        PixelFormat::Rg16Uint, PixelFormat::Rg16Sint, PixelFormat::Rg16Float,
+        PixelFormat::Rgba8Unorm, PixelFormat::Rgba8UnormSrgb, PixelFormat::Rgba8Snorm,
+        PixelFormat::Rgba8Uint, PixelFormat::Rgba8Sint, PixelFormat::Bgra8Unorm,
+        PixelFormat::Bgra8UnormSrgb, PixelFormat::Rgb10A2Unorm, PixelFormat::Rgb10A2Uint,
+        PixelFormat::Rg11B10Float, PixelFormat::Rgb9E5Float, PixelFormat::Rg32Uint,
+        PixelFormat::Rg32Sint, PixelFormat::Rg32Float, PixelFormat::Rgba16Unorm,
+        PixelFormat::Rgba16Snorm, PixelFormat::Rgba16Uint, PixelFormat::Rgba16Sint,
+        PixelFormat::Rgba16Float, PixelFormat::Rgba32Uint, PixelFormat::Rgba32Sint,
+        PixelFormat::Rgba32Float
+    ];
+
+    #[test]
+    fn test_every_uncompressed_format_round_trips_its_own_data() {
+        // The bytes avoid NaNs and -128 in the snorm formats, which aren't preserved. Large 32 bit
+        // integers and the shared exponent of rgb9e5 aren't preserved either.
+        let data = (0..64u32).map(|i| (i * 37 + 11) as u8 & 0x3f).collect::<Vec<_>>();
+        for &format in UNCOMPRESSED_FORMATS.iter() {
+            let info = format.info().unwrap();
+            let texel_size = info.bytes_per_block;
+            let data = &data[..texel_size * 4];
+            let rgba = unpack_rgba32f(format, data).unwrap();
+            assert_eq!(rgba.len(), 16);
+
+            let is_32_bit_integer = texel_size / info.component_count == 4 &&
+                                    info.component_type != ComponentType::Float;
+            if format != PixelFormat::Rgb9E5Float && !is_32_bit_integer {
+                assert_eq!(pack_rgba32f(format, &rgba).unwrap(), data, "{:?}", format);
+            }
+            for &to in UNCOMPRESSED_FORMATS.iter() {
+                let converted = convert(format, data, to).unwrap();
+                assert_eq!(converted.len(), 4 * to.info().unwrap().bytes_per_block);
+            }
+        }
+    }
+
+    #[test]
+    fn test_unorm_and_snorm_exhaustive() {
+        let bytes = (0..256).map(|i| i as u8).collect::<Vec<_>>();
+        let unorm = unpack_rgba32f(PixelFormat::R8Unorm, &bytes).unwrap();
+        for (i, texel) in unorm.chunks(4).enumerate() {
+            assert_eq!(texel, &[i as f32 / 255.0, 0.0, 0.0, 1.0]);
+        }
+        assert_eq!(pack_rgba32f(PixelFormat::R8Unorm, &unorm).unwrap(), bytes);
+
+        let snorm = unpack_rgba32f(PixelFormat::R8Snorm, &bytes).unwrap();
+        for (i, texel) in snorm.chunks(4).enumerate() {
+            let expected = ((i as u8 as i8) as f32 / 127.0).max(-1.0);
+            assert_eq!(texel[0], expected);
+        }
+        // -128 and -127 both unpack to -1.0, which packs as -127.
+        let mut expected = bytes.clone();
+        expected[0x80] = 0x81;
+        assert_eq!(pack_rgba32f(PixelFormat::R8Snorm, &snorm).unwrap(), expected);
+
+        let clamped = pack_rgba32f(PixelFormat::Rg16Unorm, &[2.0, -1.0, 0.5, 1.0]).unwrap();
+        assert_eq!(clamped, &[0xff, 0xff, 0x00, 0x00]);
+        let nan = ::std::f32::NAN;
+        assert_eq!(pack_rgba32f(PixelFormat::Rg8Snorm, &[nan, -0.5, 0.0, 0.0]).unwrap(),
+                   &[0x00, 0xc0]);
+    }
+
+    #[test]
+    fn test_integer_formats_clamp() {
+        let rgba = [-5.0, 300.0, 1.6, 1e20];
+        assert_eq!(pack_rgba32f(PixelFormat::Rgba8Uint, &rgba).unwrap(), &[0, 255, 2, 255]);
+        assert_eq!(pack_rgba32f(PixelFormat::Rgba8Sint, &rgba).unwrap(),
+                   &[0xfb, 0x7f, 0x02, 0x7f]);
+        assert_eq!(unpack_rgba32f(PixelFormat::R16Sint, &[0x00, 0x80]).unwrap(),
+                   &[-32768.0, 0.0, 0.0, 1.0]);
+        assert_eq!(unpack_rgba32f(PixelFormat::R32Uint, &[0xff, 0xff, 0xff, 0xff]).unwrap(),
+                   &[4294967295.0, 0.0, 0.0, 1.0]);
+    }
+
+    #[test]
+    fn test_bgra_srgb_to_linear_float() {
+        let bgra = [0, 128, 255, 128];
+        let rgba = unpack_rgba32f(PixelFormat::Bgra8UnormSrgb, &bgra).unwrap();
+        assert_eq!(rgba, &[1.0, srgb_to_linear(128.0 / 255.0), 0.0, 128.0 / 255.0]);
+
+        let linear = convert(PixelFormat::Bgra8UnormSrgb, &bgra, PixelFormat::Rgba32Float).unwrap();
+        let float = unpack_rgba32f(PixelFormat::Rgba32Float, &linear).unwrap();
+        assert_eq!(float, rgba);
+
+        // Every sRGB value survives a round trip through linear floats.
+        let bytes = (0..256).flat_map(|i| vec![i as u8; 4]).collect::<Vec<_>>();
+        let linear = convert(PixelFormat::Rgba8UnormSrgb, &bytes, PixelFormat::Rgba32Float)
+                         .unwrap();
+        assert_eq!(convert(PixelFormat::Rgba32Float, &linear, PixelFormat::Rgba8UnormSrgb)
+                       .unwrap(),
+                   bytes);
+        assert_eq!(convert(PixelFormat::Rgba8UnormSrgb, &bytes, PixelFormat::Bgra8UnormSrgb)
+                       .unwrap(),
+                   bytes);
+    }
+
+    #[test]
+    fn test_rgb10a2() {
+        let packed = pack_rgba32f(PixelFormat::Rgb10A2Unorm, &[1.0, 0.0, 0.5, 1.0]).unwrap();
+        let bits = 1023 | 512 << 20 | 3 << 30u32;
+        assert_eq!(packed,
+                   &[bits as u8, (bits >> 8) as u8, (bits >> 16) as u8, (bits >> 24) as u8]);
+        assert_eq!(unpack_rgba32f(PixelFormat::Rgb10A2Unorm, &packed).unwrap(),
+                   &[1.0, 0.0, 512.0 / 1023.0, 1.0]);
+
+        let packed = pack_rgba32f(PixelFormat::Rgb10A2Uint, &[1023.0, 2000.0, 7.0, 2.0]).unwrap();
+        assert_eq!(unpack_rgba32f(PixelFormat::Rgb10A2Uint, &packed).unwrap(),
+                   &[1023.0, 1023.0, 7.0, 2.0]);
+    }
+
+    #[test]
+    fn test_rg11b10f_exhaustive() {
+        for bits in 0..0x800 {
+            let rgb = unpack_rg11b10f(bits | bits << 11 | (bits >> 1) << 22);
+            if rgb[0].is_nan() {
+                assert!(unpack_rg11b10f(pack_rg11b10f(rgb))[0].is_nan());
+                continue;
+            }
+            assert_eq!(pack_rg11b10f(rgb), bits | bits << 11 | (bits >> 1) << 22, "{:#x}", bits);
+        }
+
+        assert_eq!(unpack_rg11b10f(pack_rg11b10f([1.0, 0.5, 2.0])), [1.0, 0.5, 2.0]);
+        assert_eq!(unpack_rg11b10f(pack_rg11b10f([-1.0, 1e9, 1.0 / 64.0])),
+                   [0.0, 65024.0, 1.0 / 64.0]);
+    }
+
+    #[test]
+    fn test_rgb9e5() {
+        assert_eq!(pack_rgb9e5([1.0, 0.0, 0.0]), 0x8000_0100);
+        assert_eq!(unpack_rgb9e5(0x8000_0100), [1.0, 0.0, 0.0]);
+        assert_eq!(pack_rgb9e5([0.0, 0.0, 0.0]), 0);
+        assert_eq!(unpack_rgb9e5(pack_rgb9e5([1e10, -1.0, ::std::f32::NAN])),
+                   [65408.0, 0.0, 0.0]);
+        // Rounding the largest channel up to 512 bumps the exponent.
+        assert_eq!(unpack_rgb9e5(pack_rgb9e5([511.9, 0.0, 0.0])), [512.0, 0.0, 0.0]);
+
+        // Every texel whose largest channel uses the full mantissa is canonical.
+        for exponent in 0..32 {
+            for mantissa in 256..512 {
+                let bits = mantissa | (mantissa / 3) << 9 | (511 - mantissa) << 18 | exponent << 27;
+                assert_eq!(pack_rgb9e5(unpack_rgb9e5(bits)), bits, "{:#x}", bits);
+            }
+        }
+    }
+
+    #[test]
+    fn test_half_formats() {
+        let rgba = [1.0, -2.0, 65504.0, 1e6];
+        let packed = pack_rgba32f(PixelFormat::Rgba16Float, &rgba).unwrap();
+        assert_eq!(packed, &[0x00, 0x3c, 0x00, 0xc0, 0xff, 0x7b, 0x00, 0x7c]);
+        assert_eq!(unpack_rgba32f(PixelFormat::Rgba16Float, &packed).unwrap(),
+                   &[1.0, -2.0, 65504.0, ::std::f32::INFINITY]);
+        assert_eq!(unpack_rgba32f(PixelFormat::Rg16Float, &packed[..4]).unwrap(),
+                   &[1.0, -2.0, 0.0, 1.0]);
+    }
+
+    #[test]
+    fn test_missing_channels() {
+        assert_eq!(unpack_rgba32f(PixelFormat::A8Unorm, &[255]).unwrap(), &[0.0, 0.0, 0.0, 1.0]);
+        assert_eq!(unpack_rgba32f(PixelFormat::A8Unorm, &[0]).unwrap(), &[0.0, 0.0, 0.0, 0.0]);
+        assert_eq!(convert(PixelFormat::Rgba8Unorm, &[1, 2, 3, 4], PixelFormat::A8Unorm).unwrap(),
+                   &[4]);
+        assert_eq!(convert(PixelFormat::Rg8Unorm, &[1, 2], PixelFormat::Bgra8Unorm).unwrap(),
+                   &[0, 2, 1, 255]);
+    }
+
+    #[test]
+    fn test_conversion_errors() {
+        assert_eq!(unpack_rgba32f(PixelFormat::Bc1Rgba, &[0; 8]),
+                   Err(FormatError::UnsupportedFormat(PixelFormat::Bc1Rgba)));
+        assert_eq!(convert(PixelFormat::Rgba8Unorm, &[0; 4], PixelFormat::Depth32Float),
+                   Err(FormatError::UnsupportedFormat(PixelFormat::Depth32Float)));
+        assert_eq!(pack_rgba32f(PixelFormat::Gbgr422, &[0.0; 8]),
+                   Err(FormatError::UnsupportedFormat(PixelFormat::Gbgr422)));
+        assert_eq!(convert(PixelFormat::Rgba16Float, &[0; 10], PixelFormat::Rgba8Unorm),
+                   Err(FormatError::Layout(TextureLayoutError::BufferTooSmall {
+                       len: 10,
+                       required: 16
+                   })));
+    }
+}
@@ -0,0 +1,234 @@
+//! CPU-side encoding and decoding of texture data stored in Metal pixel formats.
+//!
+//! None of the functions in this module need a `Device`, so they can be used to inspect or
+//! prepare texture data on any host.
+
+use std::error::Error;
+use std::fmt;
+use {PixelFormat, TextureLayoutError};
+
+pub mod bc;
+pub mod convert;
+pub mod yuv;
+
+/// Errors which may occur when converting texture data between pixel formats.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum FormatError {
+    /// The pixel format is not supported by the conversion.
+    UnsupportedFormat(PixelFormat),
+
+    /// The region is more than one image deep, which the conversion does not support. Each
+    /// image of a 3D region must be converted separately.
+    UnsupportedDepth(usize),
+
+    /// The data does not match the region and row pitch which describe it.
+    Layout(TextureLayoutError)
+}
+
+impl From<TextureLayoutError> for FormatError {
+    fn from(error: TextureLayoutError) -> Self {
+        FormatError::Layout(error)
+    }
+}
+
+impl fmt::Display for FormatError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            FormatError::UnsupportedFormat(format) => {
+                write!(f, "the pixel format {:?} is not supported", format)
+            }
+            FormatError::UnsupportedDepth(depth) => {
+                write!(f, "regions {} images deep are not supported", depth)
+            }
+            FormatError::Layout(ref e) => fmt::Display::fmt(e, f),
+        }
+    }
+}
+
+impl Error for FormatError {
+    fn description(&self) -> &str {
+        match *self {
+            FormatError::UnsupportedFormat(_) => "The pixel format is not supported",
+            FormatError::UnsupportedDepth(_) => "The region is more than one image deep",
+            FormatError::Layout(ref e) => e.description(),
+        }
+    }
+
+    fn cause(&self) -> Option<&Error> {
+        match *self {
+            FormatError::Layout(ref e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+/// Converts an sRGB encoded value in the range `[0.0, 1.0]` to linear.
+pub fn srgb_to_linear(value: f32) -> f32 {
+    if value <= 0.04045 {
+        value / 12.92
+    } else {
+        ((value + 0.055) / 1.055).powf(2.4)
+    }
+}
+
+/// Converts a linear value in the range `[0.0, 1.0]` to sRGB encoding.
+pub fn linear_to_srgb(value: f32) -> f32 {
+    if value <= 0.0031308 {
+        value * 12.92
+    } else {
+        1.055 * value.powf(1.0 / 2.4) - 0.055
+    }
+}
+
+/// Converts the bits of an IEEE 754 half precision float to an `f32`.
+pub fn half_to_f32(bits: u16) -> f32 {
+    let sign = ((bits as u32) & 0x8000) << 16;
+    f32::from_bits(sign | unsigned_float_to_f32((bits & 0x7fff) as u32, 10).to_bits())
+}
+
+/// Converts an `f32` to the bits of an IEEE 754 half precision float, rounding to the nearest
+/// representable value. Values which are too large become infinite.
+pub fn f32_to_half(value: f32) -> u16 {
+    let sign = (value.to_bits() >> 16) & 0x8000;
+    (sign | f32_to_unsigned_float(value.abs(), 10, false)) as u16
+}
+
+/// Converts the bits of an unsigned float with a 5 bit exponent and `mantissa_bits` bits of
+/// mantissa to an `f32`.
+fn unsigned_float_to_f32(bits: u32, mantissa_bits: u32) -> f32 {
+    let exponent = bits >> mantissa_bits;
+    let mantissa = bits & ((1 << mantissa_bits) - 1);
+    let shift = 23 - mantissa_bits;
+
+    let magnitude = match (exponent, mantissa) {
+        (0, 0) => 0,
+        (0, _) => {
+            // Subnormal values are normal as an `f32`, so shift the mantissa up until it is.
+            let normalize = mantissa.leading_zeros() - (31 - mantissa_bits);
+            let mantissa = (mantissa << normalize) & ((1 << mantissa_bits) - 1);
+            ((113 - normalize) << 23) | (mantissa << shift)
+        }
+        (0x1f, _) => 0x7f80_0000 | (mantissa << shift),
+        _ => ((exponent + 112) << 23) | (mantissa << shift),
+    };
+    f32::from_bits(magnitude)
+}
+
+/// Converts an `f32` to the bits of an unsigned float with a 5 bit exponent and `mantissa_bits`
+/// bits of mantissa, as used by half floats and the packed float formats. Ties round to even.
+///
+/// Negative values become `0.0`. Finite values which are too large become infinite, or the
+/// largest finite value if `saturate` is `true`.
+fn f32_to_unsigned_float(value: f32, mantissa_bits: u32, saturate: bool) -> u32 {
+    let infinity = 0x1f << mantissa_bits;
+    if value.is_nan() {
+        return infinity | (1 << (mantissa_bits - 1));
+    } else if value <= 0.0 {
+        return 0;
+    }
+
+    let bits = value.to_bits();
+    let exponent = (bits >> 23) as i32 - 127;
+    let (significand, shift, biased_exponent) = if exponent >= -14 {
+        // Large exponents are clamped, as they overflow to infinity either way.
+        let biased_exponent = (::std::cmp::min(exponent, 16) + 15) as u32;
+        (bits & 0x7f_ffff, 23 - mantissa_bits, biased_exponent << mantissa_bits)
+    } else {
+        // The result is subnormal, so the implicit leading bit becomes part of the mantissa.
+        let shift = (9 - mantissa_bits as i32 - exponent) as u32;
+        if shift > 24 {
+            return 0;
+        }
+        ((bits & 0x7f_ffff) | 0x80_0000, shift, 0)
+    };
+
+    let truncated = biased_exponent | (significand >> shift);
+    let remainder = significand & ((1 << shift) - 1);
+    let halfway = 1 << (shift - 1);
+    let rounded = if remainder > halfway || (remainder == halfway && truncated & 1 == 1) {
+        truncated + 1
+    } else {
+        truncated
+    };
+
+    if rounded < infinity {
+        rounded
+    } else if saturate && !value.is_infinite() {
+        infinity - 1
+    } else {
+        infinity
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::{f32_to_unsigned_float, unsigned_float_to_f32};
+
+    #[test]
+    fn test_half_to_f32() {
+        assert_eq!(half_to_f32(0x0000), 0.0);
+        assert_eq!(half_to_f32(0x8000).to_bits(), (-0.0f32).to_bits());
+        assert_eq!(half_to_f32(0x3c00), 1.0);
+        assert_eq!(half_to_f32(0xc000), -2.0);
+        assert_eq!(half_to_f32(0x3555), 0.33325195);
+        assert_eq!(half_to_f32(0x7bff), 65504.0);
+        assert_eq!(half_to_f32(0x0001), 5.9604645e-8);
+        assert_eq!(half_to_f32(0x03ff), 6.0975552e-5);
+        assert_eq!(half_to_f32(0x7c00), ::std::f32::INFINITY);
+        assert!(half_to_f32(0x7e00).is_nan());
+    }
+
+    #[test]
+    fn test_half_round_trip_is_exact() {
+        for bits in 0..0x10000 {
+            let bits = bits as u16;
+            let value = half_to_f32(bits);
+            if value.is_nan() {
+                assert!(half_to_f32(f32_to_half(value)).is_nan());
+            } else {
+                assert_eq!(f32_to_half(value), bits, "{:#x}", bits);
+            }
+        }
+    }
+
+    #[test]
+    fn test_f32_to_half_rounding() {
+        // Ties round to even.
+        assert_eq!(f32_to_half(1.0 + 2.0f32.powi(-11)), 0x3c00);
+        assert_eq!(f32_to_half(1.0 + 3.0 * 2.0f32.powi(-11)), 0x3c02);
+        assert_eq!(f32_to_half(-1.0 - 2.0f32.powi(-10) * 0.75), 0xbc01);
+        // Overflow.
+        assert_eq!(f32_to_half(65519.0), 0x7bff);
+        assert_eq!(f32_to_half(65520.0), 0x7c00);
+        assert_eq!(f32_to_half(-1e10), 0xfc00);
+        // Subnormals and underflow.
+        assert_eq!(f32_to_half(2.0f32.powi(-24)), 0x0001);
+        assert_eq!(f32_to_half(2.0f32.powi(-25)), 0x0000);
+        assert_eq!(f32_to_half(3.0 * 2.0f32.powi(-25)), 0x0002);
+        assert_eq!(f32_to_half(2.0f32.powi(-14) - 2.0f32.powi(-26)), 0x0400);
+        assert_eq!(f32_to_half(1e-30), 0x0000);
+    }
+
+    #[test]
+    fn test_small_float_saturation() {
+        assert_eq!(f32_to_unsigned_float(1e10, 6, true), 0x7bf);
+        assert_eq!(f32_to_unsigned_float(1e10, 5, false), 0x3e0);
+        assert_eq!(f32_to_unsigned_float(::std::f32::INFINITY, 6, true), 0x7c0);
+        assert_eq!(f32_to_unsigned_float(-1.0, 6, true), 0);
+        assert_eq!(unsigned_float_to_f32(0x7bf, 6), 65024.0);
+        assert_eq!(unsigned_float_to_f32(0x3df, 5), 64512.0);
+    }
+
+    #[test]
+    fn test_srgb_round_trip() {
+        for i in 0..256 {
+            let value = i as f32 / 255.0;
+            let round_trip = linear_to_srgb(srgb_to_linear(value));
+            assert!((round_trip - value).abs() < 1e-5, "{}", i);
+        }
+        assert_eq!(srgb_to_linear(0.0), 0.0);
+        assert!((srgb_to_linear(1.0) - 1.0).abs() < 1e-6);
+        assert!((srgb_to_linear(0.5) - 0.21404114).abs() < 1e-6);
+    }
+}
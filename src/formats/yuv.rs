@@ -0,0 +1,311 @@
+//! Conversion between the 4:2:2 video formats and RGBA.
+//!
+//! `PixelFormat::Gbgr422` stores each pair of texels as the bytes `Y0 Cb Y1 Cr` (YUY2), and
+//! `PixelFormat::Bgrg422` as `Cb Y0 Cr Y1` (UYVY). Both texels of a pair share their chroma
+//! samples, so decoding repeats them and encoding averages them. Rows hold `(width + 1) / 2`
+//! pairs, and the second texel of the last pair is a copy of the first when the width is odd.
+//!
+//! The RGB values are gamma encoded, as the matrices are defined in terms of them.
+
+use super::FormatError;
+use {PixelFormat, TextureLayoutError};
+
+/// The matrix which relates Y'CbCr to R'G'B'.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum YuvMatrix {
+    /// ITU-R BT.601, used by standard definition video.
+    Bt601,
+
+    /// ITU-R BT.709, used by high definition video.
+    Bt709
+}
+
+/// The range of the stored Y'CbCr values.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum YuvRange {
+    /// Y' and chroma use the whole range `[0, 255]`, with zero chroma at 128.
+    Full,
+
+    /// Y' uses `[16, 235]` and chroma uses `[16, 240]`, as in broadcast video.
+    Limited
+}
+
+impl YuvMatrix {
+    /// Returns the red and blue coefficients, `Kr` and `Kb`.
+    fn coefficients(self) -> (f32, f32) {
+        match self {
+            YuvMatrix::Bt601 => (0.299, 0.114),
+            YuvMatrix::Bt709 => (0.2126, 0.0722),
+        }
+    }
+}
+
+impl YuvRange {
+    /// Returns the offset and scale of Y', and the scale of the chroma components.
+    fn scale(self) -> (f32, f32, f32) {
+        match self {
+            YuvRange::Full => (0.0, 255.0, 255.0),
+            YuvRange::Limited => (16.0, 219.0, 224.0),
+        }
+    }
+}
+
+/// Returns the byte offsets of `Y0`, `Cb`, `Y1` and `Cr` within a pair of texels.
+fn offsets(format: PixelFormat) -> Result<[usize; 4], FormatError> {
+    match format {
+        PixelFormat::Gbgr422 => Ok([0, 1, 2, 3]),
+        PixelFormat::Bgrg422 => Ok([1, 0, 3, 2]),
+        _ => Err(FormatError::UnsupportedFormat(format)),
+    }
+}
+
+fn check_len(len: usize, row_len: usize) -> Result<(), FormatError> {
+    if row_len == 0 || len % row_len == 0 {
+        Ok(())
+    } else {
+        Err(FormatError::Layout(TextureLayoutError::BufferTooSmall {
+            len: len,
+            required: (len / row_len + 1) * row_len
+        }))
+    }
+}
+
+/// Decodes tightly packed rows of 4:2:2 data, `width` texels wide, to RGBA32F.
+///
+/// The results are not clamped, so values outside of the limited range decode to values outside
+/// of `[0.0, 1.0]`.
+pub fn decode_rgba32f(format: PixelFormat, data: &[u8], width: usize, matrix: YuvMatrix,
+                      range: YuvRange)
+                      -> Result<Vec<f32>, FormatError> {
+    let offsets = try!(offsets(format));
+    let row_len = (width + 1) / 2 * 4;
+    try!(check_len(data.len(), row_len));
+    if width == 0 {
+        return Ok(vec![]);
+    }
+
+    let (kr, kb) = matrix.coefficients();
+    let kg = 1.0 - kr - kb;
+    let (y_offset, y_scale, c_scale) = range.scale();
+
+    let mut rgba = Vec::with_capacity(data.len() / row_len * width * 4);
+    for row in data.chunks(row_len) {
+        for (pair_index, pair) in row.chunks(4).enumerate() {
+            let x = 2 * pair_index;
+            let cb = (pair[offsets[1]] as f32 - 128.0) / c_scale;
+            let cr = (pair[offsets[3]] as f32 - 128.0) / c_scale;
+            for &(texel, offset) in &[(x, offsets[0]), (x + 1, offsets[2])] {
+                if texel >= width {
+                    break;
+                }
+                let y = (pair[offset] as f32 - y_offset) / y_scale;
+                let r = y + 2.0 * (1.0 - kr) * cr;
+                let b = y + 2.0 * (1.0 - kb) * cb;
+                let g = (y - kr * r - kb * b) / kg;
+                rgba.extend_from_slice(&[r, g, b, 1.0]);
+            }
+        }
+    }
+    Ok(rgba)
+}
+
+/// Decodes tightly packed rows of 4:2:2 data, `width` texels wide, to RGBA8.
+pub fn decode_rgba8(format: PixelFormat, data: &[u8], width: usize, matrix: YuvMatrix,
+                    range: YuvRange)
+                    -> Result<Vec<u8>, FormatError> {
+    let rgba = try!(decode_rgba32f(format, data, width, matrix, range));
+    Ok(rgba.iter().map(|&value| (value.max(0.0).min(1.0) * 255.0).round() as u8).collect())
+}
+
+/// Encodes tightly packed RGBA32F texels, in rows `width` texels wide, to 4:2:2 data.
+///
+/// The alpha channel is ignored.
+pub fn encode_rgba32f(format: PixelFormat, rgba: &[f32], width: usize, matrix: YuvMatrix,
+                      range: YuvRange)
+                      -> Result<Vec<u8>, FormatError> {
+    let offsets = try!(offsets(format));
+    try!(check_len(rgba.len(), width * 4));
+    if width == 0 {
+        return Ok(vec![]);
+    }
+
+    let (kr, kb) = matrix.coefficients();
+    let kg = 1.0 - kr - kb;
+    let (y_offset, y_scale, c_scale) = range.scale();
+    let quantize = |value: f32| value.max(0.0).min(255.0).round() as u8;
+
+    let row_len = (width + 1) / 2 * 4;
+    let mut data = Vec::with_capacity(rgba.len() / (width * 4) * row_len);
+    for row in rgba.chunks(width * 4) {
+        for pair in row.chunks(8) {
+            // An odd texel at the end of the row is paired with itself.
+            let second = if pair.len() == 8 { &pair[4..] } else { pair };
+            let (mut luma, mut cb, mut cr) = ([0.0; 2], 0.0, 0.0);
+            for (y, texel) in luma.iter_mut().zip([&pair[..4], second].iter()) {
+                *y = kr * texel[0] + kg * texel[1] + kb * texel[2];
+                cb += (texel[2] - *y) / (2.0 * (1.0 - kb)) / 2.0;
+                cr += (texel[0] - *y) / (2.0 * (1.0 - kr)) / 2.0;
+            }
+
+            let mut bytes = [0; 4];
+            bytes[offsets[0]] = quantize(y_offset + y_scale * luma[0]);
+            bytes[offsets[1]] = quantize(128.0 + c_scale * cb);
+            bytes[offsets[2]] = quantize(y_offset + y_scale * luma[1]);
+            bytes[offsets[3]] = quantize(128.0 + c_scale * cr);
+            data.extend_from_slice(&bytes);
+        }
+    }
+    Ok(data)
+}
+
+/// Encodes tightly packed RGBA8 texels, in rows `width` texels wide, to 4:2:2 data.
+///
+/// The alpha channel is ignored.
+pub fn encode_rgba8(format: PixelFormat, rgba: &[u8], width: usize, matrix: YuvMatrix,
+                    range: YuvRange)
+                    -> Result<Vec<u8>, FormatError> {
+    let rgba = rgba.iter().map(|&value| value as f32 / 255.0).collect::<Vec<_>>();
+    encode_rgba32f(format, &rgba, width, matrix, range)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use formats::FormatError;
+    use {PixelFormat, TextureLayoutError};
+
+    /// White, yellow, cyan, green, magenta, red, blue and black.
+    const BARS: [[u8; 3]; 8] = [[1, 1, 1], [1, 1, 0], [0, 1, 1], [0, 1, 0], [1, 0, 1], [1, 0, 0],
+                                [0, 0, 1], [0, 0, 0]];
+
+    /// Builds one row of color bars, two texels per bar, with the given intensity.
+    fn bars(intensity: f32) -> Vec<f32> {
+        let mut rgba = vec![];
+        for bar in BARS.iter() {
+            for _ in 0..2 {
+                rgba.extend(bar.iter().map(|&c| c as f32 * intensity));
+                rgba.push(1.0);
+            }
+        }
+        rgba
+    }
+
+    /// Rearranges `(Y, Cb, Cr)` triples, one per bar, into `Gbgr422` pairs.
+    fn yuy2(bars: &[[u8; 3]]) -> Vec<u8> {
+        bars.iter().flat_map(|bar| vec![bar[0], bar[1], bar[0], bar[2]]).collect()
+    }
+
+    #[test]
+    fn test_bt601_limited_75_percent_bars() {
+        let expected = [[180, 128, 128], [162, 44, 142], [131, 156, 44], [112, 72, 58],
+                        [84, 184, 198], [65, 100, 212], [35, 212, 114], [16, 128, 128]];
+        let encoded = encode_rgba32f(PixelFormat::Gbgr422,
+                                     &bars(0.75),
+                                     16,
+                                     YuvMatrix::Bt601,
+                                     YuvRange::Limited)
+                          .unwrap();
+        assert_eq!(encoded, yuy2(&expected));
+
+        let decoded = decode_rgba32f(PixelFormat::Gbgr422,
+                                     &encoded,
+                                     16,
+                                     YuvMatrix::Bt601,
+                                     YuvRange::Limited)
+                          .unwrap();
+        for (texel, expected) in decoded.iter().zip(bars(0.75).iter()) {
+            assert!((texel - expected).abs() < 0.01, "{} {}", texel, expected);
+        }
+    }
+
+    #[test]
+    fn test_bt709_limited_100_percent_bars() {
+        let expected = [[235, 128, 128], [219, 16, 138], [188, 154, 16], [173, 42, 26],
+                        [78, 214, 230], [63, 102, 240], [32, 240, 118], [16, 128, 128]];
+        let rgba = bars(1.0).iter().map(|&c| (c * 255.0) as u8).collect::<Vec<_>>();
+        let encoded = encode_rgba8(PixelFormat::Bgrg422,
+                                   &rgba,
+                                   16,
+                                   YuvMatrix::Bt709,
+                                   YuvRange::Limited)
+                          .unwrap();
+        let uyvy = expected.iter()
+            .flat_map(|bar| vec![bar[1], bar[0], bar[2], bar[0]])
+            .collect::<Vec<_>>();
+        assert_eq!(encoded, uyvy);
+
+        let decoded = decode_rgba8(PixelFormat::Bgrg422,
+                                   &encoded,
+                                   16,
+                                   YuvMatrix::Bt709,
+                                   YuvRange::Limited)
+                          .unwrap();
+        for (texel, expected) in decoded.iter().zip(rgba.iter()) {
+            assert!((*texel as i16 - *expected as i16).abs() <= 1, "{} {}", texel, expected);
+        }
+    }
+
+    #[test]
+    fn test_full_range() {
+        let rgba = [255, 255, 255, 255, 0, 0, 0, 255, 128, 128, 128, 255, 128, 128, 128, 255];
+        let encoded = encode_rgba8(PixelFormat::Gbgr422, &rgba, 4, YuvMatrix::Bt601, YuvRange::Full)
+                          .unwrap();
+        // The first pair averages white and black chroma, which is zero either way.
+        assert_eq!(encoded, &[255, 128, 0, 128, 128, 128, 128, 128]);
+        let decoded = decode_rgba8(PixelFormat::Gbgr422, &encoded, 4, YuvMatrix::Bt601,
+                                   YuvRange::Full)
+                          .unwrap();
+        assert_eq!(decoded, &rgba[..]);
+    }
+
+    #[test]
+    fn test_limited_range_is_not_clamped_as_floats() {
+        // Y' = 255 is brighter than white in limited range.
+        let decoded = decode_rgba32f(PixelFormat::Gbgr422, &[255, 128, 255, 128], 2,
+                                     YuvMatrix::Bt709, YuvRange::Limited)
+                          .unwrap();
+        assert!(decoded[0] > 1.0);
+        let decoded = decode_rgba8(PixelFormat::Gbgr422, &[255, 128, 255, 128], 2,
+                                   YuvMatrix::Bt709, YuvRange::Limited)
+                          .unwrap();
+        assert_eq!(decoded, &[255, 255, 255, 255, 255, 255, 255, 255]);
+    }
+
+    #[test]
+    fn test_odd_width_and_shared_chroma() {
+        // Three texels per row: red, blue and green. Red and blue share their chroma.
+        let rgba = [255, 0, 0, 255, 0, 0, 255, 255, 0, 255, 0, 255];
+        let encoded = encode_rgba8(PixelFormat::Gbgr422, &rgba, 3, YuvMatrix::Bt601, YuvRange::Full)
+                          .unwrap();
+        assert_eq!(encoded.len(), 8);
+        // The last pair only holds green.
+        assert_eq!(encoded[4], encoded[6]);
+
+        let decoded = decode_rgba8(PixelFormat::Gbgr422, &encoded, 3, YuvMatrix::Bt601,
+                                   YuvRange::Full)
+                          .unwrap();
+        assert_eq!(decoded.len(), 12);
+        for (texel, expected) in decoded[8..].iter().zip([0, 255, 0, 255].iter()) {
+            assert!((*texel as i16 - *expected as i16).abs() <= 2, "{:?}", decoded);
+        }
+    }
+
+    #[test]
+    fn test_yuv_errors() {
+        assert_eq!(decode_rgba8(PixelFormat::Rgba8Unorm, &[0; 4], 1, YuvMatrix::Bt601,
+                                YuvRange::Full),
+                   Err(FormatError::UnsupportedFormat(PixelFormat::Rgba8Unorm)));
+        assert_eq!(decode_rgba8(PixelFormat::Gbgr422, &[0; 6], 4, YuvMatrix::Bt601,
+                                YuvRange::Full),
+                   Err(FormatError::Layout(TextureLayoutError::BufferTooSmall {
+                       len: 6,
+                       required: 8
+                   })));
+        assert_eq!(encode_rgba8(PixelFormat::Bgrg422, &[0; 12], 2, YuvMatrix::Bt709,
+                                YuvRange::Limited),
+                   Err(FormatError::Layout(TextureLayoutError::BufferTooSmall {
+                       len: 12,
+                       required: 16
+                   })));
+    }
+}
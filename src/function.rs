@@ -32,7 +32,7 @@ impl Function {
 impl_from_into_raw!(Function, of protocol "MTLFunction");
 
 convertible_enum! {
-    #[derive(Clone, Copy, Eq, Hash, PartialEq)]
+    #[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
     pub enum FunctionType: MTLFunctionType {
         Vertex => MTLFunctionTypeVertex,
         Fragment => MTLFunctionTypeFragment,
@@ -0,0 +1,110 @@
+use std::mem;
+use std::slice;
+use sys::{MTLDispatchThreadgroupsIndirectArguments, MTLDrawIndexedPrimitivesIndirectArguments,
+          MTLDrawPrimitivesIndirectArguments};
+use {ClearColor, Origin, Size};
+
+/// A marker for plain-old-data types which can be copied to and from GPU memory as raw bytes.
+///
+/// The `metl-derive` crate provides `#[derive(GpuPod)]` for `#[repr(C)]` structs, which checks
+/// that every field is `GpuPod` and that the struct has no padding.
+///
+/// # Safety
+///
+/// Implementors must be `Copy`, contain no padding bytes or pointers, and be valid for any bit
+/// pattern.
+pub unsafe trait GpuPod: Copy + 'static {}
+
+macro_rules! impl_gpu_pod {
+    ($($ty:ty),*) => ($(unsafe impl GpuPod for $ty {})*)
+}
+
+impl_gpu_pod!(u8, u16, u32, u64, usize, i8, i16, i32, i64, isize, f32, f64);
+
+macro_rules! impl_gpu_pod_array {
+    ($($len:expr),*) => ($(unsafe impl<T: GpuPod> GpuPod for [T; $len] {})*)
+}
+
+impl_gpu_pod_array!(1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12, 13, 14, 15, 16, 32, 64);
+
+impl_gpu_pod!(ClearColor, Origin, Size, MTLDispatchThreadgroupsIndirectArguments,
+              MTLDrawIndexedPrimitivesIndirectArguments, MTLDrawPrimitivesIndirectArguments);
+
+/// Reinterprets a byte slice as a slice of `T`. Trailing bytes which do not make up a whole
+/// element are ignored.
+///
+/// Returns `None` if the bytes are not suitably aligned for `T`, or if `T` is zero sized.
+pub fn cast_slice<T: GpuPod>(bytes: &[u8]) -> Option<&[T]> {
+    if mem::size_of::<T>() == 0 || bytes.as_ptr() as usize % mem::align_of::<T>() != 0 {
+        return None;
+    }
+    let len = bytes.len() / mem::size_of::<T>();
+    Some(unsafe { slice::from_raw_parts(bytes.as_ptr() as *const T, len) })
+}
+
+/// Reinterprets a mutable byte slice as a mutable slice of `T`. Trailing bytes which do not make
+/// up a whole element are ignored.
+///
+/// Returns `None` if the bytes are not suitably aligned for `T`, or if `T` is zero sized.
+pub fn cast_slice_mut<T: GpuPod>(bytes: &mut [u8]) -> Option<&mut [T]> {
+    if mem::size_of::<T>() == 0 || bytes.as_ptr() as usize % mem::align_of::<T>() != 0 {
+        return None;
+    }
+    let len = bytes.len() / mem::size_of::<T>();
+    Some(unsafe { slice::from_raw_parts_mut(bytes.as_mut_ptr() as *mut T, len) })
+}
+
+/// Views a slice of `T` as its underlying bytes.
+pub fn as_bytes<T: GpuPod>(values: &[T]) -> &[u8] {
+    unsafe {
+        slice::from_raw_parts(values.as_ptr() as *const u8, values.len() * mem::size_of::<T>())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_cast_slice() {
+        let values = [1u32, 2, 3, 0xdead_beef];
+        let bytes = as_bytes(&values);
+        assert_eq!(bytes.len(), 16);
+        assert_eq!(cast_slice::<u32>(bytes), Some(&values[..]));
+        assert_eq!(cast_slice::<[u32; 2]>(bytes), Some(&[[1, 2], [3, 0xdead_beef]][..]));
+
+        // Trailing bytes are ignored, but misaligned starts are rejected.
+        assert_eq!(cast_slice::<u32>(&bytes[..7]), Some(&values[..1]));
+        assert_eq!(cast_slice::<u32>(&bytes[1..]), None);
+        assert_eq!(cast_slice::<u8>(&bytes[1..]).map(|s| s.len()), Some(15));
+    }
+
+    #[test]
+    fn test_value_types_have_no_padding() {
+        use std::mem::size_of;
+        assert_eq!(size_of::<ClearColor>(), 4 * size_of::<f64>());
+        assert_eq!(size_of::<Origin>(), 3 * size_of::<usize>());
+        assert_eq!(size_of::<Size>(), 3 * size_of::<usize>());
+        assert_eq!(size_of::<MTLDrawPrimitivesIndirectArguments>(), 16);
+        assert_eq!(size_of::<MTLDrawIndexedPrimitivesIndirectArguments>(), 20);
+        assert_eq!(size_of::<MTLDispatchThreadgroupsIndirectArguments>(), 12);
+
+        let sizes = [Size::new(1, 2, 3), Size::new(4, 5, 6)];
+        let bytes = as_bytes(&sizes);
+        assert_eq!(cast_slice::<usize>(bytes), Some(&[1, 2, 3, 4, 5, 6][..]));
+        assert_eq!(cast_slice::<Size>(bytes), Some(&sizes[..]));
+    }
+
+    #[test]
+    fn test_cast_slice_mut() {
+        let mut values = [0f32; 4];
+        {
+            let bytes = unsafe {
+                ::std::slice::from_raw_parts_mut(values.as_mut_ptr() as *mut u8, 16)
+            };
+            let floats = cast_slice_mut::<[f32; 2]>(bytes).unwrap();
+            floats[1] = [1.5, -2.0];
+        }
+        assert_eq!(values, [0.0, 0.0, 1.5, -2.0]);
+    }
+}
@@ -0,0 +1,343 @@
+use libc::{self, c_void};
+use std::cell::RefCell;
+use std::fs::File;
+use std::io;
+use std::ops::{Deref, DerefMut};
+use std::os::unix::io::AsRawFd;
+use std::path::Path;
+use std::ptr;
+use std::slice;
+
+/// Returns the size in bytes of a page of virtual memory.
+pub fn page_size() -> usize {
+    unsafe { libc::sysconf(libc::_SC_PAGESIZE) as usize }
+}
+
+/// Rounds `len` up to a whole number of pages. At least one page is always returned, as Metal
+/// cannot create buffers with a length of zero.
+pub fn round_up_to_page_size(len: usize) -> usize {
+    let page_size = page_size();
+    if len == 0 { page_size } else { (len + page_size - 1) / page_size * page_size }
+}
+
+/// Host memory which can back a buffer created without copying.
+///
+/// # Safety
+///
+/// `as_ptr` must return a page aligned pointer to `allocated_len` bytes, which must be a
+/// multiple of the page size. The memory must be writable, as the GPU and the CPU may write to
+/// the buffer. It must remain valid until the allocation is dropped, and must not move if the
+/// allocation is moved.
+pub unsafe trait HostAllocation: Send + 'static {
+    /// A page aligned pointer to the start of the allocation.
+    fn as_ptr(&self) -> *const u8;
+
+    /// The length of the allocation in bytes, which is a multiple of the page size.
+    fn allocated_len(&self) -> usize;
+}
+
+/// A fixed length array of bytes, allocated on a page boundary and padded with zeroes to a whole
+/// number of pages.
+pub struct PageAlignedVec {
+    ptr: *mut u8,
+    len: usize,
+    capacity: usize
+}
+
+impl PageAlignedVec {
+    /// Creates a zeroed array of `len` bytes.
+    pub fn new(len: usize) -> Self {
+        let capacity = round_up_to_page_size(len);
+        let mut ptr = ptr::null_mut();
+        let result = unsafe { libc::posix_memalign(&mut ptr, page_size(), capacity) };
+        if result != 0 || ptr.is_null() {
+            panic!("could not allocate {} bytes of page aligned memory", capacity);
+        }
+        unsafe { ptr::write_bytes(ptr as *mut u8, 0, capacity) };
+        PageAlignedVec { ptr: ptr as *mut u8, len: len, capacity: capacity }
+    }
+
+    /// Creates an array containing a copy of `bytes`.
+    pub fn from_slice(bytes: &[u8]) -> Self {
+        let mut vec = PageAlignedVec::new(bytes.len());
+        vec.copy_from_slice(bytes);
+        vec
+    }
+
+    /// The number of bytes in the array.
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// The number of bytes allocated, including the padding at the end of the last page.
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+}
+
+impl Deref for PageAlignedVec {
+    type Target = [u8];
+    fn deref(&self) -> &Self::Target {
+        unsafe { slice::from_raw_parts(self.ptr, self.len) }
+    }
+}
+
+impl DerefMut for PageAlignedVec {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        unsafe { slice::from_raw_parts_mut(self.ptr, self.len) }
+    }
+}
+
+impl Clone for PageAlignedVec {
+    fn clone(&self) -> Self {
+        PageAlignedVec::from_slice(self)
+    }
+}
+
+impl Drop for PageAlignedVec {
+    fn drop(&mut self) {
+        unsafe { libc::free(self.ptr as *mut c_void) }
+    }
+}
+
+unsafe impl Send for PageAlignedVec {}
+unsafe impl Sync for PageAlignedVec {}
+
+unsafe impl HostAllocation for PageAlignedVec {
+    fn as_ptr(&self) -> *const u8 {
+        self.ptr
+    }
+
+    fn allocated_len(&self) -> usize {
+        self.capacity
+    }
+}
+
+/// A private memory mapping of a file, for creating buffers from large assets without reading
+/// them into memory first.
+///
+/// The mapping is copy-on-write, so writes to a buffer created from it are never written back to
+/// the file. The mapping is padded with zeroes to a whole number of pages.
+pub struct MappedFile {
+    ptr: *mut u8,
+    len: usize,
+    mapped_len: usize
+}
+
+impl MappedFile {
+    /// Maps the file at `path` into memory.
+    pub fn open<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+        let file = try!(File::open(path));
+        let len = try!(file.metadata()).len() as usize;
+        if len == 0 {
+            return Err(io::Error::new(io::ErrorKind::InvalidInput, "cannot map an empty file"));
+        }
+
+        let ptr = unsafe {
+            libc::mmap(ptr::null_mut(), len, libc::PROT_READ | libc::PROT_WRITE,
+                       libc::MAP_PRIVATE, file.as_raw_fd(), 0)
+        };
+        if ptr == libc::MAP_FAILED {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(MappedFile { ptr: ptr as *mut u8, len: len, mapped_len: round_up_to_page_size(len) })
+    }
+
+    /// The length of the file in bytes.
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// The number of bytes mapped, including the padding at the end of the last page.
+    pub fn mapped_len(&self) -> usize {
+        self.mapped_len
+    }
+}
+
+impl Deref for MappedFile {
+    type Target = [u8];
+    fn deref(&self) -> &Self::Target {
+        unsafe { slice::from_raw_parts(self.ptr, self.len) }
+    }
+}
+
+impl Drop for MappedFile {
+    fn drop(&mut self) {
+        unsafe { libc::munmap(self.ptr as *mut c_void, self.len) };
+    }
+}
+
+unsafe impl Send for MappedFile {}
+unsafe impl Sync for MappedFile {}
+
+unsafe impl HostAllocation for MappedFile {
+    fn as_ptr(&self) -> *const u8 {
+        self.ptr
+    }
+
+    fn allocated_len(&self) -> usize {
+        self.mapped_len
+    }
+}
+
+/// Owns the host memory behind a buffer created without copying, and releases it when Metal
+/// calls the buffer's deallocator.
+pub struct HostDeallocator<H: HostAllocation> {
+    allocation: RefCell<Option<H>>
+}
+
+impl<H: HostAllocation> HostDeallocator<H> {
+    pub fn new(allocation: H) -> Self {
+        HostDeallocator { allocation: RefCell::new(Some(allocation)) }
+    }
+
+    /// Returns the pointer and length which the buffer should be created with, or `None` if the
+    /// allocation has already been released.
+    pub fn allocation(&self) -> Option<(*const u8, usize)> {
+        self.allocation.borrow().as_ref().map(|allocation| {
+            (allocation.as_ptr(), allocation.allocated_len())
+        })
+    }
+
+    /// Releases the allocation. This is called by Metal with the pointer and length of the
+    /// buffer once the buffer has been destroyed, and does nothing if called again.
+    pub fn deallocate(&self, ptr: *mut c_void, len: usize) {
+        if let Some(allocation) = self.allocation.borrow_mut().take() {
+            debug_assert_eq!((allocation.as_ptr(), allocation.allocated_len()),
+                             (ptr as *const u8, len));
+            drop(allocation);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::env;
+    use std::fs::{self, File};
+    use std::io::{Read, Write};
+    use std::rc::Rc;
+    use std::sync::Arc;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    #[test]
+    fn test_round_up_to_page_size() {
+        let page_size = page_size();
+        assert!(page_size.is_power_of_two());
+        assert_eq!(round_up_to_page_size(0), page_size);
+        assert_eq!(round_up_to_page_size(1), page_size);
+        assert_eq!(round_up_to_page_size(page_size), page_size);
+        assert_eq!(round_up_to_page_size(page_size + 1), 2 * page_size);
+    }
+
+    #[test]
+    fn test_page_aligned_vec() {
+        let page_size = page_size();
+        for &len in &[0, 1, 100, page_size, page_size + 3] {
+            let mut vec = PageAlignedVec::new(len);
+            assert_eq!(vec.len(), len);
+            assert_eq!(vec.as_ptr() as usize % page_size, 0);
+            assert_eq!(vec.allocated_len(), round_up_to_page_size(len));
+            assert!(vec.iter().all(|&byte| byte == 0));
+
+            for (i, byte) in vec.iter_mut().enumerate() {
+                *byte = i as u8;
+            }
+            let copy = vec.clone();
+            assert_eq!(&copy[..], &vec[..]);
+            assert!(copy.as_ptr() != vec.as_ptr());
+
+            // The padding is zeroed.
+            let padding = unsafe {
+                ::std::slice::from_raw_parts(vec.as_ptr().offset(len as isize),
+                                             vec.capacity() - len)
+            };
+            assert!(padding.iter().all(|&byte| byte == 0));
+        }
+
+        let vec = PageAlignedVec::from_slice(b"metal");
+        assert_eq!(&vec[..], b"metal");
+    }
+
+    #[test]
+    fn test_mapped_file() {
+        let path = env::temp_dir().join(format!("metl-mapped-file-{}", ::std::process::id()));
+        let contents = (0..10000).map(|i| (i % 251) as u8).collect::<Vec<_>>();
+        File::create(&path).and_then(|mut file| file.write_all(&contents)).unwrap();
+
+        let mapped = MappedFile::open(&path).unwrap();
+        assert_eq!(mapped.len(), contents.len());
+        assert_eq!(&mapped[..], &contents[..]);
+        assert_eq!(mapped.as_ptr() as usize % page_size(), 0);
+        assert_eq!(mapped.allocated_len(), round_up_to_page_size(contents.len()));
+
+        // Writes to the mapping are not written back to the file.
+        unsafe { *(mapped.as_ptr() as *mut u8) = 255 };
+        assert_eq!(mapped[0], 255);
+        drop(mapped);
+        let mut reread = vec![];
+        File::open(&path).and_then(|mut file| file.read_to_end(&mut reread)).unwrap();
+        assert_eq!(reread, contents);
+
+        File::create(&path).unwrap();
+        assert_eq!(MappedFile::open(&path).err().map(|e| e.kind()),
+                   Some(io::ErrorKind::InvalidInput));
+        fs::remove_file(&path).unwrap();
+    }
+
+    struct CountedAllocation {
+        vec: PageAlignedVec,
+        drops: Arc<AtomicUsize>
+    }
+
+    unsafe impl HostAllocation for CountedAllocation {
+        fn as_ptr(&self) -> *const u8 {
+            self.vec.as_ptr()
+        }
+
+        fn allocated_len(&self) -> usize {
+            self.vec.allocated_len()
+        }
+    }
+
+    impl Drop for CountedAllocation {
+        fn drop(&mut self) {
+            self.drops.fetch_add(1, Ordering::SeqCst);
+        }
+    }
+
+    #[test]
+    fn test_deallocator_releases_allocation_once() {
+        let drops = Arc::new(AtomicUsize::new(0));
+        let allocation = CountedAllocation { vec: PageAlignedVec::new(10), drops: drops.clone() };
+        let deallocator = Rc::new(HostDeallocator::new(allocation));
+
+        let (ptr, len) = deallocator.allocation().unwrap();
+        assert_eq!(len, page_size());
+        assert_eq!(drops.load(Ordering::SeqCst), 0);
+
+        deallocator.deallocate(ptr as *mut _, len);
+        assert_eq!(drops.load(Ordering::SeqCst), 1);
+        assert!(deallocator.allocation().is_none());
+
+        deallocator.deallocate(ptr as *mut _, len);
+        drop(deallocator);
+        assert_eq!(drops.load(Ordering::SeqCst), 1);
+    }
+
+    #[test]
+    fn test_deallocator_drop_releases_allocation() {
+        let drops = Arc::new(AtomicUsize::new(0));
+        let allocation = CountedAllocation { vec: PageAlignedVec::new(10), drops: drops.clone() };
+        drop(HostDeallocator::new(allocation));
+        assert_eq!(drops.load(Ordering::SeqCst), 1);
+    }
+}
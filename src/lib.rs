@@ -1,12 +1,22 @@
 #![feature(collections, collections_range)]
 #![warn(trivial_casts, trivial_numeric_casts, unused_import_braces, unused_qualifications)]
 
+#[macro_use]
+extern crate bitflags;
 extern crate block;
 extern crate cocoa;
 extern crate core_foundation;
+extern crate libc;
 extern crate metal_sys;
+#[cfg(feature = "translate")]
+pub extern crate metl_translate;
 #[macro_use]
 extern crate objc;
+#[cfg(feature = "serialize")]
+extern crate serde;
+#[cfg(feature = "serialize")]
+#[macro_use]
+extern crate serde_derive;
 
 #[macro_use]
 mod raw;
@@ -17,6 +27,12 @@ mod internal;
 #[cfg(feature = "extras")]
 pub mod extras;
 
+pub mod allocator;
+
+pub mod embed;
+
+pub mod formats;
+
 mod argument;
 mod array_type;
 mod blit_command_encoder;
@@ -29,13 +45,22 @@ mod compute_command_encoder;
 mod constants;
 mod depth_stencil_state;
 mod device;
+mod diagnostic;
 mod drawable;
 mod error;
 mod fence;
 mod function;
+mod gpu_pod;
+mod host_allocation;
 mod library;
+mod library_cache;
+mod metallib;
+mod msl_layout;
+mod msl_scanner;
 mod objc_bringup;
 mod parallel_render_command_encoder;
+mod pixel_format;
+mod reflection;
 mod render_command_encoder;
 mod render_pass_attachment_descriptor;
 mod render_pass_color_attachment_descriptor;
@@ -45,10 +70,18 @@ mod render_pass_stencil_attachment_descriptor;
 mod render_pipeline_state;
 mod resource;
 mod sampler_state;
+mod shader_source;
+mod shader_struct;
+mod shader_variants;
+mod shader_watcher;
 mod stencil_descriptor;
 mod struct_member;
 mod struct_type;
+mod struct_validation;
 mod texture;
+mod texture_layout;
+#[cfg(feature = "translate")]
+mod translate;
 mod value_types;
 
 pub mod sys {
@@ -58,7 +91,7 @@ pub mod sys {
 pub use argument::{Argument, ArgumentAccess, ArgumentType, DataType};
 pub use array_type::ArrayType;
 pub use blit_command_encoder::BlitCommandEncoder;
-pub use buffer::Buffer;
+pub use buffer::{Buffer, BufferSlice, TypedBuffer};
 pub use command_buffer::{CommandBuffer, CommandBufferError};
 pub use command_encoder::CommandEncoder;
 pub use command_queue::{CommandQueue, CommandQueueError};
@@ -67,14 +100,28 @@ pub use compile_options::{CompileOptions, LanguageVersion, PreprocessorMacroValu
 pub use compute_command_encoder::ComputeCommandEncoder;
 pub use constants::{CompareFunction, PipelineOption, PixelFormat};
 pub use depth_stencil_state::{DepthStencilDescriptor, DepthStencilState};
-pub use device::{BufferRef, Device, DeviceError, FeatureSet};
+pub use device::{BufferRef, Device, DeviceError, FeatureSet, TextureFromBufferError};
+pub use diagnostic::{Diagnostic, Severity, parse_diagnostics};
 pub use drawable::Drawable;
+pub use embed::{EmbeddedLibrary, EntryPointName};
 pub use error::NSError;
 pub use fence::Fence;
 pub use function::{Function, FunctionType};
+pub use gpu_pod::{GpuPod, as_bytes, cast_slice, cast_slice_mut};
+pub use host_allocation::{HostAllocation, MappedFile, PageAlignedVec, page_size,
+                          round_up_to_page_size};
 pub use library::{Library, LibraryError};
+pub use library_cache::{CacheEntryError, CacheKey, LIBRARY_CACHE_FORMAT_VERSION, LibraryCache,
+                        compile_metallib_with_xcrun};
+pub use metallib::{MetallibArchive, MetallibError, MetallibFileType, MetallibFunction,
+                   MetallibPlatform};
+pub use msl_layout::{LayoutMember, MemberType, StructLayout, StructLayoutError};
+pub use msl_scanner::{AddressSpace, Binding, EntryPoint, EntryPointParameter, MslScanError,
+                      SourceLocation, scan_entry_points};
 pub use parallel_render_command_encoder::ParallelRenderCommandEncoder;
+pub use pixel_format::{ComponentType, PixelFormatInfo};
 pub use raw::{AsRaw, FromRaw, FromRawError, IntoRaw, StrongPtr};
+pub use reflection::{ReflectedArgument, ReflectedArray, ReflectedMember, ReflectedStruct};
 pub use render_command_encoder::{CullMode, DepthClipMode, IndexType, PrimitiveType,
                                  RenderCommandEncoder, TriangleFillModeFill, VisibilityResultMode,
                                  Winding};
@@ -86,10 +133,25 @@ pub use render_pass_depth_attachment_descriptor::RenderPassDepthAttachmentDescri
 pub use render_pass_descriptor::RenderPassDescriptor;
 pub use render_pass_stencil_attachment_descriptor::RenderPassStencilAttachmentDescriptor;
 pub use render_pipeline_state::RenderPipelineState;
-pub use resource::{CpuCacheMode, PurgeableState, Resource, ResourceOptions, StorageMode};
+pub use resource::{CpuCacheMode, PurgeableState, RESOURCE_CPU_CACHE_MODE_DEFAULT_CACHE,
+                   RESOURCE_CPU_CACHE_MODE_WRITE_COMBINED, RESOURCE_STORAGE_MODE_MANAGED,
+                   RESOURCE_STORAGE_MODE_PRIVATE, RESOURCE_STORAGE_MODE_SHARED, Resource,
+                   ResourceOptions, StorageMode};
 pub use sampler_state::{SamplerDescriptor, SamplerState};
+pub use shader_source::{AssembledSource, DirectoryProvider, EmbeddedProvider, MemoryProvider,
+                        ShaderSourceError, ShaderSourceProvider, ShaderSourceSet};
+pub use shader_struct::{ShaderStruct, ShaderType};
+pub use shader_variants::{ShaderVariantSet, VariantError, VariantKey, VariantStatus};
+pub use shader_watcher::{DeviceCompiler, ReloadError, ReloadStatus, ReloadableLibrary,
+                         ShaderCompiler, ShaderWatcher};
 pub use stencil_descriptor::StencilDescriptor;
 pub use struct_member::StructMember;
 pub use struct_type::StructType;
-pub use texture::{Texture, TextureDescriptor, TextureType, TextureUsage};
+pub use struct_validation::{StructDiff, StructMismatch};
+pub use texture::{TEXTURE_USAGE_PIXEL_FORMAT_VIEW, TEXTURE_USAGE_RENDER_TARGET,
+                  TEXTURE_USAGE_SHADER_READ, TEXTURE_USAGE_SHADER_WRITE, TEXTURE_USAGE_UNKNOWN,
+                  Texture, TextureDescriptor, TextureType, TextureUsage};
+pub use texture_layout::{TextureLayout, TextureLayoutError};
+#[cfg(feature = "translate")]
+pub use translate::TranslatedLibraryError;
 pub use value_types::{ClearColor, Origin, Region, ScissorRect, Size, Viewport};
@@ -10,7 +10,7 @@ use std::io::Error as IoError;
 use std::mem;
 use std::sync::Arc;
 use sys::MTLLibrary;
-use {Device, FromRaw, FromRawError, Function};
+use {Device, Diagnostic, FromRaw, FromRawError, Function, parse_diagnostics};
 
 pub struct Library(id);
 
@@ -23,15 +23,15 @@ impl Library {
         }
     }
 
-    pub fn function_names(&self) -> Vec<&str> {
+    pub fn function_names(&self) -> Vec<String> {
         let names_array = unsafe { self.0.functionNames() };
         let names_len = unsafe { names_array.count() };
         let mut names_vec = vec![];
         for i in 0..names_len {
             let name = unsafe {
-                CStr::from_ptr(names_array.objectAtIndex(i).UTF8String()).to_str().unwrap_or(&"")
+                CStr::from_ptr(names_array.objectAtIndex(i).UTF8String()).to_string_lossy()
             };
-            names_vec.push(name);
+            names_vec.push(name.into_owned());
         }
         names_vec
     }
@@ -60,6 +60,19 @@ pub enum LibraryError {
     Io(IoError)
 }
 
+impl LibraryError {
+    /// The errors and warnings reported by the shader compiler. Empty if the error did not come
+    /// from compiling source, or if the compiler output could not be parsed.
+    pub fn diagnostics(&self) -> Vec<Diagnostic> {
+        match *self {
+            LibraryError::SourceError(Some(ref error)) => {
+                parse_diagnostics(error.localized_description())
+            }
+            _ => vec![],
+        }
+    }
+}
+
 impl fmt::Display for LibraryError {
     fn fmt(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
         write!(formatter, "{}", self.description())
@@ -0,0 +1,636 @@
+//! A cache of compiled shader libraries on disk.
+//!
+//! Compiling shader source at runtime can take a noticeable amount of time, so a `LibraryCache`
+//! stores compiled `.metallib` data in a directory, keyed by a hash of the source and the compile
+//! options. Later runs load the library with `Device::new_library_with_data` instead of compiling
+//! it again.
+//!
+//! Each entry is a file named after its key, which starts with a header holding the cache format
+//! version, the length of the data and a checksum. Entries with a different format version, or
+//! which are truncated or corrupt, are removed when they are read and treated as missing.
+//!
+//! A cache with a size limit removes the least recently used entries first. Reading an entry
+//! updates its modification time, which is used to track when it was last used.
+
+use std::env;
+use std::error::Error;
+use std::fmt;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Read, Write};
+use std::path::{Path, PathBuf};
+use std::process;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::SystemTime;
+use {CompileOptions, Device, LanguageVersion, Library, LibraryError, PreprocessorMacroValue};
+
+/// The version of the format of cache entries. Entries written with a different version are
+/// ignored.
+pub const LIBRARY_CACHE_FORMAT_VERSION: u32 = 1;
+
+const MAGIC: &'static [u8; 4] = b"MTLC";
+const HEADER_LEN: usize = 24;
+const ENTRY_EXTENSION: &'static str = "metlcache";
+
+/// Counts the temporary files written by `LibraryCache::insert`, so that concurrent inserts of
+/// the same key in one process write to different files.
+static TEMP_ENTRY_COUNT: AtomicUsize = AtomicUsize::new(0);
+
+/// Counts the temporary directories made by `compile_metallib_with_xcrun`, so that concurrent
+/// compiles in the same process do not share one.
+static XCRUN_DIRECTORY_COUNT: AtomicUsize = AtomicUsize::new(0);
+
+/// A 64 bit FNV-1a hash, which is stable across runs and platforms unlike `DefaultHasher`.
+#[derive(Clone, Copy)]
+struct Fnv(u64);
+
+impl Fnv {
+    fn new() -> Self {
+        Fnv(0xcbf29ce484222325)
+    }
+
+    fn with_seed(seed: u64) -> Self {
+        let mut hash = Fnv::new();
+        hash.write_u64(seed);
+        hash
+    }
+
+    fn write(&mut self, bytes: &[u8]) {
+        for &byte in bytes {
+            self.0 ^= byte as u64;
+            self.0 = self.0.wrapping_mul(0x100000001b3);
+        }
+    }
+
+    fn write_u64(&mut self, value: u64) {
+        let bytes = (0..8).map(|i| (value >> (i * 8)) as u8).collect::<Vec<_>>();
+        self.write(&bytes);
+    }
+
+    /// Writes the length of `bytes` before them, so that adjacent fields cannot run together.
+    fn write_field(&mut self, bytes: &[u8]) {
+        self.write_u64(bytes.len() as u64);
+        self.write(bytes);
+    }
+}
+
+fn checksum(data: &[u8]) -> u64 {
+    let mut hash = Fnv::new();
+    hash.write(data);
+    hash.0
+}
+
+/// Identifies a cache entry by the content it was compiled from.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub struct CacheKey([u64; 2]);
+
+impl CacheKey {
+    /// Creates a key from shader source and the options it is compiled with. `version` should
+    /// change whenever compiled libraries may differ for the same source, such as when the
+    /// operating system or the application is updated.
+    pub fn new(version: &str, source: &str, options: &CompileOptions) -> Self {
+        let mut hashes = [Fnv::with_seed(0), Fnv::with_seed(1)];
+        for hash in &mut hashes {
+            hash.write_u64(LIBRARY_CACHE_FORMAT_VERSION as u64);
+            hash.write_field(version.as_bytes());
+            hash.write_field(source.as_bytes());
+            write_options(hash, options);
+        }
+        CacheKey([hashes[0].0, hashes[1].0])
+    }
+
+    /// The key as 32 hexadecimal digits, which is used as the file name of the entry.
+    pub fn to_hex(&self) -> String {
+        format!("{:016x}{:016x}", self.0[0], self.0[1])
+    }
+}
+
+impl fmt::Display for CacheKey {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(&self.to_hex())
+    }
+}
+
+fn write_options(hash: &mut Fnv, options: &CompileOptions) {
+    hash.write(&[match options.fast_math_enabled {
+                     None => 0,
+                     Some(false) => 1,
+                     Some(true) => 2,
+                 }]);
+    match options.language_version {
+        LanguageVersion::Latest => hash.write_field(b"latest"),
+        LanguageVersion::Specific(version) => hash.write_field(format!("{:?}", version).as_bytes()),
+    }
+
+    hash.write_u64(options.preprocessor_macros.len() as u64);
+    for (name, value) in &options.preprocessor_macros {
+        hash.write_field(name.as_bytes());
+        match *value {
+            PreprocessorMacroValue::Floating(f) => {
+                hash.write(b"f");
+                hash.write_u64(f.to_bits());
+            }
+            PreprocessorMacroValue::Integer(i) => {
+                hash.write(b"i");
+                hash.write_u64(i as u64);
+            }
+            PreprocessorMacroValue::String(ref s) => {
+                hash.write(b"s");
+                hash.write_field(s.as_bytes());
+            }
+        }
+    }
+}
+
+/// A directory of compiled shader libraries.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct LibraryCache {
+    directory: PathBuf,
+    version: String,
+    max_size: Option<u64>
+}
+
+impl LibraryCache {
+    /// Creates a cache which stores its entries in `directory`. The directory is created when the
+    /// first entry is inserted.
+    pub fn new<P: Into<PathBuf>>(directory: P) -> Self {
+        LibraryCache { directory: directory.into(), version: String::new(), max_size: None }
+    }
+
+    /// Sets a version string which is included in every key, so that entries from other versions
+    /// are not used. See `CacheKey::new`.
+    pub fn with_version<S: Into<String>>(self, version: S) -> Self {
+        LibraryCache { version: version.into(), ..self }
+    }
+
+    /// Limits the total size of the entries. When an insertion takes the cache over the limit,
+    /// the least recently used entries are removed.
+    pub fn with_max_size(self, max_size: u64) -> Self {
+        LibraryCache { max_size: Some(max_size), ..self }
+    }
+
+    pub fn directory(&self) -> &Path {
+        &self.directory
+    }
+
+    /// The key of the entry for `source` compiled with `options`.
+    pub fn key(&self, source: &str, options: &CompileOptions) -> CacheKey {
+        CacheKey::new(&self.version, source, options)
+    }
+
+    fn entry_path(&self, key: &CacheKey) -> PathBuf {
+        self.directory.join(format!("{}.{}", key.to_hex(), ENTRY_EXTENSION))
+    }
+
+    /// Reads the data of an entry. Returns `None` if there is no entry, or if it was written by a
+    /// different format version or is corrupt, in which case it is removed.
+    pub fn get(&self, key: &CacheKey) -> Option<Vec<u8>> {
+        let path = self.entry_path(key);
+        let mut contents = vec![];
+        if File::open(&path).and_then(|mut file| file.read_to_end(&mut contents)).is_err() {
+            return None;
+        }
+
+        match decode_entry(&contents) {
+            Ok(data) => {
+                if self.max_size.is_some() {
+                    touch(&path, &contents[..HEADER_LEN]);
+                }
+                Some(data.to_vec())
+            }
+            Err(_) => {
+                let _ = fs::remove_file(&path);
+                None
+            }
+        }
+    }
+
+    /// Stores the data of an entry, replacing any existing entry with the same key, and then
+    /// removes old entries if the cache is over its size limit.
+    pub fn insert(&self, key: &CacheKey, data: &[u8]) -> io::Result<()> {
+        try!(fs::create_dir_all(&self.directory));
+
+        // Write to a temporary file first, so that readers never see a partly written entry.
+        let path = self.entry_path(key);
+        let count = TEMP_ENTRY_COUNT.fetch_add(1, Ordering::SeqCst);
+        let temp_path = path.with_extension(format!("tmp{}-{}", process::id(), count));
+        let written = File::create(&temp_path).and_then(|mut file| {
+            try!(file.write_all(&encode_entry(data)));
+            file.sync_all()
+        });
+        if let Err(error) = written.and_then(|_| fs::rename(&temp_path, &path)) {
+            let _ = fs::remove_file(&temp_path);
+            return Err(error);
+        }
+
+        self.trim()
+    }
+
+    /// Removes an entry. Returns `true` if there was an entry to remove.
+    pub fn remove(&self, key: &CacheKey) -> bool {
+        fs::remove_file(self.entry_path(key)).is_ok()
+    }
+
+    /// Removes every entry.
+    pub fn clear(&self) -> io::Result<()> {
+        for (path, _, _) in try!(self.entries()) {
+            try!(fs::remove_file(path));
+        }
+        Ok(())
+    }
+
+    /// The total size in bytes of the entries.
+    pub fn size(&self) -> io::Result<u64> {
+        Ok(try!(self.entries()).iter().map(|&(_, len, _)| len).sum())
+    }
+
+    /// Removes the least recently used entries until the cache is within its size limit.
+    pub fn trim(&self) -> io::Result<()> {
+        let max_size = match self.max_size {
+            Some(max_size) => max_size,
+            None => return Ok(()),
+        };
+
+        for path in entries_to_remove(try!(self.entries()), max_size) {
+            try!(fs::remove_file(path));
+        }
+        Ok(())
+    }
+
+    /// Lists the entry files with their sizes and modification times.
+    fn entries(&self) -> io::Result<Vec<(PathBuf, u64, Option<SystemTime>)>> {
+        let read_dir = match fs::read_dir(&self.directory) {
+            Ok(read_dir) => read_dir,
+            Err(ref error) if error.kind() == io::ErrorKind::NotFound => return Ok(vec![]),
+            Err(error) => return Err(error),
+        };
+
+        let mut entries = vec![];
+        for entry in read_dir {
+            let entry = try!(entry);
+            let path = entry.path();
+            if path.extension().map_or(false, |extension| extension == ENTRY_EXTENSION) {
+                let metadata = try!(entry.metadata());
+                entries.push((path, metadata.len(), metadata.modified().ok()));
+            }
+        }
+        Ok(entries)
+    }
+
+    /// Loads the library for `source` from the cache, or compiles it with `compile` and stores
+    /// the result. `compile` must produce `.metallib` data, such as with
+    /// `compile_metallib_with_xcrun`.
+    ///
+    /// Entries which the device cannot load are replaced. Failing to store an entry does not fail
+    /// the load, as the library can still be used.
+    pub fn load_library<F>(&self, device: &mut Device, source: &str, options: &CompileOptions,
+                           compile: F)
+                           -> Result<Library, LibraryError>
+        where F: FnOnce(&str, &CompileOptions) -> Result<Vec<u8>, LibraryError> {
+        let key = self.key(source, options);
+        if let Some(data) = self.get(&key) {
+            match device.new_library_with_data(&data) {
+                Ok(library) => return Ok(library),
+                Err(_) => {
+                    self.remove(&key);
+                }
+            }
+        }
+
+        let data = try!(compile(source, options));
+        let library = try!(device.new_library_with_data(&data));
+        let _ = self.insert(&key, &data);
+        Ok(library)
+    }
+}
+
+/// The reason that a cache entry could not be read.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum CacheEntryError {
+    /// The entry does not start with the cache header.
+    BadHeader,
+
+    /// The entry was written by another format version.
+    VersionMismatch { version: u32 },
+
+    /// The entry is shorter or longer than its header says.
+    LengthMismatch { expected: u64, actual: u64 },
+
+    /// The data of the entry does not match its checksum.
+    ChecksumMismatch
+}
+
+impl fmt::Display for CacheEntryError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            CacheEntryError::VersionMismatch { version } => {
+                write!(f, "the entry has format version {}, but the cache uses version {}",
+                       version, LIBRARY_CACHE_FORMAT_VERSION)
+            }
+            CacheEntryError::LengthMismatch { expected, actual } => {
+                write!(f, "the entry has {} bytes of data, but its header says {}", actual,
+                       expected)
+            }
+            _ => f.write_str(self.description()),
+        }
+    }
+}
+
+impl Error for CacheEntryError {
+    fn description(&self) -> &str {
+        match *self {
+            CacheEntryError::BadHeader => "The cache entry does not have a valid header",
+            CacheEntryError::VersionMismatch { .. } => "The cache entry has another format version",
+            CacheEntryError::LengthMismatch { .. } => "The cache entry has the wrong length",
+            CacheEntryError::ChecksumMismatch => "The cache entry data does not match its checksum",
+        }
+    }
+}
+
+/// Chooses the entries to remove so that the rest fit in `max_size`, starting with the ones which
+/// were used longest ago. Entries used at the same time are ordered by path, so that file systems
+/// with coarse modification times still remove entries in a consistent order.
+fn entries_to_remove(mut entries: Vec<(PathBuf, u64, Option<SystemTime>)>, max_size: u64)
+                     -> Vec<PathBuf> {
+    entries.sort_by(|a, b| (a.2, &a.0).cmp(&(b.2, &b.0)));
+    let mut size = entries.iter().map(|&(_, len, _)| len).sum::<u64>();
+    let mut removed = vec![];
+    for (path, len, _) in entries {
+        if size <= max_size {
+            break;
+        }
+        removed.push(path);
+        size -= len;
+    }
+    removed
+}
+
+/// Marks an entry as used by writing its unchanged header back, which updates its modification
+/// time. Failing to do so only makes the entry more likely to be removed, so errors are ignored.
+fn touch(path: &Path, header: &[u8]) {
+    let _ = OpenOptions::new().write(true).open(path).and_then(|mut file| file.write_all(header));
+}
+
+fn encode_entry(data: &[u8]) -> Vec<u8> {
+    let mut entry = Vec::with_capacity(HEADER_LEN + data.len());
+    entry.extend_from_slice(MAGIC);
+    entry.extend(le_bytes(LIBRARY_CACHE_FORMAT_VERSION as u64, 4));
+    entry.extend(le_bytes(data.len() as u64, 8));
+    entry.extend(le_bytes(checksum(data), 8));
+    entry.extend_from_slice(data);
+    entry
+}
+
+fn decode_entry(entry: &[u8]) -> Result<&[u8], CacheEntryError> {
+    if entry.len() < HEADER_LEN || &entry[..4] != &MAGIC[..] {
+        return Err(CacheEntryError::BadHeader);
+    }
+
+    let version = read_le(&entry[4..8]) as u32;
+    if version != LIBRARY_CACHE_FORMAT_VERSION {
+        return Err(CacheEntryError::VersionMismatch { version: version });
+    }
+
+    let data = &entry[HEADER_LEN..];
+    let len = read_le(&entry[8..16]);
+    if len != data.len() as u64 {
+        return Err(CacheEntryError::LengthMismatch { expected: len, actual: data.len() as u64 });
+    }
+    if read_le(&entry[16..24]) != checksum(data) {
+        return Err(CacheEntryError::ChecksumMismatch);
+    }
+    Ok(data)
+}
+
+fn le_bytes(value: u64, len: usize) -> Vec<u8> {
+    (0..len).map(|i| (value >> (i * 8)) as u8).collect()
+}
+
+fn read_le(bytes: &[u8]) -> u64 {
+    bytes.iter().rev().fold(0, |value, &byte| (value << 8) | byte as u64)
+}
+
+/// Compiles shader source to `.metallib` data with the `metal` and `metallib` command line tools,
+/// which are run through `xcrun` and so need Xcode or its command line tools to be installed.
+pub fn compile_metallib_with_xcrun(source: &str, options: &CompileOptions)
+                                   -> Result<Vec<u8>, LibraryError> {
+    let sdk = if cfg!(target_os = "ios") { "iphoneos" } else { "macosx" };
+    let directory = try!(xcrun_directory());
+    let source_path = directory.join("source.metal");
+    let air_path = directory.join("source.air");
+    let library_path = directory.join("source.metallib");
+    try!(File::create(&source_path).and_then(|mut file| file.write_all(source.as_bytes())));
+
+    let mut compile = process::Command::new("xcrun");
+    compile.args(&["-sdk", sdk, "metal", "-c"]);
+    if let Some(fast_math) = options.fast_math_enabled {
+        compile.arg(if fast_math { "-ffast-math" } else { "-fno-fast-math" });
+    }
+    if let LanguageVersion::Specific(version) = options.language_version {
+        let platform = if cfg!(target_os = "ios") { "ios" } else { "macos" };
+        let number = format!("{:?}", version).replace("Version_", "").replace('_', ".");
+        compile.arg(format!("-std={}-metal{}", platform, number));
+    }
+    for (name, value) in &options.preprocessor_macros {
+        compile.arg(format!("-D{}={}", name, value));
+    }
+    compile.arg(&source_path).arg("-o").arg(&air_path);
+
+    let mut link = process::Command::new("xcrun");
+    link.args(&["-sdk", sdk, "metallib"]).arg(&air_path).arg("-o").arg(&library_path);
+
+    let result = run(&mut compile).and_then(|_| run(&mut link)).and_then(|_| {
+        let mut data = vec![];
+        try!(File::open(&library_path).and_then(|mut file| file.read_to_end(&mut data)));
+        Ok(data)
+    });
+    let _ = fs::remove_dir_all(&directory);
+    result.map_err(LibraryError::from)
+}
+
+/// Creates a new, empty temporary directory which no other compile uses.
+fn xcrun_directory() -> io::Result<PathBuf> {
+    loop {
+        let count = XCRUN_DIRECTORY_COUNT.fetch_add(1, Ordering::SeqCst);
+        let directory = env::temp_dir().join(format!("metl-xcrun-{}-{}", process::id(), count));
+        match fs::create_dir(&directory) {
+            Ok(()) => return Ok(directory),
+            // Left behind by an earlier process with the same id.
+            Err(ref error) if error.kind() == io::ErrorKind::AlreadyExists => continue,
+            Err(error) => return Err(error),
+        }
+    }
+}
+
+fn run(command: &mut process::Command) -> io::Result<()> {
+    let output = try!(command.output());
+    if output.status.success() {
+        Ok(())
+    } else {
+        let message = String::from_utf8_lossy(&output.stderr).into_owned();
+        Err(io::Error::new(io::ErrorKind::Other, message))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::env;
+    use std::fs::{self, OpenOptions};
+    use std::io::{Seek, SeekFrom, Write};
+    use std::path::PathBuf;
+    use std::thread;
+    use std::time::{Duration, UNIX_EPOCH};
+    use {CompileOptions, LanguageVersion, SpecificLanguageVersion};
+
+    fn temp_cache(name: &str) -> (PathBuf, LibraryCache) {
+        let dir = env::temp_dir().join(format!("metl-cache-{}-{}", name, ::std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        (dir.clone(), LibraryCache::new(dir))
+    }
+
+    #[test]
+    fn test_keys_are_stable() {
+        let options = CompileOptions::default()
+            .with_macro("A", 1)
+            .with_macro("B", 2.5)
+            .with_macro("C", "c");
+        let reordered = CompileOptions::default()
+            .with_macro("C", "c")
+            .with_macro("B", 2.5)
+            .with_macro("A", 1);
+        let key = CacheKey::new("", "source", &options);
+        assert_eq!(key, CacheKey::new("", "source", &reordered));
+        assert_eq!(key.to_hex().len(), 32);
+
+        assert!(key != CacheKey::new("1.1", "source", &options));
+        assert!(key != CacheKey::new("", "source ", &options));
+        assert!(key != CacheKey::new("", "source", &options.clone().with_macro("A", 2)));
+        assert!(key != CacheKey::new("", "source", &options.clone().fast_math_enabled(false)));
+        let version = LanguageVersion::Specific(SpecificLanguageVersion::Version_1_1);
+        assert!(key != CacheKey::new("", "source", &options.clone().language_version(version)));
+        assert!(CacheKey::new("ab", "c", &Default::default()) !=
+                CacheKey::new("a", "bc", &Default::default()));
+    }
+
+    #[test]
+    fn test_insert_and_get() {
+        let (dir, cache) = temp_cache("insert");
+        let key = cache.key("source", &Default::default());
+        assert_eq!(cache.get(&key), None);
+
+        cache.insert(&key, b"fake metallib").unwrap();
+        assert_eq!(cache.get(&key), Some(b"fake metallib".to_vec()));
+        cache.insert(&key, b"replaced").unwrap();
+        assert_eq!(cache.get(&key), Some(b"replaced".to_vec()));
+        assert_eq!(cache.size().unwrap(), 24 + 8);
+
+        let other_version = cache.clone().with_version("2");
+        assert_eq!(other_version.get(&other_version.key("source", &Default::default())), None);
+
+        assert!(cache.remove(&key));
+        assert!(!cache.remove(&key));
+        cache.insert(&key, b"data").unwrap();
+        cache.clear().unwrap();
+        assert_eq!(cache.size().unwrap(), 0);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_concurrent_inserts_of_the_same_key() {
+        let (dir, cache) = temp_cache("concurrent");
+        let key = cache.key("source", &Default::default());
+        let threads = (0..8u8).map(|i| {
+            let cache = cache.clone();
+            thread::spawn(move || cache.insert(&key, &[i; 64]))
+        }).collect::<Vec<_>>();
+        for thread in threads {
+            thread.join().unwrap().unwrap();
+        }
+        assert_eq!(cache.get(&key).map(|data| data.len()), Some(64));
+        assert_eq!(fs::read_dir(&dir).unwrap().count(), 1);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_corrupt_entries_are_removed() {
+        let (dir, cache) = temp_cache("corrupt");
+        let options = CompileOptions::default();
+        let entry_path = |key: &CacheKey| dir.join(format!("{}.metlcache", key));
+
+        let flipped = cache.key("flipped", &options);
+        cache.insert(&flipped, b"payload").unwrap();
+        let mut file = OpenOptions::new().write(true).open(entry_path(&flipped)).unwrap();
+        file.seek(SeekFrom::End(-1)).unwrap();
+        file.write_all(b"?").unwrap();
+        drop(file);
+        assert_eq!(cache.get(&flipped), None);
+        assert!(!entry_path(&flipped).exists());
+
+        let truncated = cache.key("truncated", &options);
+        cache.insert(&truncated, b"payload").unwrap();
+        OpenOptions::new().write(true).open(entry_path(&truncated)).unwrap().set_len(10).unwrap();
+        assert_eq!(cache.get(&truncated), None);
+
+        assert_eq!(decode_entry(b"not a cache entry at all"), Err(CacheEntryError::BadHeader));
+        let mut entry = encode_entry(b"payload");
+        entry[4] = 99;
+        assert_eq!(decode_entry(&entry), Err(CacheEntryError::VersionMismatch { version: 99 }));
+        let mut entry = encode_entry(b"payload");
+        entry.push(0);
+        assert_eq!(decode_entry(&entry),
+                   Err(CacheEntryError::LengthMismatch { expected: 7, actual: 8 }));
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_xcrun_directories_are_unique() {
+        let first = xcrun_directory().unwrap();
+        let second = xcrun_directory().unwrap();
+        assert!(first != second);
+        assert!(first.is_dir() && second.is_dir());
+        fs::remove_dir(&first).unwrap();
+        fs::remove_dir(&second).unwrap();
+    }
+
+    #[test]
+    fn test_size_limit_removes_least_recently_used_entries() {
+        let time = |secs| Some(UNIX_EPOCH + Duration::from_secs(secs));
+        let entries = vec![(PathBuf::from("c"), 100, time(20)),
+                           (PathBuf::from("a"), 100, time(30)),
+                           (PathBuf::from("b"), 100, time(10)),
+                           (PathBuf::from("d"), 100, time(40)),
+                           (PathBuf::from("e"), 100, time(50))];
+        assert_eq!(entries_to_remove(entries.clone(), 500), Vec::<PathBuf>::new());
+        assert_eq!(entries_to_remove(entries.clone(), 300),
+                   [PathBuf::from("b"), PathBuf::from("c")]);
+        assert_eq!(entries_to_remove(entries, 0).len(), 5);
+    }
+
+    #[test]
+    fn test_size_limit_breaks_ties_by_path() {
+        let time = Some(UNIX_EPOCH);
+        let entries = vec![(PathBuf::from("b"), 100, time),
+                           (PathBuf::from("c"), 100, time),
+                           (PathBuf::from("a"), 100, time),
+                           (PathBuf::from("d"), 100, None)];
+        assert_eq!(entries_to_remove(entries, 200), [PathBuf::from("d"), PathBuf::from("a")]);
+    }
+
+    #[test]
+    fn test_size_limit_keeps_the_cache_within_the_limit() {
+        let (dir, cache) = temp_cache("limit");
+        let cache = cache.with_max_size(3 * (24 + 100));
+        let payload = [0u8; 100];
+        let keys = (0..5).map(|i| cache.key(&i.to_string(), &Default::default()))
+                         .collect::<Vec<_>>();
+        for key in &keys {
+            cache.insert(key, &payload).unwrap();
+            // Reading an entry rewrites its header, which must leave it intact.
+            assert_eq!(cache.get(key).unwrap(), &payload[..]);
+        }
+
+        assert_eq!(cache.size().unwrap(), 3 * (24 + 100));
+        assert_eq!(keys.iter().filter(|key| cache.get(key).is_some()).count(), 3);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
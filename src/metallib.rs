@@ -0,0 +1,504 @@
+//! A reader for the `.metallib` container format.
+//!
+//! `Device::new_library_with_file` needs a device to look inside a compiled library. The reader
+//! in this module only parses the container, so tools can list the functions in a library on any
+//! platform. The format is undocumented; the reader understands the header and the function list,
+//! and ignores the tags and sections which it does not know about.
+//!
+//! All integers in the format are little endian. The header is followed by a function list, which
+//! is a `u32` count followed by an entry for each function. An entry is a `u32` size followed by
+//! tags, each of which is a four character name, a `u16` length and that many bytes of data. The
+//! `ENDT` tag, which has no length, ends the entry.
+
+use std::error::Error;
+use std::fmt;
+use std::fs::File;
+use std::io::{self, Read};
+use std::path::Path;
+use FunctionType;
+
+const MAGIC: &'static [u8; 4] = b"MTLB";
+const HEADER_LEN: usize = 88;
+const HASH_LEN: usize = 32;
+
+/// The kind of a compiled library.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum MetallibFileType {
+    Executable,
+    CoreImage,
+    Dynamic,
+    SymbolCompanion,
+    Unknown(u8)
+}
+
+impl MetallibFileType {
+    fn from_raw(raw: u8) -> Self {
+        match raw {
+            0 => MetallibFileType::Executable,
+            1 => MetallibFileType::CoreImage,
+            2 => MetallibFileType::Dynamic,
+            3 => MetallibFileType::SymbolCompanion,
+            _ => MetallibFileType::Unknown(raw),
+        }
+    }
+}
+
+/// The operating system that a library was compiled for.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum MetallibPlatform {
+    MacOs,
+    Ios,
+    TvOs,
+    WatchOs,
+    BridgeOs,
+    MacCatalyst,
+    IosSimulator,
+    TvOsSimulator,
+    WatchOsSimulator,
+    Unknown(u8)
+}
+
+impl MetallibPlatform {
+    fn from_raw(raw: u8) -> Self {
+        match raw {
+            1 => MetallibPlatform::MacOs,
+            2 => MetallibPlatform::Ios,
+            3 => MetallibPlatform::TvOs,
+            4 => MetallibPlatform::WatchOs,
+            5 => MetallibPlatform::BridgeOs,
+            6 => MetallibPlatform::MacCatalyst,
+            7 => MetallibPlatform::IosSimulator,
+            8 => MetallibPlatform::TvOsSimulator,
+            9 => MetallibPlatform::WatchOsSimulator,
+            _ => MetallibPlatform::Unknown(raw),
+        }
+    }
+}
+
+/// A function in a compiled library.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct MetallibFunction {
+    pub name: String,
+
+    /// The type of the function, or `None` if it is not an entry point, such as a function which
+    /// is only visible to other libraries.
+    pub function_type: Option<FunctionType>,
+
+    /// The version of the Metal Shading Language which the function was compiled with.
+    pub language_version: Option<(u16, u16)>,
+
+    /// The version of the AIR bitcode of the function.
+    pub air_version: Option<(u16, u16)>,
+
+    /// The SHA-256 hash of the bitcode of the function.
+    pub hash: Option<[u8; HASH_LEN]>,
+
+    /// The size of the bitcode of the function, in bytes.
+    pub bitcode_size: Option<u64>
+}
+
+impl MetallibFunction {
+    /// The hash of the function as a hexadecimal string.
+    pub fn hash_hex(&self) -> Option<String> {
+        self.hash.map(|hash| hash.iter().map(|byte| format!("{:02x}", byte)).collect())
+    }
+}
+
+/// The contents of a `.metallib` file.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct MetallibArchive {
+    file_version: (u16, u16),
+    file_type: MetallibFileType,
+    platform: MetallibPlatform,
+    platform_version: (u16, u8, u8),
+    functions: Vec<MetallibFunction>
+}
+
+impl MetallibArchive {
+    /// Reads the library in the file at `path`.
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<Self, MetallibError> {
+        let mut data = vec![];
+        try!(File::open(path).and_then(|mut file| file.read_to_end(&mut data)));
+        MetallibArchive::parse(&data)
+    }
+
+    /// Reads a library from the contents of a `.metallib` file.
+    pub fn parse(data: &[u8]) -> Result<Self, MetallibError> {
+        if data.len() < MAGIC.len() || &data[..4] != &MAGIC[..] {
+            return Err(MetallibError::BadMagic);
+        }
+        if data.len() < HEADER_LEN {
+            return Err(MetallibError::Truncated);
+        }
+
+        let mut header = Reader::new(data, 4);
+        let _target = try!(header.u16());
+        let file_version = (try!(header.u16()), try!(header.u16()));
+        let file_type = MetallibFileType::from_raw(try!(header.u8()));
+        let platform = MetallibPlatform::from_raw(try!(header.u8()) & 0x7f);
+        let platform_version = (try!(header.u16()), try!(header.u8()), try!(header.u8()));
+        let file_size = try!(header.u64());
+        if file_size > data.len() as u64 {
+            return Err(MetallibError::Truncated);
+        }
+        let function_list = try!(section(data, try!(header.u64()), try!(header.u64())));
+
+        let mut reader = Reader::new(function_list, 0);
+        let count = try!(reader.u32());
+        let mut functions = vec![];
+        for _ in 0..count {
+            // The size includes the size field itself. Entries may be padded after `ENDT`, so the
+            // next entry starts at the declared size rather than after the last tag.
+            let entry_size = match (try!(reader.u32()) as usize).checked_sub(4) {
+                Some(entry_size) => entry_size,
+                None => return Err(MetallibError::Truncated),
+            };
+            let mut entry = Reader::new(try!(reader.bytes(entry_size)), 0);
+            functions.push(try!(read_function(&mut entry)));
+        }
+
+        Ok(MetallibArchive {
+            file_version: file_version,
+            file_type: file_type,
+            platform: platform,
+            platform_version: platform_version,
+            functions: functions
+        })
+    }
+
+    /// The version of the container format.
+    pub fn file_version(&self) -> (u16, u16) {
+        self.file_version
+    }
+
+    pub fn file_type(&self) -> MetallibFileType {
+        self.file_type
+    }
+
+    /// The operating system which the library was compiled for.
+    pub fn platform(&self) -> MetallibPlatform {
+        self.platform
+    }
+
+    /// The minimum version of the operating system, as a major, minor and patch version.
+    pub fn platform_version(&self) -> (u16, u8, u8) {
+        self.platform_version
+    }
+
+    /// The functions in the library, in the order they are stored.
+    pub fn functions(&self) -> &[MetallibFunction] {
+        &self.functions
+    }
+
+    /// The function called `name`, if the library contains it.
+    pub fn function(&self, name: &str) -> Option<&MetallibFunction> {
+        self.functions.iter().find(|function| function.name == name)
+    }
+
+    /// The names of the functions in the library.
+    pub fn function_names(&self) -> Vec<&str> {
+        self.functions.iter().map(|function| &function.name[..]).collect()
+    }
+
+    /// The entries of `names` which the library does not contain, in the order given.
+    pub fn missing_functions<'a, I>(&self, names: I) -> Vec<&'a str>
+        where I: IntoIterator<Item = &'a str> {
+        names.into_iter().filter(|name| self.function(name).is_none()).collect()
+    }
+}
+
+fn section(data: &[u8], offset: u64, len: u64) -> Result<&[u8], MetallibError> {
+    match offset.checked_add(len) {
+        Some(end) if end <= data.len() as u64 => Ok(&data[offset as usize..end as usize]),
+        _ => Err(MetallibError::Truncated),
+    }
+}
+
+fn read_function(reader: &mut Reader) -> Result<MetallibFunction, MetallibError> {
+    let mut name = None;
+    let mut function = MetallibFunction {
+        name: String::new(),
+        function_type: None,
+        language_version: None,
+        air_version: None,
+        hash: None,
+        bitcode_size: None
+    };
+
+    loop {
+        let tag = try!(reader.bytes(4));
+        if tag == b"ENDT" {
+            break;
+        }
+        let len = try!(reader.u16()) as usize;
+        let mut data = Reader::new(try!(reader.bytes(len)), 0);
+        match tag {
+            b"NAME" => {
+                let bytes = data.rest();
+                let bytes = bytes.iter().position(|&b| b == 0).map_or(bytes, |end| &bytes[..end]);
+                match String::from_utf8(bytes.to_vec()) {
+                    Ok(string) => name = Some(string),
+                    Err(_) => return Err(MetallibError::InvalidName),
+                }
+            }
+            b"TYPE" => {
+                function.function_type = match try!(data.u8()) {
+                    0 => Some(FunctionType::Vertex),
+                    1 => Some(FunctionType::Fragment),
+                    2 => Some(FunctionType::Kernel),
+                    _ => None,
+                };
+            }
+            b"VERS" => {
+                function.air_version = Some((try!(data.u16()), try!(data.u16())));
+                function.language_version = Some((try!(data.u16()), try!(data.u16())));
+            }
+            b"HASH" => {
+                let mut hash = [0; HASH_LEN];
+                hash.copy_from_slice(try!(data.bytes(HASH_LEN)));
+                function.hash = Some(hash);
+            }
+            b"MDSZ" => function.bitcode_size = Some(try!(data.u64())),
+            _ => (),
+        }
+    }
+
+    match name {
+        Some(name) => Ok(MetallibFunction { name: name, ..function }),
+        None => Err(MetallibError::MissingName),
+    }
+}
+
+/// Reads little endian values from a byte slice.
+struct Reader<'a> {
+    data: &'a [u8],
+    position: usize
+}
+
+impl<'a> Reader<'a> {
+    fn new(data: &'a [u8], position: usize) -> Self {
+        Reader { data: data, position: position }
+    }
+
+    fn bytes(&mut self, len: usize) -> Result<&'a [u8], MetallibError> {
+        if self.data.len() - self.position < len {
+            return Err(MetallibError::Truncated);
+        }
+        self.position += len;
+        Ok(&self.data[self.position - len..self.position])
+    }
+
+    fn rest(&mut self) -> &'a [u8] {
+        let rest = &self.data[self.position..];
+        self.position = self.data.len();
+        rest
+    }
+
+    fn uint(&mut self, len: usize) -> Result<u64, MetallibError> {
+        let bytes = try!(self.bytes(len));
+        Ok(bytes.iter().rev().fold(0, |value, &byte| (value << 8) | byte as u64))
+    }
+
+    fn u8(&mut self) -> Result<u8, MetallibError> {
+        self.uint(1).map(|value| value as u8)
+    }
+
+    fn u16(&mut self) -> Result<u16, MetallibError> {
+        self.uint(2).map(|value| value as u16)
+    }
+
+    fn u32(&mut self) -> Result<u32, MetallibError> {
+        self.uint(4).map(|value| value as u32)
+    }
+
+    fn u64(&mut self) -> Result<u64, MetallibError> {
+        self.uint(8)
+    }
+}
+
+/// An error produced when a `.metallib` file cannot be read.
+#[derive(Debug)]
+pub enum MetallibError {
+    /// The data does not start with the `MTLB` magic number.
+    BadMagic,
+
+    /// The data ends before the end of the header, a section or a tag.
+    Truncated,
+
+    /// A function does not have a `NAME` tag.
+    MissingName,
+
+    /// The name of a function is not valid UTF-8.
+    InvalidName,
+
+    /// The file could not be read.
+    Io(io::Error)
+}
+
+impl fmt::Display for MetallibError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            MetallibError::Io(ref error) => write!(f, "{}", error),
+            _ => f.write_str(self.description()),
+        }
+    }
+}
+
+impl Error for MetallibError {
+    fn description(&self) -> &str {
+        match *self {
+            MetallibError::BadMagic => "The data is not a metallib file",
+            MetallibError::Truncated => "The metallib file is truncated",
+            MetallibError::MissingName => "A function in the metallib file has no name",
+            MetallibError::InvalidName => "A function name in the metallib file is not UTF-8",
+            MetallibError::Io(_) => "The metallib file could not be read",
+        }
+    }
+
+    fn cause(&self) -> Option<&Error> {
+        match *self {
+            MetallibError::Io(ref error) => Some(error),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for MetallibError {
+    fn from(error: io::Error) -> Self {
+        MetallibError::Io(error)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use FunctionType;
+
+    fn tag(entry: &mut Vec<u8>, name: &[u8], data: &[u8]) {
+        entry.extend_from_slice(name);
+        entry.extend_from_slice(&[data.len() as u8, (data.len() >> 8) as u8]);
+        entry.extend_from_slice(data);
+    }
+
+    fn le(value: u64, len: usize) -> Vec<u8> {
+        (0..len).map(|i| (value >> (i * 8)) as u8).collect()
+    }
+
+    fn function_entry(name: &str, function_type: u8, hash_byte: u8) -> Vec<u8> {
+        let mut tags = vec![];
+        tag(&mut tags, b"NAME", format!("{}\0", name).as_bytes());
+        tag(&mut tags, b"TYPE", &[function_type]);
+        tag(&mut tags, b"HASH", &[hash_byte; HASH_LEN]);
+        tag(&mut tags, b"MDSZ", &le(1024, 8));
+        tag(&mut tags, b"OFFT", &[0; 24]);
+        tag(&mut tags, b"VERS", &[2, 0, 6, 0, 2, 0, 4, 0]);
+        tags.extend_from_slice(b"ENDT");
+
+        let mut entry = le(tags.len() as u64 + 4, 4);
+        entry.extend(tags);
+        entry
+    }
+
+    fn metallib(entries: &[Vec<u8>]) -> Vec<u8> {
+        let mut function_list = le(entries.len() as u64, 4);
+        for entry in entries {
+            function_list.extend_from_slice(entry);
+        }
+
+        let mut data = MAGIC.to_vec();
+        data.extend(le(0x8001, 2));
+        data.extend(le(1, 2));
+        data.extend(le(2, 2));
+        data.push(0);
+        data.push(0x81);
+        data.extend(le(13, 2));
+        data.extend_from_slice(&[1, 0]);
+        data.extend(le((HEADER_LEN + function_list.len()) as u64, 8));
+        data.extend(le(HEADER_LEN as u64, 8));
+        data.extend(le(function_list.len() as u64, 8));
+        data.extend(vec![0; HEADER_LEN - data.len()]);
+        data.extend(function_list);
+        data
+    }
+
+    #[test]
+    fn test_parse_functions() {
+        let data = metallib(&[function_entry("vertex_main", 0, 0xab),
+                              function_entry("fragment_main", 1, 0x01),
+                              function_entry("blur", 2, 0x02),
+                              function_entry("helper", 4, 0x03)]);
+        let archive = MetallibArchive::parse(&data).unwrap();
+        assert_eq!(archive.file_version(), (1, 2));
+        assert_eq!(archive.file_type(), MetallibFileType::Executable);
+        assert_eq!(archive.platform(), MetallibPlatform::MacOs);
+        assert_eq!(archive.platform_version(), (13, 1, 0));
+        assert_eq!(archive.function_names(), ["vertex_main", "fragment_main", "blur", "helper"]);
+
+        let types = archive.functions().iter().map(|f| f.function_type).collect::<Vec<_>>();
+        assert_eq!(types,
+                   [Some(FunctionType::Vertex), Some(FunctionType::Fragment),
+                    Some(FunctionType::Kernel), None]);
+
+        let vertex_main = archive.function("vertex_main").unwrap();
+        assert_eq!(vertex_main.language_version, Some((2, 4)));
+        assert_eq!(vertex_main.air_version, Some((2, 6)));
+        assert_eq!(vertex_main.bitcode_size, Some(1024));
+        assert_eq!(vertex_main.hash_hex().unwrap(), "ab".repeat(HASH_LEN));
+
+        assert_eq!(archive.missing_functions(vec!["blur", "sharpen", "vertex_main", "fog"]),
+                   ["sharpen", "fog"]);
+    }
+
+    #[test]
+    fn test_entries_are_read_by_their_declared_size() {
+        let mut padded = function_entry("vertex_main", 0, 0xab);
+        padded.extend_from_slice(b"NAME\x04\x00pad\0");
+        let size = padded.len() as u64;
+        padded[..4].copy_from_slice(&le(size, 4));
+
+        let data = metallib(&[padded, function_entry("fragment_main", 1, 0x01)]);
+        let archive = MetallibArchive::parse(&data).unwrap();
+        assert_eq!(archive.function_names(), ["vertex_main", "fragment_main"]);
+        assert_eq!(archive.function("fragment_main").unwrap().function_type,
+                   Some(FunctionType::Fragment));
+
+        // The tags of an entry must end within its declared size.
+        let mut short = function_entry("vertex_main", 0, 0xab);
+        let size = short.len() as u64 - 2;
+        short[..4].copy_from_slice(&le(size, 4));
+        match MetallibArchive::parse(&metallib(&[short])) {
+            Err(MetallibError::Truncated) => (),
+            other => panic!("expected a truncated entry, found {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_malformed_archives() {
+        match MetallibArchive::parse(b"not a metallib") {
+            Err(MetallibError::BadMagic) => (),
+            other => panic!("expected a bad magic number, found {:?}", other),
+        }
+        match MetallibArchive::parse(b"MT") {
+            Err(MetallibError::BadMagic) => (),
+            other => panic!("expected a bad magic number, found {:?}", other),
+        }
+
+        let data = metallib(&[function_entry("vertex_main", 0, 0)]);
+        match MetallibArchive::parse(&data[..HEADER_LEN - 1]) {
+            Err(MetallibError::Truncated) => (),
+            other => panic!("expected a truncated header, found {:?}", other),
+        }
+        match MetallibArchive::parse(&data[..data.len() - 2]) {
+            Err(MetallibError::Truncated) => (),
+            other => panic!("expected a truncated archive, found {:?}", other),
+        }
+
+        let mut unnamed = le(15, 4);
+        tag(&mut unnamed, b"TYPE", &[2]);
+        unnamed.extend_from_slice(b"ENDT");
+        match MetallibArchive::parse(&metallib(&[unnamed])) {
+            Err(MetallibError::MissingName) => (),
+            other => panic!("expected a missing name, found {:?}", other),
+        }
+    }
+}
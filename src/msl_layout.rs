@@ -0,0 +1,432 @@
+use std::cmp;
+use std::error::Error;
+use std::fmt;
+use std::ops::Range;
+use DataType;
+
+fn align_up(value: usize, alignment: usize) -> usize {
+    (value + alignment - 1) / alignment * alignment
+}
+
+impl DataType {
+    /// Returns the scalar type, the number of columns and the number of rows of the type. Scalars
+    /// have one column and one row, and vectors have one column.
+    fn shape(self) -> Option<(DataType, usize, usize)> {
+        use DataType::*;
+        let shape = match self {
+            None | Struct | Array => return Option::None,
+
+            Float => (Float, 1, 1),
+            Float2 => (Float, 1, 2),
+            Float3 => (Float, 1, 3),
+            Float4 => (Float, 1, 4),
+            Float2x2 => (Float, 2, 2),
+            Float2x3 => (Float, 2, 3),
+            Float2x4 => (Float, 2, 4),
+            Float3x2 => (Float, 3, 2),
+            Float3x3 => (Float, 3, 3),
+            Float3x4 => (Float, 3, 4),
+            Float4x2 => (Float, 4, 2),
+            Float4x3 => (Float, 4, 3),
+            Float4x4 => (Float, 4, 4),
+
+            Half => (Half, 1, 1),
+            Half2 => (Half, 1, 2),
+            Half3 => (Half, 1, 3),
+            Half4 => (Half, 1, 4),
+            Half2x2 => (Half, 2, 2),
+            Half2x3 => (Half, 2, 3),
+            Half2x4 => (Half, 2, 4),
+            Half3x2 => (Half, 3, 2),
+            Half3x3 => (Half, 3, 3),
+            Half3x4 => (Half, 3, 4),
+            Half4x2 => (Half, 4, 2),
+            Half4x3 => (Half, 4, 3),
+            Half4x4 => (Half, 4, 4),
+
+            Int => (Int, 1, 1),
+            Int2 => (Int, 1, 2),
+            Int3 => (Int, 1, 3),
+            Int4 => (Int, 1, 4),
+            UInt => (UInt, 1, 1),
+            UInt2 => (UInt, 1, 2),
+            UInt3 => (UInt, 1, 3),
+            UInt4 => (UInt, 1, 4),
+
+            Short => (Short, 1, 1),
+            Short2 => (Short, 1, 2),
+            Short3 => (Short, 1, 3),
+            Short4 => (Short, 1, 4),
+            UShort => (UShort, 1, 1),
+            UShort2 => (UShort, 1, 2),
+            UShort3 => (UShort, 1, 3),
+            UShort4 => (UShort, 1, 4),
+
+            Char => (Char, 1, 1),
+            Char2 => (Char, 1, 2),
+            Char3 => (Char, 1, 3),
+            Char4 => (Char, 1, 4),
+            UChar => (UChar, 1, 1),
+            UChar2 => (UChar, 1, 2),
+            UChar3 => (UChar, 1, 3),
+            UChar4 => (UChar, 1, 4),
+
+            Bool => (Bool, 1, 1),
+            Bool2 => (Bool, 1, 2),
+            Bool3 => (Bool, 1, 3),
+            Bool4 => (Bool, 1, 4),
+        };
+        Some(shape)
+    }
+
+    /// The scalar type of a scalar, vector or matrix type, or `None` for `None`, `Struct` and
+    /// `Array`.
+    pub fn scalar_type(self) -> Option<DataType> {
+        self.shape().map(|(scalar, _, _)| scalar)
+    }
+
+    /// The number of scalar components in the type. A `float3x2` has 6 components.
+    pub fn component_count(self) -> Option<usize> {
+        self.shape().map(|(_, columns, rows)| columns * rows)
+    }
+
+    /// The number of columns of a matrix type, or `None` if the type is not a matrix.
+    pub fn column_count(self) -> Option<usize> {
+        self.shape().and_then(|(_, columns, _)| if columns > 1 { Some(columns) } else { None })
+    }
+
+    /// The size of the type in bytes, as laid out in Metal Shading Language.
+    ///
+    /// Three component vectors are padded to the size of four component vectors, and matrices
+    /// are stored as arrays of column vectors, so a `float3` is 16 bytes and a `half3x3` is 24.
+    pub fn size(self) -> Option<usize> {
+        self.shape().map(|(scalar, columns, rows)| columns * column_size(scalar, rows))
+    }
+
+    /// The alignment of the type in bytes, as laid out in Metal Shading Language. Vectors are
+    /// aligned to their size, and matrices to the size of a column.
+    pub fn alignment(self) -> Option<usize> {
+        self.shape().map(|(scalar, _, rows)| column_size(scalar, rows))
+    }
+}
+
+/// The size of a vector (or matrix column) with `rows` components of type `scalar`.
+fn column_size(scalar: DataType, rows: usize) -> usize {
+    let scalar_size = match scalar {
+        DataType::Float | DataType::Int | DataType::UInt => 4,
+        DataType::Half | DataType::Short | DataType::UShort => 2,
+        _ => 1,
+    };
+    scalar_size * if rows == 3 { 4 } else { rows }
+}
+
+/// The type of a member of a `StructLayout`.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum MemberType {
+    /// A scalar, vector or matrix type.
+    Data(DataType),
+
+    /// An array with a fixed number of elements.
+    Array(Box<MemberType>, usize),
+
+    /// A nested struct.
+    Struct(StructLayout)
+}
+
+impl MemberType {
+    pub fn array(element_type: MemberType, len: usize) -> Self {
+        MemberType::Array(Box::new(element_type), len)
+    }
+
+    /// The data type which shader reflection reports for the type.
+    pub fn data_type(&self) -> DataType {
+        match *self {
+            MemberType::Data(data_type) => data_type,
+            MemberType::Array(..) => DataType::Array,
+            MemberType::Struct(_) => DataType::Struct,
+        }
+    }
+
+    /// The size of the type in bytes.
+    pub fn size(&self) -> usize {
+        match *self {
+            MemberType::Data(data_type) => data_type.size().unwrap_or(0),
+            MemberType::Array(ref element_type, len) => element_type.stride() * len,
+            MemberType::Struct(ref layout) => layout.size(),
+        }
+    }
+
+    /// The alignment of the type in bytes.
+    pub fn alignment(&self) -> usize {
+        match *self {
+            MemberType::Data(data_type) => data_type.alignment().unwrap_or(1),
+            MemberType::Array(ref element_type, _) => element_type.alignment(),
+            MemberType::Struct(ref layout) => layout.alignment(),
+        }
+    }
+
+    /// The distance in bytes between consecutive elements of an array of the type.
+    pub fn stride(&self) -> usize {
+        align_up(self.size(), self.alignment())
+    }
+
+    fn validate(&self, name: &str) -> Result<(), StructLayoutError> {
+        match *self {
+            MemberType::Data(data_type) if data_type.size().is_none() => {
+                Err(StructLayoutError::UnsizedDataType { member: name.into() })
+            }
+            MemberType::Array(ref element_type, _) => element_type.validate(name),
+            _ => Ok(()),
+        }
+    }
+}
+
+impl From<DataType> for MemberType {
+    fn from(data_type: DataType) -> Self {
+        MemberType::Data(data_type)
+    }
+}
+
+impl From<StructLayout> for MemberType {
+    fn from(layout: StructLayout) -> Self {
+        MemberType::Struct(layout)
+    }
+}
+
+/// A member of a `StructLayout`.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct LayoutMember {
+    name: String,
+    member_type: MemberType,
+    offset: usize
+}
+
+impl LayoutMember {
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn member_type(&self) -> &MemberType {
+        &self.member_type
+    }
+
+    /// The offset of the member from the start of the struct in bytes.
+    pub fn offset(&self) -> usize {
+        self.offset
+    }
+
+    pub fn size(&self) -> usize {
+        self.member_type.size()
+    }
+}
+
+/// Computes the memory layout of a Metal Shading Language struct.
+///
+/// Members are placed in order, each at the next offset which is a multiple of its alignment.
+/// The struct is aligned to its most aligned member, and its size is padded to a multiple of its
+/// alignment. This matches the offsets which the Metal compiler reports through
+/// `StructMember::offset`.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct StructLayout {
+    members: Vec<LayoutMember>,
+    size: usize,
+    alignment: usize
+}
+
+impl StructLayout {
+    /// Lays out a struct with the given member names and types.
+    pub fn new<I, S, T>(members: I) -> Result<Self, StructLayoutError>
+        where I: IntoIterator<Item = (S, T)>,
+              S: Into<String>,
+              T: Into<MemberType> {
+        let mut layout = StructLayout { members: vec![], size: 0, alignment: 1 };
+        for (name, member_type) in members {
+            let (name, member_type) = (name.into(), member_type.into());
+            try!(member_type.validate(&name));
+            if layout.member_by_name(&name).is_some() {
+                return Err(StructLayoutError::DuplicateMember { member: name });
+            }
+
+            let alignment = member_type.alignment();
+            let offset = align_up(layout.size, alignment);
+            layout.size = offset + member_type.size();
+            layout.alignment = cmp::max(layout.alignment, alignment);
+            layout.members.push(LayoutMember {
+                name: name,
+                member_type: member_type,
+                offset: offset
+            });
+        }
+
+        // As in C++, an empty struct still occupies a byte.
+        layout.size = cmp::max(align_up(layout.size, layout.alignment), 1);
+        Ok(layout)
+    }
+
+    pub fn members(&self) -> &[LayoutMember] {
+        &self.members
+    }
+
+    pub fn member_by_name(&self, name: &str) -> Option<&LayoutMember> {
+        self.members.iter().find(|member| member.name == name)
+    }
+
+    /// The size of the struct in bytes, including any padding at the end.
+    pub fn size(&self) -> usize {
+        self.size
+    }
+
+    pub fn alignment(&self) -> usize {
+        self.alignment
+    }
+
+    /// The ranges of bytes which are padding between members and at the end of the struct. The
+    /// padding inside nested structs and three component vectors is not included.
+    pub fn padding(&self) -> Vec<Range<usize>> {
+        let mut padding = vec![];
+        let mut end = 0;
+        for member in &self.members {
+            if member.offset > end {
+                padding.push(end..member.offset);
+            }
+            end = member.offset + member.size();
+        }
+        if self.size > end && !self.members.is_empty() {
+            padding.push(end..self.size);
+        }
+        padding
+    }
+}
+
+/// An error produced when a struct cannot be laid out.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum StructLayoutError {
+    /// A member has the `None`, `Struct` or `Array` data type, which have no size on their own.
+    /// Use `MemberType::Struct` or `MemberType::Array` instead.
+    UnsizedDataType { member: String },
+
+    /// Two members have the same name.
+    DuplicateMember { member: String }
+}
+
+impl fmt::Display for StructLayoutError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            StructLayoutError::UnsizedDataType { ref member } => {
+                write!(f, "member `{}` does not have a sized data type", member)
+            }
+            StructLayoutError::DuplicateMember { ref member } => {
+                write!(f, "member `{}` is declared more than once", member)
+            }
+        }
+    }
+}
+
+impl Error for StructLayoutError {
+    fn description(&self) -> &str {
+        match *self {
+            StructLayoutError::UnsizedDataType { .. } => "A member does not have a sized data type",
+            StructLayoutError::DuplicateMember { .. } => "A member is declared more than once",
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use DataType;
+
+    #[test]
+    fn test_data_type_layout() {
+        let cases = [(DataType::Float, 4, 4, 1),
+                     (DataType::Float2, 8, 8, 2),
+                     (DataType::Float3, 16, 16, 3),
+                     (DataType::Float4, 16, 16, 4),
+                     (DataType::Half3, 8, 8, 3),
+                     (DataType::Char3, 4, 4, 3),
+                     (DataType::Bool, 1, 1, 1),
+                     (DataType::UShort2, 4, 4, 2),
+                     (DataType::Float2x2, 16, 8, 4),
+                     (DataType::Float3x3, 48, 16, 9),
+                     (DataType::Float4x3, 64, 16, 12),
+                     (DataType::Float3x2, 24, 8, 6),
+                     (DataType::Float4x4, 64, 16, 16),
+                     (DataType::Half3x3, 24, 8, 9),
+                     (DataType::Half2x4, 16, 8, 8)];
+        for &(data_type, size, alignment, components) in &cases {
+            assert_eq!(data_type.size(), Some(size));
+            assert_eq!(data_type.alignment(), Some(alignment));
+            assert_eq!(data_type.component_count(), Some(components));
+        }
+
+        assert_eq!(DataType::Half3x3.scalar_type(), Some(DataType::Half));
+        assert_eq!(DataType::UInt4.scalar_type(), Some(DataType::UInt));
+        assert_eq!(DataType::Float4x2.column_count(), Some(4));
+        assert_eq!(DataType::Float4.column_count(), None);
+        for &data_type in &[DataType::None, DataType::Struct, DataType::Array] {
+            assert_eq!(data_type.size(), None);
+            assert_eq!(data_type.alignment(), None);
+            assert_eq!(data_type.component_count(), None);
+            assert_eq!(data_type.scalar_type(), None);
+        }
+    }
+
+    #[test]
+    fn test_struct_layout() {
+        // struct Uniforms { float a; float3 b; half c; float4x4 d; char e[3]; };
+        let layout = StructLayout::new(vec![("a", MemberType::from(DataType::Float)),
+                                            ("b", DataType::Float3.into()),
+                                            ("c", DataType::Half.into()),
+                                            ("d", DataType::Float4x4.into()),
+                                            ("e", MemberType::array(DataType::Char.into(), 3))])
+            .unwrap();
+        let offsets = layout.members().iter().map(|m| m.offset()).collect::<Vec<_>>();
+        assert_eq!(offsets, [0, 16, 32, 48, 112]);
+        assert_eq!(layout.alignment(), 16);
+        assert_eq!(layout.size(), 128);
+        assert_eq!(layout.padding(), [4..16, 34..48, 115..128]);
+        assert_eq!(layout.member_by_name("d").map(|m| m.size()), Some(64));
+    }
+
+    #[test]
+    fn test_nested_struct_and_array_layout() {
+        // struct Light { float3 position; float intensity; };
+        let light = StructLayout::new(vec![("position", DataType::Float3),
+                                           ("intensity", DataType::Float)])
+            .unwrap();
+        assert_eq!(light.size(), 32);
+        assert_eq!(light.padding(), [20..32]);
+
+        // struct Scene { half2 scale; Light lights[2]; float3x3 normals[2]; uchar flag; };
+        let lights = MemberType::array(light.into(), 2);
+        assert_eq!(lights.size(), 64);
+        let scene = StructLayout::new(vec![("scale", MemberType::from(DataType::Half2)),
+                                           ("lights", lights),
+                                           ("normals",
+                                            MemberType::array(DataType::Float3x3.into(), 2)),
+                                           ("flag", DataType::UChar.into())])
+            .unwrap();
+        let offsets = scene.members().iter().map(|m| m.offset()).collect::<Vec<_>>();
+        assert_eq!(offsets, [0, 16, 80, 176]);
+        assert_eq!(scene.size(), 192);
+
+        // A struct of small members is only as aligned as its largest member.
+        let small = StructLayout::new(vec![("a", DataType::UChar), ("b", DataType::Short)])
+            .unwrap();
+        assert_eq!((small.size(), small.alignment()), (4, 2));
+        let empty = StructLayout::new(Vec::<(&str, DataType)>::new()).unwrap();
+        assert_eq!((empty.size(), empty.alignment()), (1, 1));
+        assert!(empty.padding().is_empty());
+    }
+
+    #[test]
+    fn test_struct_layout_errors() {
+        let unsized_member = StructLayout::new(vec![("a", DataType::Float),
+                                                    ("b", DataType::Struct)]);
+        assert_eq!(unsized_member,
+                   Err(StructLayoutError::UnsizedDataType { member: "b".into() }));
+
+        let duplicate = StructLayout::new(vec![("a", DataType::Float), ("a", DataType::Int)]);
+        assert_eq!(duplicate, Err(StructLayoutError::DuplicateMember { member: "a".into() }));
+    }
+}
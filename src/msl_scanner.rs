@@ -0,0 +1,631 @@
+//! A lightweight scanner for the entry points declared in Metal Shading Language source.
+//!
+//! This is not a full MSL parser. Preprocessor directives are skipped rather than evaluated, and
+//! only the declarations of `vertex`, `fragment` and `kernel` functions are examined, so it can
+//! check the interface of a shader without a device or the Metal compiler.
+
+use std::error::Error;
+use std::fmt;
+use FunctionType;
+
+/// An entry point function declared in shader source.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct EntryPoint {
+    pub name: String,
+    pub function_type: FunctionType,
+    pub return_type: String,
+    pub parameters: Vec<EntryPointParameter>,
+
+    /// The line of the source that the declaration starts on, counting from 1.
+    pub line: usize
+}
+
+impl EntryPoint {
+    pub fn parameter_by_name(&self, name: &str) -> Option<&EntryPointParameter> {
+        self.parameters.iter().find(|parameter| parameter.name == name)
+    }
+
+    /// Returns the parameter bound with `binding`, such as `Binding::Buffer(0)`.
+    pub fn parameter_by_binding(&self, binding: Binding) -> Option<&EntryPointParameter> {
+        self.parameters.iter().find(|parameter| parameter.binding.as_ref() == Some(&binding))
+    }
+}
+
+/// A parameter of an entry point function.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct EntryPointParameter {
+    pub name: String,
+
+    /// The address space of the parameter, or `None` for values passed directly.
+    pub address_space: Option<AddressSpace>,
+
+    /// The type of the parameter without its address space, such as `float4*` or
+    /// `texture2d<float, access::read>`.
+    pub type_name: String,
+
+    /// The binding attribute of the parameter, if it has one.
+    pub binding: Option<Binding>
+}
+
+/// The address space of a pointer or reference parameter.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum AddressSpace {
+    Device,
+    Constant,
+    Threadgroup,
+    Thread
+}
+
+impl AddressSpace {
+    fn from_keyword(keyword: &str) -> Option<Self> {
+        match keyword {
+            "device" => Some(AddressSpace::Device),
+            "constant" => Some(AddressSpace::Constant),
+            "threadgroup" => Some(AddressSpace::Threadgroup),
+            "thread" => Some(AddressSpace::Thread),
+            _ => None,
+        }
+    }
+}
+
+/// The attribute which binds a parameter to a resource or to a value provided by the pipeline.
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
+pub enum Binding {
+    /// `[[buffer(n)]]`
+    Buffer(usize),
+
+    /// `[[texture(n)]]`
+    Texture(usize),
+
+    /// `[[sampler(n)]]`
+    Sampler(usize),
+
+    /// `[[stage_in]]`
+    StageIn,
+
+    /// Any other attribute, such as `vertex_id` or `thread_position_in_grid`.
+    Attribute(String)
+}
+
+/// The line and column of a character in shader source, counting from 1.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub struct SourceLocation {
+    pub line: usize,
+    pub column: usize
+}
+
+impl fmt::Display for SourceLocation {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}:{}", self.line, self.column)
+    }
+}
+
+/// An error produced when shader source cannot be scanned.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum MslScanError {
+    /// A block comment is not closed.
+    UnterminatedComment(SourceLocation),
+
+    /// A string or character literal is not closed.
+    UnterminatedLiteral(SourceLocation),
+
+    /// The source ends in the middle of an entry point declaration.
+    UnexpectedEndOfInput,
+
+    /// An entry point declaration could not be understood.
+    MalformedDeclaration(SourceLocation),
+
+    /// The index of a binding attribute is not an integer literal.
+    InvalidBindingIndex { location: SourceLocation, attribute: String }
+}
+
+impl fmt::Display for MslScanError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            MslScanError::UnterminatedComment(location) |
+            MslScanError::UnterminatedLiteral(location) |
+            MslScanError::MalformedDeclaration(location) => {
+                write!(f, "{}: {}", location, self.description())
+            }
+            MslScanError::InvalidBindingIndex { location, ref attribute } => {
+                write!(f, "{}: the index of `{}` is not an integer literal", location, attribute)
+            }
+            MslScanError::UnexpectedEndOfInput => write!(f, "{}", self.description()),
+        }
+    }
+}
+
+impl Error for MslScanError {
+    fn description(&self) -> &str {
+        match *self {
+            MslScanError::UnterminatedComment(_) => "The block comment is not closed",
+            MslScanError::UnterminatedLiteral(_) => "The literal is not closed",
+            MslScanError::UnexpectedEndOfInput => "The source ends inside an entry point",
+            MslScanError::MalformedDeclaration(_) => "The entry point declaration is malformed",
+            MslScanError::InvalidBindingIndex { .. } => {
+                "The binding index is not an integer literal"
+            }
+        }
+    }
+}
+
+/// Finds the entry points declared in `source`, in the order that they are declared.
+pub fn scan_entry_points(source: &str) -> Result<Vec<EntryPoint>, MslScanError> {
+    let tokens = try!(tokenize(source));
+    let mut entry_points = vec![];
+    let mut depth = 0usize;
+    let mut i = 0;
+    while i < tokens.len() {
+        match tokens[i].kind {
+            TokenKind::Punct('{') => depth += 1,
+            TokenKind::Punct('}') => depth = depth.saturating_sub(1),
+            _ if depth == 0 => {
+                if let Some((function_type, next)) = entry_point_keyword(&tokens, i) {
+                    let (entry_point, end) = try!(parse_entry_point(&tokens, next, function_type,
+                                                                    tokens[i].location));
+                    entry_points.push(entry_point);
+                    i = end;
+                    continue;
+                }
+            }
+            _ => (),
+        }
+        i += 1;
+    }
+    Ok(entry_points)
+}
+
+#[derive(Clone, Debug, PartialEq)]
+enum TokenKind {
+    Ident(String),
+    Literal(String),
+    Punct(char)
+}
+
+#[derive(Clone, Debug)]
+struct Token {
+    kind: TokenKind,
+    location: SourceLocation
+}
+
+impl Token {
+    fn is_punct(&self, c: char) -> bool {
+        self.kind == TokenKind::Punct(c)
+    }
+
+    fn ident(&self) -> Option<&str> {
+        match self.kind {
+            TokenKind::Ident(ref ident) => Some(ident),
+            _ => None,
+        }
+    }
+
+    fn text(&self) -> String {
+        match self.kind {
+            TokenKind::Ident(ref text) | TokenKind::Literal(ref text) => text.clone(),
+            TokenKind::Punct(c) => c.to_string(),
+        }
+    }
+}
+
+fn tokenize(source: &str) -> Result<Vec<Token>, MslScanError> {
+    let chars = source.chars().collect::<Vec<_>>();
+    let mut tokens = vec![];
+    let (mut i, mut line, mut line_start) = (0, 1, 0);
+    // Whether only whitespace has been seen since the start of the line, so that a `#` starts a
+    // preprocessor directive.
+    let mut at_line_start = true;
+
+    while i < chars.len() {
+        let location = SourceLocation { line: line, column: i - line_start + 1 };
+        let c = chars[i];
+        let next = chars.get(i + 1).cloned();
+
+        if c == '\n' {
+            i += 1;
+            line += 1;
+            line_start = i;
+            at_line_start = true;
+        } else if c.is_whitespace() {
+            i += 1;
+        } else if c == '/' && next == Some('/') {
+            while i < chars.len() && chars[i] != '\n' {
+                i += 1;
+            }
+        } else if c == '/' && next == Some('*') {
+            i += 2;
+            loop {
+                if i + 1 >= chars.len() {
+                    return Err(MslScanError::UnterminatedComment(location));
+                }
+                if chars[i] == '*' && chars[i + 1] == '/' {
+                    i += 2;
+                    break;
+                }
+                if chars[i] == '\n' {
+                    line += 1;
+                    line_start = i + 1;
+                }
+                i += 1;
+            }
+        } else if c == '#' && at_line_start {
+            // Skip the directive, including any lines continued with a backslash.
+            while i < chars.len() && chars[i] != '\n' {
+                if chars[i] == '\\' && chars.get(i + 1) == Some(&'\n') {
+                    i += 1;
+                    line += 1;
+                    line_start = i + 1;
+                }
+                i += 1;
+            }
+        } else if c == '"' || c == '\'' {
+            let start = i;
+            i += 1;
+            while i < chars.len() && chars[i] != c && chars[i] != '\n' {
+                i += if chars[i] == '\\' { 2 } else { 1 };
+            }
+            if i >= chars.len() || chars[i] != c {
+                return Err(MslScanError::UnterminatedLiteral(location));
+            }
+            i += 1;
+            let text = chars[start..i].iter().cloned().collect();
+            tokens.push(Token { kind: TokenKind::Literal(text), location: location });
+        } else if c.is_alphanumeric() || c == '_' {
+            let start = i;
+            while i < chars.len() && (chars[i].is_alphanumeric() || chars[i] == '_' ||
+                                      (c.is_digit(10) && chars[i] == '.')) {
+                i += 1;
+            }
+            let text = chars[start..i].iter().cloned().collect::<String>();
+            let kind = if c.is_digit(10) {
+                TokenKind::Literal(text)
+            } else {
+                TokenKind::Ident(text)
+            };
+            tokens.push(Token { kind: kind, location: location });
+        } else {
+            i += 1;
+            tokens.push(Token { kind: TokenKind::Punct(c), location: location });
+        }
+
+        if c != '\n' && !c.is_whitespace() {
+            at_line_start = false;
+        }
+    }
+    Ok(tokens)
+}
+
+/// If the tokens at `i` introduce an entry point, either with a `vertex`, `fragment` or `kernel`
+/// qualifier or with the equivalent `[[vertex]]` style attribute, returns its function type and
+/// the index of the token after the qualifier.
+fn entry_point_keyword(tokens: &[Token], i: usize) -> Option<(FunctionType, usize)> {
+    let function_type = |ident: Option<&str>| {
+        match ident {
+            Some("vertex") => Some(FunctionType::Vertex),
+            Some("fragment") => Some(FunctionType::Fragment),
+            Some("kernel") => Some(FunctionType::Kernel),
+            _ => None,
+        }
+    };
+
+    if let Some(function_type) = function_type(tokens[i].ident()) {
+        return Some((function_type, i + 1));
+    }
+
+    let is_attribute = tokens.len() > i + 4 && tokens[i].is_punct('[') &&
+                       tokens[i + 1].is_punct('[') && tokens[i + 3].is_punct(']') &&
+                       tokens[i + 4].is_punct(']');
+    if is_attribute {
+        function_type(tokens[i + 2].ident()).map(|function_type| (function_type, i + 5))
+    } else {
+        None
+    }
+}
+
+/// Parses an entry point declaration from the token after its qualifier, returning the entry
+/// point and the index of the token after its parameter list.
+fn parse_entry_point(tokens: &[Token], start: usize, function_type: FunctionType,
+                     location: SourceLocation)
+                     -> Result<(EntryPoint, usize), MslScanError> {
+    let open = match tokens[start..].iter().position(|token| token.is_punct('(')) {
+        Some(open) => start + open,
+        None => return Err(MslScanError::UnexpectedEndOfInput),
+    };
+    let name = match tokens[open - 1].ident() {
+        Some(name) if open - 1 > start => name.to_string(),
+        _ => return Err(MslScanError::MalformedDeclaration(location)),
+    };
+    let return_type = join_tokens(&tokens[start..open - 1]);
+
+    let mut parameters = vec![];
+    let mut depth = 0;
+    let mut parameter_start = open + 1;
+    for i in (open + 1)..tokens.len() {
+        match tokens[i].kind {
+            TokenKind::Punct('(') | TokenKind::Punct('[') | TokenKind::Punct('<') => depth += 1,
+            TokenKind::Punct(')') if depth == 0 => {
+                if let Some(parameter) = try!(parse_parameter(&tokens[parameter_start..i],
+                                                              location)) {
+                    parameters.push(parameter);
+                }
+                let entry_point = EntryPoint {
+                    name: name,
+                    function_type: function_type,
+                    return_type: return_type,
+                    parameters: parameters,
+                    line: location.line
+                };
+                return Ok((entry_point, i + 1));
+            }
+            TokenKind::Punct(')') | TokenKind::Punct(']') | TokenKind::Punct('>') => depth -= 1,
+            TokenKind::Punct(',') if depth == 0 => {
+                match try!(parse_parameter(&tokens[parameter_start..i], location)) {
+                    Some(parameter) => parameters.push(parameter),
+                    None => return Err(MslScanError::MalformedDeclaration(tokens[i].location)),
+                }
+                parameter_start = i + 1;
+            }
+            _ => (),
+        }
+    }
+    Err(MslScanError::UnexpectedEndOfInput)
+}
+
+/// Parses a single parameter, returning `None` for an empty or `void` parameter list.
+fn parse_parameter(tokens: &[Token], location: SourceLocation)
+                   -> Result<Option<EntryPointParameter>, MslScanError> {
+    // Separate the `[[...]]` attributes from the declaration.
+    let mut declaration = vec![];
+    let mut attributes = vec![];
+    let mut i = 0;
+    while i < tokens.len() {
+        if tokens[i].is_punct('[') && tokens.get(i + 1).map_or(false, |t| t.is_punct('[')) {
+            let end = match (i + 2..tokens.len()).find(|&j| {
+                tokens[j].is_punct(']') && tokens.get(j + 1).map_or(false, |t| t.is_punct(']'))
+            }) {
+                Some(end) => end,
+                None => return Err(MslScanError::MalformedDeclaration(tokens[i].location)),
+            };
+            attributes.push(&tokens[i + 2..end]);
+            i = end + 2;
+        } else {
+            declaration.push(&tokens[i]);
+            i += 1;
+        }
+    }
+
+    let is_void = declaration.len() == 1 && declaration[0].ident() == Some("void");
+    if declaration.is_empty() || is_void {
+        return Ok(None);
+    }
+
+    // Remove any array extent following the name.
+    if declaration.last().map_or(false, |token| token.is_punct(']')) {
+        match declaration.iter().rposition(|token| token.is_punct('[')) {
+            Some(open) => declaration.truncate(open),
+            None => return Err(MslScanError::MalformedDeclaration(location)),
+        }
+    }
+
+    let name = match declaration.pop().and_then(|token| token.ident()) {
+        Some(name) if !declaration.is_empty() => name.to_string(),
+        _ => return Err(MslScanError::MalformedDeclaration(location)),
+    };
+
+    let mut address_space = None;
+    let mut type_tokens = vec![];
+    for token in declaration {
+        match token.ident().and_then(AddressSpace::from_keyword) {
+            Some(space) if address_space.is_none() => address_space = Some(space),
+            _ => type_tokens.push(token.clone()),
+        }
+    }
+
+    let mut binding = None;
+    for attribute in attributes {
+        if let Some(parsed) = try!(parse_binding(attribute)) {
+            binding = binding.or(Some(parsed));
+        }
+    }
+
+    Ok(Some(EntryPointParameter {
+        name: name,
+        address_space: address_space,
+        type_name: join_tokens(&type_tokens),
+        binding: binding
+    }))
+}
+
+fn parse_binding(attribute: &[Token]) -> Result<Option<Binding>, MslScanError> {
+    let name = match attribute.first().and_then(Token::ident) {
+        Some(name) => name,
+        None => return Ok(None),
+    };
+
+    let index = || {
+        let index = match attribute.get(2) {
+            Some(&Token { kind: TokenKind::Literal(ref index), .. }) => index.parse().ok(),
+            _ => None,
+        };
+        let is_call = attribute.len() == 4 && attribute[1].is_punct('(') &&
+                      attribute[3].is_punct(')');
+        match index {
+            Some(index) if is_call => Ok(index),
+            _ => {
+                Err(MslScanError::InvalidBindingIndex {
+                    location: attribute[0].location,
+                    attribute: join_tokens(attribute)
+                })
+            }
+        }
+    };
+
+    let binding = match name {
+        "buffer" => Binding::Buffer(try!(index())),
+        "texture" => Binding::Texture(try!(index())),
+        "sampler" => Binding::Sampler(try!(index())),
+        "stage_in" => Binding::StageIn,
+        _ => Binding::Attribute(join_tokens(attribute)),
+    };
+    Ok(Some(binding))
+}
+
+/// Joins tokens into normalized source text, with spaces only between adjacent words and after
+/// commas.
+fn join_tokens(tokens: &[Token]) -> String {
+    let mut text = String::new();
+    let mut previous: Option<&Token> = None;
+    for token in tokens {
+        let needs_space = match (previous.map(|token| &token.kind), &token.kind) {
+            (Some(&TokenKind::Punct(',')), _) => true,
+            (Some(&TokenKind::Punct(_)), _) | (_, &TokenKind::Punct(_)) | (None, _) => false,
+            _ => true,
+        };
+        if needs_space {
+            text.push(' ');
+        }
+        text.push_str(&token.text());
+        previous = Some(token);
+    }
+    text
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use FunctionType;
+
+    // The shader used by the integration tests.
+    const SHADER: &'static str = r"
+using namespace metal;
+
+struct ColoredVertex
+{
+    float4 position [[position]];
+    float4 color;
+};
+
+vertex ColoredVertex vertex_main(constant float4 *position [[buffer(0)]],
+                                 constant float4 *color [[buffer(1)]],
+                                 uint vid [[vertex_id]])
+{
+    ColoredVertex vert;
+    vert.position = position[vid];
+    vert.color = color[vid];
+    return vert;
+}
+
+fragment float4 fragment_main(ColoredVertex vert [[stage_in]])
+{
+    return vert.color;
+}
+";
+
+    fn parameter(name: &str, address_space: Option<AddressSpace>, type_name: &str,
+                 binding: Option<Binding>)
+                 -> EntryPointParameter {
+        EntryPointParameter {
+            name: name.into(),
+            address_space: address_space,
+            type_name: type_name.into(),
+            binding: binding
+        }
+    }
+
+    #[test]
+    fn test_scan_entry_points() {
+        let entry_points = scan_entry_points(SHADER).unwrap();
+        assert_eq!(entry_points.len(), 2);
+
+        let vertex = &entry_points[0];
+        assert_eq!(vertex.name, "vertex_main");
+        assert_eq!(vertex.function_type, FunctionType::Vertex);
+        assert_eq!(vertex.return_type, "ColoredVertex");
+        assert_eq!(vertex.line, 10);
+        assert_eq!(vertex.parameters,
+                   [parameter("position", Some(AddressSpace::Constant), "float4*",
+                              Some(Binding::Buffer(0))),
+                    parameter("color", Some(AddressSpace::Constant), "float4*",
+                              Some(Binding::Buffer(1))),
+                    parameter("vid", None, "uint", Some(Binding::Attribute("vertex_id".into())))]);
+        assert_eq!(vertex.parameter_by_binding(Binding::Buffer(1)).map(|p| &p.name[..]),
+                   Some("color"));
+
+        let fragment = &entry_points[1];
+        assert_eq!(fragment.name, "fragment_main");
+        assert_eq!(fragment.function_type, FunctionType::Fragment);
+        assert_eq!(fragment.return_type, "float4");
+        assert_eq!(fragment.parameters,
+                   [parameter("vert", None, "ColoredVertex", Some(Binding::StageIn))]);
+    }
+
+    #[test]
+    fn test_scan_kernels_and_textures() {
+        let source = r#"
+#include <metal_stdlib>
+#define THREADS \
+    64
+using namespace metal;
+
+/* kernel void commented_out(device float *data [[buffer(0)]]) {} */
+constant float scale = 2.0f; // kernel void also_commented_out()
+
+[[kernel]] void blur(texture2d<float, access::read> input [[texture(0)]],
+                     texture2d<float, access::write> output [[ texture(1) ]],
+                     sampler linear [[sampler(3)]],
+                     const device Params &params [[buffer(2)]],
+                     threadgroup float4 *cache [[threadgroup(0)]],
+                     uint2 gid [[thread_position_in_grid]])
+{
+    if (gid.x > 0) { output.write(input.read(gid) * scale, gid); }
+}
+
+kernel void empty(void) {}
+fragment half4 shade(float4 color [[stage_in]], constant float weights[4] [[buffer(1)]]);
+"#;
+        let entry_points = scan_entry_points(source).unwrap();
+        let names = entry_points.iter().map(|e| &e.name[..]).collect::<Vec<_>>();
+        assert_eq!(names, ["blur", "empty", "shade"]);
+
+        let blur = &entry_points[0];
+        assert_eq!(blur.function_type, FunctionType::Kernel);
+        assert_eq!(blur.return_type, "void");
+        assert_eq!(blur.line, 10);
+        assert_eq!(blur.parameters,
+                   [parameter("input", None, "texture2d<float, access::read>",
+                              Some(Binding::Texture(0))),
+                    parameter("output", None, "texture2d<float, access::write>",
+                              Some(Binding::Texture(1))),
+                    parameter("linear", None, "sampler", Some(Binding::Sampler(3))),
+                    parameter("params", Some(AddressSpace::Device), "const Params&",
+                              Some(Binding::Buffer(2))),
+                    parameter("cache", Some(AddressSpace::Threadgroup), "float4*",
+                              Some(Binding::Attribute("threadgroup(0)".into()))),
+                    parameter("gid", None, "uint2",
+                              Some(Binding::Attribute("thread_position_in_grid".into())))]);
+
+        assert!(entry_points[1].parameters.is_empty());
+        assert_eq!(entry_points[2].function_type, FunctionType::Fragment);
+        assert_eq!(entry_points[2].parameters[1],
+                   parameter("weights", Some(AddressSpace::Constant), "float",
+                             Some(Binding::Buffer(1))));
+    }
+
+    #[test]
+    fn test_scan_errors() {
+        let location = |line, column| SourceLocation { line: line, column: column };
+        assert_eq!(scan_entry_points("kernel void f() {}\n  /* unterminated"),
+                   Err(MslScanError::UnterminatedComment(location(2, 3))));
+        assert_eq!(scan_entry_points("constant char *s = \"abc;"),
+                   Err(MslScanError::UnterminatedLiteral(location(1, 20))));
+        assert_eq!(scan_entry_points("vertex float4 main(uint vid"),
+                   Err(MslScanError::UnexpectedEndOfInput));
+        assert_eq!(scan_entry_points("fragment (float4 color)"),
+                   Err(MslScanError::MalformedDeclaration(location(1, 1))));
+        assert_eq!(scan_entry_points("vertex float4 f(device float *p [[buffer(INDEX)]])"),
+                   Err(MslScanError::InvalidBindingIndex {
+                       location: location(1, 35),
+                       attribute: "buffer(INDEX)".into()
+                   }));
+    }
+}
@@ -0,0 +1,365 @@
+use PixelFormat;
+
+/// The numeric interpretation of the components stored in a pixel format.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum ComponentType {
+    /// Unsigned normalized integers, which map to floats in the range `[0.0, 1.0]`.
+    Unorm,
+
+    /// Signed normalized integers, which map to floats in the range `[-1.0, 1.0]`.
+    Snorm,
+
+    /// Unsigned integers.
+    Uint,
+
+    /// Signed integers.
+    Sint,
+
+    /// Floating point values, including the packed and block-compressed float formats.
+    Float
+}
+
+/// Describes the memory layout of a `PixelFormat`.
+///
+/// Uncompressed formats are described as blocks of 1x1 texels. Block-compressed formats, and the
+/// subsampled 4:2:2 video formats, store a block of several texels in `bytes_per_block` bytes.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub struct PixelFormatInfo {
+    /// The number of bytes used to store a single block.
+    pub bytes_per_block: usize,
+
+    /// The width of a block, in texels.
+    pub block_width: usize,
+
+    /// The height of a block, in texels.
+    pub block_height: usize,
+
+    /// The number of components (channels) stored in the format.
+    pub component_count: usize,
+
+    /// How the components of the format are interpreted.
+    pub component_type: ComponentType,
+
+    /// The format stores color data in the sRGB color space.
+    pub is_srgb: bool,
+
+    /// The format has a depth component.
+    pub is_depth: bool,
+
+    /// The format has a stencil component.
+    pub is_stencil: bool,
+
+    /// The format is block-compressed.
+    pub is_compressed: bool
+}
+
+impl PixelFormatInfo {
+    fn uncompressed(bytes: usize, components: usize, component_type: ComponentType) -> Self {
+        PixelFormatInfo {
+            bytes_per_block: bytes,
+            block_width: 1,
+            block_height: 1,
+            component_count: components,
+            component_type: component_type,
+            is_srgb: false,
+            is_depth: false,
+            is_stencil: false,
+            is_compressed: false
+        }
+    }
+
+    fn compressed(bytes: usize, components: usize, component_type: ComponentType) -> Self {
+        PixelFormatInfo {
+            block_width: 4,
+            block_height: 4,
+            is_compressed: true,
+            ..PixelFormatInfo::uncompressed(bytes, components, component_type)
+        }
+    }
+
+    fn srgb(self) -> Self {
+        PixelFormatInfo { is_srgb: true, ..self }
+    }
+
+    fn depth(self) -> Self {
+        PixelFormatInfo { is_depth: true, ..self }
+    }
+
+    fn stencil(self) -> Self {
+        PixelFormatInfo { is_stencil: true, ..self }
+    }
+
+    /// The number of blocks needed to cover `width` texels.
+    pub fn blocks_wide(&self, width: usize) -> usize {
+        (width + self.block_width - 1) / self.block_width
+    }
+
+    /// The number of blocks needed to cover `height` texels.
+    pub fn blocks_high(&self, height: usize) -> usize {
+        (height + self.block_height - 1) / self.block_height
+    }
+
+    /// The minimum number of bytes needed to store a row of blocks covering `width` texels.
+    pub fn bytes_per_row(&self, width: usize) -> usize {
+        self.blocks_wide(width) * self.bytes_per_block
+    }
+
+    /// The minimum number of bytes needed to store an image of `width` x `height` texels.
+    pub fn bytes_per_image(&self, width: usize, height: usize) -> usize {
+        self.bytes_per_row(width) * self.blocks_high(height)
+    }
+}
+
+impl PixelFormat {
+    /// Returns the layout information for the format, or `None` for `PixelFormat::Invalid`.
+    pub fn info(self) -> Option<PixelFormatInfo> {
+        use self::ComponentType::{Float, Sint, Snorm, Uint, Unorm};
+        let uncompressed = PixelFormatInfo::uncompressed;
+        let compressed = PixelFormatInfo::compressed;
+
+        let info = match self {
+            PixelFormat::Invalid => return None,
+
+            PixelFormat::A8Unorm => uncompressed(1, 1, Unorm),
+            PixelFormat::R8Unorm => uncompressed(1, 1, Unorm),
+            PixelFormat::R8Snorm => uncompressed(1, 1, Snorm),
+            PixelFormat::R8Uint => uncompressed(1, 1, Uint),
+            PixelFormat::R8Sint => uncompressed(1, 1, Sint),
+
+            PixelFormat::R16Unorm => uncompressed(2, 1, Unorm),
+            PixelFormat::R16Snorm => uncompressed(2, 1, Snorm),
+            PixelFormat::R16Uint => uncompressed(2, 1, Uint),
+            PixelFormat::R16Sint => uncompressed(2, 1, Sint),
+            PixelFormat::R16Float => uncompressed(2, 1, Float),
+            PixelFormat::Rg8Unorm => uncompressed(2, 2, Unorm),
+            PixelFormat::Rg8Snorm => uncompressed(2, 2, Snorm),
+            PixelFormat::Rg8Uint => uncompressed(2, 2, Uint),
+            PixelFormat::Rg8Sint => uncompressed(2, 2, Sint),
+
+            PixelFormat::R32Uint => uncompressed(4, 1, Uint),
+            PixelFormat::R32Sint => uncompressed(4, 1, Sint),
+            PixelFormat::R32Float => uncompressed(4, 1, Float),
+            PixelFormat::Rg16Unorm => uncompressed(4, 2, Unorm),
+            PixelFormat::Rg16Snorm => uncompressed(4, 2, Snorm),
+            PixelFormat::Rg16Uint => uncompressed(4, 2, Uint),
+            PixelFormat::Rg16Sint => uncompressed(4, 2, Sint),
+            PixelFormat::Rg16Float => uncompressed(4, 2, Float),
+            PixelFormat::Rgba8Unorm => uncompressed(4, 4, Unorm),
+            PixelFormat::Rgba8UnormSrgb => uncompressed(4, 4, Unorm).srgb(),
+            PixelFormat::Rgba8Snorm => uncompressed(4, 4, Snorm),
+            PixelFormat::Rgba8Uint => uncompressed(4, 4, Uint),
+            PixelFormat::Rgba8Sint => uncompressed(4, 4, Sint),
+            PixelFormat::Bgra8Unorm => uncompressed(4, 4, Unorm),
+            PixelFormat::Bgra8UnormSrgb => uncompressed(4, 4, Unorm).srgb(),
+            PixelFormat::Rgb10A2Unorm => uncompressed(4, 4, Unorm),
+            PixelFormat::Rgb10A2Uint => uncompressed(4, 4, Uint),
+            PixelFormat::Rg11B10Float => uncompressed(4, 3, Float),
+            PixelFormat::Rgb9E5Float => uncompressed(4, 3, Float),
+
+            PixelFormat::Rg32Uint => uncompressed(8, 2, Uint),
+            PixelFormat::Rg32Sint => uncompressed(8, 2, Sint),
+            PixelFormat::Rg32Float => uncompressed(8, 2, Float),
+            PixelFormat::Rgba16Unorm => uncompressed(8, 4, Unorm),
+            PixelFormat::Rgba16Snorm => uncompressed(8, 4, Snorm),
+            PixelFormat::Rgba16Uint => uncompressed(8, 4, Uint),
+            PixelFormat::Rgba16Sint => uncompressed(8, 4, Sint),
+            PixelFormat::Rgba16Float => uncompressed(8, 4, Float),
+
+            PixelFormat::Rgba32Uint => uncompressed(16, 4, Uint),
+            PixelFormat::Rgba32Sint => uncompressed(16, 4, Sint),
+            PixelFormat::Rgba32Float => uncompressed(16, 4, Float),
+
+            PixelFormat::Bc1Rgba => compressed(8, 4, Unorm),
+            PixelFormat::Bc1RgbaSrgb => compressed(8, 4, Unorm).srgb(),
+            PixelFormat::Bc2Rgba => compressed(16, 4, Unorm),
+            PixelFormat::Bc2RgbaSrgb => compressed(16, 4, Unorm).srgb(),
+            PixelFormat::Bc3Rgba => compressed(16, 4, Unorm),
+            PixelFormat::Bc3RgbaSrgb => compressed(16, 4, Unorm).srgb(),
+            PixelFormat::Bc4RUnorm => compressed(8, 1, Unorm),
+            PixelFormat::Bc4RSnorm => compressed(8, 1, Snorm),
+            PixelFormat::Bc5RgUnorm => compressed(16, 2, Unorm),
+            PixelFormat::Bc5RgSnorm => compressed(16, 2, Snorm),
+            PixelFormat::Bc6HRgbFloat => compressed(16, 3, Float),
+            PixelFormat::Bc6HRgbUfloat => compressed(16, 3, Float),
+            PixelFormat::Bc7RgbaUnorm => compressed(16, 4, Unorm),
+            PixelFormat::Bc7RgbaUnormSrgb => compressed(16, 4, Unorm).srgb(),
+
+            // Each 32-bit block stores two texels which share their chroma samples.
+            PixelFormat::Gbgr422 | PixelFormat::Bgrg422 => {
+                PixelFormatInfo { block_width: 2, ..uncompressed(4, 3, Unorm) }
+            }
+
+            PixelFormat::Depth32Float => uncompressed(4, 1, Float).depth(),
+            PixelFormat::Stencil8 => uncompressed(1, 1, Uint).stencil(),
+            PixelFormat::Depth24UnormStencil8 => uncompressed(4, 2, Unorm).depth().stencil(),
+            // The stencil value is stored in the low byte of a padded 32-bit word.
+            PixelFormat::Depth32FloatStencil8 => uncompressed(8, 2, Float).depth().stencil(),
+        };
+
+        Some(info)
+    }
+
+    /// Returns `true` if the format stores color data in the sRGB color space.
+    pub fn is_srgb(self) -> bool {
+        self.info().map_or(false, |info| info.is_srgb)
+    }
+
+    /// Returns `true` if the format has a depth component.
+    pub fn is_depth(self) -> bool {
+        self.info().map_or(false, |info| info.is_depth)
+    }
+
+    /// Returns `true` if the format has a stencil component.
+    pub fn is_stencil(self) -> bool {
+        self.info().map_or(false, |info| info.is_stencil)
+    }
+
+    /// Returns `true` if the format is block-compressed.
+    pub fn is_compressed(self) -> bool {
+        self.info().map_or(false, |info| info.is_compressed)
+    }
+
+    /// Returns the sRGB counterpart of this format. If the format is already sRGB it is returned
+    /// unchanged, and if it has no sRGB counterpart `None` is returned.
+    pub fn srgb(self) -> Option<PixelFormat> {
+        match self {
+            PixelFormat::Rgba8Unorm | PixelFormat::Rgba8UnormSrgb => {
+                Some(PixelFormat::Rgba8UnormSrgb)
+            }
+            PixelFormat::Bgra8Unorm | PixelFormat::Bgra8UnormSrgb => {
+                Some(PixelFormat::Bgra8UnormSrgb)
+            }
+            PixelFormat::Bc1Rgba | PixelFormat::Bc1RgbaSrgb => Some(PixelFormat::Bc1RgbaSrgb),
+            PixelFormat::Bc2Rgba | PixelFormat::Bc2RgbaSrgb => Some(PixelFormat::Bc2RgbaSrgb),
+            PixelFormat::Bc3Rgba | PixelFormat::Bc3RgbaSrgb => Some(PixelFormat::Bc3RgbaSrgb),
+            PixelFormat::Bc7RgbaUnorm | PixelFormat::Bc7RgbaUnormSrgb => {
+                Some(PixelFormat::Bc7RgbaUnormSrgb)
+            }
+            _ => None,
+        }
+    }
+
+    /// Returns the linear (non-sRGB) counterpart of this format. Formats which are not sRGB are
+    /// returned unchanged.
+    pub fn linear(self) -> PixelFormat {
+        match self {
+            PixelFormat::Rgba8UnormSrgb => PixelFormat::Rgba8Unorm,
+            PixelFormat::Bgra8UnormSrgb => PixelFormat::Bgra8Unorm,
+            PixelFormat::Bc1RgbaSrgb => PixelFormat::Bc1Rgba,
+            PixelFormat::Bc2RgbaSrgb => PixelFormat::Bc2Rgba,
+            PixelFormat::Bc3RgbaSrgb => PixelFormat::Bc3Rgba,
+            PixelFormat::Bc7RgbaUnormSrgb => PixelFormat::Bc7RgbaUnorm,
+            other => other,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use PixelFormat;
+    use PixelFormat::*;
+
+    const ALL_FORMATS: &'static [PixelFormat] = &[
+        Invalid, A8Unorm, R8Unorm, R8Snorm, R8Uint, R8Sint, R16Unorm, R16Snorm, R16Uint, R16Sint,
+        R16Float, Rg8Unorm, Rg8Snorm, Rg8Uint, Rg8Sint, R32Uint, R32Sint, R32Float, Rg16Unorm,
+        Rg16Snorm, Rg16Uint, Rg16Sint, Rg16Float, Rgba8Unorm, Rgba8UnormSrgb, Rgba8Snorm,
+        Rgba8Uint, Rgba8Sint, Bgra8Unorm, Bgra8UnormSrgb, Rgb10A2Unorm, Rgb10A2Uint, Rg11B10Float,
+        Rgb9E5Float, Rg32Uint, Rg32Sint, Rg32Float, Rgba16Unorm, Rgba16Snorm, Rgba16Uint,
+        Rgba16Sint, Rgba16Float, Rgba32Uint, Rgba32Sint, Rgba32Float, Bc1Rgba, Bc1RgbaSrgb,
+        Bc2Rgba, Bc2RgbaSrgb, Bc3Rgba, Bc3RgbaSrgb, Bc4RUnorm, Bc4RSnorm, Bc5RgUnorm, Bc5RgSnorm,
+        Bc6HRgbFloat, Bc6HRgbUfloat, Bc7RgbaUnorm, Bc7RgbaUnormSrgb, Gbgr422, Bgrg422,
+        Depth32Float, Stencil8, Depth24UnormStencil8, Depth32FloatStencil8
+    ];
+
+    #[test]
+    fn test_bytes_per_block_table() {
+        let table = [(R8Unorm, 1, 1, 1), (Rg8Unorm, 2, 1, 1), (R16Float, 2, 1, 1),
+                     (Rgba8UnormSrgb, 4, 1, 1), (Rgb9E5Float, 4, 1, 1), (Rg32Float, 8, 1, 1),
+                     (Rgba16Float, 8, 1, 1), (Rgba32Float, 16, 1, 1), (Bc1Rgba, 8, 4, 4),
+                     (Bc4RSnorm, 8, 4, 4), (Bc3Rgba, 16, 4, 4), (Bc6HRgbUfloat, 16, 4, 4),
+                     (Gbgr422, 4, 2, 1), (Stencil8, 1, 1, 1), (Depth32FloatStencil8, 8, 1, 1)];
+
+        for &(format, bytes, width, height) in table.iter() {
+            let info = format.info().unwrap();
+            assert_eq!((info.bytes_per_block, info.block_width, info.block_height),
+                       (bytes, width, height),
+                       "{:?}",
+                       format);
+        }
+    }
+
+    #[test]
+    fn test_every_format_is_consistent() {
+        for &format in ALL_FORMATS {
+            let info = match format.info() {
+                Some(info) => info,
+                None => {
+                    assert_eq!(format, Invalid);
+                    continue;
+                }
+            };
+            assert!(info.bytes_per_block > 0, "{:?}", format);
+            assert!(info.component_count > 0 && info.component_count <= 4, "{:?}", format);
+            assert_eq!(info.is_compressed,
+                       info.block_width == 4 && info.block_height == 4,
+                       "{:?}",
+                       format);
+            assert_eq!(info.is_srgb, format.srgb() == Some(format), "{:?}", format);
+            assert_eq!(format.linear().is_srgb(), false, "{:?}", format);
+            if let Some(srgb) = format.srgb() {
+                assert_eq!(srgb.linear(), format.linear(), "{:?}", format);
+                assert_eq!(srgb.info().unwrap().bytes_per_block, info.bytes_per_block);
+            }
+        }
+    }
+
+    #[test]
+    fn test_depth_stencil_classification() {
+        for &format in ALL_FORMATS {
+            let is_depth = match format {
+                Depth32Float | Depth24UnormStencil8 | Depth32FloatStencil8 => true,
+                _ => false,
+            };
+            let is_stencil = match format {
+                Stencil8 | Depth24UnormStencil8 | Depth32FloatStencil8 => true,
+                _ => false,
+            };
+            assert_eq!(format.is_depth(), is_depth, "{:?}", format);
+            assert_eq!(format.is_stencil(), is_stencil, "{:?}", format);
+        }
+    }
+
+    #[test]
+    fn test_component_types() {
+        assert_eq!(R8Snorm.info().unwrap().component_type, ComponentType::Snorm);
+        assert_eq!(Rgba16Sint.info().unwrap().component_type, ComponentType::Sint);
+        assert_eq!(Rgb10A2Uint.info().unwrap().component_type, ComponentType::Uint);
+        assert_eq!(Rg11B10Float.info().unwrap().component_type, ComponentType::Float);
+        assert_eq!(Bc5RgUnorm.info().unwrap().component_count, 2);
+        assert_eq!(A8Unorm.info().unwrap().component_count, 1);
+    }
+
+    #[test]
+    fn test_bytes_per_row_rounds_up_to_blocks() {
+        let bc1 = Bc1Rgba.info().unwrap();
+        assert_eq!(bc1.bytes_per_row(10), 24);
+        assert_eq!(bc1.bytes_per_image(10, 5), 48);
+
+        let rgba = Rgba8Unorm.info().unwrap();
+        assert_eq!(rgba.bytes_per_row(10), 40);
+        assert_eq!(rgba.bytes_per_image(10, 5), 200);
+
+        let yuv = Bgrg422.info().unwrap();
+        assert_eq!(yuv.bytes_per_row(5), 12);
+    }
+
+    #[test]
+    fn test_srgb_linear_counterparts() {
+        assert_eq!(Rgba8Unorm.srgb(), Some(Rgba8UnormSrgb));
+        assert_eq!(Bgra8UnormSrgb.linear(), Bgra8Unorm);
+        assert_eq!(Bc7RgbaUnorm.srgb(), Some(Bc7RgbaUnormSrgb));
+        assert_eq!(Rgba16Float.srgb(), None);
+        assert_eq!(Rgba16Float.linear(), Rgba16Float);
+    }
+}
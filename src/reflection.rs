@@ -0,0 +1,246 @@
+//! Owned copies of shader reflection objects.
+//!
+//! The reflection wrappers such as `StructType` refer to Objective-C objects, so they can only be
+//! inspected while the reflection data is alive and on a system with Metal. These types hold the
+//! same information as plain data, so that it can be kept, compared and built by hand in tests.
+//!
+//! With the `serialize` feature, the types implement serde's `Serialize` and `Deserialize`, so
+//! that reflection captured on a Mac can be saved and loaded on other systems.
+
+use {Argument, ArgumentAccess, ArgumentType, ArrayType, DataType, StructMember, StructType,
+     TextureType};
+
+/// An owned copy of an `Argument`.
+///
+/// The fields which only apply to some types of argument are `None` for the others.
+#[derive(Clone, Debug, Eq, PartialEq)]
+#[cfg_attr(feature = "serialize", derive(Deserialize, Serialize))]
+pub struct ReflectedArgument {
+    pub name: String,
+    pub access: ArgumentAccess,
+    pub is_active: bool,
+    pub index: usize,
+    pub argument_type: ArgumentType,
+
+    pub buffer_alignment: Option<usize>,
+    pub buffer_data_size: Option<usize>,
+    pub buffer_data_type: Option<DataType>,
+    pub buffer_struct_type: Option<ReflectedStruct>,
+
+    pub texture_data_type: Option<DataType>,
+    pub texture_type: Option<TextureType>,
+
+    pub threadgroup_memory_alignment: Option<usize>,
+    pub threadgroup_memory_data_size: Option<usize>
+}
+
+impl ReflectedArgument {
+    /// Creates an argument with none of the type specific fields set.
+    pub fn new<S: Into<String>>(name: S, argument_type: ArgumentType, index: usize) -> Self {
+        ReflectedArgument {
+            name: name.into(),
+            access: ArgumentAccess::ReadOnly,
+            is_active: true,
+            index: index,
+            argument_type: argument_type,
+            buffer_alignment: None,
+            buffer_data_size: None,
+            buffer_data_type: None,
+            buffer_struct_type: None,
+            texture_data_type: None,
+            texture_type: None,
+            threadgroup_memory_alignment: None,
+            threadgroup_memory_data_size: None
+        }
+    }
+}
+
+impl<'a> From<&'a Argument> for ReflectedArgument {
+    fn from(argument: &'a Argument) -> Self {
+        ReflectedArgument {
+            name: argument.name().into(),
+            access: argument.access(),
+            is_active: argument.is_active(),
+            index: argument.index(),
+            argument_type: argument.get_type(),
+            buffer_alignment: argument.buffer_alignment(),
+            buffer_data_size: argument.buffer_data_size(),
+            buffer_data_type: argument.buffer_data_type(),
+            buffer_struct_type: argument.buffer_struct_type().as_ref().map(From::from),
+            texture_data_type: argument.texture_data_type(),
+            texture_type: argument.texture_type(),
+            threadgroup_memory_alignment: argument.threadgroup_memory_alignment(),
+            threadgroup_memory_data_size: argument.threadgroup_memory_data_size()
+        }
+    }
+}
+
+/// An owned copy of a `StructType`.
+#[derive(Clone, Debug, Eq, PartialEq)]
+#[cfg_attr(feature = "serialize", derive(Deserialize, Serialize))]
+pub struct ReflectedStruct {
+    pub members: Vec<ReflectedMember>
+}
+
+impl ReflectedStruct {
+    pub fn new(members: Vec<ReflectedMember>) -> Self {
+        ReflectedStruct { members: members }
+    }
+
+    pub fn member_by_name(&self, name: &str) -> Option<&ReflectedMember> {
+        self.members.iter().find(|member| member.name == name)
+    }
+}
+
+impl<'a> From<&'a StructType> for ReflectedStruct {
+    fn from(struct_type: &'a StructType) -> Self {
+        ReflectedStruct::new(struct_type.members().iter().map(From::from).collect())
+    }
+}
+
+/// An owned copy of a `StructMember`.
+#[derive(Clone, Debug, Eq, PartialEq)]
+#[cfg_attr(feature = "serialize", derive(Deserialize, Serialize))]
+pub struct ReflectedMember {
+    pub name: String,
+    pub data_type: DataType,
+    pub offset: usize,
+
+    /// The array type of the member, if its data type is `Array`.
+    pub array_type: Option<ReflectedArray>,
+
+    /// The struct type of the member, if its data type is `Struct`.
+    pub struct_type: Option<ReflectedStruct>
+}
+
+impl ReflectedMember {
+    /// Creates a scalar, vector or matrix member.
+    pub fn new<S: Into<String>>(name: S, data_type: DataType, offset: usize) -> Self {
+        ReflectedMember {
+            name: name.into(),
+            data_type: data_type,
+            offset: offset,
+            array_type: None,
+            struct_type: None
+        }
+    }
+
+    /// Creates an array member.
+    pub fn array<S: Into<String>>(name: S, array_type: ReflectedArray, offset: usize) -> Self {
+        ReflectedMember {
+            array_type: Some(array_type),
+            ..ReflectedMember::new(name, DataType::Array, offset)
+        }
+    }
+
+    /// Creates a nested struct member.
+    pub fn structure<S>(name: S, struct_type: ReflectedStruct, offset: usize) -> Self
+        where S: Into<String> {
+        ReflectedMember {
+            struct_type: Some(struct_type),
+            ..ReflectedMember::new(name, DataType::Struct, offset)
+        }
+    }
+}
+
+impl<'a> From<&'a StructMember> for ReflectedMember {
+    fn from(member: &'a StructMember) -> Self {
+        ReflectedMember {
+            name: member.name().into(),
+            data_type: member.data_type(),
+            offset: member.offset(),
+            array_type: member.array_type().as_ref().map(From::from),
+            struct_type: member.struct_type().as_ref().map(From::from)
+        }
+    }
+}
+
+/// An owned copy of an `ArrayType`.
+#[derive(Clone, Debug, Eq, PartialEq)]
+#[cfg_attr(feature = "serialize", derive(Deserialize, Serialize))]
+pub struct ReflectedArray {
+    pub array_length: usize,
+    pub element_type: DataType,
+    pub stride: usize,
+
+    /// The array type of the elements, if their data type is `Array`.
+    pub element_array_type: Option<Box<ReflectedArray>>,
+
+    /// The struct type of the elements, if their data type is `Struct`.
+    pub element_struct_type: Option<ReflectedStruct>
+}
+
+impl ReflectedArray {
+    /// Creates an array of scalars, vectors or matrices.
+    pub fn new(element_type: DataType, array_length: usize, stride: usize) -> Self {
+        ReflectedArray {
+            array_length: array_length,
+            element_type: element_type,
+            stride: stride,
+            element_array_type: None,
+            element_struct_type: None
+        }
+    }
+}
+
+impl<'a> From<&'a ArrayType> for ReflectedArray {
+    fn from(array_type: &'a ArrayType) -> Self {
+        ReflectedArray {
+            array_length: array_type.array_length(),
+            element_type: array_type.element_type(),
+            stride: array_type.stride(),
+            element_array_type: array_type.element_array_type()
+                                          .as_ref()
+                                          .map(|array_type| Box::new(From::from(array_type))),
+            element_struct_type: array_type.element_struct_type().as_ref().map(From::from)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use {ArgumentType, DataType};
+
+    fn uniforms_argument() -> ReflectedArgument {
+        let mut lights = ReflectedArray::new(DataType::Struct, 2, 32);
+        lights.element_struct_type = Some(ReflectedStruct::new(vec![
+            ReflectedMember::new("position", DataType::Float4, 0),
+            ReflectedMember::new("intensity", DataType::Float, 16)
+        ]));
+        let uniforms = ReflectedStruct::new(vec![
+            ReflectedMember::new("model_view_projection", DataType::Float4x4, 0),
+            ReflectedMember::array("lights", lights, 64)
+        ]);
+
+        ReflectedArgument {
+            buffer_alignment: Some(16),
+            buffer_data_size: Some(128),
+            buffer_data_type: Some(DataType::Struct),
+            buffer_struct_type: Some(uniforms),
+            ..ReflectedArgument::new("uniforms", ArgumentType::Buffer, 1)
+        }
+    }
+
+    #[test]
+    fn test_build_reflection_by_hand() {
+        let argument = uniforms_argument();
+        let uniforms = argument.buffer_struct_type.as_ref().unwrap();
+        let lights = uniforms.member_by_name("lights").unwrap();
+        assert_eq!(lights.data_type, DataType::Array);
+        assert_eq!(lights.array_type.as_ref().map(|array| array.array_length), Some(2));
+        assert!(uniforms.member_by_name("color").is_none());
+        assert_eq!(argument.clone(), argument);
+    }
+
+    #[cfg(feature = "serialize")]
+    #[test]
+    fn test_json_round_trip() {
+        extern crate serde_json;
+
+        let argument = uniforms_argument();
+        let json = serde_json::to_string(&argument).unwrap();
+        assert!(json.contains("\"model_view_projection\""));
+        assert_eq!(serde_json::from_str::<ReflectedArgument>(&json).unwrap(), argument);
+    }
+}
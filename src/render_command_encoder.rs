@@ -3,7 +3,8 @@ use cocoa::foundation::NSUInteger;
 use sys::{MTLCullMode, MTLDepthClipMode, MTLIndexType, MTLPrimitiveType, MTLTriangleFillMode,
           MTLRenderCommandEncoder, MTLVisibilityResultMode, MTLWinding, MTLScissorRect,
           MTLViewport};
-use {AsRaw, DepthStencilState, RenderPipelineState};
+use allocator::BufferAllocation;
+use {Argument, AsRaw, Buffer, DepthStencilState, RenderPipelineState, ShaderStruct};
 
 pub struct RenderCommandEncoder(id);
 
@@ -28,6 +29,32 @@ impl RenderCommandEncoder {
         unsafe { self.0.setDepthStencilState(*depth_stencil_state.as_raw()) }
     }
 
+    /// Binds `buffer` to the fragment shader argument table at `index`, starting `offset` bytes
+    /// into the buffer.
+    pub fn set_fragment_buffer(&mut self, buffer: &Buffer, offset: usize, index: usize) {
+        unsafe {
+            self.0.setFragmentBuffer_offset_atIndex(*buffer.as_raw(), offset as NSUInteger,
+                                                    index as NSUInteger)
+        }
+    }
+
+    /// Binds the region of `allocation` to the fragment shader argument table at `index`.
+    pub fn set_fragment_allocation(&mut self, allocation: &BufferAllocation, index: usize) {
+        unsafe {
+            self.0.setFragmentBuffer_offset_atIndex(*allocation.as_raw(),
+                                                    allocation.offset() as NSUInteger,
+                                                    index as NSUInteger)
+        }
+    }
+
+    /// Binds `buffer` to the fragment shader buffer `argument`, which holds a `T` at `offset`.
+    /// In debug builds, this panics if `T` does not match the struct type of the argument.
+    pub fn set_fragment_struct_buffer<T: ShaderStruct>(&mut self, buffer: &Buffer, offset: usize,
+                                                       argument: &Argument) {
+        argument.debug_assert_struct::<T>();
+        self.set_fragment_buffer(buffer, offset, argument.index())
+    }
+
     pub fn set_front_facing_winding<W: Into<MTLWinding>>(&mut self, winding: W) {
         unsafe { self.0.setFrontFacingWinding(winding.into()) }
     }
@@ -54,6 +81,32 @@ impl RenderCommandEncoder {
         unsafe { self.0.setTriangleFillMode(triangle_fill_mode.into()) }
     }
 
+    /// Binds `buffer` to the vertex shader argument table at `index`, starting `offset` bytes
+    /// into the buffer.
+    pub fn set_vertex_buffer(&mut self, buffer: &Buffer, offset: usize, index: usize) {
+        unsafe {
+            self.0.setVertexBuffer_offset_atIndex(*buffer.as_raw(), offset as NSUInteger,
+                                                  index as NSUInteger)
+        }
+    }
+
+    /// Binds the region of `allocation` to the vertex shader argument table at `index`.
+    pub fn set_vertex_allocation(&mut self, allocation: &BufferAllocation, index: usize) {
+        unsafe {
+            self.0.setVertexBuffer_offset_atIndex(*allocation.as_raw(),
+                                                  allocation.offset() as NSUInteger,
+                                                  index as NSUInteger)
+        }
+    }
+
+    /// Binds `buffer` to the vertex shader buffer `argument`, which holds a `T` at `offset`. In
+    /// debug builds, this panics if `T` does not match the struct type of the argument.
+    pub fn set_vertex_struct_buffer<T: ShaderStruct>(&mut self, buffer: &Buffer, offset: usize,
+                                                     argument: &Argument) {
+        argument.debug_assert_struct::<T>();
+        self.set_vertex_buffer(buffer, offset, argument.index())
+    }
+
     pub fn set_viewport<V: Into<MTLViewport>>(&mut self, viewport: V) {
         unsafe { self.0.setViewport(viewport.into()) }
     }
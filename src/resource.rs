@@ -5,21 +5,19 @@ use cocoa::foundation::NSString;
 use std::convert::Into;
 use std::ffi::CStr;
 use std::mem;
-use sys::{MTLCPUCacheMode, MTLPurgeableState, MTLResource, MTLResourceCPUCacheModeDefaultCache,
-          MTLResourceCPUCacheModeShift, MTLResourceCPUCacheModeWriteCombined,
-          MTLResourceOptionCPUCacheModeDefault, MTLResourceOptionCPUCacheModeWriteCombined,
-          MTLResourceOptions, MTLResourceStorageModePrivate, MTLResourceStorageModeShared,
+use sys::{MTLCPUCacheMode, MTLPurgeableState, MTLResource, MTLResourceCPUCacheModeMask,
+          MTLResourceCPUCacheModeShift, MTLResourceOptions, MTLResourceStorageModeMask,
           MTLResourceStorageModeShift, MTLStorageMode};
 use Device;
 
 pub struct Resource(id);
 
 impl Resource {
-    pub fn cpu_cache_mode(self) -> CpuCacheMode {
+    pub fn cpu_cache_mode(&self) -> CpuCacheMode {
         unsafe { self.0.cpuCacheMode().into() }
     }
 
-    pub fn storage_mode(self) -> StorageMode {
+    pub fn storage_mode(&self) -> StorageMode {
         unsafe { self.0.storageMode().into() }
     }
 
@@ -42,20 +40,95 @@ impl Resource {
 
 impl_from_into_raw!(Resource, of protocol "MTLResource");
 
-// TODO(burtonageo): Implement properly
-pub struct ResourceOptions;
+bitflags! {
+    /// Options used when creating buffers and textures.
+    ///
+    /// The CPU cache mode and storage mode are packed into fields of the flags, in the same layout
+    /// as `MTLResourceOptions`. Because the default value of each field is zero, testing for the
+    /// default modes with `contains` always succeeds; use `cpu_cache_mode` and `storage_mode` to
+    /// read the fields instead.
+    pub flags ResourceOptions: usize {
+        /// The default CPU cache mode, which guarantees that reads and writes are executed in
+        /// the expected order.
+        const RESOURCE_CPU_CACHE_MODE_DEFAULT_CACHE =
+            (::sys::MTLCPUCacheMode::MTLCPUCacheModeDefaultCache as usize) <<
+            ::sys::MTLResourceCPUCacheModeShift,
+
+        /// A CPU cache mode which is optimized for resources that the CPU only writes to.
+        const RESOURCE_CPU_CACHE_MODE_WRITE_COMBINED =
+            (::sys::MTLCPUCacheMode::MTLCPUCacheModeWriteCombined as usize) <<
+            ::sys::MTLResourceCPUCacheModeShift,
+
+        /// The resource is stored in memory shared by the CPU and the GPU.
+        const RESOURCE_STORAGE_MODE_SHARED =
+            (::sys::MTLStorageMode::MTLStorageModeShared as usize) <<
+            ::sys::MTLResourceStorageModeShift,
+
+        /// The CPU and the GPU each keep a copy of the resource, which must be synchronized
+        /// explicitly. Only available in OS X.
+        const RESOURCE_STORAGE_MODE_MANAGED =
+            (::sys::MTLStorageMode::MTLStorageModeManaged as usize) <<
+            ::sys::MTLResourceStorageModeShift,
+
+        /// The resource is only stored in memory accessible to the GPU.
+        const RESOURCE_STORAGE_MODE_PRIVATE =
+            (::sys::MTLStorageMode::MTLStorageModePrivate as usize) <<
+            ::sys::MTLResourceStorageModeShift
+    }
+}
+
+impl ResourceOptions {
+    /// Creates resource options with the given CPU cache mode and storage mode.
+    pub fn new(cpu_cache_mode: CpuCacheMode, storage_mode: StorageMode) -> Self {
+        let mut options = ResourceOptions::empty();
+        options.set_cpu_cache_mode(cpu_cache_mode);
+        options.set_storage_mode(storage_mode);
+        options
+    }
+
+    pub fn cpu_cache_mode(&self) -> CpuCacheMode {
+        match (self.bits & MTLResourceCPUCacheModeMask) >> MTLResourceCPUCacheModeShift {
+            1 => CpuCacheMode::WriteCombined,
+            _ => CpuCacheMode::DefaultCache,
+        }
+    }
+
+    pub fn set_cpu_cache_mode(&mut self, cpu_cache_mode: CpuCacheMode) {
+        let mode: MTLCPUCacheMode = cpu_cache_mode.into();
+        self.bits = (self.bits & !MTLResourceCPUCacheModeMask) |
+                    ((mode as usize) << MTLResourceCPUCacheModeShift);
+    }
+
+    pub fn storage_mode(&self) -> StorageMode {
+        match (self.bits & MTLResourceStorageModeMask) >> MTLResourceStorageModeShift {
+            1 => StorageMode::Managed,
+            2 => StorageMode::Private,
+            _ => StorageMode::Shared,
+        }
+    }
+
+    pub fn set_storage_mode(&mut self, storage_mode: StorageMode) {
+        let mode: MTLStorageMode = storage_mode.into();
+        self.bits = (self.bits & !MTLResourceStorageModeMask) |
+                    ((mode as usize) << MTLResourceStorageModeShift);
+    }
+}
+
+impl Default for ResourceOptions {
+    fn default() -> Self {
+        ResourceOptions::empty()
+    }
+}
 
-// TODO(burtonageo): Implement properly
 impl Into<MTLResourceOptions> for ResourceOptions {
     fn into(self) -> MTLResourceOptions {
-        MTLResourceOptionCPUCacheModeDefault
+        MTLResourceOptions::from_bits_truncate(self.bits())
     }
 }
 
-// TODO(burtonageo): Implement properly
 impl From<MTLResourceOptions> for ResourceOptions {
-    fn from(_options: MTLResourceOptions) -> Self {
-        ResourceOptions
+    fn from(options: MTLResourceOptions) -> Self {
+        ResourceOptions::from_bits_truncate(options.bits())
     }
 }
 
@@ -63,6 +136,7 @@ convertible_enum! {
     #[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
     pub enum StorageMode: MTLStorageMode {
         Shared => MTLStorageModeShared,
+        Managed => MTLStorageModeManaged,
         Private => MTLStorageModePrivate
     }
 }
@@ -84,3 +158,57 @@ convertible_enum! {
        Empty => MTLPurgeableStateEmpty
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use sys::{MTLResourceCPUCacheModeWriteCombined, MTLResourceOptions,
+              MTLResourceStorageModeManaged, MTLResourceStorageModePrivate};
+
+    #[test]
+    fn test_resource_options_layout() {
+        assert_eq!(RESOURCE_CPU_CACHE_MODE_DEFAULT_CACHE.bits(), 0x00);
+        assert_eq!(RESOURCE_CPU_CACHE_MODE_WRITE_COMBINED.bits(), 0x01);
+        assert_eq!(RESOURCE_STORAGE_MODE_SHARED.bits(), 0x00);
+        assert_eq!(RESOURCE_STORAGE_MODE_MANAGED.bits(), 0x10);
+        assert_eq!(RESOURCE_STORAGE_MODE_PRIVATE.bits(), 0x20);
+    }
+
+    #[test]
+    fn test_resource_options_fields() {
+        let options = ResourceOptions::default();
+        assert_eq!(options.cpu_cache_mode(), CpuCacheMode::DefaultCache);
+        assert_eq!(options.storage_mode(), StorageMode::Shared);
+
+        let mut options = RESOURCE_CPU_CACHE_MODE_WRITE_COMBINED | RESOURCE_STORAGE_MODE_PRIVATE;
+        assert_eq!(options.cpu_cache_mode(), CpuCacheMode::WriteCombined);
+        assert_eq!(options.storage_mode(), StorageMode::Private);
+
+        options.set_storage_mode(StorageMode::Managed);
+        assert_eq!(options, RESOURCE_CPU_CACHE_MODE_WRITE_COMBINED | RESOURCE_STORAGE_MODE_MANAGED);
+        options.set_cpu_cache_mode(CpuCacheMode::DefaultCache);
+        assert_eq!(options, RESOURCE_STORAGE_MODE_MANAGED);
+        assert_eq!(ResourceOptions::new(CpuCacheMode::WriteCombined, StorageMode::Shared),
+                   RESOURCE_CPU_CACHE_MODE_WRITE_COMBINED);
+    }
+
+    #[test]
+    fn test_resource_options_round_trip() {
+        for &cpu_cache_mode in &[CpuCacheMode::DefaultCache, CpuCacheMode::WriteCombined] {
+            for &storage_mode in &[StorageMode::Shared, StorageMode::Managed,
+                                   StorageMode::Private] {
+                let options = ResourceOptions::new(cpu_cache_mode, storage_mode);
+                let raw: MTLResourceOptions = options.into();
+                assert_eq!(raw.bits(), options.bits());
+                assert_eq!(ResourceOptions::from(raw), options);
+            }
+        }
+
+        let raw = MTLResourceCPUCacheModeWriteCombined | MTLResourceStorageModePrivate;
+        let options = ResourceOptions::from(raw);
+        assert_eq!(options.cpu_cache_mode(), CpuCacheMode::WriteCombined);
+        assert_eq!(options.storage_mode(), StorageMode::Private);
+        assert_eq!(ResourceOptions::from(MTLResourceStorageModeManaged).storage_mode(),
+                   StorageMode::Managed);
+    }
+}
@@ -0,0 +1,678 @@
+//! Assembly of shader source split across several files.
+//!
+//! The Metal runtime compiler only sees a single string, so it cannot resolve `#include "..."`
+//! directives which refer to other files of ours. A `ShaderSourceSet` expands those includes
+//! before compiling, loading files through a list of `ShaderSourceProvider`s. Includes of system
+//! headers such as `<metal_stdlib>` are left for the compiler.
+
+use std::collections::{HashMap, HashSet};
+use std::error::Error;
+use std::fmt;
+use std::fs::File;
+use std::io::{self, Read};
+use std::mem;
+use std::ops::Deref;
+use std::path::PathBuf;
+
+/// A source of shader files, identified by `/` separated paths.
+pub trait ShaderSourceProvider {
+    /// Loads the file at `path`. Returns an error of kind `io::ErrorKind::NotFound` if the
+    /// provider does not have the file, so that the next provider can be tried.
+    fn load(&self, path: &str) -> io::Result<String>;
+}
+
+fn not_found(path: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::NotFound, format!("`{}` was not found", path))
+}
+
+/// Loads shader files from a directory on disk.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct DirectoryProvider {
+    root: PathBuf
+}
+
+impl DirectoryProvider {
+    pub fn new<P: Into<PathBuf>>(root: P) -> Self {
+        DirectoryProvider { root: root.into() }
+    }
+}
+
+impl ShaderSourceProvider for DirectoryProvider {
+    fn load(&self, path: &str) -> io::Result<String> {
+        let mut source = String::new();
+        let mut file = try!(File::open(self.root.join(path)));
+        try!(file.read_to_string(&mut source));
+        Ok(source)
+    }
+}
+
+/// Provides shader files which are compiled into the program, usually with `include_str!`.
+///
+/// ```ignore
+/// let provider = EmbeddedProvider::new(&[("common.metal", include_str!("common.metal"))]);
+/// ```
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct EmbeddedProvider {
+    files: &'static [(&'static str, &'static str)]
+}
+
+impl EmbeddedProvider {
+    pub fn new(files: &'static [(&'static str, &'static str)]) -> Self {
+        EmbeddedProvider { files: files }
+    }
+}
+
+impl ShaderSourceProvider for EmbeddedProvider {
+    fn load(&self, path: &str) -> io::Result<String> {
+        self.files
+            .iter()
+            .find(|&&(name, _)| name == path)
+            .map(|&(_, source)| source.to_string())
+            .ok_or_else(|| not_found(path))
+    }
+}
+
+/// Provides shader files held in memory, which can be changed at any time.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct MemoryProvider {
+    files: HashMap<String, String>
+}
+
+impl MemoryProvider {
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// Adds or replaces the file at `path`.
+    pub fn with_file<P, S>(mut self, path: P, source: S) -> Self
+        where P: Into<String>,
+              S: Into<String> {
+        self.insert(path, source);
+        self
+    }
+
+    /// Adds or replaces the file at `path`.
+    pub fn insert<P, S>(&mut self, path: P, source: S)
+        where P: Into<String>,
+              S: Into<String> {
+        self.files.insert(path.into(), source.into());
+    }
+
+    pub fn remove(&mut self, path: &str) -> Option<String> {
+        self.files.remove(path)
+    }
+}
+
+impl ShaderSourceProvider for MemoryProvider {
+    fn load(&self, path: &str) -> io::Result<String> {
+        self.files.get(path).cloned().ok_or_else(|| not_found(path))
+    }
+}
+
+/// Assembles shader source from files loaded through a list of providers.
+///
+/// `#include "path"` directives are replaced by the contents of the file. Paths are resolved
+/// relative to the including file first, and then relative to the root of the providers, which
+/// are tried in the order they were added. Paths which lead outside of the root with `..` are
+/// rejected, and includes inside comments are ignored. Files containing `#pragma once`, or
+/// wrapped in an `#ifndef`/`#define` include guard, are only expanded once, and their
+/// `#pragma once` lines are left out of the output. `#line` directives are emitted around every
+/// file, so that compiler errors refer to the original files and lines.
+#[derive(Default)]
+pub struct ShaderSourceSet {
+    providers: Vec<Box<ShaderSourceProvider>>
+}
+
+impl ShaderSourceSet {
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// Adds a provider, which is tried after the existing providers.
+    pub fn with_provider<P: ShaderSourceProvider + 'static>(mut self, provider: P) -> Self {
+        self.add_provider(provider);
+        self
+    }
+
+    /// Adds a provider, which is tried after the existing providers.
+    pub fn add_provider<P: ShaderSourceProvider + 'static>(&mut self, provider: P) {
+        self.providers.push(Box::new(provider));
+    }
+
+    /// Loads the file at `path` from the first provider which has it.
+    pub fn load(&self, path: &str) -> Result<String, ShaderSourceError> {
+        let path = normalize_path(path);
+        if escapes_root(&path) {
+            return Err(ShaderSourceError::OutsideRoot { path: path, included_from: None });
+        }
+        for provider in &self.providers {
+            match provider.load(&path) {
+                Ok(source) => return Ok(source),
+                Err(ref error) if error.kind() == io::ErrorKind::NotFound => continue,
+                Err(error) => return Err(ShaderSourceError::Io { path: path, error: error }),
+            }
+        }
+        Err(ShaderSourceError::NotFound { path: path, included_from: None })
+    }
+
+    /// Assembles the file at `path` and everything it includes.
+    pub fn assemble(&self, path: &str) -> Result<AssembledSource, ShaderSourceError> {
+        self.assemble_with_paths(path).0
+    }
+
+    /// Like `assemble`, but also returns the paths of every file which was loaded or looked for,
+    /// including files which were not found. The paths are returned even if assembly fails, so
+    /// that a caller can tell when the source may have been fixed.
+    pub fn assemble_with_paths(&self, path: &str)
+                               -> (Result<AssembledSource, ShaderSourceError>, Vec<String>) {
+        let path = normalize_path(path);
+        let mut assembler = Assembler::new(self);
+        if !escapes_root(&path) {
+            assembler.attempted.push(path.clone());
+        }
+        let result = self.load(&path).and_then(|source| assembler.expand(&path, source));
+        let attempted = mem::replace(&mut assembler.attempted, vec![]);
+        (result.map(|_| assembler.into_assembled()), attempted)
+    }
+
+    /// Assembles `source`, which is named `path` in `#line` directives and used to resolve its
+    /// relative includes, but is not loaded from the providers.
+    pub fn assemble_source(&self, path: &str, source: &str)
+                           -> Result<AssembledSource, ShaderSourceError> {
+        let mut assembler = Assembler::new(self);
+        try!(assembler.expand(&normalize_path(path), source.to_string()));
+        Ok(assembler.into_assembled())
+    }
+}
+
+impl fmt::Debug for ShaderSourceSet {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "ShaderSourceSet {{ providers: {} }}", self.providers.len())
+    }
+}
+
+/// Shader source with its includes expanded, ready to be passed to
+/// `Device::new_library_with_source`.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct AssembledSource {
+    source: String,
+    files: Vec<(String, String)>
+}
+
+impl AssembledSource {
+    /// The assembled source.
+    pub fn source(&self) -> &str {
+        &self.source
+    }
+
+    pub fn into_source(self) -> String {
+        self.source
+    }
+
+    /// The paths of the files which make up the source, starting with the root file.
+    pub fn paths(&self) -> Vec<&str> {
+        self.files.iter().map(|&(ref path, _)| &path[..]).collect()
+    }
+
+    /// The original contents of one of the files which make up the source.
+    pub fn file(&self, path: &str) -> Option<&str> {
+        self.files.iter().find(|&&(ref name, _)| name == path).map(|&(_, ref source)| &source[..])
+    }
+}
+
+impl Deref for AssembledSource {
+    type Target = str;
+    fn deref(&self) -> &Self::Target {
+        &self.source
+    }
+}
+
+struct Assembler<'a> {
+    set: &'a ShaderSourceSet,
+    output: String,
+    files: Vec<(String, String)>,
+    attempted: Vec<String>,
+    stack: Vec<String>,
+    included_once: HashSet<String>
+}
+
+impl<'a> Assembler<'a> {
+    fn new(set: &'a ShaderSourceSet) -> Self {
+        Assembler {
+            set: set,
+            output: String::new(),
+            files: vec![],
+            attempted: vec![],
+            stack: vec![],
+            included_once: HashSet::new()
+        }
+    }
+
+    fn into_assembled(self) -> AssembledSource {
+        AssembledSource { source: self.output, files: self.files }
+    }
+
+    fn expand(&mut self, path: &str, source: String) -> Result<(), ShaderSourceError> {
+        if self.stack.iter().any(|file| file == path) {
+            let mut chain = self.stack.clone();
+            chain.push(path.into());
+            return Err(ShaderSourceError::IncludeCycle(chain));
+        }
+        if is_included_once(&source) {
+            self.included_once.insert(path.into());
+        }
+        if !self.files.iter().any(|&(ref file, _)| file == path) {
+            self.files.push((path.into(), source.clone()));
+        }
+
+        self.stack.push(path.into());
+        self.output.push_str(&format!("#line 1 \"{}\"\n", path));
+        for (i, (line, code)) in source.lines().zip(code_lines(&source)).enumerate() {
+            let line_number = i + 1;
+            if directive(&code, "pragma") == Some("once") {
+                // Keep an empty line, so that the following lines keep their numbers.
+                self.output.push('\n');
+                continue;
+            }
+            let include = match parse_include(&code) {
+                Some(Ok(include)) => include,
+                Some(Err(())) => {
+                    return Err(ShaderSourceError::MalformedInclude {
+                        path: path.into(),
+                        line: line_number
+                    })
+                }
+                None => {
+                    self.output.push_str(line);
+                    self.output.push('\n');
+                    continue;
+                }
+            };
+
+            let (included_path, included_source) = try!(self.resolve(path, include, line_number));
+            if !self.included_once.contains(&included_path) {
+                try!(self.expand(&included_path, included_source));
+            }
+            self.output.push_str(&format!("#line {} \"{}\"\n", line_number + 1, path));
+        }
+        self.stack.pop();
+        Ok(())
+    }
+
+    /// Finds an included file, first relative to the including file and then from the root.
+    fn resolve(&mut self, includer: &str, include: &str, line: usize)
+               -> Result<(String, String), ShaderSourceError> {
+        let relative = match includer.rfind('/') {
+            Some(slash) => normalize_path(&format!("{}/{}", &includer[..slash], include)),
+            None => normalize_path(include),
+        };
+        let mut candidates = vec![relative];
+        let absolute = normalize_path(include);
+        if !candidates.contains(&absolute) {
+            candidates.push(absolute);
+        }
+        candidates.retain(|candidate| !escapes_root(candidate));
+        if candidates.is_empty() {
+            return Err(ShaderSourceError::OutsideRoot {
+                path: include.into(),
+                included_from: Some((includer.into(), line))
+            });
+        }
+
+        for candidate in candidates {
+            if !self.attempted.contains(&candidate) {
+                self.attempted.push(candidate.clone());
+            }
+            match self.set.load(&candidate) {
+                Ok(source) => return Ok((candidate, source)),
+                Err(ShaderSourceError::NotFound { .. }) => continue,
+                Err(error) => return Err(error),
+            }
+        }
+        Err(ShaderSourceError::NotFound {
+            path: include.into(),
+            included_from: Some((includer.into(), line))
+        })
+    }
+}
+
+/// Parses a `#include "path"` directive. Returns `None` for lines which are not quoted includes,
+/// including system includes such as `#include <metal_stdlib>`.
+fn parse_include(line: &str) -> Option<Result<&str, ()>> {
+    let rest = directive(line, "include");
+    let rest = match rest {
+        Some(rest) if !rest.starts_with('<') => rest,
+        _ => return None,
+    };
+
+    if !rest.starts_with('"') {
+        return Some(Err(()));
+    }
+    let rest = &rest[1..];
+    Some(match rest.find('"') {
+        Some(end) if end > 0 => Ok(&rest[..end]),
+        _ => Err(()),
+    })
+}
+
+/// If `line` is the preprocessor directive `name`, returns the rest of the line.
+fn directive<'a>(line: &'a str, name: &str) -> Option<&'a str> {
+    let line = line.trim();
+    if !line.starts_with('#') {
+        return None;
+    }
+    let line = line[1..].trim_left();
+    if line.starts_with(name) {
+        let rest = &line[name.len()..];
+        if rest.is_empty() || rest.starts_with(char::is_whitespace) || rest.starts_with('"') ||
+           rest.starts_with('<') {
+            return Some(rest.trim());
+        }
+    }
+    None
+}
+
+/// Returns `true` if the file contains `#pragma once`, or starts with an include guard.
+fn is_included_once(source: &str) -> bool {
+    let code = code_lines(source);
+    if code.iter().any(|line| directive(line, "pragma") == Some("once")) {
+        return true;
+    }
+
+    let mut lines = code.iter().filter(|line| !line.trim().is_empty());
+    match (lines.next().and_then(|line| directive(line, "ifndef")),
+           lines.next().and_then(|line| directive(line, "define"))) {
+        (Some(guard), Some(defined)) => {
+            !guard.is_empty() && defined.split_whitespace().next() == Some(guard)
+        }
+        _ => false,
+    }
+}
+
+/// Returns each line of `source` with its comments replaced by spaces, so that directives inside
+/// comments are not seen. Block comments may span several lines.
+fn code_lines(source: &str) -> Vec<String> {
+    let mut in_comment = false;
+    let mut lines = vec![];
+    for line in source.lines() {
+        let mut code = String::new();
+        let mut rest = line;
+        loop {
+            if in_comment {
+                match rest.find("*/") {
+                    Some(end) => {
+                        code.push(' ');
+                        rest = &rest[end + 2..];
+                        in_comment = false;
+                    }
+                    None => break,
+                }
+            } else {
+                match (rest.find("/*"), rest.find("//")) {
+                    (Some(start), line_comment) if line_comment.map_or(true, |l| start < l) => {
+                        code.push_str(&rest[..start]);
+                        rest = &rest[start + 2..];
+                        in_comment = true;
+                    }
+                    (_, Some(start)) => {
+                        code.push_str(&rest[..start]);
+                        break;
+                    }
+                    _ => {
+                        code.push_str(rest);
+                        break;
+                    }
+                }
+            }
+        }
+        lines.push(code);
+    }
+    lines
+}
+
+/// Returns `true` if a normalized path starts with `..`, and so lies outside of the root.
+fn escapes_root(path: &str) -> bool {
+    path == ".." || path.starts_with("../")
+}
+
+/// Removes `.` and empty components from a path, and resolves `..` components where possible.
+fn normalize_path(path: &str) -> String {
+    let mut components: Vec<&str> = vec![];
+    for component in path.split('/') {
+        match component {
+            "" | "." => (),
+            ".." if components.last().map_or(false, |&last| last != "..") => {
+                components.pop();
+            }
+            _ => components.push(component),
+        }
+    }
+    components.join("/")
+}
+
+/// An error produced while assembling shader source.
+#[derive(Debug)]
+pub enum ShaderSourceError {
+    /// No provider has the file. `included_from` is the file and line which included it.
+    NotFound { path: String, included_from: Option<(String, usize)> },
+
+    /// The path leads outside of the root of the providers. `included_from` is the file and line
+    /// which included it.
+    OutsideRoot { path: String, included_from: Option<(String, usize)> },
+
+    /// A provider failed to load the file.
+    Io { path: String, error: io::Error },
+
+    /// The files include each other. The chain starts and ends with the same file.
+    IncludeCycle(Vec<String>),
+
+    /// An `#include` directive is not followed by a quoted path.
+    MalformedInclude { path: String, line: usize }
+}
+
+impl fmt::Display for ShaderSourceError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            ShaderSourceError::NotFound { ref path, included_from: Some((ref file, line)) } => {
+                write!(f, "`{}` included from {}:{} was not found", path, file, line)
+            }
+            ShaderSourceError::NotFound { ref path, included_from: None } => {
+                write!(f, "`{}` was not found", path)
+            }
+            ShaderSourceError::OutsideRoot { ref path, included_from: Some((ref file, line)) } => {
+                write!(f, "`{}` included from {}:{} is outside of the root", path, file, line)
+            }
+            ShaderSourceError::OutsideRoot { ref path, included_from: None } => {
+                write!(f, "`{}` is outside of the root", path)
+            }
+            ShaderSourceError::Io { ref path, ref error } => {
+                write!(f, "`{}` could not be loaded: {}", path, error)
+            }
+            ShaderSourceError::IncludeCycle(ref chain) => {
+                write!(f, "the includes form a cycle: {}", chain.join(" -> "))
+            }
+            ShaderSourceError::MalformedInclude { ref path, line } => {
+                write!(f, "{}:{}: the include directive is malformed", path, line)
+            }
+        }
+    }
+}
+
+impl Error for ShaderSourceError {
+    fn description(&self) -> &str {
+        match *self {
+            ShaderSourceError::NotFound { .. } => "The shader file was not found",
+            ShaderSourceError::OutsideRoot { .. } => "The shader file is outside of the root",
+            ShaderSourceError::Io { .. } => "The shader file could not be loaded",
+            ShaderSourceError::IncludeCycle(_) => "The shader files include each other",
+            ShaderSourceError::MalformedInclude { .. } => "The include directive is malformed",
+        }
+    }
+
+    fn cause(&self) -> Option<&Error> {
+        match *self {
+            ShaderSourceError::Io { ref error, .. } => Some(error),
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::env;
+    use std::fs::{self, File};
+    use std::io::Write;
+
+    fn lines(source: &str) -> Vec<&str> {
+        source.lines().collect()
+    }
+
+    #[test]
+    fn test_expand_includes_with_line_directives() {
+        let files = MemoryProvider::new()
+            .with_file("shaders/main.metal",
+                       "#include <metal_stdlib>\n#include \"common.h\"\nvertex void main() {}\n")
+            .with_file("shaders/common.h", "#pragma once\n  #  include \"../lib/math.h\"\nCOMMON\n")
+            .with_file("lib/math.h", "MATH\n");
+        let set = ShaderSourceSet::new().with_provider(files);
+
+        let assembled = set.assemble("shaders/./main.metal").unwrap();
+        assert_eq!(lines(&assembled),
+                   ["#line 1 \"shaders/main.metal\"",
+                    "#include <metal_stdlib>",
+                    "#line 1 \"shaders/common.h\"",
+                    "",
+                    "#line 1 \"lib/math.h\"",
+                    "MATH",
+                    "#line 3 \"shaders/common.h\"",
+                    "COMMON",
+                    "#line 3 \"shaders/main.metal\"",
+                    "vertex void main() {}"]);
+        assert_eq!(assembled.paths(), ["shaders/main.metal", "shaders/common.h", "lib/math.h"]);
+        assert_eq!(assembled.file("lib/math.h"), Some("MATH\n"));
+    }
+
+    #[test]
+    fn test_include_guards() {
+        let files = MemoryProvider::new()
+            .with_file("a.h", "// A\n#ifndef A_H\n#define A_H\n#include \"b.h\"\n#endif\n")
+            .with_file("b.h", "#pragma once\n#include \"a.h\"\nB\n")
+            .with_file("unguarded.h", "UNGUARDED\n");
+        let set = ShaderSourceSet::new().with_provider(files);
+
+        let source = "#include \"a.h\"\n#include \"b.h\"\n#include \"unguarded.h\"\n\
+                      #include \"unguarded.h\"\n";
+        let assembled = set.assemble_source("main.metal", source).unwrap();
+        let count = |text: &str| assembled.lines().filter(|&line| line == text).count();
+        assert_eq!(count("#line 1 \"a.h\""), 1);
+        assert_eq!(count("#line 1 \"b.h\""), 1);
+        assert_eq!(count("UNGUARDED"), 2);
+    }
+
+    #[test]
+    fn test_include_errors() {
+        let files = MemoryProvider::new()
+            .with_file("a.h", "#include \"b.h\"\n")
+            .with_file("b.h", "\n#include \"a.h\"\n")
+            .with_file("missing.h", "#include \"nowhere.h\"\n")
+            .with_file("malformed.h", "#include common.h\n");
+        let set = ShaderSourceSet::new().with_provider(files);
+
+        match set.assemble("a.h") {
+            Err(ShaderSourceError::IncludeCycle(chain)) => assert_eq!(chain, ["a.h", "b.h", "a.h"]),
+            other => panic!("expected an include cycle, found {:?}", other),
+        }
+        match set.assemble("missing.h") {
+            Err(ShaderSourceError::NotFound { path, included_from }) => {
+                assert_eq!(path, "nowhere.h");
+                assert_eq!(included_from, Some(("missing.h".into(), 1)));
+            }
+            other => panic!("expected a missing file, found {:?}", other),
+        }
+        match set.assemble("malformed.h") {
+            Err(ShaderSourceError::MalformedInclude { path, line }) => {
+                assert_eq!((&path[..], line), ("malformed.h", 1));
+            }
+            other => panic!("expected a malformed include, found {:?}", other),
+        }
+        assert!(set.assemble("other.h").is_err());
+    }
+
+    #[test]
+    fn test_paths_outside_the_root_are_rejected() {
+        let files = MemoryProvider::new()
+            .with_file("shaders/main.metal", "#include \"../../secret.h\"\n")
+            .with_file("shaders/up.metal", "#include \"../common.h\"\n")
+            .with_file("common.h", "COMMON\n");
+        let set = ShaderSourceSet::new().with_provider(files);
+
+        match set.assemble("shaders/main.metal") {
+            Err(ShaderSourceError::OutsideRoot { path, included_from }) => {
+                assert_eq!(path, "../../secret.h");
+                assert_eq!(included_from, Some(("shaders/main.metal".into(), 1)));
+            }
+            other => panic!("expected a path outside of the root, found {:?}", other),
+        }
+        match set.load("shaders/../../common.h") {
+            Err(ShaderSourceError::OutsideRoot { path, included_from: None }) => {
+                assert_eq!(path, "../common.h");
+            }
+            other => panic!("expected a path outside of the root, found {:?}", other),
+        }
+        assert!(set.assemble("shaders/up.metal").unwrap().contains("COMMON"));
+    }
+
+    #[test]
+    fn test_includes_in_comments_are_ignored() {
+        let files = MemoryProvider::new()
+            .with_file("a.h", "A\n")
+            .with_file("once.h", "/* #pragma once */\nONCE\n");
+        let set = ShaderSourceSet::new().with_provider(files);
+
+        let source = "/*\n#include \"missing.h\"\n*/ #include \"a.h\"\n\
+                      // #include \"missing.h\"\n/* a */ #include \"once.h\" /* b\n*/\n\
+                      #include \"once.h\"\n";
+        let assembled = set.assemble_source("main.metal", source).unwrap();
+        assert_eq!(assembled.paths(), ["main.metal", "a.h", "once.h"]);
+        assert_eq!(assembled.lines().filter(|&line| line == "ONCE").count(), 2);
+    }
+
+    #[test]
+    fn test_assemble_reports_the_paths_it_looked_for() {
+        let files = MemoryProvider::new()
+            .with_file("shaders/main.metal", "#include \"common.h\"\n#include \"missing.h\"\n")
+            .with_file("common.h", "COMMON\n");
+        let set = ShaderSourceSet::new().with_provider(files);
+
+        let (result, paths) = set.assemble_with_paths("shaders/main.metal");
+        assert!(result.is_err());
+        assert_eq!(paths,
+                   ["shaders/main.metal", "shaders/common.h", "common.h", "shaders/missing.h",
+                    "missing.h"]);
+        let (result, paths) = set.assemble_with_paths("absent.metal");
+        assert!(result.is_err());
+        assert_eq!(paths, ["absent.metal"]);
+    }
+
+    #[test]
+    fn test_providers_are_searched_in_order() {
+        static EMBEDDED: &'static [(&'static str, &'static str)] = &[("common.h", "EMBEDDED\n"),
+                                                                     ("embedded.h", "ONLY\n")];
+        let dir = env::temp_dir().join(format!("metl-shader-source-{}", ::std::process::id()));
+        fs::create_dir_all(dir.join("include")).unwrap();
+        File::create(dir.join("include/common.h"))
+            .and_then(|mut file| file.write_all(b"DISK\n"))
+            .unwrap();
+
+        let set = ShaderSourceSet::new()
+            .with_provider(DirectoryProvider::new(&dir))
+            .with_provider(EmbeddedProvider::new(EMBEDDED));
+        let source = "#include \"include/common.h\"\n#include \"common.h\"\n\
+                      #include \"embedded.h\"\n";
+        let assembled = set.assemble_source("main.metal", source).unwrap();
+        let body = assembled.lines().filter(|line| !line.starts_with('#')).collect::<Vec<_>>();
+        assert_eq!(body, ["DISK", "EMBEDDED", "ONLY"]);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
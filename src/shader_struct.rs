@@ -0,0 +1,149 @@
+use {DataType, GpuPod, MemberType, StructLayout};
+
+/// A Rust type which has the same size and layout as a Metal Shading Language type, so that it
+/// can be used as a member of a `ShaderStruct`.
+///
+/// Scalars map to their MSL equivalents (`f32` is `float`, `u16` is `ushort`), arrays of two or
+/// four scalars map to vectors (`[f32; 4]` is `float4`), and arrays of two or four component
+/// columns map to matrices (`[[f32; 4]; 3]` is `float3x4`). Three component vectors are 16 bytes
+/// in MSL, so they have no mapping; use a four component array instead.
+///
+/// # Safety
+///
+/// `size_of::<Self>()` must equal the MSL size of the type, and `ALIGNMENT` must be its MSL
+/// alignment.
+pub unsafe trait ShaderType: GpuPod {
+    /// The alignment of the type in Metal Shading Language, which may be larger than its
+    /// alignment in Rust.
+    const ALIGNMENT: usize;
+
+    /// The name of the type in Metal Shading Language.
+    fn msl_type_name() -> String;
+
+    /// The layout of the type.
+    fn member_type() -> MemberType;
+
+    /// Appends the declarations of the structs which the type depends on, followed by the
+    /// declaration of the type itself if it is a struct. Declarations already in the list are not
+    /// added again.
+    fn msl_declarations(declarations: &mut Vec<String>) {
+        let _ = declarations;
+    }
+}
+
+/// A `#[repr(C)]` struct with a matching Metal Shading Language declaration.
+///
+/// The `metl-derive` crate provides `#[derive(ShaderStruct)]`, which fails to compile if the
+/// members of the Rust struct are not at the offsets that the Metal compiler would place them.
+pub trait ShaderStruct: ShaderType {
+    /// The MSL layout of the struct.
+    fn struct_layout() -> StructLayout;
+
+    /// The MSL declaration of the struct alone, such as
+    /// `struct Light {\n    float4 position;\n    float intensity;\n};\n`.
+    fn msl_declaration() -> String;
+
+    /// The MSL declarations of the struct and every struct it depends on, in dependency order.
+    /// The result can be prepended to shader source, or defined as a macro with
+    /// `CompileOptions::with_struct_declarations`.
+    fn msl_source() -> String {
+        let mut declarations = vec![];
+        Self::msl_declarations(&mut declarations);
+        declarations.concat()
+    }
+}
+
+macro_rules! impl_shader_type {
+    ($($ty:ty => $name:expr, $data_type:ident;)*) => ($(
+        unsafe impl ShaderType for $ty {
+            const ALIGNMENT: usize = ::std::mem::size_of::<$ty>();
+
+            fn msl_type_name() -> String {
+                $name.into()
+            }
+
+            fn member_type() -> MemberType {
+                MemberType::Data(DataType::$data_type)
+            }
+        }
+    )*)
+}
+
+impl_shader_type! {
+    f32 => "float", Float;
+    [f32; 2] => "float2", Float2;
+    [f32; 4] => "float4", Float4;
+    i32 => "int", Int;
+    [i32; 2] => "int2", Int2;
+    [i32; 4] => "int4", Int4;
+    u32 => "uint", UInt;
+    [u32; 2] => "uint2", UInt2;
+    [u32; 4] => "uint4", UInt4;
+    i16 => "short", Short;
+    [i16; 2] => "short2", Short2;
+    [i16; 4] => "short4", Short4;
+    u16 => "ushort", UShort;
+    [u16; 2] => "ushort2", UShort2;
+    [u16; 4] => "ushort4", UShort4;
+    i8 => "char", Char;
+    [i8; 2] => "char2", Char2;
+    [i8; 4] => "char4", Char4;
+    u8 => "uchar", UChar;
+    [u8; 2] => "uchar2", UChar2;
+    [u8; 4] => "uchar4", UChar4;
+}
+
+macro_rules! impl_shader_matrix {
+    ($($ty:ty => $name:expr, $data_type:ident, $column:ty;)*) => ($(
+        unsafe impl ShaderType for $ty {
+            const ALIGNMENT: usize = ::std::mem::size_of::<$column>();
+
+            fn msl_type_name() -> String {
+                $name.into()
+            }
+
+            fn member_type() -> MemberType {
+                MemberType::Data(DataType::$data_type)
+            }
+        }
+    )*)
+}
+
+impl_shader_matrix! {
+    [[f32; 2]; 2] => "float2x2", Float2x2, [f32; 2];
+    [[f32; 4]; 2] => "float2x4", Float2x4, [f32; 4];
+    [[f32; 2]; 3] => "float3x2", Float3x2, [f32; 2];
+    [[f32; 4]; 3] => "float3x4", Float3x4, [f32; 4];
+    [[f32; 2]; 4] => "float4x2", Float4x2, [f32; 2];
+    [[f32; 4]; 4] => "float4x4", Float4x4, [f32; 4];
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::mem;
+
+    fn check<T: ShaderType>() {
+        let member_type = T::member_type();
+        assert_eq!(mem::size_of::<T>(), member_type.size(), "{}", T::msl_type_name());
+        assert_eq!(T::ALIGNMENT, member_type.alignment(), "{}", T::msl_type_name());
+    }
+
+    #[test]
+    fn test_shader_types_match_msl_layout() {
+        check::<f32>();
+        check::<[f32; 2]>();
+        check::<[f32; 4]>();
+        check::<[i32; 4]>();
+        check::<u16>();
+        check::<[u16; 2]>();
+        check::<[i8; 4]>();
+        check::<u8>();
+        check::<[[f32; 2]; 2]>();
+        check::<[[f32; 4]; 2]>();
+        check::<[[f32; 2]; 3]>();
+        check::<[[f32; 4]; 3]>();
+        check::<[[f32; 2]; 4]>();
+        check::<[[f32; 4]; 4]>();
+    }
+}
@@ -0,0 +1,547 @@
+//! Compiling the same shader source under many combinations of preprocessor macros.
+//!
+//! A `ShaderVariantSet` declares the macros which vary, called dimensions, and the values each may
+//! take. A `VariantKey` picks one value for each dimension. Keys are enumerated and validated in
+//! plain Rust, and compiled on demand with a `ShaderCompiler`, usually a `DeviceCompiler`. Keys
+//! which produce the same compile options share one library.
+
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::error::Error;
+use std::fmt;
+use std::sync::mpsc::{Receiver, TryRecvError};
+use {CompileOptions, DeviceCompiler, Function, Library, PreprocessorMacroValue, ShaderCompiler};
+
+/// A choice of macro values, one for each dimension of a `ShaderVariantSet`. Dimensions whose
+/// macro is not defined in the variant are absent from the key.
+#[derive(Clone, Debug, Default, Eq, Hash, PartialEq)]
+pub struct VariantKey {
+    macros: BTreeMap<String, PreprocessorMacroValue>
+}
+
+impl VariantKey {
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// Defines a macro in the variant.
+    pub fn with<S, V>(mut self, name: S, value: V) -> Self
+        where S: Into<String>,
+              V: Into<PreprocessorMacroValue> {
+        self.macros.insert(name.into(), value.into());
+        self
+    }
+
+    /// Turns on a flag declared with `ShaderVariantSet::with_flag`.
+    pub fn with_flag<S: Into<String>>(self, name: S) -> Self {
+        self.with(name, 1)
+    }
+
+    pub fn get(&self, name: &str) -> Option<&PreprocessorMacroValue> {
+        self.macros.get(name)
+    }
+
+    pub fn is_defined(&self, name: &str) -> bool {
+        self.macros.contains_key(name)
+    }
+
+    /// The macros defined by the variant, in name order.
+    pub fn macros(&self) -> &BTreeMap<String, PreprocessorMacroValue> {
+        &self.macros
+    }
+}
+
+/// Formats the key as `NAME=VALUE` pairs separated by commas, such as `MSAA=4,SHADOWS=1`.
+impl fmt::Display for VariantKey {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for (i, (name, value)) in self.macros.iter().enumerate() {
+            if i > 0 {
+                try!(f.write_str(","));
+            }
+            try!(write!(f, "{}={}", name, value));
+        }
+        Ok(())
+    }
+}
+
+/// A macro which varies, and the values it may take. `None` means the macro is not defined.
+#[derive(Clone, Debug)]
+struct Dimension {
+    name: String,
+    values: Vec<Option<PreprocessorMacroValue>>
+}
+
+/// The state of one compiled variant.
+enum Compiled<C: ShaderCompiler> {
+    Compiling(Receiver<Result<C::Library, C::Error>>),
+    Finished(C::Library),
+    Failed(C::Error)
+}
+
+/// The state of a variant, returned by `ShaderVariantSet::status`.
+#[derive(Debug)]
+pub enum VariantStatus<'a, L: 'a, E: 'a> {
+    /// The variant has not been compiled.
+    NotRequested,
+
+    /// The variant is being compiled in the background.
+    Compiling,
+
+    /// The variant compiled successfully.
+    Compiled(&'a L),
+
+    /// The variant failed to compile.
+    Failed(&'a E)
+}
+
+/// The dimensions of a shader's variants, and the libraries compiled for them.
+///
+/// ```ignore
+/// let mut variants = ShaderVariantSet::new(SOURCE)
+///     .with_flag("SHADOWS")
+///     .with_flag("SKINNING")
+///     .with_dimension("MSAA", vec![1, 2, 4])
+///     .with_filter(|key| !key.is_defined("SKINNING") || key.get("MSAA") != Some(&4.into()));
+/// variants.request_all(&mut device);
+/// let key = VariantKey::new().with_flag("SHADOWS").with("MSAA", 4);
+/// let function = try!(variants.function(&mut device, &key, "fragment_main"));
+/// ```
+pub struct ShaderVariantSet<C: ShaderCompiler = DeviceCompiler> {
+    source: String,
+    options: CompileOptions,
+    dimensions: Vec<Dimension>,
+    filters: Vec<Box<Fn(&VariantKey) -> bool>>,
+    compiled: HashMap<CompileOptions, Compiled<C>>
+}
+
+impl<C: ShaderCompiler> ShaderVariantSet<C> {
+    /// Creates a set with no dimensions, which has a single variant.
+    pub fn new<S: Into<String>>(source: S) -> Self {
+        ShaderVariantSet {
+            source: source.into(),
+            options: CompileOptions::default(),
+            dimensions: vec![],
+            filters: vec![],
+            compiled: HashMap::new()
+        }
+    }
+
+    /// Sets the options which every variant is compiled with. The macros of a variant replace
+    /// macros with the same name in these options.
+    pub fn with_options(self, options: CompileOptions) -> Self {
+        ShaderVariantSet { options: options, ..self }
+    }
+
+    /// Adds a dimension which is either undefined or defined as `1`, for use with `#ifdef`.
+    pub fn with_flag<S: Into<String>>(self, name: S) -> Self {
+        self.with_dimension_values(name, vec![None, Some(1.into())])
+    }
+
+    /// Adds a dimension which is defined as one of `values`.
+    pub fn with_dimension<S, I>(self, name: S, values: I) -> Self
+        where S: Into<String>,
+              I: IntoIterator,
+              I::Item: Into<PreprocessorMacroValue> {
+        let values = values.into_iter().map(|value| Some(value.into())).collect();
+        self.with_dimension_values(name, values)
+    }
+
+    /// Adds a dimension which is defined as one of `values`, or undefined for `None`. Repeated
+    /// values are ignored. Adding a dimension with an existing name replaces it.
+    pub fn with_dimension_values<S>(mut self, name: S, values: Vec<Option<PreprocessorMacroValue>>)
+                                    -> Self
+        where S: Into<String> {
+        let name = name.into();
+        let mut unique = vec![];
+        for value in values {
+            if !unique.contains(&value) {
+                unique.push(value);
+            }
+        }
+
+        self.dimensions.retain(|dimension| dimension.name != name);
+        self.dimensions.push(Dimension { name: name, values: unique });
+        self
+    }
+
+    /// Adds a filter, so that only the combinations for which it returns `true` are variants.
+    pub fn with_filter<F: Fn(&VariantKey) -> bool + 'static>(mut self, filter: F) -> Self {
+        self.filters.push(Box::new(filter));
+        self
+    }
+
+    pub fn source(&self) -> &str {
+        &self.source
+    }
+
+    /// Every variant, in the order of the dimensions' values, leaving out combinations rejected by
+    /// a filter and combinations with the same compile options as an earlier variant.
+    pub fn variants(&self) -> Vec<VariantKey> {
+        let mut keys = vec![VariantKey::new()];
+        for dimension in &self.dimensions {
+            keys = keys.iter()
+                .flat_map(|key| {
+                    dimension.values.iter().map(move |value| {
+                        match *value {
+                            Some(ref value) => key.clone().with(&dimension.name[..], value.clone()),
+                            None => key.clone(),
+                        }
+                    })
+                })
+                .collect();
+        }
+
+        let mut seen = HashSet::new();
+        keys.into_iter()
+            .filter(|key| self.filters.iter().all(|filter| filter(key)))
+            .filter(|key| seen.insert(self.options_for(key)))
+            .collect()
+    }
+
+    /// Checks that `key` gives every dimension one of its values, and is not rejected by a filter.
+    pub fn validate<E>(&self, key: &VariantKey) -> Result<(), VariantError<E>> {
+        for name in key.macros.keys() {
+            if !self.dimensions.iter().any(|dimension| dimension.name == *name) {
+                return Err(VariantError::UnknownMacro { name: name.clone() });
+            }
+        }
+
+        for dimension in &self.dimensions {
+            let value = key.get(&dimension.name).cloned();
+            if !dimension.values.contains(&value) {
+                let name = dimension.name.clone();
+                return Err(match value {
+                    Some(value) => VariantError::UnsupportedValue { name: name, value: value },
+                    None => VariantError::MissingMacro { name: name },
+                });
+            }
+        }
+
+        if self.filters.iter().all(|filter| filter(key)) {
+            Ok(())
+        } else {
+            Err(VariantError::Excluded { key: key.clone() })
+        }
+    }
+
+    /// The options which the variant is compiled with.
+    pub fn options_for(&self, key: &VariantKey) -> CompileOptions {
+        let mut options = self.options.clone();
+        for (name, value) in &key.macros {
+            options.preprocessor_macros.insert(name.clone(), value.clone());
+        }
+        options
+    }
+
+    /// Starts compiling the variant in the background, unless it has already been started.
+    pub fn request(&mut self, compiler: &mut C, key: &VariantKey)
+                   -> Result<(), VariantError<C::Error>> {
+        try!(self.validate(key));
+        let options = self.options_for(key);
+        if !self.compiled.contains_key(&options) {
+            let receiver = compiler.compile_with_options(&self.source, &options);
+            self.compiled.insert(options, Compiled::Compiling(receiver));
+        }
+        Ok(())
+    }
+
+    /// Starts compiling every variant which has not been started.
+    pub fn request_all(&mut self, compiler: &mut C) {
+        for key in self.variants() {
+            let _ = self.request(compiler, &key);
+        }
+    }
+
+    /// Collects the results of finished background compiles, and returns how many finished.
+    pub fn update(&mut self) -> usize {
+        let mut finished = 0;
+        for compiled in self.compiled.values_mut() {
+            let result = match *compiled {
+                Compiled::Compiling(ref receiver) => {
+                    match receiver.try_recv() {
+                        Ok(result) => result,
+                        Err(TryRecvError::Empty) => continue,
+                        Err(TryRecvError::Disconnected) => {
+                            // Leave the variant to be reported by `wait`.
+                            continue;
+                        }
+                    }
+                }
+                _ => continue,
+            };
+            *compiled = match result {
+                Ok(library) => Compiled::Finished(library),
+                Err(error) => Compiled::Failed(error),
+            };
+            finished += 1;
+        }
+        finished
+    }
+
+    /// The state of the variant, as of the last `update`.
+    pub fn status(&self, key: &VariantKey) -> VariantStatus<C::Library, C::Error> {
+        match self.compiled.get(&self.options_for(key)) {
+            None => VariantStatus::NotRequested,
+            Some(&Compiled::Compiling(_)) => VariantStatus::Compiling,
+            Some(&Compiled::Finished(ref library)) => VariantStatus::Compiled(library),
+            Some(&Compiled::Failed(ref error)) => VariantStatus::Failed(error),
+        }
+    }
+
+    /// The library of the variant, if it has finished compiling as of the last `update`.
+    pub fn library(&self, key: &VariantKey) -> Option<&C::Library> {
+        match self.status(key) {
+            VariantStatus::Compiled(library) => Some(library),
+            _ => None,
+        }
+    }
+
+    /// Returns the library of the variant, compiling it and waiting for the compile to finish if
+    /// needed.
+    ///
+    /// If the variant failed to compile, the error is returned and the variant is forgotten, so
+    /// that the next request compiles it again.
+    pub fn wait(&mut self, compiler: &mut C, key: &VariantKey)
+                -> Result<&mut C::Library, VariantError<C::Error>> {
+        try!(self.request(compiler, key));
+        let options = self.options_for(key);
+        let result = match self.compiled.remove(&options) {
+            Some(Compiled::Compiling(receiver)) => {
+                receiver.recv().map_err(|_| VariantError::CompilerDisconnected)
+                                .and_then(|result| result.map_err(VariantError::Compile))
+            }
+            Some(Compiled::Finished(library)) => Ok(library),
+            Some(Compiled::Failed(error)) => Err(VariantError::Compile(error)),
+            None => unreachable!(),
+        };
+
+        let compiled = self.compiled.entry(options).or_insert(Compiled::Finished(try!(result)));
+        match *compiled {
+            Compiled::Finished(ref mut library) => Ok(library),
+            _ => unreachable!(),
+        }
+    }
+
+    /// Forgets every compiled variant.
+    pub fn clear(&mut self) {
+        self.compiled.clear();
+    }
+}
+
+impl<C: ShaderCompiler<Library = Library>> ShaderVariantSet<C> {
+    /// Returns a function from the library of the variant, compiling it if needed.
+    pub fn function(&mut self, compiler: &mut C, key: &VariantKey, name: &str)
+                    -> Result<Function, VariantError<C::Error>> {
+        let library = try!(self.wait(compiler, key));
+        library.new_function_with_name(name)
+               .ok_or_else(|| VariantError::MissingFunction { name: name.into() })
+    }
+}
+
+impl<C: ShaderCompiler> fmt::Debug for ShaderVariantSet<C> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "ShaderVariantSet {{ dimensions: {:?}, compiled: {} }}", self.dimensions,
+               self.compiled.len())
+    }
+}
+
+/// An error produced when looking up or compiling a variant.
+#[derive(Debug)]
+pub enum VariantError<E> {
+    /// The key defines a macro which is not a dimension.
+    UnknownMacro { name: String },
+
+    /// The key does not define a dimension which must be defined.
+    MissingMacro { name: String },
+
+    /// The key gives a dimension a value which it does not allow.
+    UnsupportedValue { name: String, value: PreprocessorMacroValue },
+
+    /// The key is rejected by a filter.
+    Excluded { key: VariantKey },
+
+    /// The compiler reported an error.
+    Compile(E),
+
+    /// The compiler dropped its sender without sending a result.
+    CompilerDisconnected,
+
+    /// The library of the variant has no function with the name.
+    MissingFunction { name: String }
+}
+
+impl<E: fmt::Display> fmt::Display for VariantError<E> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            VariantError::UnknownMacro { ref name } => {
+                write!(f, "`{}` is not a dimension of the variants", name)
+            }
+            VariantError::MissingMacro { ref name } => {
+                write!(f, "the variant does not define `{}`", name)
+            }
+            VariantError::UnsupportedValue { ref name, ref value } => {
+                write!(f, "`{}` cannot be {}", name, value)
+            }
+            VariantError::Excluded { ref key } => write!(f, "`{}` is not a variant", key),
+            VariantError::Compile(ref error) => write!(f, "{}", error),
+            VariantError::CompilerDisconnected => {
+                f.write_str("the compiler finished without a result")
+            }
+            VariantError::MissingFunction { ref name } => {
+                write!(f, "the variant has no function named `{}`", name)
+            }
+        }
+    }
+}
+
+impl<E: Error> Error for VariantError<E> {
+    fn description(&self) -> &str {
+        match *self {
+            VariantError::UnknownMacro { .. } => "The macro is not a dimension of the variants",
+            VariantError::MissingMacro { .. } => "The variant does not define a dimension",
+            VariantError::UnsupportedValue { .. } => "The dimension does not allow the value",
+            VariantError::Excluded { .. } => "The key is not a variant",
+            VariantError::Compile(_) => "The variant could not be compiled",
+            VariantError::CompilerDisconnected => "The compiler finished without a result",
+            VariantError::MissingFunction { .. } => "The variant has no function with the name",
+        }
+    }
+
+    fn cause(&self) -> Option<&Error> {
+        match *self {
+            VariantError::Compile(ref error) => Some(error),
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::mpsc::{self, Receiver};
+    use {CompileOptions, PreprocessorMacroValue, ShaderCompiler};
+
+    /// Compiles immediately, failing if `BROKEN` is defined, and counts its compiles.
+    #[derive(Default)]
+    struct FakeCompiler {
+        compiles: usize
+    }
+
+    impl ShaderCompiler for FakeCompiler {
+        type Library = String;
+        type Error = String;
+
+        fn compile(&mut self, source: &str) -> Receiver<Result<String, String>> {
+            self.compile_with_options(source, &CompileOptions::default())
+        }
+
+        fn compile_with_options(&mut self, source: &str, options: &CompileOptions)
+                                -> Receiver<Result<String, String>> {
+            self.compiles += 1;
+            let (sender, receiver) = mpsc::channel();
+            let defines = options.to_defines();
+            let result = if defines.contains("BROKEN") { Err(defines) } else { Ok(defines) };
+            sender.send(result.map(|defines| format!("{}{}", defines, source))).unwrap();
+            receiver
+        }
+    }
+
+    fn lighting_variants() -> ShaderVariantSet<FakeCompiler> {
+        ShaderVariantSet::new("fragment void main() {}\n")
+            .with_flag("SHADOWS")
+            .with_flag("SKINNING")
+            .with_dimension("MSAA", vec![1, 2, 4, 2])
+            .with_filter(|key| !key.is_defined("SKINNING") || key.get("MSAA") != Some(&4.into()))
+    }
+
+    #[test]
+    fn test_enumerate_variants() {
+        let variants = lighting_variants().variants();
+        assert_eq!(variants.len(), 2 * 2 * 3 - 2);
+        assert_eq!(variants[0], VariantKey::new().with("MSAA", 1));
+        assert_eq!(variants[3].to_string(), "MSAA=1,SKINNING=1");
+        assert!(!variants.contains(&VariantKey::new().with_flag("SKINNING").with("MSAA", 4)));
+
+        // Variants which only differ by macros already in the base options are the same variant.
+        let deduplicated = ShaderVariantSet::<FakeCompiler>::new("")
+            .with_options(CompileOptions::default().with_macro("FOG", 1))
+            .with_flag("FOG")
+            .with_flag("SHADOWS");
+        assert_eq!(deduplicated.variants(),
+                   [VariantKey::new(), VariantKey::new().with_flag("SHADOWS")]);
+        assert_eq!(ShaderVariantSet::<FakeCompiler>::new("").variants(), [VariantKey::new()]);
+    }
+
+    #[test]
+    fn test_validate_keys() {
+        let variants = lighting_variants();
+        let valid = VariantKey::new().with_flag("SHADOWS").with("MSAA", 2);
+        assert!(variants.validate::<()>(&valid).is_ok());
+        assert_eq!(variants.options_for(&valid).preprocessor_macros.len(), 2);
+
+        match variants.validate::<()>(&valid.clone().with("FOG", 1)) {
+            Err(VariantError::UnknownMacro { ref name }) if name == "FOG" => (),
+            other => panic!("expected an unknown macro, found {:?}", other),
+        }
+        match variants.validate::<()>(&VariantKey::new().with_flag("SHADOWS")) {
+            Err(VariantError::MissingMacro { ref name }) if name == "MSAA" => (),
+            other => panic!("expected a missing macro, found {:?}", other),
+        }
+        match variants.validate::<()>(&VariantKey::new().with("MSAA", 8)) {
+            Err(VariantError::UnsupportedValue { ref name, ref value }) if name == "MSAA" => {
+                assert_eq!(*value, PreprocessorMacroValue::Integer(8));
+            }
+            other => panic!("expected an unsupported value, found {:?}", other),
+        }
+        match variants.validate::<()>(&VariantKey::new().with_flag("SKINNING").with("MSAA", 4)) {
+            Err(VariantError::Excluded { .. }) => (),
+            other => panic!("expected an excluded key, found {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_compile_variants() {
+        let mut compiler = FakeCompiler::default();
+        let mut variants = lighting_variants()
+            .with_dimension_values("BROKEN", vec![None, Some("yes".into())]);
+        let shadows = VariantKey::new().with_flag("SHADOWS").with("MSAA", 2);
+        let broken = shadows.clone().with("BROKEN", "yes");
+
+        match variants.status(&shadows) {
+            VariantStatus::NotRequested => (),
+            status => panic!("expected no compile, found {:?}", status),
+        }
+        variants.request(&mut compiler, &shadows).unwrap();
+        variants.request(&mut compiler, &shadows).unwrap();
+        assert_eq!(compiler.compiles, 1);
+        assert!(variants.library(&shadows).is_none());
+
+        assert_eq!(variants.update(), 1);
+        assert!(variants.library(&shadows).unwrap().starts_with("#define MSAA 2\n"));
+        assert!(variants.wait(&mut compiler, &shadows).is_ok());
+        assert_eq!(compiler.compiles, 1);
+
+        variants.request(&mut compiler, &broken).unwrap();
+        variants.update();
+        match variants.status(&broken) {
+            VariantStatus::Failed(_) => (),
+            status => panic!("expected a failure, found {:?}", status),
+        }
+        match variants.wait(&mut compiler, &broken) {
+            Err(VariantError::Compile(_)) => (),
+            other => panic!("expected a compile error, found {:?}", other),
+        }
+        assert!(variants.wait(&mut compiler, &broken).is_err());
+        assert_eq!(compiler.compiles, 3);
+
+        // The first variant is already compiled, and the broken one was forgotten.
+        variants.request_all(&mut compiler);
+        assert_eq!(variants.variants().len(), 20);
+        assert_eq!(compiler.compiles, 3 + 19);
+        variants.update();
+        let compiled = variants.variants()
+            .iter()
+            .filter(|key| variants.library(key).is_some())
+            .count();
+        assert_eq!(compiled, 10);
+    }
+}
@@ -0,0 +1,554 @@
+//! Recompiling shaders when their files change, for use during development.
+//!
+//! A `ShaderWatcher` polls the modification times and sizes of a set of files, and reports a
+//! change once the files have stopped changing for a debounce interval, so that an editor saving
+//! several files does not trigger several compiles. A `ReloadableLibrary` uses a watcher to
+//! assemble and recompile a shader in the background, replacing its library only when the compile
+//! succeeds.
+
+use std::collections::HashMap;
+use std::error::Error;
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{Receiver, TryRecvError};
+use std::time::{Duration, Instant, SystemTime};
+use {CompileOptions, Device, DirectoryProvider, Library, LibraryError, ShaderSourceError,
+     ShaderSourceSet};
+
+/// The state of a file when it was last polled. A missing file has no stamp.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+struct FileStamp {
+    modified: Option<SystemTime>,
+    len: u64
+}
+
+impl FileStamp {
+    fn of(path: &Path) -> Option<FileStamp> {
+        fs::metadata(path).ok().map(|metadata| {
+            FileStamp { modified: metadata.modified().ok(), len: metadata.len() }
+        })
+    }
+}
+
+/// Polls a set of files for changes.
+#[derive(Clone, Debug)]
+pub struct ShaderWatcher {
+    files: HashMap<PathBuf, Option<FileStamp>>,
+    debounce: Duration,
+    last_change: Option<Instant>
+}
+
+impl ShaderWatcher {
+    /// Creates a watcher which reports changes once the files have not changed for `debounce`.
+    pub fn new(debounce: Duration) -> Self {
+        ShaderWatcher { files: HashMap::new(), debounce: debounce, last_change: None }
+    }
+
+    /// Starts watching a file. The current state of the file is not reported as a change.
+    pub fn watch<P: Into<PathBuf>>(&mut self, path: P) {
+        let path = path.into();
+        let stamp = FileStamp::of(&path);
+        self.files.entry(path).or_insert(stamp);
+    }
+
+    /// Watches exactly the given files, keeping the state of files which were already watched.
+    pub fn set_paths<I>(&mut self, paths: I)
+        where I: IntoIterator,
+              I::Item: Into<PathBuf> {
+        let mut files = HashMap::new();
+        for path in paths {
+            let path = path.into();
+            let stamp = match self.files.get(&path) {
+                Some(&stamp) => stamp,
+                None => FileStamp::of(&path),
+            };
+            files.insert(path, stamp);
+        }
+        self.files = files;
+    }
+
+    pub fn paths(&self) -> Vec<&Path> {
+        self.files.keys().map(|path| path.as_path()).collect()
+    }
+
+    pub fn debounce(&self) -> Duration {
+        self.debounce
+    }
+
+    /// Checks the files, and returns `true` if they have changed and then stayed unchanged for the
+    /// debounce interval.
+    pub fn poll(&mut self) -> bool {
+        self.poll_at(Instant::now())
+    }
+
+    /// Like `poll`, but with the current time given explicitly.
+    pub fn poll_at(&mut self, now: Instant) -> bool {
+        for (path, stamp) in &mut self.files {
+            let current = FileStamp::of(path);
+            if current != *stamp {
+                *stamp = current;
+                self.last_change = Some(now);
+            }
+        }
+
+        match self.last_change {
+            Some(last_change) if now.duration_since(last_change) >= self.debounce => {
+                self.last_change = None;
+                true
+            }
+            _ => false,
+        }
+    }
+}
+
+/// Compiles shader source in the background, such as with `Device::new_library_with_source_async`.
+pub trait ShaderCompiler {
+    type Library;
+    type Error;
+
+    /// Starts compiling `source`. The result is sent to the receiver when the compile finishes.
+    fn compile(&mut self, source: &str) -> Receiver<Result<Self::Library, Self::Error>>;
+
+    /// Starts compiling `source` with `options` instead of the compiler's own options.
+    fn compile_with_options(&mut self, source: &str, options: &CompileOptions)
+                            -> Receiver<Result<Self::Library, Self::Error>>;
+}
+
+/// Compiles shaders with a `Device`.
+pub struct DeviceCompiler {
+    device: Device,
+    options: CompileOptions
+}
+
+impl DeviceCompiler {
+    pub fn new(device: Device, options: CompileOptions) -> Self {
+        DeviceCompiler { device: device, options: options }
+    }
+
+    pub fn device(&self) -> &Device {
+        &self.device
+    }
+
+    /// Changes the options used by later compiles.
+    pub fn set_options(&mut self, options: CompileOptions) {
+        self.options = options;
+    }
+}
+
+impl ShaderCompiler for DeviceCompiler {
+    type Library = Library;
+    type Error = LibraryError;
+
+    fn compile(&mut self, source: &str) -> Receiver<Result<Library, LibraryError>> {
+        self.device.new_library_with_source_async(source, &self.options)
+    }
+
+    fn compile_with_options(&mut self, source: &str, options: &CompileOptions)
+                            -> Receiver<Result<Library, LibraryError>> {
+        self.device.new_library_with_source_async(source, options)
+    }
+}
+
+/// Compiles with the default options, unless others are given.
+impl ShaderCompiler for Device {
+    type Library = Library;
+    type Error = LibraryError;
+
+    fn compile(&mut self, source: &str) -> Receiver<Result<Library, LibraryError>> {
+        self.new_library_with_source_async(source, &CompileOptions::default())
+    }
+
+    fn compile_with_options(&mut self, source: &str, options: &CompileOptions)
+                            -> Receiver<Result<Library, LibraryError>> {
+        self.new_library_with_source_async(source, options)
+    }
+}
+
+/// The result of `ReloadableLibrary::update`.
+#[derive(Debug)]
+pub enum ReloadStatus<E> {
+    /// Nothing has finished since the last update.
+    Unchanged,
+
+    /// A compile has been started.
+    Compiling,
+
+    /// The library was replaced, and has the given generation.
+    Reloaded(u64),
+
+    /// Reloading failed, and the previous library is still in use.
+    Failed(ReloadError<E>)
+}
+
+/// The reason that a `ReloadableLibrary` could not reload.
+#[derive(Debug)]
+pub enum ReloadError<E> {
+    /// The shader source could not be assembled.
+    Source(ShaderSourceError),
+
+    /// The compiler reported an error.
+    Compile(E),
+
+    /// The compiler dropped its sender without sending a result.
+    CompilerDisconnected
+}
+
+impl<E: fmt::Display> fmt::Display for ReloadError<E> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            ReloadError::Source(ref error) => write!(f, "{}", error),
+            ReloadError::Compile(ref error) => write!(f, "{}", error),
+            ReloadError::CompilerDisconnected => {
+                f.write_str("the compiler finished without a result")
+            }
+        }
+    }
+}
+
+impl<E: Error> Error for ReloadError<E> {
+    fn description(&self) -> &str {
+        match *self {
+            ReloadError::Source(_) => "The shader source could not be assembled",
+            ReloadError::Compile(_) => "The shader could not be compiled",
+            ReloadError::CompilerDisconnected => "The compiler finished without a result",
+        }
+    }
+
+    fn cause(&self) -> Option<&Error> {
+        match *self {
+            ReloadError::Source(ref error) => Some(error),
+            ReloadError::Compile(ref error) => Some(error),
+            ReloadError::CompilerDisconnected => None,
+        }
+    }
+}
+
+/// A library which is recompiled from a directory of shader files when they change.
+///
+/// Call `update` regularly, such as once a frame. The library is only replaced when a compile
+/// succeeds; the generation counts the replacements, so that dependents such as pipeline states
+/// can tell when they need to be rebuilt.
+pub struct ReloadableLibrary<C: ShaderCompiler = DeviceCompiler> {
+    compiler: C,
+    options: Option<CompileOptions>,
+    directory: PathBuf,
+    root: String,
+    sources: ShaderSourceSet,
+    watcher: ShaderWatcher,
+    library: Option<C::Library>,
+    generation: u64,
+    compiling: Option<Receiver<Result<C::Library, C::Error>>>,
+    changed_while_compiling: bool,
+    start_compile: bool
+}
+
+impl<C: ShaderCompiler> ReloadableLibrary<C> {
+    /// Creates a library which compiles the file `root` in `directory`, along with the files it
+    /// includes. The first compile is started by the first call to `update`.
+    pub fn new<P: Into<PathBuf>>(compiler: C, directory: P, root: &str, debounce: Duration)
+                                 -> Self {
+        let directory = directory.into();
+        let mut watcher = ShaderWatcher::new(debounce);
+        watcher.watch(directory.join(root));
+        ReloadableLibrary {
+            compiler: compiler,
+            options: None,
+            sources: ShaderSourceSet::new().with_provider(DirectoryProvider::new(&directory)),
+            directory: directory,
+            root: root.into(),
+            watcher: watcher,
+            library: None,
+            generation: 0,
+            compiling: None,
+            changed_while_compiling: false,
+            start_compile: true
+        }
+    }
+
+    /// Compiles the library with `options` instead of the compiler's own options.
+    pub fn with_options(self, options: CompileOptions) -> Self {
+        ReloadableLibrary { options: Some(options), ..self }
+    }
+
+    /// Changes the options used to compile the library, and compiles it again at the next update.
+    pub fn set_options(&mut self, options: CompileOptions) {
+        self.options = Some(options);
+        self.reload();
+    }
+
+    /// The most recent library which compiled successfully.
+    pub fn library(&self) -> Option<&C::Library> {
+        self.library.as_ref()
+    }
+
+    /// The number of times the library has been replaced. This is 0 until the first successful
+    /// compile.
+    pub fn generation(&self) -> u64 {
+        self.generation
+    }
+
+    pub fn compiler(&mut self) -> &mut C {
+        &mut self.compiler
+    }
+
+    /// Returns `true` while a compile is in progress.
+    pub fn is_compiling(&self) -> bool {
+        self.compiling.is_some()
+    }
+
+    /// The files which are being watched: the root file and the files which the last assembly
+    /// included or looked for, even if it failed.
+    pub fn watched_paths(&self) -> Vec<&Path> {
+        self.watcher.paths()
+    }
+
+    /// Compiles the library again at the next update, even if no files have changed.
+    pub fn reload(&mut self) {
+        self.start_compile = true;
+    }
+
+    /// Checks for changed files and finished compiles.
+    pub fn update(&mut self) -> ReloadStatus<C::Error> {
+        self.update_at(Instant::now())
+    }
+
+    /// Like `update`, but with the current time given explicitly.
+    pub fn update_at(&mut self, now: Instant) -> ReloadStatus<C::Error> {
+        if self.watcher.poll_at(now) {
+            if self.compiling.is_some() {
+                self.changed_while_compiling = true;
+            } else {
+                self.start_compile = true;
+            }
+        }
+
+        let result = match self.compiling.as_ref().map(|receiver| receiver.try_recv()) {
+            None | Some(Err(TryRecvError::Empty)) => None,
+            Some(Ok(result)) => Some(result.map_err(ReloadError::Compile)),
+            Some(Err(TryRecvError::Disconnected)) => Some(Err(ReloadError::CompilerDisconnected)),
+        };
+        if let Some(result) = result {
+            self.compiling = None;
+            if self.changed_while_compiling {
+                self.changed_while_compiling = false;
+                self.start_compile = true;
+            }
+            return match result {
+                Ok(library) => {
+                    self.library = Some(library);
+                    self.generation += 1;
+                    ReloadStatus::Reloaded(self.generation)
+                }
+                Err(error) => ReloadStatus::Failed(error),
+            };
+        }
+
+        if self.start_compile && self.compiling.is_none() {
+            self.start_compile = false;
+            // Watch the files which were looked for even if assembly fails, so that adding a
+            // missing include starts another compile.
+            let (assembled, paths) = self.sources.assemble_with_paths(&self.root);
+            let directory = &self.directory;
+            self.watcher.set_paths(paths.iter().map(|path| directory.join(path)));
+            let assembled = match assembled {
+                Ok(assembled) => assembled,
+                Err(error) => return ReloadStatus::Failed(ReloadError::Source(error)),
+            };
+            self.compiling = Some(match self.options {
+                Some(ref options) => self.compiler.compile_with_options(&assembled, options),
+                None => self.compiler.compile(&assembled),
+            });
+            return ReloadStatus::Compiling;
+        }
+
+        ReloadStatus::Unchanged
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::env;
+    use std::fs::{self, File};
+    use std::io::Write;
+    use std::sync::mpsc::{self, Receiver, Sender};
+    use std::time::{Duration, Instant};
+    use CompileOptions;
+
+    /// Sends the result of each compile when the test asks for it.
+    #[derive(Default)]
+    struct FakeCompiler {
+        sources: Vec<String>,
+        senders: Vec<Sender<Result<String, String>>>
+    }
+
+    impl FakeCompiler {
+        fn finish(&mut self) {
+            let sender = self.senders.remove(0);
+            let source = self.sources.last().unwrap().clone();
+            let result = if source.contains("error") { Err(source) } else { Ok(source) };
+            sender.send(result).unwrap();
+        }
+    }
+
+    impl ShaderCompiler for FakeCompiler {
+        type Library = String;
+        type Error = String;
+
+        fn compile(&mut self, source: &str) -> Receiver<Result<String, String>> {
+            let (sender, receiver) = mpsc::channel();
+            self.sources.push(source.into());
+            self.senders.push(sender);
+            receiver
+        }
+
+        fn compile_with_options(&mut self, source: &str, options: &CompileOptions)
+                                -> Receiver<Result<String, String>> {
+            self.compile(&format!("{}{}", options.to_defines(), source))
+        }
+    }
+
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir = env::temp_dir().join(format!("metl-{}-{}", name, ::std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    fn write(path: &Path, contents: &str) {
+        File::create(path).and_then(|mut file| file.write_all(contents.as_bytes())).unwrap();
+    }
+
+    #[test]
+    fn test_watcher_debounces_changes() {
+        let dir = temp_dir("watcher");
+        let path = dir.join("shader.metal");
+        write(&path, "a");
+
+        let debounce = Duration::from_millis(100);
+        let mut watcher = ShaderWatcher::new(debounce);
+        watcher.watch(&path);
+        let start = Instant::now();
+        assert!(!watcher.poll_at(start));
+
+        // The sizes differ, so the changes are seen even if the modification time is coarse.
+        write(&path, "ab");
+        assert!(!watcher.poll_at(start));
+        write(&path, "abc");
+        assert!(!watcher.poll_at(start + debounce / 2));
+        assert!(!watcher.poll_at(start + debounce));
+        assert!(watcher.poll_at(start + debounce / 2 + debounce));
+        assert!(!watcher.poll_at(start + debounce * 3));
+
+        fs::remove_file(&path).unwrap();
+        assert!(!watcher.poll_at(start + debounce * 4));
+        assert!(watcher.poll_at(start + debounce * 5));
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_reload_on_change() {
+        let dir = temp_dir("reload");
+        write(&dir.join("main.metal"), "#include \"common.h\"\nmain\n");
+        write(&dir.join("common.h"), "common\n");
+
+        let debounce = Duration::from_millis(100);
+        let mut library = ReloadableLibrary::new(FakeCompiler::default(), &dir, "main.metal",
+                                                 debounce);
+        let start = Instant::now();
+        match library.update_at(start) {
+            ReloadStatus::Compiling => (),
+            status => panic!("expected a compile to start, found {:?}", status),
+        }
+        assert_eq!(library.watched_paths().len(), 2);
+        assert!(library.library().is_none());
+        match library.update_at(start) {
+            ReloadStatus::Unchanged => (),
+            status => panic!("expected no change, found {:?}", status),
+        }
+
+        library.compiler().finish();
+        match library.update_at(start) {
+            ReloadStatus::Reloaded(1) => (),
+            status => panic!("expected the first generation, found {:?}", status),
+        }
+        assert!(library.library().unwrap().contains("common\n"));
+
+        // A failed compile keeps the previous library.
+        write(&dir.join("common.h"), "error\n");
+        library.update_at(start);
+        match library.update_at(start + debounce) {
+            ReloadStatus::Compiling => (),
+            status => panic!("expected a compile to start, found {:?}", status),
+        }
+        library.compiler().finish();
+        match library.update_at(start + debounce) {
+            ReloadStatus::Failed(ReloadError::Compile(_)) => (),
+            status => panic!("expected a compile error, found {:?}", status),
+        }
+        assert!(library.library().unwrap().contains("common\n"));
+        assert_eq!(library.generation(), 1);
+
+        // Changes made while compiling start another compile when the first one finishes.
+        write(&dir.join("common.h"), "fixed common\n");
+        library.reload();
+        library.update_at(start + debounce * 2);
+        library.update_at(start + debounce * 3);
+        assert!(library.is_compiling());
+        library.compiler().finish();
+        match library.update_at(start + debounce * 3) {
+            ReloadStatus::Reloaded(2) => (),
+            status => panic!("expected the second generation, found {:?}", status),
+        }
+        match library.update_at(start + debounce * 3) {
+            ReloadStatus::Compiling => (),
+            status => panic!("expected another compile to start, found {:?}", status),
+        }
+        library.compiler().finish();
+        library.update_at(start + debounce * 3);
+        assert_eq!(library.generation(), 3);
+        assert_eq!(library.compiler().sources.len(), 4);
+
+        // Missing includes are reported without compiling.
+        write(&dir.join("main.metal"), "#include \"missing.h\"\n");
+        library.update_at(start + debounce * 4);
+        match library.update_at(start + debounce * 5) {
+            ReloadStatus::Failed(ReloadError::Source(_)) => (),
+            status => panic!("expected a source error, found {:?}", status),
+        }
+        assert!(!library.is_compiling());
+
+        // Adding the missing include compiles the library again.
+        assert!(library.watched_paths().contains(&dir.join("missing.h").as_path()));
+        write(&dir.join("missing.h"), "missing\n");
+        library.update_at(start + debounce * 6);
+        match library.update_at(start + debounce * 7) {
+            ReloadStatus::Compiling => (),
+            status => panic!("expected a compile to start, found {:?}", status),
+        }
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_reload_with_options() {
+        let dir = temp_dir("reload-options");
+        write(&dir.join("main.metal"), "main\n");
+
+        let options = CompileOptions::default().with_macro("QUALITY", 2);
+        let mut library = ReloadableLibrary::new(FakeCompiler::default(), &dir, "main.metal",
+                                                 Duration::from_millis(100))
+                              .with_options(options);
+        let start = Instant::now();
+        library.update_at(start);
+        library.compiler().finish();
+        library.update_at(start);
+        assert!(library.library().unwrap().starts_with("#define QUALITY 2\n"));
+
+        library.set_options(CompileOptions::default());
+        library.update_at(start);
+        library.compiler().finish();
+        library.update_at(start);
+        assert!(!library.library().unwrap().contains("QUALITY"));
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,320 @@
+use std::error::Error;
+use std::fmt;
+use {Argument, DataType, MemberType, ReflectedArray, ReflectedStruct, ShaderStruct, StructLayout};
+
+/// A difference between a Rust struct description and the struct reflected from a shader.
+///
+/// Members are named by their path from the outer struct, such as `lights[].position`.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum StructMismatch {
+    /// The shader struct has a member which the Rust struct does not.
+    MissingMember { member: String },
+
+    /// The Rust struct has a member which the shader struct does not.
+    UnexpectedMember { member: String },
+
+    /// A member is at a different offset.
+    OffsetMismatch { member: String, expected: usize, actual: usize },
+
+    /// A member has a different type.
+    TypeMismatch { member: String, expected: DataType, actual: DataType },
+
+    /// An array member has a different number of elements.
+    ArrayLengthMismatch { member: String, expected: usize, actual: usize },
+
+    /// The elements of an array member are a different distance apart.
+    StrideMismatch { member: String, expected: usize, actual: usize },
+
+    /// The struct has a different size.
+    SizeMismatch { expected: usize, actual: usize }
+}
+
+impl fmt::Display for StructMismatch {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            StructMismatch::MissingMember { ref member } => {
+                write!(f, "member `{}` is missing from the Rust struct", member)
+            }
+            StructMismatch::UnexpectedMember { ref member } => {
+                write!(f, "member `{}` is not in the shader struct", member)
+            }
+            StructMismatch::OffsetMismatch { ref member, expected, actual } => {
+                write!(f, "member `{}` is at offset {}, but the shader expects {}", member,
+                       actual, expected)
+            }
+            StructMismatch::TypeMismatch { ref member, expected, actual } => {
+                write!(f, "member `{}` has type {:?}, but the shader expects {:?}", member,
+                       actual, expected)
+            }
+            StructMismatch::ArrayLengthMismatch { ref member, expected, actual } => {
+                write!(f, "member `{}` has {} elements, but the shader expects {}", member,
+                       actual, expected)
+            }
+            StructMismatch::StrideMismatch { ref member, expected, actual } => {
+                write!(f, "member `{}` has a stride of {}, but the shader expects {}", member,
+                       actual, expected)
+            }
+            StructMismatch::SizeMismatch { expected, actual } => {
+                write!(f, "the struct is {} bytes, but the shader expects {}", actual, expected)
+            }
+        }
+    }
+}
+
+/// The differences between a Rust struct description and the struct reflected from a shader.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct StructDiff {
+    mismatches: Vec<StructMismatch>
+}
+
+impl StructDiff {
+    pub fn mismatches(&self) -> &[StructMismatch] {
+        &self.mismatches
+    }
+
+    /// Returns `true` if the structs match.
+    pub fn is_empty(&self) -> bool {
+        self.mismatches.is_empty()
+    }
+
+    fn push(&mut self, mismatch: StructMismatch) {
+        self.mismatches.push(mismatch);
+    }
+
+    fn diff_struct(&mut self, path: &str, layout: &StructLayout, reflected: &ReflectedStruct) {
+        for reflected_member in &reflected.members {
+            let member = join(path, &reflected_member.name);
+            let layout_member = match layout.member_by_name(&reflected_member.name) {
+                Some(layout_member) => layout_member,
+                None => {
+                    self.push(StructMismatch::MissingMember { member: member });
+                    continue;
+                }
+            };
+
+            if layout_member.offset() != reflected_member.offset {
+                self.push(StructMismatch::OffsetMismatch {
+                    member: member.clone(),
+                    expected: reflected_member.offset,
+                    actual: layout_member.offset()
+                });
+            }
+
+            self.diff_type(&member, layout_member.member_type(), reflected_member.data_type,
+                           reflected_member.array_type.as_ref(),
+                           reflected_member.struct_type.as_ref());
+        }
+
+        for layout_member in layout.members() {
+            if reflected.member_by_name(layout_member.name()).is_none() {
+                let member = join(path, layout_member.name());
+                self.push(StructMismatch::UnexpectedMember { member: member });
+            }
+        }
+    }
+
+    fn diff_type(&mut self, member: &str, member_type: &MemberType, data_type: DataType,
+                 array_type: Option<&ReflectedArray>, struct_type: Option<&ReflectedStruct>) {
+        match (member_type, array_type, struct_type) {
+            (&MemberType::Array(ref element_type, len), Some(array_type), _) => {
+                if len != array_type.array_length {
+                    self.push(StructMismatch::ArrayLengthMismatch {
+                        member: member.into(),
+                        expected: array_type.array_length,
+                        actual: len
+                    });
+                }
+
+                if element_type.stride() != array_type.stride {
+                    self.push(StructMismatch::StrideMismatch {
+                        member: member.into(),
+                        expected: array_type.stride,
+                        actual: element_type.stride()
+                    });
+                }
+
+                let element = format!("{}[]", member);
+                self.diff_type(&element, element_type, array_type.element_type,
+                               array_type.element_array_type.as_ref().map(|array| &**array),
+                               array_type.element_struct_type.as_ref());
+            }
+            (&MemberType::Struct(ref layout), _, Some(struct_type)) => {
+                self.diff_struct(member, layout, struct_type);
+            }
+            _ if member_type.data_type() != data_type => {
+                self.push(StructMismatch::TypeMismatch {
+                    member: member.into(),
+                    expected: data_type,
+                    actual: member_type.data_type()
+                });
+            }
+            _ => (),
+        }
+    }
+}
+
+fn join(path: &str, name: &str) -> String {
+    if path.is_empty() { name.into() } else { format!("{}.{}", path, name) }
+}
+
+impl fmt::Display for StructDiff {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for (i, mismatch) in self.mismatches.iter().enumerate() {
+            if i > 0 {
+                try!(writeln!(f));
+            }
+            try!(write!(f, "{}", mismatch));
+        }
+        Ok(())
+    }
+}
+
+impl Error for StructDiff {
+    fn description(&self) -> &str {
+        "The Rust struct does not match the shader struct"
+    }
+}
+
+impl StructLayout {
+    /// Compares the layout with a struct reflected from a shader. If `data_size` is given, it is
+    /// compared with the size of the layout, as the reflected struct does not record its size.
+    pub fn diff(&self, reflected: &ReflectedStruct, data_size: Option<usize>) -> StructDiff {
+        let mut diff = StructDiff::default();
+        diff.diff_struct("", self, reflected);
+        if let Some(data_size) = data_size {
+            if data_size != self.size() {
+                diff.push(StructMismatch::SizeMismatch {
+                    expected: data_size,
+                    actual: self.size()
+                });
+            }
+        }
+        diff
+    }
+
+    /// Checks that the layout matches a struct reflected from a shader. See `diff`.
+    pub fn validate(&self, reflected: &ReflectedStruct, data_size: Option<usize>)
+                    -> Result<(), StructDiff> {
+        let diff = self.diff(reflected, data_size);
+        if diff.is_empty() { Ok(()) } else { Err(diff) }
+    }
+}
+
+impl Argument {
+    /// In debug builds, panics if `T` does not match the struct type of this buffer argument.
+    /// Does nothing in release builds, or if the argument is not a buffer of structs.
+    pub fn debug_assert_struct<T: ShaderStruct>(&self) {
+        if cfg!(debug_assertions) {
+            if let Some(struct_type) = self.buffer_struct_type() {
+                let reflected = ReflectedStruct::from(&struct_type);
+                let layout = T::struct_layout();
+                if let Err(diff) = layout.validate(&reflected, self.buffer_data_size()) {
+                    panic!("`{}` does not match the shader struct bound to `{}`:\n{}",
+                           T::msl_type_name(), self.name(), diff);
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use {DataType, MemberType, ReflectedArray, ReflectedMember, ReflectedStruct, StructLayout};
+
+    fn light_layout() -> StructLayout {
+        StructLayout::new(vec![("position", DataType::Float4), ("intensity", DataType::Float)])
+            .unwrap()
+    }
+
+    fn reflected_light() -> ReflectedStruct {
+        ReflectedStruct::new(vec![ReflectedMember::new("position", DataType::Float4, 0),
+                                  ReflectedMember::new("intensity", DataType::Float, 16)])
+    }
+
+    #[test]
+    fn test_matching_structs() {
+        let layout = StructLayout::new(vec![("color", MemberType::from(DataType::Float3)),
+                                            ("lights",
+                                             MemberType::array(light_layout().into(), 4))])
+            .unwrap();
+
+        let mut lights = ReflectedArray::new(DataType::Struct, 4, 32);
+        lights.element_struct_type = Some(reflected_light());
+        let reflected = ReflectedStruct::new(vec![ReflectedMember::new("color",
+                                                                       DataType::Float3, 0),
+                                                  ReflectedMember::array("lights", lights, 16)]);
+
+        assert_eq!(layout.validate(&reflected, Some(144)), Ok(()));
+        assert_eq!(layout.validate(&reflected, None), Ok(()));
+    }
+
+    #[test]
+    fn test_mismatched_members() {
+        let layout = StructLayout::new(vec![("position", MemberType::from(DataType::Float4)),
+                                            ("light", light_layout().into()),
+                                            ("scale", DataType::Float.into())])
+            .unwrap();
+
+        let mut light = reflected_light();
+        light.members[1].data_type = DataType::Half;
+        let reflected = ReflectedStruct::new(vec![ReflectedMember::new("position",
+                                                                       DataType::Float3, 0),
+                                                  ReflectedMember::structure("light", light, 16),
+                                                  ReflectedMember::new("weight",
+                                                                       DataType::Float, 48)]);
+
+        let diff = layout.diff(&reflected, Some(80));
+        assert_eq!(diff.mismatches(),
+                   &[StructMismatch::TypeMismatch {
+                         member: "position".into(),
+                         expected: DataType::Float3,
+                         actual: DataType::Float4
+                     },
+                     StructMismatch::TypeMismatch {
+                         member: "light.intensity".into(),
+                         expected: DataType::Half,
+                         actual: DataType::Float
+                     },
+                     StructMismatch::MissingMember { member: "weight".into() },
+                     StructMismatch::UnexpectedMember { member: "scale".into() },
+                     StructMismatch::SizeMismatch { expected: 80, actual: 64 }]);
+        assert_eq!(diff.to_string().lines().count(), 5);
+    }
+
+    #[test]
+    fn test_mismatched_offsets_and_arrays() {
+        let layout = StructLayout::new(vec![("scale", MemberType::from(DataType::Float)),
+                                            ("weights",
+                                             MemberType::array(DataType::Float2.into(), 3))])
+            .unwrap();
+        let reflected = ReflectedStruct::new(vec![
+            ReflectedMember::new("scale", DataType::Float, 0),
+            ReflectedMember::array("weights", ReflectedArray::new(DataType::Float4, 2, 16), 16)
+        ]);
+
+        let diff = layout.diff(&reflected, None);
+        assert_eq!(diff.mismatches(),
+                   &[StructMismatch::OffsetMismatch {
+                         member: "weights".into(),
+                         expected: 16,
+                         actual: 8
+                     },
+                     StructMismatch::ArrayLengthMismatch {
+                         member: "weights".into(),
+                         expected: 2,
+                         actual: 3
+                     },
+                     StructMismatch::StrideMismatch {
+                         member: "weights".into(),
+                         expected: 16,
+                         actual: 8
+                     },
+                     StructMismatch::TypeMismatch {
+                         member: "weights[]".into(),
+                         expected: DataType::Float4,
+                         actual: DataType::Float2
+                     }]);
+        assert!(layout.validate(&reflected, None).is_err());
+    }
+}
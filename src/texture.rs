@@ -6,7 +6,7 @@ use std::mem;
 use std::ops::{Deref, Range};
 use sys::{MTLTexture, MTLTextureDescriptor, MTLTextureType, MTLTextureUsage};
 use {CpuCacheMode, FromRaw, FromRawError, PixelFormat, Region, Resource, ResourceOptions, Size,
-     StorageMode};
+     StorageMode, TextureLayout, TextureLayoutError};
 #[cfg(target_os = "ios")]
 use Buffer;
 
@@ -30,49 +30,74 @@ impl Texture {
         Self::from_raw(raw_tex)
     }
 
+    /// Returns the byte layout of the texture's mipmap levels and slices.
+    pub fn layout(&self) -> Result<TextureLayout, TextureLayoutError> {
+        TextureLayout::new(self.pixel_format(), self.texture_type(), self.size(),
+                           self.mipmap_level_count(), self.array_length())
+    }
+
     pub fn replace_region_at_slice(&mut self, region: Region, slice: usize, mipmap_level: usize,
                                    bytes: &AsRef<[u8]>, bytes_per_row: usize,
-                                   bytes_per_image: usize) {
+                                   bytes_per_image: usize)
+                                   -> Result<(), TextureLayoutError> {
+        let bytes = bytes.as_ref();
+        try!(self.check_upload(bytes, region, mipmap_level, slice, bytes_per_row,
+                               bytes_per_image));
         unsafe {
             self.0.replaceRegion_mipmapLevel_slice_withBytes_bytesPerRow_bytesPerImage(
                 region.into(), mipmap_level as NSUInteger, slice as NSUInteger,
-                bytes.as_ref().as_ptr() as *const _, bytes_per_row as NSUInteger,
+                bytes.as_ptr() as *const _, bytes_per_row as NSUInteger,
                 bytes_per_image as NSUInteger);
         }
+        Ok(())
     }
 
     pub fn replace_region(&mut self, mipmap_level: usize, region: Region, bytes: &AsRef<[u8]>,
-                          bytes_per_row: usize) {
+                          bytes_per_row: usize)
+                          -> Result<(), TextureLayoutError> {
         let bytes = bytes.as_ref();
-        if bytes.len() % bytes_per_row != 0 {
-            return;
-        }
+        try!(self.check_upload(bytes, region, mipmap_level, 0, bytes_per_row, 0));
         unsafe {
             self.0.replaceRegion_mipmapLevel_withBytes_bytesPerRow(region.into(),
                                                                    mipmap_level as NSUInteger,
                                                                    bytes.as_ptr() as *const _,
                                                                    bytes_per_row as NSUInteger);
         }
+        Ok(())
     }
 
-    // TODO(George): Investigate non-allocating APIs, and add predicates in rust code so that
-    // panics can be generated
+    /// Reads `region` of a slice of the texture into `bytes_storage`, which is resized to the
+    /// minimum length needed to hold the region with the given row and image pitches.
     pub fn get_bytes_at_slice(&self, bytes_storage: &mut Vec<u8>, bytes_per_row: usize,
                               bytes_per_image: usize, region: Region, mipmap_level: usize,
-                              slice: usize) {
-        bytes_storage.resize(self.width() * self.height() * self.depth(), 0u8);
+                              slice: usize)
+                              -> Result<(), TextureLayoutError> {
+        let len = try!(try!(self.layout()).check_region(region, mipmap_level, slice,
+                                                        bytes_per_row, bytes_per_image));
+        bytes_storage.resize(len, 0u8);
+        if len == 0 {
+            return Ok(());
+        }
         unsafe {
             self.0.getBytes_bytesPerRow_bytesPerImage_fromRegion_mipmapLevel_slice(
                 bytes_storage.as_mut_ptr() as *mut _, bytes_per_row as NSUInteger,
                 bytes_per_image as NSUInteger, region.into(), mipmap_level as NSUInteger,
                 slice as NSUInteger);
         }
-        bytes_storage.shrink_to_fit();
+        Ok(())
     }
 
+    /// Reads `region` of the texture into `bytes_storage`, which is resized to the minimum length
+    /// needed to hold the region with the given row pitch.
     pub fn get_bytes(&self, bytes_storage: &mut Vec<u8>, bytes_per_row: usize, region: Region,
-                     mipmap_level: usize) {
-        bytes_storage.resize(self.width() * self.height() * self.depth(), 0u8);
+                     mipmap_level: usize)
+                     -> Result<(), TextureLayoutError> {
+        let len = try!(try!(self.layout()).check_region(region, mipmap_level, 0, bytes_per_row,
+                                                        0));
+        bytes_storage.resize(len, 0u8);
+        if len == 0 {
+            return Ok(());
+        }
         unsafe {
             self.0
                 .getBytes_bytesPerRow_fromRegion_mipmapLevel(bytes_storage.as_mut_ptr() as *mut _,
@@ -80,7 +105,19 @@ impl Texture {
                                                              region.into(),
                                                              mipmap_level as NSUInteger);
         }
-        bytes_storage.shrink_to_fit();
+        Ok(())
+    }
+
+    fn check_upload(&self, bytes: &[u8], region: Region, mipmap_level: usize, slice: usize,
+                    bytes_per_row: usize, bytes_per_image: usize)
+                    -> Result<(), TextureLayoutError> {
+        let required = try!(try!(self.layout()).check_region(region, mipmap_level, slice,
+                                                             bytes_per_row, bytes_per_image));
+        if bytes.len() < required {
+            Err(TextureLayoutError::BufferTooSmall { len: bytes.len(), required: required })
+        } else {
+            Ok(())
+        }
     }
 
     pub fn texture_type(&self) -> TextureType {
@@ -147,8 +184,10 @@ impl Texture {
 #[cfg(target_os = "macos")]
 impl Texture {}
 
-#[cfg(target_os = "ios")]
 impl Texture {
+    /// The buffer which the texture was created from, if any. The returned `Buffer` wraps the
+    /// same `MTLBuffer` as the one passed to `Device::new_texture_from_buffer`.
+    #[cfg(target_os = "ios")]
     pub fn buffer(&self) -> Option<Buffer> {
         unsafe { FromRaw::from_raw(MTLTexture::buffer(self.0)).ok() }
     }
@@ -276,6 +315,7 @@ impl TextureDescriptor {
         unsafe { self.0.resourceOptions().into() }
     }
 
+    /// Sets the CPU cache mode and storage mode of the descriptor together.
     pub fn set_resource_options(&mut self, options: ResourceOptions) {
         unsafe { self.0.setResourceOptions(options.into()) }
     }
@@ -296,10 +336,19 @@ impl TextureDescriptor {
         unsafe { self.0.setStorageMode(storage_mode.into()) }
     }
 
+    /// Returns the byte layout of the mipmap levels and slices of textures created with this
+    /// descriptor.
+    pub fn layout(&self) -> Result<TextureLayout, TextureLayoutError> {
+        TextureLayout::new(self.pixel_format(), self.texture_type(), self.size(),
+                           self.mipmap_level_count(), self.array_length())
+    }
+
     pub fn usage(&self) -> TextureUsage {
         unsafe { MTLTextureDescriptor::usage(self.0).into() }
     }
 
+    /// Sets the ways in which textures created with this descriptor may be used, for example
+    /// `TEXTURE_USAGE_SHADER_READ | TEXTURE_USAGE_RENDER_TARGET`.
     pub fn set_usage(&mut self, usage: TextureUsage) {
         unsafe { self.0.setUsage(usage.into()) }
     }
@@ -315,6 +364,7 @@ impl_from_into_raw!(TextureDescriptor, of class "MTLTextureDescriptor");
 
 convertible_enum! {
     #[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+    #[cfg_attr(feature = "serialize", derive(Deserialize, Serialize))]
     pub enum TextureType: MTLTextureType {
         Type1D => MTLTextureType1D,
         Type1DArray => MTLTextureType1DArray,
@@ -326,13 +376,67 @@ convertible_enum! {
     }
 }
 
-convertible_enum! {
-    #[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
-    pub enum TextureUsage: MTLTextureUsage {
-        Unknown => MTLTextureUsageUnknown,
-        ShaderRead => MTLTextureUsageShaderRead,
-        ShaderWrite => MTLTextureUsageShaderWrite,
-        RenderTarget => MTLTextureUsageRenderTarget,
-        PixelFormatView => MTLTextureUsagePixelFormatView
+bitflags! {
+    /// The ways in which a texture may be used. Usages can be combined with `|`.
+    pub flags TextureUsage: usize {
+        /// The usage of the texture is not known.
+        const TEXTURE_USAGE_UNKNOWN = 0x0000,
+
+        /// The texture is read or sampled by shaders.
+        const TEXTURE_USAGE_SHADER_READ = 0x0001,
+
+        /// The texture is written to by shaders.
+        const TEXTURE_USAGE_SHADER_WRITE = 0x0002,
+
+        /// The texture is used as a color, depth or stencil render target.
+        const TEXTURE_USAGE_RENDER_TARGET = 0x0004,
+
+        /// The texture is used to create texture views with a different pixel format.
+        const TEXTURE_USAGE_PIXEL_FORMAT_VIEW = 0x0010
+    }
+}
+
+impl Default for TextureUsage {
+    fn default() -> Self {
+        TEXTURE_USAGE_SHADER_READ
+    }
+}
+
+impl Into<MTLTextureUsage> for TextureUsage {
+    fn into(self) -> MTLTextureUsage {
+        MTLTextureUsage::from_bits_truncate(self.bits())
+    }
+}
+
+impl From<MTLTextureUsage> for TextureUsage {
+    fn from(usage: MTLTextureUsage) -> Self {
+        TextureUsage::from_bits_truncate(usage.bits())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use sys::{MTLTextureUsage, MTLTextureUsagePixelFormatView, MTLTextureUsageRenderTarget,
+              MTLTextureUsageShaderRead, MTLTextureUsageShaderWrite};
+
+    #[test]
+    fn test_texture_usage_round_trip() {
+        let pairs = [(TEXTURE_USAGE_SHADER_READ, MTLTextureUsageShaderRead),
+                     (TEXTURE_USAGE_SHADER_WRITE, MTLTextureUsageShaderWrite),
+                     (TEXTURE_USAGE_RENDER_TARGET, MTLTextureUsageRenderTarget),
+                     (TEXTURE_USAGE_PIXEL_FORMAT_VIEW, MTLTextureUsagePixelFormatView)];
+        for &(usage, raw) in &pairs {
+            let converted: MTLTextureUsage = usage.into();
+            assert_eq!(converted, raw);
+            assert_eq!(TextureUsage::from(raw), usage);
+        }
+
+        let usage = TEXTURE_USAGE_SHADER_READ | TEXTURE_USAGE_RENDER_TARGET;
+        let raw: MTLTextureUsage = usage.into();
+        assert_eq!(raw, MTLTextureUsageShaderRead | MTLTextureUsageRenderTarget);
+        assert_eq!(TextureUsage::from(raw), usage);
+        assert_eq!(TextureUsage::from(MTLTextureUsage::all()), TextureUsage::all());
+        assert!(TEXTURE_USAGE_UNKNOWN.is_empty());
     }
 }
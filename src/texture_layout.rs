@@ -0,0 +1,401 @@
+use std::cmp;
+use std::error::Error;
+use std::fmt;
+use {PixelFormat, PixelFormatInfo, Region, Size, TextureType};
+
+/// Computes the byte layout of the mipmap levels and slices of a texture.
+///
+/// All sizes are given for tightly packed data unless a row alignment is set with
+/// `with_row_alignment`, in which case every row of blocks is padded to a multiple of it.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub struct TextureLayout {
+    format: PixelFormatInfo,
+    texture_type: TextureType,
+    size: Size,
+    mipmap_level_count: usize,
+    array_length: usize,
+    row_alignment: usize
+}
+
+impl TextureLayout {
+    /// Creates the layout of a texture with the given format, type and size in texels. Mipmap
+    /// level counts and array lengths of zero are treated as one.
+    ///
+    /// Returns an error if `pixel_format` is `PixelFormat::Invalid`.
+    pub fn new(pixel_format: PixelFormat, texture_type: TextureType, size: Size,
+               mipmap_level_count: usize, array_length: usize)
+               -> Result<Self, TextureLayoutError> {
+        let format = try!(pixel_format.info().ok_or(TextureLayoutError::InvalidPixelFormat));
+        Ok(TextureLayout {
+            format: format,
+            texture_type: texture_type,
+            size: size,
+            mipmap_level_count: cmp::max(mipmap_level_count, 1),
+            array_length: cmp::max(array_length, 1),
+            row_alignment: 1
+        })
+    }
+
+    /// Pad every row of blocks to a multiple of `alignment` bytes.
+    pub fn with_row_alignment(self, alignment: usize) -> Self {
+        TextureLayout { row_alignment: cmp::max(alignment, 1), ..self }
+    }
+
+    /// The memory layout of the texture's pixel format.
+    pub fn pixel_format_info(&self) -> PixelFormatInfo {
+        self.format
+    }
+
+    /// The type of the texture, which decides how many slices it has.
+    pub fn texture_type(&self) -> TextureType {
+        self.texture_type
+    }
+
+    /// The number of mipmap levels, which is at least one.
+    pub fn mipmap_level_count(&self) -> usize {
+        self.mipmap_level_count
+    }
+
+    /// The alignment in bytes of each row of blocks, which is one for tightly packed rows.
+    pub fn row_alignment(&self) -> usize {
+        self.row_alignment
+    }
+
+    /// The number of 2D slices in each mipmap level. Cube textures have six slices per array
+    /// element. The depth of a 3D texture is not counted as slices.
+    pub fn slice_count(&self) -> usize {
+        match self.texture_type {
+            TextureType::TypeCube => 6 * self.array_length,
+            TextureType::Type1DArray | TextureType::Type2DArray => self.array_length,
+            _ => 1,
+        }
+    }
+
+    /// The dimensions of the mipmap level `level`, in texels.
+    pub fn level_size(&self, level: usize) -> Size {
+        let shrink = |dimension: usize| {
+            cmp::max(dimension.checked_shr(level as u32).unwrap_or(0), 1)
+        };
+        let depth = match self.texture_type {
+            TextureType::Type3D => shrink(self.size.depth),
+            _ => 1,
+        };
+        Size::new(shrink(self.size.width), shrink(self.size.height), depth)
+    }
+
+    /// The minimum number of bytes needed for a row of blocks in mipmap level `level`.
+    pub fn min_bytes_per_row(&self, level: usize) -> usize {
+        self.format.bytes_per_row(self.level_size(level).width)
+    }
+
+    /// The number of bytes in a row of blocks in mipmap level `level`, including alignment
+    /// padding.
+    pub fn bytes_per_row(&self, level: usize) -> usize {
+        round_up(self.min_bytes_per_row(level), self.row_alignment)
+    }
+
+    /// The minimum number of bytes needed for a 2D image in mipmap level `level`.
+    pub fn min_bytes_per_image(&self, level: usize) -> usize {
+        let size = self.level_size(level);
+        self.format.bytes_per_image(size.width, size.height)
+    }
+
+    /// The number of bytes in a 2D image in mipmap level `level`, including row padding.
+    pub fn bytes_per_image(&self, level: usize) -> usize {
+        self.bytes_per_row(level) * self.format.blocks_high(self.level_size(level).height)
+    }
+
+    /// The number of bytes used by mipmap level `level` of a single slice, including the full
+    /// depth of 3D textures.
+    pub fn bytes_per_level(&self, level: usize) -> usize {
+        self.bytes_per_image(level) * self.level_size(level).depth
+    }
+
+    /// The number of bytes used by every mipmap level of a single slice.
+    pub fn bytes_per_slice(&self) -> usize {
+        (0..self.mipmap_level_count).map(|level| self.bytes_per_level(level)).sum()
+    }
+
+    /// The number of bytes used by the whole texture.
+    pub fn total_size(&self) -> usize {
+        self.bytes_per_slice() * self.slice_count()
+    }
+
+    /// The byte offset of mipmap level `level` of slice `slice`, when slices are stored one after
+    /// the other, each with its mipmap levels in order.
+    pub fn offset(&self, slice: usize, level: usize) -> usize {
+        let level_offset: usize = (0..level).map(|level| self.bytes_per_level(level)).sum();
+        slice * self.bytes_per_slice() + level_offset
+    }
+
+    /// Checks that `region` lies within mipmap level `level` of slice `slice`, and that a buffer
+    /// with the given row and image pitches can hold it.
+    ///
+    /// A `bytes_per_image` of zero can be used for regions which are a single image deep.
+    /// On success, the minimum buffer length needed to hold the region is returned.
+    pub fn check_region(&self, region: Region, level: usize, slice: usize, bytes_per_row: usize,
+                        bytes_per_image: usize)
+                        -> Result<usize, TextureLayoutError> {
+        if level >= self.mipmap_level_count {
+            return Err(TextureLayoutError::MipmapLevelOutOfRange {
+                level: level,
+                level_count: self.mipmap_level_count
+            });
+        }
+
+        if slice >= self.slice_count() {
+            return Err(TextureLayoutError::SliceOutOfRange {
+                slice: slice,
+                slice_count: self.slice_count()
+            });
+        }
+
+        let Region { origin, size } = region;
+        let level_size = self.level_size(level);
+        let fits = |start: usize, len: usize, limit: usize| {
+            start.checked_add(len).map_or(false, |end| end <= limit)
+        };
+        if !fits(origin.x, size.width, level_size.width) ||
+           !fits(origin.y, size.height, level_size.height) ||
+           !fits(origin.z, size.depth, level_size.depth) {
+            return Err(TextureLayoutError::RegionOutOfBounds {
+                region: region,
+                level_size: level_size
+            });
+        }
+
+        let (block_width, block_height) = (self.format.block_width, self.format.block_height);
+        let is_aligned = |start: usize, len: usize, block: usize, limit: usize| {
+            start % block == 0 && (len % block == 0 || start + len == limit)
+        };
+        if !is_aligned(origin.x, size.width, block_width, level_size.width) ||
+           !is_aligned(origin.y, size.height, block_height, level_size.height) {
+            return Err(TextureLayoutError::RegionNotBlockAligned(region));
+        }
+
+        if size.width == 0 || size.height == 0 || size.depth == 0 {
+            return Ok(0);
+        }
+
+        let row_len = self.format.bytes_per_row(size.width);
+        if bytes_per_row < row_len {
+            return Err(TextureLayoutError::BytesPerRowTooSmall {
+                bytes_per_row: bytes_per_row,
+                required: row_len
+            });
+        }
+
+        let rows = self.format.blocks_high(size.height);
+        let image_len = try!((rows - 1).checked_mul(bytes_per_row)
+                                       .and_then(|len| len.checked_add(row_len))
+                                       .ok_or(TextureLayoutError::LengthOverflow));
+        let rows_len = try!(rows.checked_mul(bytes_per_row)
+                                .ok_or(TextureLayoutError::LengthOverflow));
+        if size.depth > 1 && bytes_per_image < rows_len {
+            return Err(TextureLayoutError::BytesPerImageTooSmall {
+                bytes_per_image: bytes_per_image,
+                required: rows_len
+            });
+        }
+
+        (size.depth - 1).checked_mul(bytes_per_image)
+                        .and_then(|len| len.checked_add(image_len))
+                        .ok_or(TextureLayoutError::LengthOverflow)
+    }
+}
+
+fn round_up(value: usize, alignment: usize) -> usize {
+    (value + alignment - 1) / alignment * alignment
+}
+
+/// Errors which describe a mismatch between a texture and the arguments used to read from or
+/// write to it.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum TextureLayoutError {
+    /// The texture has the `Invalid` pixel format.
+    InvalidPixelFormat,
+
+    /// The mipmap level does not exist in the texture.
+    MipmapLevelOutOfRange { level: usize, level_count: usize },
+
+    /// The slice does not exist in the texture.
+    SliceOutOfRange { slice: usize, slice_count: usize },
+
+    /// The region extends outside of the mipmap level.
+    RegionOutOfBounds { region: Region, level_size: Size },
+
+    /// The region does not start and end on block boundaries of a block-compressed format.
+    RegionNotBlockAligned(Region),
+
+    /// The row pitch is smaller than a row of the region.
+    BytesPerRowTooSmall { bytes_per_row: usize, required: usize },
+
+    /// The image pitch is smaller than an image of the region.
+    BytesPerImageTooSmall { bytes_per_image: usize, required: usize },
+
+    /// The number of bytes needed to hold the region with the given pitches does not fit in a
+    /// `usize`.
+    LengthOverflow,
+
+    /// The byte buffer is too small to hold the region.
+    BufferTooSmall { len: usize, required: usize }
+}
+
+impl fmt::Display for TextureLayoutError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            TextureLayoutError::MipmapLevelOutOfRange { level, level_count } => {
+                write!(f, "mipmap level {} is out of range (level count {})", level, level_count)
+            }
+            TextureLayoutError::SliceOutOfRange { slice, slice_count } => {
+                write!(f, "slice {} is out of range (slice count {})", slice, slice_count)
+            }
+            TextureLayoutError::BytesPerRowTooSmall { bytes_per_row, required } => {
+                write!(f, "bytes per row {} is smaller than {}", bytes_per_row, required)
+            }
+            TextureLayoutError::BytesPerImageTooSmall { bytes_per_image, required } => {
+                write!(f, "bytes per image {} is smaller than {}", bytes_per_image, required)
+            }
+            TextureLayoutError::BufferTooSmall { len, required } => {
+                write!(f, "buffer length {} is smaller than {}", len, required)
+            }
+            _ => write!(f, "{}", self.description()),
+        }
+    }
+}
+
+impl Error for TextureLayoutError {
+    fn description(&self) -> &str {
+        match *self {
+            TextureLayoutError::InvalidPixelFormat => "The texture has an invalid pixel format",
+            TextureLayoutError::MipmapLevelOutOfRange { .. } => "The mipmap level is out of range",
+            TextureLayoutError::SliceOutOfRange { .. } => "The slice is out of range",
+            TextureLayoutError::RegionOutOfBounds { .. } => {
+                "The region lies outside of the mipmap level"
+            }
+            TextureLayoutError::RegionNotBlockAligned(_) => {
+                "The region is not aligned to the block size of the pixel format"
+            }
+            TextureLayoutError::BytesPerRowTooSmall { .. } => "The bytes per row is too small",
+            TextureLayoutError::BytesPerImageTooSmall { .. } => "The bytes per image is too small",
+            TextureLayoutError::LengthOverflow => "The region is too large to hold in memory",
+            TextureLayoutError::BufferTooSmall { .. } => "The buffer is too small for the region",
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use {PixelFormat, Region, Size, TextureType};
+
+    fn layout(format: PixelFormat, texture_type: TextureType, size: (usize, usize, usize),
+              levels: usize, array_length: usize)
+              -> TextureLayout {
+        TextureLayout::new(format, texture_type, size.into(), levels, array_length).unwrap()
+    }
+
+    #[test]
+    fn test_mip_chain_sizes() {
+        let layout = layout(PixelFormat::Rgba8Unorm, TextureType::Type2D, (64, 16, 1), 7, 1);
+        assert_eq!(layout.level_size(0), Size::new(64, 16, 1));
+        assert_eq!(layout.level_size(3), Size::new(8, 2, 1));
+        assert_eq!(layout.level_size(6), Size::new(1, 1, 1));
+        assert_eq!(layout.bytes_per_row(2), 64);
+        assert_eq!(layout.bytes_per_image(2), 256);
+        let expected = (64 * 16 + 32 * 8 + 16 * 4 + 8 * 2 + 4 + 2 + 1) * 4;
+        assert_eq!(layout.total_size(), expected);
+    }
+
+    #[test]
+    fn test_compressed_levels_round_up_to_blocks() {
+        let layout = layout(PixelFormat::Bc1Rgba, TextureType::Type2D, (10, 6, 1), 4, 1);
+        assert_eq!(layout.min_bytes_per_row(0), 24);
+        assert_eq!(layout.min_bytes_per_image(0), 48);
+        // 5x3, 2x1 and 1x1 all fit in a single row of blocks
+        assert_eq!(layout.min_bytes_per_image(1), 16);
+        assert_eq!(layout.min_bytes_per_image(2), 8);
+        assert_eq!(layout.min_bytes_per_image(3), 8);
+        assert_eq!(layout.total_size(), 48 + 16 + 8 + 8);
+    }
+
+    #[test]
+    fn test_row_alignment() {
+        let layout = layout(PixelFormat::R8Unorm, TextureType::Type2D, (100, 10, 1), 1, 1)
+                         .with_row_alignment(256);
+        assert_eq!(layout.min_bytes_per_row(0), 100);
+        assert_eq!(layout.bytes_per_row(0), 256);
+        assert_eq!(layout.bytes_per_image(0), 2560);
+        assert_eq!(layout.total_size(), 2560);
+    }
+
+    #[test]
+    fn test_cube_array_and_3d_slices() {
+        let cube = layout(PixelFormat::Rgba16Float, TextureType::TypeCube, (8, 8, 1), 1, 1);
+        assert_eq!(cube.slice_count(), 6);
+        assert_eq!(cube.total_size(), 6 * 8 * 8 * 8);
+        assert_eq!(cube.offset(2, 0), 2 * 8 * 8 * 8);
+
+        let array = layout(PixelFormat::R32Float, TextureType::Type2DArray, (4, 4, 1), 2, 3);
+        assert_eq!(array.slice_count(), 3);
+        assert_eq!(array.bytes_per_slice(), (16 + 4) * 4);
+        assert_eq!(array.offset(1, 1), (16 + 4) * 4 + 16 * 4);
+
+        let volume = layout(PixelFormat::R8Uint, TextureType::Type3D, (4, 4, 8), 3, 1);
+        assert_eq!(volume.slice_count(), 1);
+        assert_eq!(volume.level_size(1), Size::new(2, 2, 4));
+        assert_eq!(volume.bytes_per_level(1), 16);
+        assert_eq!(volume.total_size(), 128 + 16 + 2);
+    }
+
+    #[test]
+    fn test_check_region() {
+        let layout = layout(PixelFormat::Rgba8Unorm, TextureType::Type2D, (16, 16, 1), 2, 1);
+        assert_eq!(layout.check_region(Region::new_2d(0, 0, 16, 16), 0, 0, 64, 0), Ok(1024));
+        assert_eq!(layout.check_region(Region::new_2d(4, 4, 2, 3), 0, 0, 100, 0),
+                   Ok(2 * 100 + 8));
+        assert_eq!(layout.check_region(Region::new_2d(0, 0, 16, 16), 0, 0, 32, 0),
+                   Err(TextureLayoutError::BytesPerRowTooSmall {
+                       bytes_per_row: 32,
+                       required: 64
+                   }));
+        assert_eq!(layout.check_region(Region::new_2d(0, 0, 16, 16), 2, 0, 64, 0),
+                   Err(TextureLayoutError::MipmapLevelOutOfRange { level: 2, level_count: 2 }));
+        assert_eq!(layout.check_region(Region::new_2d(0, 0, 1, 1), 0, 1, 4, 0),
+                   Err(TextureLayoutError::SliceOutOfRange { slice: 1, slice_count: 1 }));
+        match layout.check_region(Region::new_2d(8, 8, 16, 16), 1, 0, 64, 0) {
+            Err(TextureLayoutError::RegionOutOfBounds { .. }) => {}
+            other => panic!("unexpected result {:?}", other),
+        }
+        match layout.check_region(Region::new_2d(usize::max_value(), 0, 2, 1), 0, 0, 64, 0) {
+            Err(TextureLayoutError::RegionOutOfBounds { .. }) => {}
+            other => panic!("unexpected result {:?}", other),
+        }
+        assert_eq!(layout.check_region(Region::new_2d(0, 0, 16, 16), 0, 0, usize::max_value(), 0),
+                   Err(TextureLayoutError::LengthOverflow));
+    }
+
+    #[test]
+    fn test_check_region_blocks_and_depth() {
+        let bc = layout(PixelFormat::Bc3Rgba, TextureType::Type2D, (10, 10, 1), 1, 1);
+        assert_eq!(bc.check_region(Region::new_2d(4, 4, 6, 6), 0, 0, 32, 0), Ok(64));
+        assert_eq!(bc.check_region(Region::new_2d(2, 0, 4, 4), 0, 0, 48, 0),
+                   Err(TextureLayoutError::RegionNotBlockAligned(Region::new_2d(2, 0, 4, 4))));
+
+        let volume = layout(PixelFormat::R16Uint, TextureType::Type3D, (4, 4, 4), 1, 1);
+        let region = Region::new_3d(0, 0, 0, 4, 4, 4);
+        assert_eq!(volume.check_region(region, 0, 0, 8, 32), Ok(128));
+        assert_eq!(volume.check_region(region, 0, 0, 8, 0),
+                   Err(TextureLayoutError::BytesPerImageTooSmall {
+                       bytes_per_image: 0,
+                       required: 32
+                   }));
+    }
+
+    #[test]
+    fn test_invalid_pixel_format() {
+        let layout = TextureLayout::new(PixelFormat::Invalid, TextureType::Type2D,
+                                        Size::new(1, 1, 1), 1, 1);
+        assert_eq!(layout, Err(TextureLayoutError::InvalidPixelFormat));
+    }
+}
@@ -0,0 +1,87 @@
+use metl_translate::{self, ShaderTranslator, TranslatedShader, TranslationError,
+                     TranslationSource};
+use std::error::Error;
+use std::fmt::{self, Display, Formatter};
+use {CompileOptions, Device, FunctionType, Library, LibraryError};
+
+impl Device {
+    /// Translates `source` into MSL with `translator`, and compiles it like
+    /// `new_library_with_source`. The translated shader is returned along with the library, as it
+    /// has the Metal index of each binding and the names of the entry points in the MSL source.
+    pub fn new_library_with_translated_source(&mut self, translator: &ShaderTranslator,
+                                              source: TranslationSource,
+                                              compile_options: &CompileOptions)
+                                              -> Result<(Library, TranslatedShader),
+                                                        TranslatedLibraryError> {
+        let shader = try!(translator.translate(source));
+        let library = try!(self.new_library_with_source(shader.source(), compile_options));
+        Ok((library, shader))
+    }
+}
+
+impl From<metl_translate::FunctionType> for FunctionType {
+    fn from(function_type: metl_translate::FunctionType) -> Self {
+        match function_type {
+            metl_translate::FunctionType::Vertex => FunctionType::Vertex,
+            metl_translate::FunctionType::Fragment => FunctionType::Fragment,
+            metl_translate::FunctionType::Kernel => FunctionType::Kernel,
+        }
+    }
+}
+
+impl From<FunctionType> for metl_translate::FunctionType {
+    fn from(function_type: FunctionType) -> Self {
+        match function_type {
+            FunctionType::Vertex => metl_translate::FunctionType::Vertex,
+            FunctionType::Fragment => metl_translate::FunctionType::Fragment,
+            FunctionType::Kernel => metl_translate::FunctionType::Kernel,
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum TranslatedLibraryError {
+    Translation(TranslationError),
+
+    /// The translated MSL source did not compile.
+    Library(LibraryError)
+}
+
+impl From<TranslationError> for TranslatedLibraryError {
+    fn from(error: TranslationError) -> Self {
+        TranslatedLibraryError::Translation(error)
+    }
+}
+
+impl From<LibraryError> for TranslatedLibraryError {
+    fn from(error: LibraryError) -> Self {
+        TranslatedLibraryError::Library(error)
+    }
+}
+
+impl Display for TranslatedLibraryError {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        match *self {
+            TranslatedLibraryError::Translation(ref error) => write!(f, "{}", error),
+            TranslatedLibraryError::Library(ref error) => {
+                write!(f, "the translated shader did not compile: {}", error)
+            }
+        }
+    }
+}
+
+impl Error for TranslatedLibraryError {
+    fn description(&self) -> &str {
+        match *self {
+            TranslatedLibraryError::Translation(_) => "The shader could not be translated",
+            TranslatedLibraryError::Library(_) => "The translated shader did not compile",
+        }
+    }
+
+    fn cause(&self) -> Option<&Error> {
+        match *self {
+            TranslatedLibraryError::Translation(ref error) => Some(error),
+            TranslatedLibraryError::Library(ref error) => Some(error),
+        }
+    }
+}
@@ -8,6 +8,7 @@ use sys::{MTLClearColor, MTLDispatchThreadgroupsIndirectArguments,
 use sys::{MTLClearColorMake, MTLOriginMake, MTLSizeMake};
 
 #[derive(Clone, Copy, Default, Debug, PartialEq)]
+#[repr(C)]
 pub struct ClearColor {
     pub red: f64,
     pub green: f64,
@@ -49,6 +50,7 @@ impl Into<MTLClearColor> for ClearColor {
 }
 
 #[derive(Clone, Copy, Default, Debug, Hash, PartialEq, Eq)]
+#[repr(C)]
 pub struct Origin {
     pub x: usize,
     pub y: usize,
@@ -162,6 +164,7 @@ impl Into<MTLScissorRect> for ScissorRect {
 }
 
 #[derive(Clone, Copy, Default, Debug, Hash, PartialEq, Eq)]
+#[repr(C)]
 pub struct Size {
     pub width: usize,
     pub height: usize,
@@ -1,14 +1,29 @@
 
 extern crate metl;
 extern crate cocoa;
+#[macro_use]
+extern crate metl_derive;
 
 use cocoa::base::{BOOL, nil};
 use cocoa::foundation::NSString;
 use metl::{CompileOptions, DepthStencilDescriptor, Device, FeatureSet, LanguageVersion, PixelFormat, SpecificLanguageVersion,
-           SamplerDescriptor, TextureDescriptor};
+           SamplerDescriptor, TextureDescriptor, TextureFromBufferError, TextureLayoutError};
 use metl::LibraryError;
 use metl::{FromRaw, FromRawError, IntoRaw};
 use metl::sys::{MTLCompileOptions, MTLLanguageVersion};
+use metl::allocator::LinearBufferAllocator;
+use metl::{CpuCacheMode, PageAlignedVec, RESOURCE_STORAGE_MODE_PRIVATE, ResourceOptions,
+           ShaderStruct, StorageMode};
+use metl::{DeviceCompiler, FunctionType, LibraryCache, MemoryProvider, MetallibArchive,
+           ReloadStatus, ReloadableLibrary, Severity, ShaderSourceSet, ShaderVariantSet,
+           VariantKey, compile_metallib_with_xcrun};
+use std::env;
+use std::fs::{self, File};
+use std::io::Write;
+use std::mem;
+use std::process;
+use std::thread;
+use std::time::Duration;
 
 #[test]
 fn get_device() {
@@ -152,6 +167,307 @@ fn create_texture() {
     assert_eq!(texture.texture_type(), metl::TextureType::Type2D);
 }
 
+#[test]
+fn texture_from_buffer_reports_bad_layouts() {
+    let mut device = Device::system_default_device().unwrap();
+    let buffer = device.new_buffer_with_length(4096, ResourceOptions::default());
+    let descriptor = TextureDescriptor::new_2d(PixelFormat::Rgba8Unorm, 16, 16, false);
+
+    match device.new_texture_from_buffer(&buffer, &descriptor, 0, 32) {
+        Err(TextureFromBufferError::Layout(TextureLayoutError::BytesPerRowTooSmall { .. })) => {}
+        _ => panic!("expected the row pitch to be rejected"),
+    }
+    match device.new_texture_from_buffer(&buffer, &descriptor, 1024, 256) {
+        Err(TextureFromBufferError::Layout(TextureLayoutError::BufferTooSmall { len, .. })) => {
+            assert_eq!(len, 4096)
+        }
+        _ => panic!("expected the texture not to fit in the buffer"),
+    }
+    assert!(device.new_texture_from_buffer(&buffer, &descriptor, 8192, 256).is_err());
+}
+
+#[test]
+fn create_buffer_and_write_contents() {
+    let mut device = Device::system_default_device().unwrap();
+    let mut buffer = device.new_buffer_with_length(64, ResourceOptions::default());
+    assert_eq!(buffer.length(), 64);
+    assert_eq!(buffer.storage_mode(), StorageMode::Shared);
+
+    for (i, byte) in unsafe { buffer.contents_mut() }.unwrap().iter_mut().enumerate() {
+        *byte = i as u8;
+    }
+    assert_eq!(buffer.contents().unwrap()[63], 63);
+    assert_eq!(buffer.contents_as::<u32>().unwrap().len(), 16);
+    assert_eq!(buffer.contents_as::<u32>().unwrap()[0], u32::from_le(0x03020100));
+}
+
+#[test]
+fn private_buffer_contents_are_not_accessible() {
+    let mut device = Device::system_default_device().unwrap();
+    let mut buffer = device.new_buffer_with_length(64, RESOURCE_STORAGE_MODE_PRIVATE);
+    assert_eq!(buffer.storage_mode(), StorageMode::Private);
+    assert!(buffer.contents().is_none());
+    assert!(unsafe { buffer.contents_mut() }.is_none());
+}
+
+#[test]
+fn sub_allocations_only_access_their_own_region() {
+    let mut device = Device::system_default_device().unwrap();
+    let buffer = device.new_buffer_with_length(256, ResourceOptions::default());
+    let mut allocator = LinearBufferAllocator::new(buffer);
+    let mut first = allocator.allocate(16, 16).unwrap();
+    let mut second = allocator.allocate(16, 16).unwrap();
+    assert_eq!((first.offset(), second.offset()), (0, 16));
+
+    for byte in unsafe { first.contents_mut() }.unwrap() {
+        *byte = 1;
+    }
+    for byte in unsafe { second.contents_mut() }.unwrap() {
+        *byte = 2;
+    }
+    assert_eq!(first.contents().unwrap(), &[1; 16][..]);
+    assert_eq!(second.contents().unwrap(), &[2; 16][..]);
+
+    allocator.reset();
+    assert!(first.is_stale());
+    assert!(unsafe { first.contents_mut() }.is_none());
+    assert!(!allocator.allocate(16, 16).unwrap().is_stale());
+}
+
+#[test]
+fn create_buffer_with_host_allocation() {
+    let mut device = Device::system_default_device().unwrap();
+    let bytes = PageAlignedVec::from_slice(b"page aligned");
+    let ptr = bytes.as_ptr();
+    let buffer = device.new_buffer_with_host_allocation(bytes, ResourceOptions::default())
+                       .unwrap();
+    assert_eq!(buffer.length(), metl::page_size());
+    assert_eq!(buffer.contents().unwrap().as_ptr(), ptr);
+    assert_eq!(&buffer.contents().unwrap()[..12], b"page aligned");
+}
+
+#[test]
+fn create_buffer_with_bytes_no_copy() {
+    let mut device = Device::system_default_device().unwrap();
+    let mut bytes = PageAlignedVec::from_slice(b"borrowed");
+    let ptr = bytes.as_ptr();
+    {
+        // No command buffers use the buffer, so it is finished with when it is dropped.
+        let buffer = unsafe {
+            device.new_buffer_with_bytes_no_copy(&mut bytes, ResourceOptions::default())
+        }.unwrap();
+        assert_eq!(buffer.contents().unwrap().as_ptr(), ptr);
+    }
+    assert_eq!(&bytes[..8], b"borrowed");
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, GpuPod)]
+#[repr(C)]
+struct Vertex {
+    position: [f32; 4],
+    color: [f32; 4]
+}
+
+#[test]
+fn create_typed_buffer_from_slice() {
+    let vertices = [Vertex { position: [0.0, 1.0, 0.0, 1.0], color: [1.0, 0.0, 0.0, 1.0] },
+                    Vertex { position: [-1.0, -1.0, 0.0, 1.0], color: [0.0, 1.0, 0.0, 1.0] },
+                    Vertex { position: [1.0, -1.0, 0.0, 1.0], color: [0.0, 0.0, 1.0, 1.0] }];
+    let mut device = Device::system_default_device().unwrap();
+    let options = ResourceOptions::new(CpuCacheMode::WriteCombined, StorageMode::Shared);
+    let mut buffer = device.new_buffer_from_slice(&vertices, options);
+    assert_eq!(buffer.len(), 3);
+    assert_eq!(buffer.as_slice().unwrap(), &vertices[..]);
+    assert_eq!(buffer.get(3), None);
+
+    let moved = Vertex { position: [0.5, 0.5, 0.0, 1.0], ..vertices[0] };
+    unsafe { buffer.set(0, moved) };
+    assert_eq!(buffer.get(0), Some(moved));
+
+    let slice = buffer.slice(1..3);
+    assert_eq!(slice.offset(), 32);
+    assert_eq!(slice.len(), 2);
+    assert_eq!(slice.byte_len(), 64);
+    assert_eq!(slice.as_slice().unwrap(), &vertices[1..]);
+}
+
+#[derive(Clone, Copy, GpuPod, ShaderStruct)]
+#[repr(C)]
+struct Light {
+    position: [f32; 4],
+    color: [f32; 2],
+    intensity: f32,
+    range: f32
+}
+
+#[derive(Clone, Copy, GpuPod, ShaderStruct)]
+#[repr(C)]
+struct Uniforms {
+    model_view_projection: [[f32; 4]; 4],
+    light: Light
+}
+
+#[test]
+fn compile_shader_with_derived_struct_declarations() {
+    assert_eq!(Uniforms::msl_source(),
+               "struct Light {\n    float4 position;\n    float2 color;\n    float intensity;\n    \
+                float range;\n};\nstruct Uniforms {\n    float4x4 model_view_projection;\n    \
+                Light light;\n};\n");
+    assert_eq!(Uniforms::struct_layout().size(), mem::size_of::<Uniforms>());
+
+    const UNIFORMS_SHADER: &'static str = r"
+using namespace metal;
+
+UNIFORMS
+
+vertex float4 vertex_main(constant Uniforms &uniforms [[buffer(0)]]) {
+    return uniforms.model_view_projection * uniforms.light.position;
+}
+";
+    let mut device = Device::system_default_device().unwrap();
+    let options = CompileOptions::default().with_struct_declarations::<Uniforms, _>("UNIFORMS");
+    assert!(device.new_library_with_source(UNIFORMS_SHADER, &options).is_ok());
+
+    let source = format!("{}{}", Uniforms::msl_source(), UNIFORMS_SHADER.replace("UNIFORMS", ""));
+    assert!(device.new_library_with_source(&source, &Default::default()).is_ok());
+}
+
+#[test]
+fn compile_shader_with_included_files() {
+    let files = MemoryProvider::new()
+        .with_file("shaders/main.metal", "#include <metal_stdlib>\n#include \"common.h\"\n\
+                                          using namespace metal;\n\
+                                          vertex float4 vertex_main(constant Vertex *vertices \
+                                          [[buffer(0)]], uint vid [[vertex_id]]) {\n\
+                                          return vertices[vid].position;\n}\n")
+        .with_file("shaders/common.h", "#pragma once\nstruct Vertex {\n    float4 position;\n};\n");
+    let sources = ShaderSourceSet::new().with_provider(files);
+    let source = sources.assemble("shaders/main.metal").unwrap();
+
+    let mut device = Device::system_default_device().unwrap();
+    assert!(device.new_library_with_source(&source, &Default::default()).is_ok());
+    assert!(device.new_library_with_source_async(&source, &Default::default())
+                  .recv()
+                  .unwrap()
+                  .is_ok());
+}
+
+#[test]
+fn reload_library_from_directory() {
+    let dir = env::temp_dir().join(format!("metl-reload-{}", process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    File::create(dir.join("main.metal")).and_then(|mut file| file.write_all(SHADER.as_bytes()))
+                                        .unwrap();
+
+    let compiler = DeviceCompiler::new(Device::system_default_device().unwrap(),
+                                       Default::default());
+    let mut library = ReloadableLibrary::new(compiler, &dir, "main.metal",
+                                             Duration::from_millis(0));
+    loop {
+        match library.update() {
+            ReloadStatus::Reloaded(generation) => {
+                assert_eq!(generation, 1);
+                break;
+            }
+            ReloadStatus::Failed(error) => panic!("{}", error),
+            _ => thread::sleep(Duration::from_millis(10)),
+        }
+    }
+    assert!(library.library().unwrap().function_names().contains(&"vertex_main".to_string()));
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn load_library_through_cache() {
+    let mut device = Device::system_default_device().unwrap();
+    let options = CompileOptions::default();
+    let data = compile_metallib_with_xcrun(SHADER, &options).ok().unwrap();
+    assert!(device.new_library_with_data(&data).is_ok());
+    assert!(device.new_library_with_data(b"not a metallib").is_err());
+
+    let dir = env::temp_dir().join(format!("metl-library-cache-{}", process::id()));
+    let cache = LibraryCache::new(&dir).with_version("test");
+    let compiled = cache.load_library(&mut device, SHADER, &options, compile_metallib_with_xcrun);
+    assert!(compiled.is_ok());
+    assert!(cache.get(&cache.key(SHADER, &options)).is_some());
+
+    let cached = cache.load_library(&mut device, SHADER, &options, |_, _| {
+        panic!("the library should have been loaded from the cache")
+    });
+    assert!(cached.ok().unwrap().function_names().contains(&"vertex_main".to_string()));
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn read_metallib_functions() {
+    let mut device = Device::system_default_device().unwrap();
+    let data = compile_metallib_with_xcrun(SHADER, &CompileOptions::default()).ok().unwrap();
+    let archive = MetallibArchive::parse(&data).unwrap();
+    let function = archive.function("vertex_main").unwrap();
+    assert_eq!(function.function_type, Some(FunctionType::Vertex));
+    assert!(function.hash.is_some());
+
+    let library = device.new_library_with_data(&data).unwrap();
+    let mut names = archive.function_names();
+    names.sort();
+    let mut library_names = library.function_names();
+    library_names.sort();
+    assert_eq!(names, library_names);
+}
+
+#[test]
+fn compile_shader_variants() {
+    const VARIANT_SHADER: &'static str = r"
+using namespace metal;
+
+kernel void scale(device float *values [[buffer(0)]], uint id [[thread_position_in_grid]]) {
+#ifdef DOUBLE
+    values[id] *= 2.0 * SCALE;
+#else
+    values[id] *= SCALE;
+#endif
+}
+";
+    let mut device = Device::system_default_device().unwrap();
+    let mut variants = ShaderVariantSet::new(VARIANT_SHADER)
+        .with_flag("DOUBLE")
+        .with_dimension("SCALE", vec![1.0, 0.5]);
+    assert_eq!(variants.variants().len(), 4);
+
+    variants.request_all(&mut device);
+    let key = VariantKey::new().with_flag("DOUBLE").with("SCALE", 0.5);
+    assert!(variants.function(&mut device, &key, "scale").is_ok());
+    assert!(variants.function(&mut device, &key, "missing").is_err());
+    assert!(variants.function(&mut device, &VariantKey::new(), "scale").is_err());
+}
+
+#[cfg(feature = "translate")]
+#[test]
+fn compile_translated_wgsl() {
+    use metl::metl_translate::{Binding, ShaderTranslator, TranslationSource};
+
+    const WGSL_SHADER: &'static str = "
+@group(0) @binding(0) var<storage, read_write> values: array<f32>;
+@group(0) @binding(1) var<uniform> scale: f32;
+
+@compute @workgroup_size(64)
+fn main(@builtin(global_invocation_id) id: vec3<u32>) {
+    values[id.x] *= scale;
+}
+";
+    let mut device = Device::system_default_device().unwrap();
+    let translator = ShaderTranslator::new().with_msl_version(2, 0);
+    let (mut library, shader) =
+        device.new_library_with_translated_source(&translator,
+                                                  TranslationSource::Wgsl(WGSL_SHADER),
+                                                  &Default::default())
+              .unwrap();
+    assert_eq!(shader.bindings().get(0, 1), Some(&Binding::Buffer(1)));
+    let function = library.new_function_with_name(shader.msl_name("main").unwrap()).unwrap();
+    assert_eq!(function.function_type(),
+               metl::FunctionType::from(shader.entry_points()[0].function_type));
+}
+
 #[test]
 fn create_invalid_shader() {
     let mut device = Device::system_default_device().unwrap();
@@ -163,6 +479,32 @@ fn create_invalid_shader() {
     }
 }
 
+#[test]
+fn compile_shader_diagnostics() {
+    let mut device = Device::system_default_device().unwrap();
+    const WARNING_SHADER: &'static str = r"
+kernel void kernel_main() {
+    int unused = 0;
+}
+";
+    let (_, warnings) = device.new_library_with_source_and_diagnostics(WARNING_SHADER,
+                                                                         &Default::default())
+                              .ok()
+                              .unwrap();
+    assert!(warnings.iter().all(|warning| warning.severity == Severity::Warning));
+
+    const ERROR_SHADER: &'static str = r"
+kernel void kernel_main() {
+    undeclared = 0;
+}
+";
+    let error = device.new_library_with_source(ERROR_SHADER, &Default::default()).err().unwrap();
+    let diagnostics = error.diagnostics();
+    assert!(!diagnostics.is_empty());
+    assert_eq!((diagnostics[0].severity, diagnostics[0].line), (Severity::Error, 3));
+    assert!(diagnostics[0].render(|_| Some(ERROR_SHADER)).contains("undeclared = 0;"));
+}
+
 #[test]
 fn create_invalid_shader_async() {
     let mut device = Device::system_default_device().unwrap();